@@ -1,4 +1,4 @@
-use std::collections::{BTreeSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 
 use wit_parser::{
     Case, Field, Flags, Function, FunctionKind, Handle, Int, Record, Resolve, Stream, Type,
@@ -69,8 +69,45 @@ pub fn is_list_of(resolve: &Resolve, expected: Type, ty: &Type) -> bool {
     }
 }
 
+/// Async paths within a type and whether the type itself is async
+pub type AsyncPaths = (BTreeSet<VecDeque<Option<u32>>>, bool);
+
+/// Memoized [`async_paths_ty`] results, keyed by [`Type`].
+///
+/// Computing async paths requires walking the whole type tree, which is wasteful when the same
+/// function signature is bound repeatedly. The cache must only be used with a single [`Resolve`],
+/// since [`TypeId`]s are not meaningful across resolves.
+#[derive(Clone, Debug, Default)]
+pub struct AsyncPathCache(HashMap<Type, AsyncPaths>);
+
+impl AsyncPathCache {
+    /// Returns the async paths of `ty`, walking the type tree only on first lookup
+    pub fn async_paths_ty(&mut self, resolve: &Resolve, ty: &Type) -> &AsyncPaths {
+        self.0
+            .entry(*ty)
+            .or_insert_with(|| async_paths_ty(resolve, ty))
+    }
+
+    /// Returns the async paths of `id`, walking the type tree only on first lookup
+    pub fn async_paths_tyid(&mut self, resolve: &Resolve, id: TypeId) -> &AsyncPaths {
+        self.async_paths_ty(resolve, &Type::Id(id))
+    }
+
+    /// Returns the number of cached types
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if no types are cached
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 #[must_use]
-pub fn async_paths_ty(resolve: &Resolve, ty: &Type) -> (BTreeSet<VecDeque<Option<u32>>>, bool) {
+pub fn async_paths_ty(resolve: &Resolve, ty: &Type) -> AsyncPaths {
     if let Type::Id(ty) = ty {
         async_paths_tyid(resolve, *ty)
     } else {
@@ -79,7 +116,7 @@ pub fn async_paths_ty(resolve: &Resolve, ty: &Type) -> (BTreeSet<VecDeque<Option
 }
 
 #[must_use]
-pub fn async_paths_tyid(resolve: &Resolve, id: TypeId) -> (BTreeSet<VecDeque<Option<u32>>>, bool) {
+pub fn async_paths_tyid(resolve: &Resolve, id: TypeId) -> AsyncPaths {
     match &resolve.types[id].kind {
         TypeDefKind::List(ty) => {
             let mut paths = BTreeSet::default();
@@ -199,3 +236,237 @@ pub fn async_paths_tyid(resolve: &Resolve, id: TypeId) -> (BTreeSet<VecDeque<Opt
         TypeDefKind::Unknown => unreachable!(),
     }
 }
+
+/// Structural summary of a type, e.g. for estimating the fan-out of async values transmitted
+/// in an invocation
+///
+/// Counts are per occurrence within the type tree, so a `list<future<u32>>` has a single
+/// future, even though a value of that type may contain any number of them.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TypeShape {
+    /// Number of `future`s
+    pub futures: usize,
+    /// Number of `stream`s
+    pub streams: usize,
+    /// Number of `list`s
+    pub lists: usize,
+}
+
+impl TypeShape {
+    /// Returns `true` if the type contains any `future`s or `stream`s
+    #[must_use]
+    pub fn is_async(&self) -> bool {
+        self.futures > 0 || self.streams > 0
+    }
+}
+
+impl core::ops::Add for TypeShape {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            futures: self.futures + rhs.futures,
+            streams: self.streams + rhs.streams,
+            lists: self.lists + rhs.lists,
+        }
+    }
+}
+
+impl core::iter::Sum for TypeShape {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), core::ops::Add::add)
+    }
+}
+
+#[must_use]
+pub fn type_shape(resolve: &Resolve, ty: &Type) -> TypeShape {
+    if let Type::Id(ty) = ty {
+        type_shape_tyid(resolve, *ty)
+    } else {
+        TypeShape::default()
+    }
+}
+
+#[must_use]
+pub fn type_shape_tyid(resolve: &Resolve, id: TypeId) -> TypeShape {
+    let nested = |ty: &Option<Type>| {
+        ty.as_ref()
+            .map(|ty| type_shape(resolve, ty))
+            .unwrap_or_default()
+    };
+    match &resolve.types[id].kind {
+        TypeDefKind::List(ty) => {
+            type_shape(resolve, ty)
+                + TypeShape {
+                    lists: 1,
+                    ..TypeShape::default()
+                }
+        }
+        TypeDefKind::Option(ty) | TypeDefKind::Type(ty) => type_shape(resolve, ty),
+        TypeDefKind::Result(ty) => nested(&ty.ok) + nested(&ty.err),
+        TypeDefKind::Variant(ty) => ty.cases.iter().map(|Case { ty, .. }| nested(ty)).sum(),
+        TypeDefKind::Tuple(ty) => ty.types.iter().map(|ty| type_shape(resolve, ty)).sum(),
+        TypeDefKind::Record(Record { fields }) => fields
+            .iter()
+            .map(|Field { ty, .. }| type_shape(resolve, ty))
+            .sum(),
+        TypeDefKind::Future(ty) => {
+            nested(ty)
+                + TypeShape {
+                    futures: 1,
+                    ..TypeShape::default()
+                }
+        }
+        TypeDefKind::Stream(Stream { element, .. }) => {
+            nested(element)
+                + TypeShape {
+                    streams: 1,
+                    ..TypeShape::default()
+                }
+        }
+        TypeDefKind::Resource
+        | TypeDefKind::Flags(..)
+        | TypeDefKind::Enum(..)
+        | TypeDefKind::Handle(Handle::Own(..) | Handle::Borrow(..)) => TypeShape::default(),
+        TypeDefKind::Unknown => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn async_path_cache() {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push_str(
+                "test.wit",
+                r#"
+package wrpc-test:introspect;
+
+interface test {
+    record rec {
+        a: future<u32>,
+        b: list<stream<u8>>,
+    }
+    f: func(v: rec, s: string) -> stream<rec>;
+}
+"#,
+            )
+            .expect("failed to parse WIT");
+        let iface = resolve.packages[pkg].interfaces["test"];
+        let func = &resolve.interfaces[iface].functions["f"];
+        let tys: Vec<_> = func
+            .params
+            .iter()
+            .map(|(_, ty)| *ty)
+            .chain(func.results.iter_types().copied())
+            .collect();
+
+        let mut cache = AsyncPathCache::default();
+        assert!(cache.is_empty());
+        let bind = |cache: &mut AsyncPathCache| {
+            tys.iter()
+                .map(|ty| cache.async_paths_ty(&resolve, ty).clone())
+                .collect::<Vec<_>>()
+        };
+        let first = bind(&mut cache);
+        assert_eq!(cache.len(), tys.len());
+        let second = bind(&mut cache);
+        assert_eq!(cache.len(), tys.len());
+        assert_eq!(first, second);
+        assert_eq!(
+            first,
+            tys.iter()
+                .map(|ty| async_paths_ty(&resolve, ty))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            first[0],
+            (
+                BTreeSet::from([vec![Some(0)].into(), vec![Some(1), None].into()]),
+                false
+            )
+        );
+        assert_eq!(first[1], (BTreeSet::default(), false));
+        assert_eq!(
+            first[2],
+            (
+                BTreeSet::from([vec![None, Some(0)].into(), vec![None, Some(1), None].into()]),
+                true
+            )
+        );
+        assert_eq!(
+            cache.async_paths_tyid(
+                &resolve,
+                match tys[0] {
+                    Type::Id(id) => id,
+                    _ => unreachable!(),
+                }
+            ),
+            &first[0]
+        );
+    }
+
+    #[test]
+    fn type_shape() {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push_str(
+                "test.wit",
+                r#"
+package wrpc-test:introspect;
+
+interface test {
+    record rec {
+        a: future<u32>,
+        b: option<future<list<u8>>>,
+        c: stream<tuple<string, list<u32>>>,
+        d: string,
+    }
+    f: func(v: rec, s: list<string>) -> result<u32, string>;
+}
+"#,
+            )
+            .expect("failed to parse WIT");
+        let iface = resolve.packages[pkg].interfaces["test"];
+        let func = &resolve.interfaces[iface].functions["f"];
+        let shapes: Vec<_> = func
+            .params
+            .iter()
+            .map(|(_, ty)| super::type_shape(&resolve, ty))
+            .chain(
+                func.results
+                    .iter_types()
+                    .map(|ty| super::type_shape(&resolve, ty)),
+            )
+            .collect();
+        assert_eq!(
+            shapes,
+            [
+                TypeShape {
+                    futures: 2,
+                    streams: 1,
+                    lists: 2,
+                },
+                TypeShape {
+                    futures: 0,
+                    streams: 0,
+                    lists: 1,
+                },
+                TypeShape::default(),
+            ]
+        );
+        assert!(shapes[0].is_async());
+        assert!(!shapes[1].is_async());
+        assert_eq!(
+            shapes.into_iter().sum::<TypeShape>(),
+            TypeShape {
+                futures: 2,
+                streams: 1,
+                lists: 3,
+            }
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Decoding of wRPC values directly into [`serde_json::Value`], e.g. for bridging wRPC to JSON APIs
+//!
+//! Values are mapped as follows:
+//!
+//! - `bool`, integers and floats map to JSON booleans and numbers
+//! - `char` and `string` map to JSON strings
+//! - `list<u8>` maps to a base64-encoded (standard alphabet, padded) JSON string
+//! - other `list`s and `tuple`s map to JSON arrays
+//! - `record`s map to JSON objects keyed by field name
+//! - `option` maps to `null` for `none` and to the payload for `some`
+//! - `result` maps to `{"ok": payload}` or `{"err": payload}`
+//! - `variant` maps to `{"case": payload}` or `{"case": null}`, `enum` maps to the case name
+//! - `flags` map to a JSON array of the names of set flags
+//!
+//! This mapping is lossy: `list<u8>` and `string`, `list` and `tuple`, `none` and `some(none)`
+//! as well as `option<T>` and `T` with a `null` mapping cannot be told apart without the
+//! type. Non-finite floats have no JSON representation and map to `null`.
+//! Resources cannot be represented and fail to decode.
+
+use core::iter::zip;
+use core::pin::Pin;
+
+use base64::Engine as _;
+use serde_json::{Map, Number, Value};
+use tokio::io::{AsyncRead, AsyncReadExt as _};
+use tracing::{instrument, trace};
+use wasm_tokio::cm::AsyncReadValue as _;
+use wasm_tokio::{AsyncReadCore as _, AsyncReadLeb128 as _, AsyncReadUtf8 as _};
+use wasmtime::component::types::{Case, Field};
+use wasmtime::component::Type;
+
+use crate::{field_error, read_flags};
+
+fn float(v: f64) -> Value {
+    Number::from_f64(v).map_or(Value::Null, Value::Number)
+}
+
+/// Read encoded value of type [`Type`] from an [`AsyncRead`] into a [`serde_json::Value`]
+#[instrument(level = "trace", skip_all)]
+pub async fn read_json<R>(r: &mut Pin<&mut R>, ty: &Type) -> std::io::Result<Value>
+where
+    R: AsyncRead + Unpin,
+{
+    match ty {
+        Type::Bool => r.read_bool().await.map(Value::Bool),
+        Type::S8 => r.read_i8().await.map(Value::from),
+        Type::U8 => r.read_u8().await.map(Value::from),
+        Type::S16 => r.read_i16_leb128().await.map(Value::from),
+        Type::U16 => r.read_u16_leb128().await.map(Value::from),
+        Type::S32 => r.read_i32_leb128().await.map(Value::from),
+        Type::U32 => r.read_u32_leb128().await.map(Value::from),
+        Type::S64 => r.read_i64_leb128().await.map(Value::from),
+        Type::U64 => r.read_u64_leb128().await.map(Value::from),
+        Type::Float32 => r.read_f32_le().await.map(|v| float(v.into())),
+        Type::Float64 => r.read_f64_le().await.map(float),
+        Type::Char => r.read_char_utf8().await.map(|v| Value::String(v.into())),
+        Type::String => {
+            let mut s = String::default();
+            r.read_core_name(&mut s).await?;
+            Ok(Value::String(s))
+        }
+        Type::List(ty) => {
+            let n = r.read_u32_leb128().await?;
+            let n = n.try_into().unwrap_or(usize::MAX);
+            let ty = ty.ty();
+            if ty == Type::U8 {
+                let mut buf = vec![0; n];
+                r.read_exact(&mut buf).await?;
+                return Ok(Value::String(
+                    base64::engine::general_purpose::STANDARD.encode(buf),
+                ));
+            }
+            let mut vs = Vec::with_capacity(n);
+            for i in 0..n {
+                trace!(i, "reading list element value");
+                vs.push(Box::pin(read_json(r, &ty)).await?);
+            }
+            Ok(Value::Array(vs))
+        }
+        Type::Record(ty) => {
+            let mut vs = Map::default();
+            for Field { name, ty } in ty.fields() {
+                trace!(name, "reading struct field value");
+                let v = Box::pin(read_json(r, &ty))
+                    .await
+                    .map_err(|err| field_error(name, err))?;
+                vs.insert(name.to_string(), v);
+            }
+            Ok(Value::Object(vs))
+        }
+        Type::Tuple(ty) => {
+            let types = ty.types();
+            let mut vs = Vec::with_capacity(types.len());
+            for (i, ty) in types.enumerate() {
+                trace!(i, "reading tuple element value");
+                vs.push(Box::pin(read_json(r, &ty)).await?);
+            }
+            Ok(Value::Array(vs))
+        }
+        Type::Variant(ty) => {
+            let discriminant = r.read_u32_leb128().await?;
+            let discriminant = discriminant
+                .try_into()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+            let Case { name, ty } = ty.cases().nth(discriminant).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("unknown variant discriminant `{discriminant}`"),
+                )
+            })?;
+            let v = if let Some(ty) = ty {
+                trace!(variant = name, "reading nested variant value");
+                Box::pin(read_json(r, &ty)).await?
+            } else {
+                Value::Null
+            };
+            Ok(Value::Object(Map::from_iter([(name.to_string(), v)])))
+        }
+        Type::Enum(ty) => {
+            let discriminant = r.read_u32_leb128().await?;
+            let discriminant = discriminant
+                .try_into()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+            let name = ty.names().nth(discriminant).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("unknown enum discriminant `{discriminant}`"),
+                )
+            })?;
+            Ok(Value::String(name.to_string()))
+        }
+        Type::Option(ty) => {
+            if r.read_option_status().await? {
+                trace!("reading nested `option::some` value");
+                Box::pin(read_json(r, &ty.ty())).await
+            } else {
+                Ok(Value::Null)
+            }
+        }
+        Type::Result(ty) => {
+            let (name, ty) = if r.read_result_status().await? {
+                ("ok", ty.ok())
+            } else {
+                ("err", ty.err())
+            };
+            let v = if let Some(ty) = ty {
+                trace!(name, "reading nested `result` value");
+                Box::pin(read_json(r, &ty)).await?
+            } else {
+                Value::Null
+            };
+            Ok(Value::Object(Map::from_iter([(name.to_string(), v)])))
+        }
+        Type::Flags(ty) => {
+            let names = ty.names();
+            let n = names.len();
+            let cap = n.div_ceil(8);
+            let mut buf = vec![0; cap];
+            if n <= 128 {
+                let flags = read_flags(cap, r).await?;
+                buf.copy_from_slice(&flags.to_le_bytes()[..cap]);
+            } else {
+                r.read_exact(&mut buf).await?;
+            }
+            let vs = zip(0.., names)
+                .filter(|(i, _)| buf[i / 8] & (1 << (i % 8)) != 0)
+                .map(|(_, name)| Value::String(name.to_string()))
+                .collect();
+            Ok(Value::Array(vs))
+        }
+        Type::Own(..) | Type::Borrow(..) => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "resources cannot be represented as JSON",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{bail, Context as _};
+    use serde_json::json;
+    use wasmtime::component::types;
+    use wasmtime::Engine;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn record_of_lists() -> anyhow::Result<()> {
+        let engine = Engine::default();
+        let component = wasmtime::component::Component::new(
+            &engine,
+            r#"(component
+                (type $e' (enum "a" "b"))
+                (import "e" (type $e (eq $e')))
+                (type $fl' (flags "x" "y" "z"))
+                (import "fl" (type $fl (eq $fl')))
+                (type $r' (record
+                    (field "nums" (list u32))
+                    (field "bytes" (list u8))
+                    (field "names" (list (option string)))
+                    (field "enums" (list $e))
+                    (field "results" (list (result u32 (error string))))
+                    (field "flags" $fl)
+                ))
+                (import "r" (type $r (eq $r')))
+                (import "f" (func (param "r" $r)))
+            )"#,
+        )?;
+        let Some(types::ComponentItem::ComponentFunc(f)) =
+            component.component_type().get_import(&engine, "f")
+        else {
+            bail!("function import missing")
+        };
+        let ty = f.params().next().context("record parameter missing")?;
+        let buf: &[u8] = &[
+            // nums
+            0x03, 0x01, 0x80, 0x01, 0x00, //
+            // bytes
+            0x03, b'f', b'o', b'o', //
+            // names
+            0x02, 0x01, 0x04, b't', b'e', b's', b't', 0x00, //
+            // enums
+            0x02, 0x01, 0x00, //
+            // results
+            0x02, 0x00, 0x2a, 0x01, 0x03, b'b', b'a', b'd', //
+            // flags
+            0b101,
+        ];
+        let mut r = buf;
+        let v = read_json(&mut Pin::new(&mut r), &ty).await?;
+        assert!(r.is_empty(), "trailing bytes: {r:02x?}");
+        assert_eq!(
+            v,
+            json!({
+                "nums": [1, 128, 0],
+                "bytes": "Zm9v",
+                "names": ["test", null],
+                "enums": ["b", "a"],
+                "results": [{ "ok": 42 }, { "err": "bad" }],
+                "flags": ["x", "z"],
+            })
+        );
+
+        // payload truncated within the `names` field
+        let mut r = &buf[..12];
+        let err = read_json(&mut Pin::new(&mut r), &ty)
+            .await
+            .expect_err("truncated record should fail to decode");
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        let err = anyhow::Error::new(err);
+        assert!(
+            format!("{err:#}").starts_with("failed to read `names` field: "),
+            "unexpected error: {err:#}"
+        );
+        Ok(())
+    }
+}
@@ -1,20 +1,22 @@
 #![allow(clippy::type_complexity)] // TODO: https://github.com/bytecodealliance/wrpc/issues/2
 
+use core::borrow::Borrow;
 use core::future::Future;
 use core::iter::zip;
-use core::ops::{BitOrAssign, Shl};
+use core::ops::{BitOrAssign, RangeInclusive, Shl};
 use core::pin::{pin, Pin};
+use core::task::{Context, Poll};
 use core::time::Duration;
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use anyhow::{bail, Context as _};
+use anyhow::{bail, ensure, Context as _};
 use bytes::{BufMut as _, Bytes, BytesMut};
 use futures::future::try_join_all;
 use futures::stream::FuturesUnordered;
 use futures::{Stream, TryStreamExt as _};
-use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, ReadBuf};
 use tokio::sync::Mutex;
 use tokio::time::Instant;
 use tokio::try_join;
@@ -24,8 +26,8 @@ use tracing::{debug, error, instrument, trace, warn, Instrument as _, Span};
 use uuid::Uuid;
 use wasm_tokio::cm::AsyncReadValue as _;
 use wasm_tokio::{
-    AsyncReadCore as _, AsyncReadLeb128 as _, AsyncReadUtf8 as _, CoreNameEncoder,
-    CoreVecEncoderBytes, Leb128Encoder, Utf8Codec,
+    AsyncReadLeb128 as _, AsyncReadUtf8 as _, CoreNameEncoder, CoreVecEncoderBytes, Leb128Encoder,
+    Utf8Codec,
 };
 use wasmtime::component::types::{self, Case, Field};
 use wasmtime::component::{
@@ -36,6 +38,11 @@ use wasmtime_wasi::pipe::AsyncReadStream;
 use wasmtime_wasi::{InputStream, StreamError, WasiView};
 use wrpc_transport::{Index as _, Invoke, InvokeExt as _, ListDecoderU8};
 
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "json")]
+pub use json::read_json;
+
 // this returns the RPC name for a wasmtime function name.
 // Unfortunately, the [`types::ComponentFunc`] does not include the kind information and we want to
 // avoid (re-)parsing the WIT here.
@@ -53,6 +60,100 @@ fn rpc_func_name(name: &str) -> &str {
 
 pub struct RemoteResource(pub Bytes);
 
+/// Conversion of Rust values into dynamic [`Val`]s
+pub trait IntoVal {
+    fn into_val(self) -> Val;
+}
+
+impl IntoVal for Val {
+    fn into_val(self) -> Val {
+        self
+    }
+}
+
+macro_rules! impl_into_val {
+    ($t:ty, $v:ident) => {
+        impl IntoVal for $t {
+            fn into_val(self) -> Val {
+                Val::$v(self.into())
+            }
+        }
+    };
+}
+
+impl_into_val!(bool, Bool);
+impl_into_val!(u8, U8);
+impl_into_val!(u16, U16);
+impl_into_val!(u32, U32);
+impl_into_val!(u64, U64);
+impl_into_val!(i8, S8);
+impl_into_val!(i16, S16);
+impl_into_val!(i32, S32);
+impl_into_val!(i64, S64);
+impl_into_val!(f32, Float32);
+impl_into_val!(f64, Float64);
+impl_into_val!(char, Char);
+impl_into_val!(String, String);
+impl_into_val!(&str, String);
+
+/// Convenience constructors for compound [`Val`]s
+pub trait ValExt {
+    /// Constructs a [`Val::List`] from `items`
+    fn list(items: impl IntoIterator<Item = impl IntoVal>) -> Val {
+        Val::List(items.into_iter().map(IntoVal::into_val).collect())
+    }
+
+    /// Constructs a unit value, i.e. an empty [`Val::Tuple`].
+    ///
+    /// [`ValEncoder`] accepts this as the payload of a variant case without a payload type and
+    /// encodes it identically to a case without a payload.
+    fn unit() -> Val {
+        Val::Tuple(Vec::default())
+    }
+
+    /// Constructs a [`Val::Tuple`] from `items`
+    fn tuple(items: impl IntoIterator<Item = impl IntoVal>) -> Val {
+        Val::Tuple(items.into_iter().map(IntoVal::into_val).collect())
+    }
+
+    /// Constructs a [`Val::Record`] from `fields`
+    fn record(fields: impl IntoIterator<Item = (impl Into<String>, impl IntoVal)>) -> Val {
+        Val::Record(
+            fields
+                .into_iter()
+                .map(|(name, v)| (name.into(), v.into_val()))
+                .collect(),
+        )
+    }
+
+    /// Returns the value nested at `path`, if any.
+    ///
+    /// Each path element indexes a field of a [`Val::Record`], an element of a [`Val::Tuple`] or
+    /// an element of a [`Val::List`]. Payloads of variants, options and results do not consume
+    /// a path element, matching the paths used for asynchronous values, so they are descended
+    /// into transparently. Returns `None` for out-of-range indices, missing payloads and leaf
+    /// values.
+    fn get(&self, path: &[usize]) -> Option<&Val>
+    where
+        Self: Borrow<Val>,
+    {
+        let v: &Val = self.borrow();
+        let Some((i, rest)) = path.split_first() else {
+            return Some(v);
+        };
+        match v {
+            Val::Record(vs) => vs.get(*i).and_then(|(_, v)| v.get(rest)),
+            Val::Tuple(vs) | Val::List(vs) => vs.get(*i)?.get(rest),
+            Val::Variant(_, Some(v))
+            | Val::Option(Some(v))
+            | Val::Result(Ok(Some(v)) | Err(Some(v))) => v.get(path),
+            _ => None,
+        }
+    }
+}
+
+impl ValExt for Val {}
+
 pub struct ValEncoder<'a, T, W> {
     pub store: StoreContextMut<'a, T>,
     pub ty: &'a Type,
@@ -87,6 +188,51 @@ impl<T, W> ValEncoder<'_, T, W> {
     }
 }
 
+/// Returns the payload of a variant case to encode along with its type, if any.
+///
+/// A case without a payload is encoded as just the discriminant, which is identical to the
+/// encoding of a case with a unit payload, since an empty `tuple` is encoded as zero bytes.
+/// Hence, both `None` and a unit value (see [`ValExt::unit`]) are accepted as the payload of a case
+/// without a payload type and both are encoded identically. Since the component model does not
+/// allow empty `tuple` types, [`read_value`] always reads `None` for such cases.
+///
+/// A missing payload of a case with a payload type is an error.
+fn variant_payload(v: Option<&Val>, ty: Option<Type>) -> wasmtime::Result<Option<(&Val, Type)>> {
+    match (v, ty) {
+        (None, None) => Ok(None),
+        (Some(Val::Tuple(vs)), None) if vs.is_empty() => Ok(None),
+        (Some(..), None) => bail!("variant case does not have a payload"),
+        (None, Some(..)) => bail!("payload missing for variant case"),
+        (Some(v), Some(ty)) => Ok(Some((v, ty))),
+    }
+}
+
+/// Pairs the fields of a record value with the corresponding field types.
+///
+/// Fields are encoded without any framing, so a value with missing, extra or reordered fields
+/// would silently desynchronize the peer's decoder. Such values are rejected instead.
+fn record_fields<'a>(
+    vs: &'a [(String, Val)],
+    ty: &types::Record,
+) -> wasmtime::Result<Vec<(&'a str, &'a Val, Type)>> {
+    let fields = ty.fields();
+    ensure!(
+        vs.len() == fields.len(),
+        "record value has {} fields, but the record type has {}",
+        vs.len(),
+        fields.len()
+    );
+    zip(vs, fields)
+        .map(|((name, v), Field { name: expected, ty })| {
+            ensure!(
+                name == expected,
+                "record value field `{name}` does not match record type field `{expected}`"
+            );
+            Ok((name.as_str(), v, ty))
+        })
+        .collect()
+}
+
 fn find_enum_discriminant<'a, T>(
     iter: impl IntoIterator<Item = T>,
     names: impl IntoIterator<Item = &'a str>,
@@ -223,9 +369,10 @@ where
                 Ok(())
             }
             (Val::Record(vs), Type::Record(ty)) => {
+                let fields = record_fields(vs, ty)?;
                 dst.reserve(vs.len());
                 let mut deferred = Vec::with_capacity(vs.len());
-                for ((name, v), Field { ref ty, .. }) in zip(vs, ty.fields()) {
+                for (name, v, ref ty) in fields {
                     let mut enc = self.with_type(ty);
                     enc.encode(v, dst)
                         .with_context(|| format!("failed to encode `{name}` field"))?;
@@ -237,6 +384,12 @@ where
                 Ok(())
             }
             (Val::Tuple(vs), Type::Tuple(ty)) => {
+                let n = ty.types().len();
+                ensure!(
+                    vs.len() == n,
+                    "tuple value has {} elements, but the tuple type has {n}",
+                    vs.len()
+                );
                 dst.reserve(vs.len());
                 let mut deferred = Vec::with_capacity(vs.len());
                 for (v, ref ty) in zip(vs, ty.types()) {
@@ -283,8 +436,7 @@ where
                     }
                     0x1_0000_0000.. => bail!("case count does not fit in u32"),
                 };
-                if let Some(v) = v {
-                    let ty = ty.context("type missing for variant")?;
+                if let Some((v, ty)) = variant_payload(v.as_deref(), ty)? {
                     let mut enc = self.with_type(&ty);
                     enc.encode(v, dst)
                         .context("failed to encode variant value")?;
@@ -590,6 +742,14 @@ where
     }
 }
 
+/// Annotates an error encountered while reading a record field with the field name
+fn field_error(name: &str, err: std::io::Error) -> std::io::Error {
+    std::io::Error::new(
+        err.kind(),
+        anyhow::Error::new(err).context(format!("failed to read `{name}` field")),
+    )
+}
+
 #[inline]
 async fn read_flags(n: usize, r: &mut (impl AsyncRead + Unpin)) -> std::io::Result<u128> {
     let mut buf = 0u128.to_le_bytes();
@@ -597,8 +757,83 @@ async fn read_flags(n: usize, r: &mut (impl AsyncRead + Unpin)) -> std::io::Resu
     Ok(u128::from_le_bytes(buf))
 }
 
+/// Constraint on a decoded value, enforced by [`read_value_strict`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Constraint {
+    /// Only the listed discriminants are allowed for a `variant`, `enum`, `option` or `result`.
+    ///
+    /// `option` uses `0` for `none` and `1` for `some`, `result` uses `0` for `ok` and `1` for
+    /// `err`, matching the encoding.
+    Discriminants(Vec<u32>),
+    /// Integer value must be within the range
+    Range(RangeInclusive<i128>),
+}
+
+/// Set of [`Constraint`]s on values at specific paths, enforced by [`read_value_strict`]
+///
+/// Each path element indexes a field of a `record`, an element of a `tuple` or an element of a
+/// `list`. Unlike the paths used for asynchronous values, the payload of a `variant`, `option`
+/// or `result` value is indexed by its discriminant, as used by [`Constraint::Discriminants`],
+/// so that constraints on outer and nested discriminants can be told apart.
+/// `None` matches any index, e.g. all elements of a `list` or payloads of all cases.
+/// Constraints, which do not apply to the type of the value at their path, are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ValuePolicy {
+    constraints: Vec<(Box<[Option<usize>]>, Constraint)>,
+}
+
+impl ValuePolicy {
+    /// Constructs a new [`ValuePolicy`] without any constraints
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `constraint` on values at `path`
+    #[must_use]
+    pub fn with(mut self, path: impl Into<Box<[Option<usize>]>>, constraint: Constraint) -> Self {
+        self.constraints.push((path.into(), constraint));
+        self
+    }
+
+    fn constraints<'a>(&'a self, path: &'a [usize]) -> impl Iterator<Item = &'a Constraint> {
+        self.constraints.iter().filter_map(move |(p, c)| {
+            (p.len() == path.len() && zip(p.iter(), path).all(|(p, i)| p.is_none_or(|p| p == *i)))
+                .then_some(c)
+        })
+    }
+
+    fn check_int(&self, path: &[usize], v: impl Into<i128>) -> std::io::Result<()> {
+        let v = v.into();
+        for c in self.constraints(path) {
+            if let Constraint::Range(range) = c {
+                if !range.contains(&v) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("value `{v}` at path `{path:?}` is not within `{range:?}`"),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_discriminant(&self, path: &[usize], discriminant: u32) -> std::io::Result<()> {
+        for c in self.constraints(path) {
+            if let Constraint::Discriminants(allowed) = c {
+                if !allowed.contains(&discriminant) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("discriminant `{discriminant}` at path `{path:?}` is not allowed"),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Read encoded value of type [`Type`] from an [`AsyncRead`] into a [`Val`]
-#[instrument(level = "trace", skip_all, fields(ty, path))]
 pub async fn read_value<T, R>(
     store: &mut impl AsContextMut<Data = T>,
     r: &mut Pin<&mut R>,
@@ -607,6 +842,56 @@ pub async fn read_value<T, R>(
     ty: &Type,
     path: &[usize],
 ) -> std::io::Result<()>
+where
+    T: WasiView + WrpcView,
+    R: AsyncRead + wrpc_transport::Index<R> + Send + Unpin + 'static,
+{
+    read_value_with(
+        store,
+        r,
+        resources,
+        val,
+        ty,
+        path,
+        &ValuePolicy::default(),
+        path,
+    )
+    .await
+}
+
+/// Like [`read_value`], but additionally enforces `policy` while reading the value.
+///
+/// This is intended for untrusted peers: values, which are well-formed, but not allowed by the
+/// policy, like an unexpected `variant` case, are rejected as soon as they are read, rather
+/// than after the whole value is decoded.
+pub async fn read_value_strict<T, R>(
+    store: &mut impl AsContextMut<Data = T>,
+    r: &mut Pin<&mut R>,
+    resources: &[ResourceType],
+    val: &mut Val,
+    ty: &Type,
+    path: &[usize],
+    policy: &ValuePolicy,
+) -> std::io::Result<()>
+where
+    T: WasiView + WrpcView,
+    R: AsyncRead + wrpc_transport::Index<R> + Send + Unpin + 'static,
+{
+    read_value_with(store, r, resources, val, ty, path, policy, path).await
+}
+
+#[instrument(level = "trace", skip_all, fields(ty, path))]
+#[allow(clippy::too_many_arguments)]
+async fn read_value_with<T, R>(
+    store: &mut impl AsContextMut<Data = T>,
+    r: &mut Pin<&mut R>,
+    resources: &[ResourceType],
+    val: &mut Val,
+    ty: &Type,
+    path: &[usize],
+    policy: &ValuePolicy,
+    policy_path: &[usize],
+) -> std::io::Result<()>
 where
     T: WasiView + WrpcView,
     R: AsyncRead + wrpc_transport::Index<R> + Send + Unpin + 'static,
@@ -619,41 +904,49 @@ where
         }
         Type::S8 => {
             let v = r.read_i8().await?;
+            policy.check_int(policy_path, v)?;
             *val = Val::S8(v);
             Ok(())
         }
         Type::U8 => {
             let v = r.read_u8().await?;
+            policy.check_int(policy_path, v)?;
             *val = Val::U8(v);
             Ok(())
         }
         Type::S16 => {
             let v = r.read_i16_leb128().await?;
+            policy.check_int(policy_path, v)?;
             *val = Val::S16(v);
             Ok(())
         }
         Type::U16 => {
             let v = r.read_u16_leb128().await?;
+            policy.check_int(policy_path, v)?;
             *val = Val::U16(v);
             Ok(())
         }
         Type::S32 => {
             let v = r.read_i32_leb128().await?;
+            policy.check_int(policy_path, v)?;
             *val = Val::S32(v);
             Ok(())
         }
         Type::U32 => {
             let v = r.read_u32_leb128().await?;
+            policy.check_int(policy_path, v)?;
             *val = Val::U32(v);
             Ok(())
         }
         Type::S64 => {
             let v = r.read_i64_leb128().await?;
+            policy.check_int(policy_path, v)?;
             *val = Val::S64(v);
             Ok(())
         }
         Type::U64 => {
             let v = r.read_u64_leb128().await?;
+            policy.check_int(policy_path, v)?;
             *val = Val::U64(v);
             Ok(())
         }
@@ -673,8 +966,15 @@ where
             Ok(())
         }
         Type::String => {
-            let mut s = String::default();
-            r.read_core_name(&mut s).await?;
+            let n = r.read_u32_leb128().await?;
+            let mut buf = Vec::default();
+            r.take(n.into()).read_to_end(&mut buf).await?;
+            // unlike `read_core_name`, this fails on a truncated string
+            if buf.len() as u64 != u64::from(n) {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            let s = String::from_utf8(buf)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
             *val = Val::String(s);
             Ok(())
         }
@@ -684,12 +984,25 @@ where
             let mut vs = Vec::with_capacity(n);
             let ty = ty.ty();
             let mut path = path.to_vec();
+            let mut policy_path = policy_path.to_vec();
             for i in 0..n {
                 let mut v = Val::Bool(false);
                 path.push(i);
+                policy_path.push(i);
                 trace!(i, "reading list element value");
-                Box::pin(read_value(store, r, resources, &mut v, &ty, &path)).await?;
+                Box::pin(read_value_with(
+                    store,
+                    r,
+                    resources,
+                    &mut v,
+                    &ty,
+                    &path,
+                    policy,
+                    &policy_path,
+                ))
+                .await?;
                 path.pop();
+                policy_path.pop();
                 vs.push(v);
             }
             *val = Val::List(vs);
@@ -699,12 +1012,26 @@ where
             let fields = ty.fields();
             let mut vs = Vec::with_capacity(fields.len());
             let mut path = path.to_vec();
+            let mut policy_path = policy_path.to_vec();
             for (i, Field { name, ty }) in fields.enumerate() {
                 let mut v = Val::Bool(false);
                 path.push(i);
+                policy_path.push(i);
                 trace!(i, "reading struct field value");
-                Box::pin(read_value(store, r, resources, &mut v, &ty, &path)).await?;
+                Box::pin(read_value_with(
+                    store,
+                    r,
+                    resources,
+                    &mut v,
+                    &ty,
+                    &path,
+                    policy,
+                    &policy_path,
+                ))
+                .await
+                .map_err(|err| field_error(name, err))?;
                 path.pop();
+                policy_path.pop();
                 vs.push((name.to_string(), v));
             }
             *val = Val::Record(vs);
@@ -714,12 +1041,25 @@ where
             let types = ty.types();
             let mut vs = Vec::with_capacity(types.len());
             let mut path = path.to_vec();
+            let mut policy_path = policy_path.to_vec();
             for (i, ty) in types.enumerate() {
                 let mut v = Val::Bool(false);
                 path.push(i);
+                policy_path.push(i);
                 trace!(i, "reading tuple element value");
-                Box::pin(read_value(store, r, resources, &mut v, &ty, &path)).await?;
+                Box::pin(read_value_with(
+                    store,
+                    r,
+                    resources,
+                    &mut v,
+                    &ty,
+                    &path,
+                    policy,
+                    &policy_path,
+                ))
+                .await?;
                 path.pop();
+                policy_path.pop();
                 vs.push(v);
             }
             *val = Val::Tuple(vs);
@@ -727,6 +1067,7 @@ where
         }
         Type::Variant(ty) => {
             let discriminant = r.read_u32_leb128().await?;
+            policy.check_discriminant(policy_path, discriminant)?;
             let discriminant = discriminant
                 .try_into()
                 .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
@@ -740,7 +1081,17 @@ where
             if let Some(ty) = ty {
                 let mut v = Val::Bool(false);
                 trace!(variant = name, "reading nested variant value");
-                Box::pin(read_value(store, r, resources, &mut v, &ty, path)).await?;
+                Box::pin(read_value_with(
+                    store,
+                    r,
+                    resources,
+                    &mut v,
+                    &ty,
+                    path,
+                    policy,
+                    &[policy_path, &[discriminant]].concat(),
+                ))
+                .await?;
                 *val = Val::Variant(name, Some(Box::new(v)));
             } else {
                 *val = Val::Variant(name, None);
@@ -749,6 +1100,7 @@ where
         }
         Type::Enum(ty) => {
             let discriminant = r.read_u32_leb128().await?;
+            policy.check_discriminant(policy_path, discriminant)?;
             let discriminant = discriminant
                 .try_into()
                 .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
@@ -763,10 +1115,21 @@ where
         }
         Type::Option(ty) => {
             let ok = r.read_option_status().await?;
+            policy.check_discriminant(policy_path, ok.into())?;
             if ok {
                 let mut v = Val::Bool(false);
                 trace!("reading nested `option::some` value");
-                Box::pin(read_value(store, r, resources, &mut v, &ty.ty(), path)).await?;
+                Box::pin(read_value_with(
+                    store,
+                    r,
+                    resources,
+                    &mut v,
+                    &ty.ty(),
+                    path,
+                    policy,
+                    &[policy_path, &[1]].concat(),
+                ))
+                .await?;
                 *val = Val::Option(Some(Box::new(v)));
             } else {
                 *val = Val::Option(None);
@@ -775,11 +1138,22 @@ where
         }
         Type::Result(ty) => {
             let ok = r.read_result_status().await?;
+            policy.check_discriminant(policy_path, (!ok).into())?;
             if ok {
                 if let Some(ty) = ty.ok() {
                     let mut v = Val::Bool(false);
                     trace!("reading nested `result::ok` value");
-                    Box::pin(read_value(store, r, resources, &mut v, &ty, path)).await?;
+                    Box::pin(read_value_with(
+                        store,
+                        r,
+                        resources,
+                        &mut v,
+                        &ty,
+                        path,
+                        policy,
+                        &[policy_path, &[0]].concat(),
+                    ))
+                    .await?;
                     *val = Val::Result(Ok(Some(Box::new(v))));
                 } else {
                     *val = Val::Result(Ok(None));
@@ -787,7 +1161,17 @@ where
             } else if let Some(ty) = ty.err() {
                 let mut v = Val::Bool(false);
                 trace!("reading nested `result::err` value");
-                Box::pin(read_value(store, r, resources, &mut v, &ty, path)).await?;
+                Box::pin(read_value_with(
+                    store,
+                    r,
+                    resources,
+                    &mut v,
+                    &ty,
+                    path,
+                    policy,
+                    &[policy_path, &[1]].concat(),
+                ))
+                .await?;
                 *val = Val::Result(Err(Some(Box::new(v))));
             } else {
                 *val = Val::Result(Err(None));
@@ -923,6 +1307,158 @@ where
     }
 }
 
+/// In-memory byte stream over a fully-buffered payload, used by [`encode_value`] and
+/// [`decode_value`].
+///
+/// Nested streams cannot be indexed, since values round-tripped in memory must not have
+/// asynchronous components.
+struct BufferedIo(Bytes);
+
+impl wrpc_transport::Index<Self> for BufferedIo {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        bail!("nested stream at path `{path:?}` is not available in memory")
+    }
+}
+
+impl AsyncRead for BufferedIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let n = buf.remaining().min(self.0.len());
+        buf.put_slice(&self.0.split_to(n));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for BufferedIo {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "in-memory stream is read-only",
+        )))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Encodes `val` of type [`Type`] and immediately decodes it back using the same type.
+///
+/// This is useful for debugging interoperability issues: comparing the returned [`Val`] to
+/// `val` tells whether a bug is in the encoding or the decoding of a particular value.
+/// The whole payload must be consumed by decoding, values with asynchronous components,
+/// like `wasi:io/input-stream` resources, are not supported.
+#[instrument(level = "trace", skip(store, resources))]
+pub async fn roundtrip_dynamic<T>(
+    store: &mut impl AsContextMut<Data = T>,
+    resources: &[ResourceType],
+    val: &Val,
+    ty: &Type,
+) -> anyhow::Result<Val>
+where
+    T: WasiView + WrpcView,
+{
+    let mut buf = BytesMut::default();
+    let mut enc = ValEncoder::<_, BufferedIo>::new(store.as_context_mut(), ty, resources);
+    enc.encode(val, &mut buf)
+        .context("failed to encode value")?;
+    ensure!(
+        enc.deferred.is_none(),
+        "value has asynchronous components, which cannot be round-tripped in memory"
+    );
+    trace!(?buf, "decoding encoded value");
+    decode_value(store, resources, ty, buf.freeze()).await
+}
+
+/// Returns `true` if values of type [`Type`] can only be transmitted once, i.e. if they contain
+/// a `wasi:io/input-stream` or an owned resource handle, ownership of which is transferred.
+fn is_single_shot(ty: &Type) -> bool {
+    match ty {
+        Type::Own(..) => true,
+        Type::Borrow(ty) => *ty == ResourceType::host::<InputStream>(),
+        Type::List(ty) => is_single_shot(&ty.ty()),
+        Type::Record(ty) => ty.fields().any(|Field { ty, .. }| is_single_shot(&ty)),
+        Type::Tuple(ty) => ty.types().any(|ty| is_single_shot(&ty)),
+        Type::Variant(ty) => ty
+            .cases()
+            .any(|Case { ty, .. }| ty.as_ref().is_some_and(is_single_shot)),
+        Type::Option(ty) => is_single_shot(&ty.ty()),
+        Type::Result(ty) => {
+            ty.ok().as_ref().is_some_and(is_single_shot)
+                || ty.err().as_ref().is_some_and(is_single_shot)
+        }
+        _ => false,
+    }
+}
+
+/// Encodes a borrowed `val` of type [`Type`] into a single payload without consuming it.
+///
+/// This allows the same value to be transmitted multiple times, e.g. broadcast to several
+/// peers, without cloning it. Values of types containing a `wasi:io/input-stream` or an owned
+/// resource handle can only be transmitted once and are rejected before anything is encoded.
+#[instrument(level = "trace", skip(store, resources))]
+pub fn encode_value<T>(
+    store: &mut impl AsContextMut<Data = T>,
+    resources: &[ResourceType],
+    val: &Val,
+    ty: &Type,
+) -> anyhow::Result<Bytes>
+where
+    T: WasiView + WrpcView,
+{
+    ensure!(
+        !is_single_shot(ty),
+        "values of this type can only be transmitted once and cannot be encoded by reference"
+    );
+    let mut buf = BytesMut::default();
+    let mut enc = ValEncoder::<_, BufferedIo>::new(store.as_context_mut(), ty, resources);
+    enc.encode(val, &mut buf)
+        .context("failed to encode value")?;
+    debug_assert!(enc.deferred.is_none());
+    Ok(buf.freeze())
+}
+
+/// Decodes a value of type [`Type`] from a single, fully-buffered payload.
+///
+/// This is useful for tooling, which has the whole payload and the type in memory and does not
+/// need a transport. This function fails if the payload is incomplete, if it contains trailing
+/// bytes or if the value requires asynchronous I/O, like `wasi:io/input-stream` resources,
+/// which cannot be satisfied from a single buffer.
+#[instrument(level = "trace", skip(store, resources))]
+pub async fn decode_value<T>(
+    store: &mut impl AsContextMut<Data = T>,
+    resources: &[ResourceType],
+    ty: &Type,
+    payload: Bytes,
+) -> anyhow::Result<Val>
+where
+    T: WasiView + WrpcView,
+{
+    let mut r = pin!(BufferedIo(payload));
+    let mut v = Val::Bool(false);
+    read_value(store, &mut r, resources, &mut v, ty, &[])
+        .await
+        .with_context(|| format!("failed to decode value from {:02x?}", r.0))?;
+    ensure!(
+        r.0.is_empty(),
+        "decoding value left {} trailing bytes: {:02x?}",
+        r.0.len(),
+        r.0
+    );
+    Ok(v)
+}
+
 /// A table of shared resources exported by the component
 #[derive(Debug, Default)]
 pub struct SharedResourceTable(HashMap<Uuid, ResourceAny>);
@@ -1405,3 +1941,520 @@ pub trait ServeExt: wrpc_transport::Serve {
 }
 
 impl<T: wrpc_transport::Serve> ServeExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn val_constructors() {
+        let v = Val::record([
+            ("a", 42_u32.into_val()),
+            ("b", "test".into_val()),
+            ("c", true.into_val()),
+            ("d", Val::list([1.5_f64, 2.5])),
+            ("e", Val::tuple(['x'.into_val(), (-1_i8).into_val()])),
+        ]);
+        assert_eq!(
+            v,
+            Val::Record(vec![
+                ("a".into(), Val::U32(42)),
+                ("b".into(), Val::String("test".into())),
+                ("c".into(), Val::Bool(true)),
+                (
+                    "d".into(),
+                    Val::List(vec![Val::Float64(1.5), Val::Float64(2.5)])
+                ),
+                ("e".into(), Val::Tuple(vec![Val::Char('x'), Val::S8(-1)])),
+            ])
+        );
+        assert_eq!(Val::unit(), Val::Tuple(vec![]));
+    }
+
+    #[test]
+    fn val_get() {
+        let v = Val::record([
+            ("a", 42_u32.into_val()),
+            (
+                "b",
+                Val::tuple([
+                    "foo".into_val(),
+                    Val::Option(Some(Box::new(Val::tuple([1_u8, 2])))),
+                    Val::list(["bar", "baz"]),
+                ]),
+            ),
+            (
+                "c",
+                Val::Variant("some".into(), Some(Box::new(Val::list([true])))),
+            ),
+        ]);
+        assert_eq!(v.get(&[]), Some(&v));
+        assert_eq!(v.get(&[0]), Some(&Val::U32(42)));
+        assert_eq!(v.get(&[1, 0]), Some(&Val::String("foo".into())));
+        // option payloads are descended into without consuming a path element
+        assert_eq!(v.get(&[1, 1, 1]), Some(&Val::U8(2)));
+        assert_eq!(v.get(&[1, 2, 1]), Some(&Val::String("baz".into())));
+        assert_eq!(v.get(&[2, 0]), Some(&Val::Bool(true)));
+
+        // out-of-range indices
+        assert_eq!(v.get(&[3]), None);
+        assert_eq!(v.get(&[1, 3]), None);
+        assert_eq!(v.get(&[1, 2, 2]), None);
+        // leaf values and missing payloads
+        assert_eq!(v.get(&[0, 0]), None);
+        assert_eq!(v.get(&[1, 0, 0]), None);
+        assert_eq!(Val::Option(None).get(&[0]), None);
+        assert_eq!(Val::Variant("none".into(), None).get(&[0]), None);
+    }
+
+    #[test]
+    fn variant_unit_payload() -> wasmtime::Result<()> {
+        let (_, ty) = func_param_ty(
+            r#"
+            (type $t' (variant (case "none") (case "num" u32)))
+            (import "t" (type $t (eq $t')))
+            "#,
+        );
+        let Type::Variant(ty) = ty else {
+            bail!("variant parameter expected")
+        };
+        let Ok([none, num]) =
+            <[_; 2]>::try_from(ty.cases().map(|Case { ty, .. }| ty).collect::<Vec<_>>())
+        else {
+            bail!("unexpected variant cases")
+        };
+        let unit = Val::unit();
+        let n = Val::U32(42);
+
+        // case without a payload
+        assert!(variant_payload(None, none.clone())?.is_none());
+        assert!(variant_payload(Some(&unit), none.clone())?.is_none());
+        assert!(variant_payload(Some(&n), none.clone()).is_err());
+
+        // case with a payload
+        assert!(variant_payload(None, num.clone()).is_err());
+        let (v, ty) =
+            variant_payload(Some(&n), num.clone())?.context("payload should be encoded")?;
+        assert_eq!(v, &n);
+        assert_eq!(ty, Type::U32);
+        Ok(())
+    }
+
+    #[test]
+    fn record_field_mismatch() -> wasmtime::Result<()> {
+        let (_, ty) = func_param_ty(
+            r#"
+            (type $t' (record (field "a" u32) (field "b" string)))
+            (import "t" (type $t (eq $t')))
+            "#,
+        );
+        let Type::Record(ty) = ty else {
+            bail!("record parameter expected")
+        };
+        let a = ("a".to_string(), Val::U32(42));
+        let b = ("b".to_string(), Val::String("test".into()));
+
+        let vs = [a.clone(), b.clone()];
+        let fields = record_fields(&vs, &ty)?;
+        assert_eq!(fields, [("a", &a.1, Type::U32), ("b", &b.1, Type::String)]);
+
+        let err =
+            record_fields(std::slice::from_ref(&a), &ty).expect_err("missing field should fail");
+        assert_eq!(
+            err.to_string(),
+            "record value has 1 fields, but the record type has 2"
+        );
+        let err = record_fields(&[a.clone(), b.clone(), a.clone()], &ty)
+            .expect_err("extra field should fail");
+        assert_eq!(
+            err.to_string(),
+            "record value has 3 fields, but the record type has 2"
+        );
+        let err = record_fields(&[b, a], &ty).expect_err("reordered fields should fail");
+        assert_eq!(
+            err.to_string(),
+            "record value field `b` does not match record type field `a`"
+        );
+        Ok(())
+    }
+
+    /// [Invoke] implementation, which fails all invocations
+    struct NoopInvoke;
+
+    impl Invoke for NoopInvoke {
+        type Context = ();
+        type Outgoing = BufferedIo;
+        type Incoming = BufferedIo;
+
+        async fn invoke<P>(
+            &self,
+            (): Self::Context,
+            instance: &str,
+            func: &str,
+            _params: Bytes,
+            _paths: impl AsRef<[P]> + Send,
+        ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)>
+        where
+            P: AsRef<[Option<usize>]> + Send + Sync,
+        {
+            bail!("cannot invoke `{instance}.{func}`")
+        }
+    }
+
+    struct Ctx {
+        wasi: wasmtime_wasi::WasiCtx,
+        table: wasmtime_wasi::ResourceTable,
+        shared_resources: SharedResourceTable,
+    }
+
+    impl WrpcView for Ctx {
+        type Invoke = NoopInvoke;
+
+        fn client(&self) -> &Self::Invoke {
+            &NoopInvoke
+        }
+
+        fn shared_resources(&mut self) -> &mut SharedResourceTable {
+            &mut self.shared_resources
+        }
+    }
+
+    impl WasiView for Ctx {
+        fn ctx(&mut self) -> &mut wasmtime_wasi::WasiCtx {
+            &mut self.wasi
+        }
+
+        fn table(&mut self) -> &mut wasmtime_wasi::ResourceTable {
+            &mut self.table
+        }
+    }
+
+    /// Compiles a component importing a function with a single parameter of type `$t`, which is
+    /// declared by `wat_ty`, and returns a [`wasmtime::Store`] along with the parameter [`Type`].
+    ///
+    /// `wasi:io/streams` is imported by the component, so `wat_ty` can refer to the
+    /// `$input-stream` resource, which is substituted by the host `wasi:io/streams.input-stream`.
+    fn func_param_ty(wat_ty: &str) -> (wasmtime::Store<Ctx>, Type) {
+        let engine = Engine::new(wasmtime::Config::new().async_support(true))
+            .expect("failed to construct engine");
+        let component = wasmtime::component::Component::new(
+            &engine,
+            format!(
+                r#"(component
+                (import "wasi:io/streams@0.2.0" (instance $streams
+                    (export "input-stream" (type (sub resource)))
+                ))
+                (alias export $streams "input-stream" (type $input-stream))
+                {wat_ty}
+                (import "f" (func (param "v" $t)))
+            )"#
+            ),
+        )
+        .expect("failed to compile component");
+        let mut linker = wasmtime::component::Linker::<Ctx>::new(&engine);
+        wasmtime_wasi::add_to_linker_async(&mut linker).expect("failed to link WASI");
+        linker
+            .root()
+            .func_new("f", |_, _, _| Ok(()))
+            .expect("failed to define function");
+        let Some(types::ComponentItem::ComponentFunc(f)) = linker
+            .substituted_component_type(&component)
+            .expect("failed to substitute component type")
+            .get_import(&engine, "f")
+        else {
+            panic!("function import missing")
+        };
+        let ty = f.params().next().expect("function parameter missing");
+        let store = wasmtime::Store::new(
+            &engine,
+            Ctx {
+                wasi: wasmtime_wasi::WasiCtxBuilder::new().build(),
+                table: wasmtime_wasi::ResourceTable::new(),
+                shared_resources: SharedResourceTable::default(),
+            },
+        );
+        (store, ty)
+    }
+
+    #[tokio::test]
+    async fn roundtrip() -> wasmtime::Result<()> {
+        let (mut store, r) = func_param_ty(
+            r#"
+            (type $v' (variant (case "none") (case "num" u32)))
+            (import "v" (type $v (eq $v')))
+            (type $t' (record (field "a" u32) (field "b" (list string)) (field "c" $v)))
+            (import "t" (type $t (eq $t')))
+            "#,
+        );
+        let (_, s) = func_param_ty("(type $t s64)");
+
+        let v = Val::record([
+            ("a", 300_u32.into_val()),
+            ("b", Val::list(["foo", ""])),
+            (
+                "c",
+                Val::Variant("num".into(), Some(Box::new(Val::U32(42)))),
+            ),
+        ]);
+        assert_eq!(roundtrip_dynamic(&mut store, &[], &v, &r).await?, v);
+        let v = Val::S64(-0x2000);
+        assert_eq!(roundtrip_dynamic(&mut store, &[], &v, &s).await?, v);
+
+        // a unit payload of a case without a payload type is decoded as no payload
+        let v = Val::record([
+            ("a", 0_u32.into_val()),
+            ("b", Val::list(Vec::<String>::default())),
+            (
+                "c",
+                Val::Variant("none".into(), Some(Box::new(Val::unit()))),
+            ),
+        ]);
+        let Val::Record(fields) = roundtrip_dynamic(&mut store, &[], &v, &r).await? else {
+            bail!("record expected")
+        };
+        assert_eq!(fields[2], ("c".into(), Val::Variant("none".into(), None)));
+
+        // mismatched types are detected
+        let err = roundtrip_dynamic(&mut store, &[], &Val::U32(42), &s)
+            .await
+            .expect_err("mismatched type should fail to encode");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to encode value: value type mismatch"
+        );
+        let v = Val::record([
+            ("a", 42_u32.into_val()),
+            ("c", Val::Variant("none".into(), None)),
+        ]);
+        let err = roundtrip_dynamic(&mut store, &[], &v, &r)
+            .await
+            .expect_err("record with a missing field should fail to encode");
+        assert!(
+            format!("{err:#}").starts_with("failed to encode value: "),
+            "unexpected error: {err:#}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decode() -> wasmtime::Result<()> {
+        let (mut store, r) = func_param_ty(
+            r#"
+            (type $t' (record (field "a" u32) (field "b" string)))
+            (import "t" (type $t (eq $t')))
+            "#,
+        );
+        let (mut s_store, s) = func_param_ty("(type $t (tuple u8 (own $input-stream)))");
+
+        let v = decode_value(&mut store, &[], &r, Bytes::from_static(b"\x2a\x04test")).await?;
+        assert_eq!(
+            v,
+            Val::record([("a", 42_u32.into_val()), ("b", "test".into_val())])
+        );
+
+        let err = decode_value(&mut store, &[], &r, Bytes::from_static(b"\x2a\x04tes"))
+            .await
+            .expect_err("incomplete payload should fail");
+        assert!(
+            format!("{err:#}").starts_with("failed to decode value from "),
+            "unexpected error: {err:#}"
+        );
+        let err = decode_value(&mut store, &[], &r, Bytes::from_static(b"\x2a\x00\x00"))
+            .await
+            .expect_err("trailing bytes should fail");
+        assert!(
+            err.to_string()
+                .starts_with("decoding value left 1 trailing bytes"),
+            "unexpected error: {err:#}"
+        );
+
+        let err = decode_value(&mut s_store, &[], &s, Bytes::from_static(b"\x2a\x00"))
+            .await
+            .expect_err("stream should fail");
+        assert!(
+            format!("{err:#}").ends_with("nested stream at path `[1]` is not available in memory"),
+            "unexpected error: {err:#}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn encode_ref() -> wasmtime::Result<()> {
+        let (mut store, r) = func_param_ty(
+            r#"
+            (type $t' (record (field "a" u32) (field "b" (list string))))
+            (import "t" (type $t (eq $t')))
+            "#,
+        );
+        let (mut s_store, s) = func_param_ty("(type $t (option (own $input-stream)))");
+
+        // the same value is encoded for two peers
+        let v = Val::record([("a", 42_u32.into_val()), ("b", Val::list(["foo", "bar"]))]);
+        let a = encode_value(&mut store, &[], &v, &r)?;
+        let b = encode_value(&mut store, &[], &v, &r)?;
+        assert_eq!(a, b);
+        assert_eq!(a, b"\x2a\x02\x03foo\x03bar".as_slice());
+        assert_eq!(decode_value(&mut store, &[], &r, a).await?, v);
+
+        let err = encode_value(&mut s_store, &[], &Val::Option(None), &s)
+            .expect_err("owned resource should fail");
+        assert_eq!(
+            err.to_string(),
+            "values of this type can only be transmitted once and cannot be encoded by reference"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_strict() -> wasmtime::Result<()> {
+        let (mut store, ty) = func_param_ty(
+            r#"
+            (type $v' (variant (case "none") (case "num" (option u32)) (case "str" string)))
+            (import "v" (type $v (eq $v')))
+            (type $t' (record (field "a" u32) (field "b" (list s8)) (field "c" $v)))
+            (import "t" (type $t (eq $t')))
+            "#,
+        );
+        let policy = ValuePolicy::new()
+            .with([Some(0)], Constraint::Range(0..=100))
+            .with([Some(1), None], Constraint::Range(-1..=1))
+            .with([Some(2)], Constraint::Discriminants(vec![0, 1]))
+            .with([Some(2), Some(1)], Constraint::Discriminants(vec![1]))
+            .with([Some(2), Some(1), Some(1)], Constraint::Range(0..=10));
+
+        async fn read(
+            store: &mut wasmtime::Store<Ctx>,
+            ty: &Type,
+            policy: &ValuePolicy,
+            v: Val,
+        ) -> anyhow::Result<Val> {
+            let mut buf = BytesMut::default();
+            ValEncoder::<_, BufferedIo>::new(store.as_context_mut(), ty, &[])
+                .encode(&v, &mut buf)?;
+            let mut r = pin!(BufferedIo(buf.freeze()));
+            let mut v = Val::Bool(false);
+            read_value_strict(store, &mut r, &[], &mut v, ty, &[], policy).await?;
+            Ok(v)
+        }
+
+        let v = Val::record([
+            ("a", 42_u32.into_val()),
+            ("b", Val::list([-1_i8, 0, 1])),
+            ("c", Val::Variant("none".into(), None)),
+        ]);
+        assert_eq!(read(&mut store, &ty, &policy, v.clone()).await?, v);
+        let v = Val::record([
+            ("a", 42_u32.into_val()),
+            ("b", Val::list(Vec::<i8>::default())),
+            (
+                "c",
+                Val::Variant(
+                    "num".into(),
+                    Some(Box::new(Val::Option(Some(Box::new(Val::U32(5)))))),
+                ),
+            ),
+        ]);
+        assert_eq!(read(&mut store, &ty, &policy, v.clone()).await?, v);
+
+        // well-formed, but out-of-policy discriminant
+        let err = read(
+            &mut store,
+            &ty,
+            &policy,
+            Val::record([
+                ("a", 42_u32.into_val()),
+                ("b", Val::list(Vec::<i8>::default())),
+                (
+                    "c",
+                    Val::Variant("str".into(), Some(Box::new(Val::String("foo".into())))),
+                ),
+            ]),
+        )
+        .await
+        .expect_err("out-of-policy discriminant should be rejected");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to read `c` field: discriminant `2` at path `[2]` is not allowed"
+        );
+
+        // nested discriminants are constrained separately from the outer one
+        let err = read(
+            &mut store,
+            &ty,
+            &policy,
+            Val::record([
+                ("a", 42_u32.into_val()),
+                ("b", Val::list(Vec::<i8>::default())),
+                (
+                    "c",
+                    Val::Variant("num".into(), Some(Box::new(Val::Option(None)))),
+                ),
+            ]),
+        )
+        .await
+        .expect_err("out-of-policy nested discriminant should be rejected");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to read `c` field: discriminant `0` at path `[2, 1]` is not allowed"
+        );
+
+        let err = read(
+            &mut store,
+            &ty,
+            &policy,
+            Val::record([
+                ("a", 42_u32.into_val()),
+                ("b", Val::list(Vec::<i8>::default())),
+                (
+                    "c",
+                    Val::Variant(
+                        "num".into(),
+                        Some(Box::new(Val::Option(Some(Box::new(Val::U32(42)))))),
+                    ),
+                ),
+            ]),
+        )
+        .await
+        .expect_err("out-of-range payload should be rejected");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to read `c` field: value `42` at path `[2, 1, 1]` is not within `0..=10`"
+        );
+
+        let err = read(
+            &mut store,
+            &ty,
+            &policy,
+            Val::record([
+                ("a", 300_u32.into_val()),
+                ("b", Val::list(Vec::<i8>::default())),
+                ("c", Val::Variant("none".into(), None)),
+            ]),
+        )
+        .await
+        .expect_err("out-of-range integer should be rejected");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to read `a` field: value `300` at path `[0]` is not within `0..=100`"
+        );
+
+        // wildcard matches all list elements
+        let err = read(
+            &mut store,
+            &ty,
+            &policy,
+            Val::record([
+                ("a", 0_u32.into_val()),
+                ("b", Val::list([0_i8, 2])),
+                ("c", Val::Variant("none".into(), None)),
+            ]),
+        )
+        .await
+        .expect_err("out-of-range list element should be rejected");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to read `b` field: value `2` at path `[1, 1]` is not within `-1..=1`"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,427 @@
+//! Derive macros for `wrpc_transport::Encode` and `wrpc_transport::Decode`
+//!
+//! Structs are encoded as tuples of their fields in declaration order, see
+//! `wrpc_transport::derive` for details.
+//!
+//! Structs with a single field annotated with `#[wrpc(transparent)]` are encoded exactly as the
+//! field.
+//!
+//! Enums without fields are encoded as their discriminants, which may be explicit and
+//! non-contiguous.
+//!
+//! Generated code refers to `::wrpc_transport` by default, which can be overridden using
+//! `#[wrpc(crate = "path::to::wrpc_transport")]`.
+
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Data, DataEnum, DeriveInput, Expr, ExprLit, Fields, Lit,
+    LitStr, Path,
+};
+
+/// Maximum number of struct fields, which is the maximum arity of tuples implementing
+/// `Encode` and `Decode`
+const MAX_FIELDS: usize = 16;
+
+/// Derives `wrpc_transport::Encode` for a struct or enum and a reference to it
+#[proc_macro_derive(Encode, attributes(wrpc))]
+pub fn derive_encode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let res = if let Data::Enum(data) = &input.data {
+        Enum::parse(&input, data).map(|e| e.expand_encode())
+    } else {
+        Struct::parse(&input).map(|s| s.expand_encode())
+    };
+    res.unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Derives `wrpc_transport::Decode` for a struct or enum
+#[proc_macro_derive(Decode, attributes(wrpc))]
+pub fn derive_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let res = if let Data::Enum(data) = &input.data {
+        Enum::parse(&input, data).map(|e| e.expand_decode())
+    } else {
+        Struct::parse(&input).map(|s| s.expand_decode())
+    };
+    res.unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// `#[wrpc(...)]` attributes of the item, which a derive macro is applied to
+struct Attrs {
+    krate: Path,
+    transparent: bool,
+}
+
+impl Attrs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut krate = parse_quote!(::wrpc_transport);
+        let mut transparent = false;
+        for attr in &input.attrs {
+            if !attr.path().is_ident("wrpc") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("crate") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    krate = path.parse()?;
+                    Ok(())
+                } else if meta.path.is_ident("transparent") {
+                    transparent = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported `wrpc` attribute"))
+                }
+            })?;
+        }
+        Ok(Self { krate, transparent })
+    }
+}
+
+/// Struct, which a derive macro is applied to
+struct Struct<'a> {
+    input: &'a DeriveInput,
+    fields: &'a Fields,
+    krate: Path,
+    /// Whether the struct is encoded as its only field, rather than a tuple of fields
+    transparent: bool,
+}
+
+impl<'a> Struct<'a> {
+    fn parse(input: &'a DeriveInput) -> syn::Result<Self> {
+        let Attrs { krate, transparent } = Attrs::parse(input)?;
+        let Data::Struct(data) = &input.data else {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`Encode` and `Decode` can only be derived for structs and enums",
+            ));
+        };
+        if data.fields.len() > MAX_FIELDS {
+            return Err(syn::Error::new_spanned(
+                &data.fields,
+                format!("structs with more than {MAX_FIELDS} fields are not supported"),
+            ));
+        }
+        if transparent && data.fields.len() != 1 {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`#[wrpc(transparent)]` requires a struct with exactly one field",
+            ));
+        }
+        Ok(Self {
+            input,
+            fields: &data.fields,
+            krate,
+            transparent,
+        })
+    }
+
+    /// Returns bindings of the struct fields, in declaration order
+    fn bindings(&self) -> Vec<Ident> {
+        (0..self.fields.len())
+            .map(|i| format_ident!("f{i}"))
+            .collect()
+    }
+
+    /// Returns a pattern, which binds the struct fields to [`Self::bindings`]. The same tokens
+    /// construct the struct from the bindings when used as an expression.
+    fn pattern(&self) -> TokenStream {
+        let bindings = self.bindings();
+        match self.fields {
+            Fields::Named(fields) => {
+                let names = fields.named.iter().map(|field| &field.ident);
+                quote!(Self { #(#names: #bindings),* })
+            }
+            Fields::Unnamed(..) => quote!(Self(#(#bindings),*)),
+            Fields::Unit => quote!(Self),
+        }
+    }
+
+    /// Returns the tuple of field types or the type of the only field of a transparent struct
+    fn fields_ty(&self) -> TokenStream {
+        let tys = self.fields.iter().map(|field| &field.ty);
+        if self.transparent {
+            quote!(#(#tys)*)
+        } else {
+            quote!((#(#tys,)*))
+        }
+    }
+
+    /// Returns an expression or pattern of the tuple of [`Self::bindings`] or the binding of the
+    /// only field of a transparent struct
+    fn bindings_tuple(&self) -> TokenStream {
+        let bindings = self.bindings();
+        if self.transparent {
+            quote!(#(#bindings)*)
+        } else {
+            quote!((#(#bindings,)*))
+        }
+    }
+
+    fn expand_encode(&self) -> TokenStream {
+        let Self { input, krate, .. } = self;
+        let name = &input.ident;
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+        let fields_ty = self.fields_ty();
+        let ref_tys = self.fields.iter().map(|field| &field.ty);
+        let refs_ty = if self.transparent {
+            quote!(#(&'__wrpc_a #ref_tys)*)
+        } else {
+            quote!((#(&'__wrpc_a #ref_tys,)*))
+        };
+        let bindings = self.bindings_tuple();
+        let pattern = self.pattern();
+        // struct without fields converts into `()`, which is returned by an empty body
+        let into_fields = if self.fields.is_empty() {
+            quote!()
+        } else {
+            quote! {
+                let #pattern = self;
+                #bindings
+            }
+        };
+
+        let mut ref_generics = input.generics.clone();
+        ref_generics.params.insert(0, parse_quote!('__wrpc_a));
+        ref_generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(Self: '__wrpc_a));
+        let (ref_impl_generics, _, ref_where_clause) = ref_generics.split_for_impl();
+
+        let mut enc_generics = input.generics.clone();
+        enc_generics.params.push(parse_quote!(__W));
+        enc_generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#fields_ty: #krate::Encode<__W>));
+        let (enc_impl_generics, _, enc_where_clause) = enc_generics.split_for_impl();
+
+        let mut enc_ref_generics = input.generics.clone();
+        enc_ref_generics.params.insert(0, parse_quote!('__wrpc_a));
+        enc_ref_generics.params.push(parse_quote!(__W));
+        let predicates = &mut enc_ref_generics.make_where_clause().predicates;
+        predicates.push(parse_quote!(#name #ty_generics: '__wrpc_a));
+        predicates.push(parse_quote!(#fields_ty: #krate::Encode<__W>));
+        predicates.push(parse_quote!(
+            <#fields_ty as #krate::Encode<__W>>::Encoder: #krate::derive::Encoder<#refs_ty>
+        ));
+        let (enc_ref_impl_generics, _, enc_ref_where_clause) = enc_ref_generics.split_for_impl();
+
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics #krate::derive::IntoStructFields for #name #ty_generics #where_clause {
+                type Fields = #fields_ty;
+
+                fn into_fields(self) -> Self::Fields {
+                    #into_fields
+                }
+            }
+
+            #[automatically_derived]
+            impl #ref_impl_generics #krate::derive::StructFieldRefs<'__wrpc_a> for #name #ty_generics #ref_where_clause {
+                type Refs = #refs_ty;
+
+                fn field_refs(&'__wrpc_a self) -> Self::Refs {
+                    #into_fields
+                }
+            }
+
+            #[automatically_derived]
+            impl #enc_impl_generics #krate::Encode<__W> for #name #ty_generics #enc_where_clause {
+                type Encoder = #krate::derive::StructEncoder<
+                    Self,
+                    <#fields_ty as #krate::Encode<__W>>::Encoder,
+                >;
+            }
+
+            #[automatically_derived]
+            impl #enc_ref_impl_generics #krate::Encode<__W> for &'__wrpc_a #name #ty_generics #enc_ref_where_clause {
+                type Encoder = #krate::derive::StructEncoder<
+                    #name #ty_generics,
+                    <#fields_ty as #krate::Encode<__W>>::Encoder,
+                >;
+            }
+        }
+    }
+
+    fn expand_decode(&self) -> TokenStream {
+        let Self { input, krate, .. } = self;
+        let name = &input.ident;
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+        let fields_ty = self.fields_ty();
+        let bindings = self.bindings_tuple();
+        let pattern = self.pattern();
+
+        let mut dec_generics = input.generics.clone();
+        dec_generics.params.push(parse_quote!(__R));
+        let predicates = &mut dec_generics.make_where_clause().predicates;
+        predicates.push(parse_quote!(Self: 'static));
+        predicates.push(parse_quote!(__R: 'static));
+        predicates.push(parse_quote!(#fields_ty: #krate::Decode<__R>));
+        let (dec_impl_generics, _, dec_where_clause) = dec_generics.split_for_impl();
+
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics #krate::derive::FromStructFields for #name #ty_generics #where_clause {
+                type Fields = #fields_ty;
+
+                fn from_fields(#bindings: Self::Fields) -> Self {
+                    #pattern
+                }
+            }
+
+            #[automatically_derived]
+            impl #dec_impl_generics #krate::Decode<__R> for #name #ty_generics #dec_where_clause {
+                type Decoder = #krate::derive::StructDecoder<
+                    Self,
+                    <#fields_ty as #krate::Decode<__R>>::Decoder,
+                >;
+                type ListDecoder = #krate::ListDecoder<Self::Decoder, __R>;
+            }
+        }
+    }
+}
+
+/// Enum without fields, which a derive macro is applied to
+struct Enum<'a> {
+    input: &'a DeriveInput,
+    krate: Path,
+    /// Variants along with their discriminants, in declaration order
+    variants: Vec<(&'a Ident, u32)>,
+}
+
+impl<'a> Enum<'a> {
+    fn parse(input: &'a DeriveInput, data: &'a DataEnum) -> syn::Result<Self> {
+        let Attrs { krate, transparent } = Attrs::parse(input)?;
+        if transparent {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`#[wrpc(transparent)]` is not supported on enums",
+            ));
+        }
+        let mut variants: Vec<(&Ident, u32)> = Vec::with_capacity(data.variants.len());
+        let mut next = Some(0);
+        for variant in &data.variants {
+            if !matches!(variant.fields, Fields::Unit) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "enum variants with fields are not supported",
+                ));
+            }
+            let disc = match &variant.discriminant {
+                Some((
+                    _,
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Int(lit), ..
+                    }),
+                )) => lit.base10_parse()?,
+                Some((_, expr)) => {
+                    return Err(syn::Error::new_spanned(
+                        expr,
+                        "enum discriminant must be an integer literal",
+                    ))
+                }
+                None => next.ok_or_else(|| {
+                    syn::Error::new_spanned(variant, "enum discriminant overflows `u32`")
+                })?,
+            };
+            if let Some((prev, _)) = variants.iter().find(|(_, d)| *d == disc) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    format!("discriminant `{disc}` is already assigned to `{prev}`"),
+                ));
+            }
+            variants.push((&variant.ident, disc));
+            next = disc.checked_add(1);
+        }
+        Ok(Self {
+            input,
+            krate,
+            variants,
+        })
+    }
+
+    fn expand_encode(&self) -> TokenStream {
+        let Self { input, krate, .. } = self;
+        let name = &input.ident;
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+        let names = self.variants.iter().map(|(name, _)| name);
+        let discs = self.variants.iter().map(|(_, disc)| disc);
+        // uninhabited enums have no variants to match on
+        let discriminant = if self.variants.is_empty() {
+            quote!(match *self {})
+        } else {
+            quote! {
+                match self {
+                    #(Self::#names => #discs,)*
+                }
+            }
+        };
+
+        let mut enc_generics = input.generics.clone();
+        enc_generics.params.push(parse_quote!(__W));
+        let (enc_impl_generics, _, enc_where_clause) = enc_generics.split_for_impl();
+
+        let mut enc_ref_generics = input.generics.clone();
+        enc_ref_generics.params.insert(0, parse_quote!('__wrpc_a));
+        enc_ref_generics.params.push(parse_quote!(__W));
+        enc_ref_generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#name #ty_generics: '__wrpc_a));
+        let (enc_ref_impl_generics, _, enc_ref_where_clause) = enc_ref_generics.split_for_impl();
+
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics #krate::derive::IntoEnumDiscriminant for #name #ty_generics #where_clause {
+                fn discriminant(&self) -> u32 {
+                    #discriminant
+                }
+            }
+
+            #[automatically_derived]
+            impl #enc_impl_generics #krate::Encode<__W> for #name #ty_generics #enc_where_clause {
+                type Encoder = #krate::derive::EnumEncoder<Self>;
+            }
+
+            #[automatically_derived]
+            impl #enc_ref_impl_generics #krate::Encode<__W> for &'__wrpc_a #name #ty_generics #enc_ref_where_clause {
+                type Encoder = #krate::derive::EnumEncoder<#name #ty_generics>;
+            }
+        }
+    }
+
+    fn expand_decode(&self) -> TokenStream {
+        let Self { input, krate, .. } = self;
+        let name = &input.ident;
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+        let names = self.variants.iter().map(|(name, _)| name);
+        let discs = self.variants.iter().map(|(_, disc)| disc);
+
+        let mut dec_generics = input.generics.clone();
+        dec_generics.params.push(parse_quote!(__R));
+        let predicates = &mut dec_generics.make_where_clause().predicates;
+        predicates.push(parse_quote!(Self: 'static));
+        predicates.push(parse_quote!(__R: 'static));
+        let (dec_impl_generics, _, dec_where_clause) = dec_generics.split_for_impl();
+
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics #krate::derive::FromEnumDiscriminant for #name #ty_generics #where_clause {
+                fn from_discriminant(disc: u32) -> ::core::option::Option<Self> {
+                    match disc {
+                        #(#discs => ::core::option::Option::Some(Self::#names),)*
+                        _ => ::core::option::Option::None,
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl #dec_impl_generics #krate::Decode<__R> for #name #ty_generics #dec_where_clause {
+                type Decoder = #krate::derive::EnumDecoder<Self>;
+                type ListDecoder = #krate::ListDecoder<Self::Decoder, __R>;
+            }
+        }
+    }
+}
@@ -1,22 +1,25 @@
 #![allow(clippy::type_complexity)]
 
 use core::future::Future;
-use core::iter::zip;
+use core::iter::{self, zip};
+use core::num::NonZeroUsize;
 use core::pin::{pin, Pin};
 use core::task::{ready, Context, Poll};
+use core::time::Duration;
 use core::{mem, str};
 
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, PoisonError, Weak};
 
-use anyhow::{anyhow, ensure, Context as _};
+use anyhow::{anyhow, bail, ensure, Context as _};
 use async_nats::{HeaderMap, Message, PublishMessage, ServerInfo, StatusCode, Subject, Subscriber};
 use bytes::{Buf as _, Bytes};
-use futures::future::try_join_all;
+use futures::future::{try_join_all, Either};
 use futures::sink::SinkExt as _;
 use futures::{Stream, StreamExt};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tokio::sync::oneshot;
-use tokio::try_join;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::{select, time, try_join};
 use tracing::{debug, instrument, trace, warn};
 use wrpc_transport::Index as _;
 
@@ -34,6 +37,77 @@ pub fn result_subject(prefix: &str) -> String {
     format!("{prefix}.results")
 }
 
+/// Connect to NATS.io server and ensure that the connection is fully established before
+/// returning the resulting [`async_nats::Client`].
+///
+/// Initial connection is retried according to `options` until it succeeds, so the server does
+/// not need to be running yet when this function is called.
+/// Event callback configured in `options`, if any, is replaced, use
+/// [`connect_with_event_callback`] to handle connection events.
+///
+/// ```
+/// # use core::time::Duration;
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> anyhow::Result<()> {
+/// # let mut server = tokio::process::Command::new("nats-server")
+/// #     .args(["-p", "14222"])
+/// #     .kill_on_drop(true)
+/// #     .spawn()?;
+/// let nats = wrpc_transport_nats::connect(
+///     "nats://127.0.0.1:14222",
+///     async_nats::ConnectOptions::new().connection_timeout(Duration::from_secs(1)),
+/// )
+/// .await?;
+/// assert_eq!(
+///     nats.connection_state(),
+///     async_nats::connection::State::Connected
+/// );
+/// let wrpc = wrpc_transport_nats::Client::new(nats, "rust", None);
+/// # server.kill().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn connect(
+    addrs: impl async_nats::ToServerAddrs,
+    options: async_nats::ConnectOptions,
+) -> anyhow::Result<async_nats::Client> {
+    connect_with_event_callback(addrs, options, |_| async {}).await
+}
+
+/// Like [`connect`], but also passes all connection events to `event_callback`
+#[instrument(level = "trace", skip_all)]
+pub async fn connect_with_event_callback<F, Fut>(
+    addrs: impl async_nats::ToServerAddrs,
+    options: async_nats::ConnectOptions,
+    event_callback: F,
+) -> anyhow::Result<async_nats::Client>
+where
+    F: Fn(async_nats::Event) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + Sync + 'static,
+{
+    let (conn_tx, mut conn_rx) = mpsc::channel(1);
+    let client = async_nats::connect_with_options(
+        addrs,
+        options
+            .retry_on_initial_connect()
+            .event_callback(move |event| {
+                if let async_nats::Event::Connected = event {
+                    debug!("NATS.io server connection established");
+                    // receiver is dropped once first connection is established
+                    let _ = conn_tx.try_send(());
+                }
+                event_callback(event)
+            }),
+    )
+    .await
+    .context("failed to connect to NATS.io server")?;
+    conn_rx
+        .recv()
+        .await
+        .context("failed to await NATS.io server connection to be established")?;
+    Ok(client)
+}
+
 #[must_use]
 #[inline]
 pub fn index_path(prefix: &str, path: &[usize]) -> String {
@@ -93,11 +167,100 @@ fn corrupted_memory_error() -> std::io::Error {
     std::io::Error::new(std::io::ErrorKind::Other, "corrupted memory state")
 }
 
+/// Returns an error corresponding to a non-success handshake response status, if any
+fn handshake_status_error(
+    status: Option<StatusCode>,
+    description: Option<&str>,
+) -> Option<std::io::Error> {
+    match status? {
+        StatusCode::NO_RESPONDERS => Some(std::io::ErrorKind::NotConnected.into()),
+        StatusCode::TIMEOUT => Some(std::io::ErrorKind::TimedOut.into()),
+        StatusCode::REQUEST_TERMINATED => Some(std::io::ErrorKind::UnexpectedEof.into()),
+        code if !code.is_success() => Some(std::io::Error::other(
+            if let Some(description) = description {
+                format!("received a response with code `{code}` ({description})")
+            } else {
+                format!("received a response with code `{code}`")
+            },
+        )),
+        _ => None,
+    }
+}
+
+/// Configuration of opt-in at-least-once invocation delivery.
+///
+/// NATS.io core provides at-most-once delivery, so an invocation handshake may be lost.
+/// With redelivery configured, the invoking [Client] waits for the handshake to be acknowledged
+/// and retransmits it, if no acknowledgement is received within `timeout`, doubling the timeout
+/// on each retry. The reply subject of the handshake, which is unique per invocation, serves as
+/// the idempotency key, which the serving [Client] uses to deduplicate retransmitted handshakes.
+///
+/// Note, that deduplication is local to the serving process, so it does not apply across
+/// multiple servers in a queue group.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Redelivery {
+    /// Time to wait for the first handshake acknowledgement
+    pub timeout: Duration,
+    /// Maximum number of handshake retransmissions
+    pub max_retries: usize,
+    /// Maximum number of accepted invocations remembered by server for deduplication
+    pub dedupe_capacity: usize,
+}
+
+impl Default for Redelivery {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(1),
+            max_retries: 3,
+            dedupe_capacity: 1024,
+        }
+    }
+}
+
+/// Bounded cache of recently accepted invocations, mapping the reply subject of the handshake
+/// to the inbox of the server
+#[derive(Debug, Default)]
+struct Dedupe {
+    capacity: usize,
+    accepted: HashMap<String, String>,
+    order: VecDeque<String>,
+}
+
+impl Dedupe {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            accepted: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn get(&self, tx: &str) -> Option<&str> {
+        self.accepted.get(tx).map(String::as_str)
+    }
+
+    fn insert(&mut self, tx: String, rx: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.order.len() >= self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.accepted.remove(&evicted);
+            }
+        }
+        if self.accepted.insert(tx.clone(), rx).is_none() {
+            self.order.push_back(tx);
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Client {
     nats: Arc<async_nats::Client>,
     prefix: Arc<str>,
     queue_group: Option<Arc<str>>,
+    redelivery: Option<Redelivery>,
+    subscriber: Option<CachingSubscriber>,
 }
 
 impl Client {
@@ -110,8 +273,153 @@ impl Client {
             nats: nats.into(),
             prefix: prefix.into(),
             queue_group,
+            redelivery: None,
+            subscriber: None,
         }
     }
+
+    /// Enables at-least-once invocation delivery, see [Redelivery]
+    #[must_use]
+    pub fn with_redelivery(mut self, redelivery: Redelivery) -> Self {
+        self.redelivery = Some(redelivery);
+        self
+    }
+
+    /// Subscribes on invocation subjects using `subscriber`, which shares a single NATS.io
+    /// subscription between all servers of the same function using the same `subscriber`.
+    ///
+    /// Queue group subscriptions are never shared.
+    #[must_use]
+    pub fn with_subscriber(mut self, subscriber: CachingSubscriber) -> Self {
+        self.subscriber = Some(subscriber);
+        self
+    }
+
+    /// Returns a [Client] sharing the connection and configuration of this one, which uses
+    /// subject prefix `prefix` instead, e.g. to route an invocation to a particular tenant
+    #[must_use]
+    pub fn on_prefix(&self, prefix: impl Into<Arc<str>>) -> Self {
+        Self {
+            nats: Arc::clone(&self.nats),
+            prefix: prefix.into(),
+            queue_group: self.queue_group.clone(),
+            redelivery: self.redelivery,
+            subscriber: self.subscriber.clone(),
+        }
+    }
+
+    /// Invoke function `func` on instance `instance` served under subject prefix `prefix`
+    ///
+    /// This is like [`InvokeExt::invoke_values_blocking`](wrpc_transport::InvokeExt::invoke_values_blocking),
+    /// but it overrides the prefix of this [Client] for a single invocation,
+    /// see [`Client::on_prefix`].
+    #[instrument(level = "trace", skip(self, cx, params, paths))]
+    pub async fn invoke_values_on<P, Params, Results>(
+        &self,
+        prefix: &str,
+        cx: Option<HeaderMap>,
+        instance: &str,
+        func: &str,
+        params: Params,
+        paths: impl AsRef<[P]> + Send,
+    ) -> anyhow::Result<Results>
+    where
+        P: AsRef<[Option<usize>]> + Send + Sync,
+        Params: wrpc_transport::TupleEncode<ParamWriter> + Send,
+        Results: wrpc_transport::TupleDecode<Reader> + Send,
+        <Params::Encoder as tokio_util::codec::Encoder<Params>>::Error:
+            std::error::Error + Send + Sync + 'static,
+        <Results::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        use wrpc_transport::InvokeExt as _;
+
+        self.on_prefix(prefix)
+            .invoke_values_blocking(cx, instance, func, params, paths)
+            .await
+    }
+
+    /// Serve function `func` on instance `instance` as a member of queue group `group`
+    ///
+    /// Invocations are load-balanced across all servers subscribed in the same queue group,
+    /// each invocation is delivered to exactly one of them.
+    /// This is like [`Serve::serve`](wrpc_transport::Serve::serve), but it overrides the queue
+    /// group of this [Client], see [`ClientBuilder::queue_group`].
+    #[instrument(level = "trace", skip(self, group, paths))]
+    pub async fn serve_shared(
+        &self,
+        group: impl Into<Arc<str>>,
+        instance: &str,
+        func: &str,
+        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+    ) -> anyhow::Result<
+        impl Stream<Item = anyhow::Result<(Option<HeaderMap>, SubjectWriter, Reader)>> + 'static,
+    > {
+        let subject = invocation_subject(&self.prefix, instance, func);
+        let group = group.into();
+        debug!(subject, ?group, "queue-subscribing on invocation subject");
+        let sub = self
+            .nats
+            .queue_subscribe(subject, group.to_string())
+            .await?;
+        Ok(self.accept(sub.map(Ok), paths.into()))
+    }
+}
+
+/// Builder of [Client]s sharing a single NATS.io connection and configuration.
+///
+/// Clients constructed by [`ClientBuilder::build`] only hold references to the shared state,
+/// which makes them cheap to construct per invocation. If enabled by
+/// [`ClientBuilder::cache_subscriptions`], all constructed clients share a
+/// [`CachingSubscriber`], which avoids re-subscribing on identical subjects.
+#[derive(Clone, Debug)]
+pub struct ClientBuilder(Client);
+
+impl ClientBuilder {
+    /// Constructs a new [`ClientBuilder`] using connection `nats` and an empty subject prefix
+    pub fn new(nats: impl Into<Arc<async_nats::Client>>) -> Self {
+        Self(Client::new(nats, "", None))
+    }
+
+    /// Sets the subject prefix used by constructed clients
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<Arc<str>>) -> Self {
+        self.0.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the queue group used by constructed clients for serving
+    #[must_use]
+    pub fn queue_group(mut self, queue_group: impl Into<Arc<str>>) -> Self {
+        self.0.queue_group = Some(queue_group.into());
+        self
+    }
+
+    /// Enables at-least-once invocation delivery for constructed clients, see [Redelivery]
+    #[must_use]
+    pub fn redelivery(self, redelivery: Redelivery) -> Self {
+        Self(self.0.with_redelivery(redelivery))
+    }
+
+    /// Shares subscriptions of all constructed clients using a single [`CachingSubscriber`],
+    /// which buffers at most `capacity` messages per subscription, see [`Client::with_subscriber`]
+    #[must_use]
+    pub fn cache_subscriptions(self, capacity: NonZeroUsize) -> Self {
+        let subscriber = CachingSubscriber::new(Arc::clone(&self.0.nats), capacity);
+        Self(self.0.with_subscriber(subscriber))
+    }
+
+    /// Returns the [`CachingSubscriber`] shared by constructed clients, if any
+    #[must_use]
+    pub fn subscriber(&self) -> Option<&CachingSubscriber> {
+        self.0.subscriber.as_ref()
+    }
+
+    /// Constructs a new [Client] sharing the connection and configuration of this builder
+    #[must_use]
+    pub fn build(&self) -> Client {
+        self.0.clone()
+    }
 }
 
 #[derive(Debug)]
@@ -130,23 +438,185 @@ impl Stream for ByteSubscription {
     }
 }
 
-#[derive(Default)]
-enum SubscriberTree {
+#[derive(Debug)]
+struct CachedSubscription {
+    tx: broadcast::Sender<Message>,
+    /// Guard held by each consumer, the subscription is dropped once all guards are dropped
+    guard: Weak<oneshot::Sender<()>>,
+}
+
+impl CachedSubscription {
+    fn consume(&self) -> Option<(broadcast::Receiver<Message>, Arc<oneshot::Sender<()>>)> {
+        let guard = self.guard.upgrade()?;
+        Some((self.tx.subscribe(), guard))
+    }
+}
+
+fn broadcast_stream(
+    rx: broadcast::Receiver<Message>,
+    guard: Arc<oneshot::Sender<()>>,
+) -> impl Stream<Item = anyhow::Result<Message>> {
+    futures::stream::unfold((rx, guard), |(mut rx, guard)| async move {
+        match rx.recv().await {
+            Ok(msg) => Some((Ok(msg), (rx, guard))),
+            Err(broadcast::error::RecvError::Lagged(n)) => Some((
+                Err(anyhow!(
+                    "cached subscription consumer lagged, `{n}` messages dropped"
+                )),
+                (rx, guard),
+            )),
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    })
+}
+
+/// Subscriber memoizing subscriptions by subject.
+///
+/// Subscribing to a subject, which already has an active subscription, shares the
+/// underlying NATS.io subscription instead of creating a new one. The NATS.io subscription
+/// is dropped once the last consumer of it goes away.
+///
+/// Every consumer receives all messages. A consumer falling more than `capacity` messages
+/// behind the fastest one misses messages, which is reported as an error by its stream.
+///
+/// See [`Client::with_subscriber`] for using a [`CachingSubscriber`] for serving.
+#[derive(Clone, Debug)]
+pub struct CachingSubscriber {
+    nats: Arc<async_nats::Client>,
+    subscriptions: Arc<std::sync::Mutex<HashMap<String, CachedSubscription>>>,
+    capacity: NonZeroUsize,
+}
+
+impl CachingSubscriber {
+    /// Constructs a new [`CachingSubscriber`], buffering at most `capacity` messages
+    /// per subscription for the slowest consumer
+    pub fn new(nats: impl Into<Arc<async_nats::Client>>, capacity: NonZeroUsize) -> Self {
+        Self {
+            nats: nats.into(),
+            subscriptions: Arc::default(),
+            capacity,
+        }
+    }
+
+    /// Returns the number of active NATS.io subscriptions
+    pub fn len(&self) -> usize {
+        self.subscriptions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .filter(|sub| sub.guard.strong_count() > 0)
+            .count()
+    }
+
+    /// Returns `true` if there are no active NATS.io subscriptions
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Subscribe on `subject`, reusing an active subscription, if one exists
+    #[instrument(level = "trace", skip_all)]
+    pub async fn subscribe(
+        &self,
+        subject: impl Into<String>,
+    ) -> anyhow::Result<impl Stream<Item = anyhow::Result<Message>> + Send + 'static> {
+        let subject = subject.into();
+        if let Some((rx, guard)) = self
+            .subscriptions
+            .lock()
+            .map_err(|_| corrupted_memory_error())?
+            .get(&subject)
+            .and_then(CachedSubscription::consume)
+        {
+            trace!(subject, "reusing cached subscription");
+            return Ok(broadcast_stream(rx, guard));
+        }
+        debug!(subject, "subscribing on subject");
+        let mut sub = self
+            .nats
+            .subscribe(Subject::from(subject.as_str()))
+            .await
+            .context("failed to subscribe on subject")?;
+        let mut subscriptions = self
+            .subscriptions
+            .lock()
+            .map_err(|_| corrupted_memory_error())?;
+        if let Some((rx, guard)) = subscriptions
+            .get(&subject)
+            .and_then(CachedSubscription::consume)
+        {
+            // another consumer subscribed concurrently, drop the redundant subscription
+            trace!(subject, "reusing concurrently cached subscription");
+            return Ok(broadcast_stream(rx, guard));
+        }
+        let (tx, rx) = broadcast::channel(self.capacity.get());
+        let (done_tx, mut done_rx) = oneshot::channel();
+        let guard = Arc::new(done_tx);
+        let weak = Arc::downgrade(&guard);
+        subscriptions.insert(
+            subject.clone(),
+            CachedSubscription {
+                tx: tx.clone(),
+                guard: weak.clone(),
+            },
+        );
+        let cached = Arc::clone(&self.subscriptions);
+        tokio::spawn(async move {
+            loop {
+                select! {
+                    msg = sub.next() => {
+                        let Some(msg) = msg else {
+                            break;
+                        };
+                        // sending only fails if all consumers are gone, in which case
+                        // `done_rx` resolves
+                        _ = tx.send(msg);
+                    }
+                    _ = &mut done_rx => break,
+                }
+            }
+            trace!(subject, "dropping cached subscription");
+            if let Ok(mut cached) = cached.lock() {
+                if cached
+                    .get(&subject)
+                    .is_some_and(|sub| Weak::ptr_eq(&sub.guard, &weak))
+                {
+                    cached.remove(&subject);
+                }
+            }
+        });
+        Ok(broadcast_stream(rx, guard))
+    }
+}
+
+/// Tree of subscribers for asynchronous values, keyed by their structural path.
+///
+/// `None` in a path denotes a wildcard, e.g. any element of a `list`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SubscriberTree<T = Subscriber>(SubscriberNode<T>);
+
+impl<T> Default for SubscriberTree<T> {
+    fn default() -> Self {
+        Self(SubscriberNode::Empty)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+enum SubscriberNode<T> {
     #[default]
     Empty,
-    Leaf(Subscriber),
+    Leaf(T),
     IndexNode {
-        subscriber: Option<Subscriber>,
-        nested: Vec<Option<SubscriberTree>>,
+        subscriber: Option<T>,
+        nested: Vec<Option<SubscriberNode<T>>>,
     },
     WildcardNode {
-        subscriber: Option<Subscriber>,
-        nested: Option<Box<SubscriberTree>>,
+        subscriber: Option<T>,
+        nested: Option<Box<SubscriberNode<T>>>,
     },
 }
 
-impl<'a> From<(&'a [Option<usize>], Subscriber)> for SubscriberTree {
-    fn from((path, sub): (&'a [Option<usize>], Subscriber)) -> Self {
+impl<'a, T> From<(&'a [Option<usize>], T)> for SubscriberNode<T> {
+    fn from((path, sub): (&'a [Option<usize>], T)) -> Self {
         match path {
             [] => Self::Leaf(sub),
             [None, path @ ..] => Self::WildcardNode {
@@ -167,44 +637,118 @@ impl<'a> From<(&'a [Option<usize>], Subscriber)> for SubscriberTree {
     }
 }
 
-impl<P: AsRef<[Option<usize>]>> FromIterator<(P, Subscriber)> for SubscriberTree {
-    fn from_iter<T: IntoIterator<Item = (P, Subscriber)>>(iter: T) -> Self {
-        let mut root = Self::Empty;
+impl<P: AsRef<[Option<usize>]>, T> FromIterator<(P, T)> for SubscriberTree<T> {
+    fn from_iter<I: IntoIterator<Item = (P, T)>>(iter: I) -> Self {
+        let mut root = SubscriberNode::Empty;
         for (path, sub) in iter {
             if !root.insert(path.as_ref(), sub) {
-                return Self::Empty;
+                return Self::default();
             }
         }
-        root
+        Self(root)
     }
 }
 
-impl SubscriberTree {
+/// Subscribes on [`subscribe_path`] under `prefix` once per unique path in `paths` and
+/// constructs a [`SubscriberTree`] of the resulting subscriptions.
+///
+/// Several paths may resolve to the same subject, e.g. when a type contains multiple
+/// asynchronous values at the same position. A single subscription is used for each subject.
+#[instrument(level = "trace", skip_all)]
+async fn subscribe_nested<P, T, F, Fut>(
+    prefix: &str,
+    paths: &[P],
+    subscribe: F,
+) -> anyhow::Result<SubscriberTree<T>>
+where
+    P: AsRef<[Option<usize>]>,
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut seen = HashSet::with_capacity(paths.len());
+    let paths: Vec<_> = paths
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|path| seen.insert(*path))
+        .collect();
+    let subs = try_join_all(
+        paths
+            .iter()
+            .map(|path| subscribe(subscribe_path(prefix, path))),
+    )
+    .await?;
+    let is_empty = paths.is_empty();
+    let nested: SubscriberTree<T> = zip(paths, subs).collect();
+    ensure!(
+        is_empty == nested.is_empty(),
+        "failed to construct subscription tree"
+    );
+    Ok(nested)
+}
+
+impl<T> SubscriberTree<T> {
     #[inline]
     fn is_empty(&self) -> bool {
-        matches!(self, SubscriberTree::Empty)
+        matches!(self.0, SubscriberNode::Empty)
+    }
+
+    fn take(&mut self, path: &[usize]) -> Option<T> {
+        self.0.take(path)
     }
 
+    /// Takes the subscriber at `path`, failing with an error describing the shape of the tree,
+    /// if there is none
+    fn try_take(&mut self, path: &[usize]) -> anyhow::Result<T> {
+        self.take(path).with_context(|| {
+            format!(
+                "unknown subscription for path `{path:?}`, subscription tree:\n{}",
+                self.render()
+            )
+        })
+    }
+
+    /// Renders the structure of the tree as an indented string with one node per line, e.g.:
+    ///
+    /// ```text
+    /// index
+    ///   0: leaf
+    ///   2: wildcard (subscribed)
+    ///     *: leaf
+    /// ```
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+        self.0.render(&mut s, 0);
+        s
+    }
+
+    /// Applies `f` to each subscriber in the tree, preserving the structure
+    pub fn map_subscriber<U>(self, mut f: impl FnMut(T) -> U) -> SubscriberTree<U> {
+        SubscriberTree(self.0.map_subscriber(&mut f))
+    }
+}
+
+impl<T> SubscriberNode<T> {
     #[instrument(level = "trace", skip_all)]
-    fn take(&mut self, path: &[usize]) -> Option<Subscriber> {
+    fn take(&mut self, path: &[usize]) -> Option<T> {
         let Some((i, path)) = path.split_first() else {
             return match mem::take(self) {
-                SubscriberTree::Empty => None,
-                SubscriberTree::Leaf(subscriber) => Some(subscriber),
-                SubscriberTree::IndexNode { subscriber, nested } => {
+                SubscriberNode::Empty => None,
+                SubscriberNode::Leaf(subscriber) => Some(subscriber),
+                SubscriberNode::IndexNode { subscriber, nested } => {
                     if !nested.is_empty() {
-                        *self = SubscriberTree::IndexNode {
+                        *self = SubscriberNode::IndexNode {
                             subscriber: None,
                             nested,
                         }
                     }
                     subscriber
                 }
-                SubscriberTree::WildcardNode { .. } => None,
+                SubscriberNode::WildcardNode { .. } => None,
                 // TODO: Demux the subscription
-                //SubscriberTree::WildcardNode { subscriber, nested } => {
+                //SubscriberNode::WildcardNode { subscriber, nested } => {
                 //    if let Some(nested) = nested {
-                //        *self = SubscriberTree::WildcardNode {
+                //        *self = SubscriberNode::WildcardNode {
                 //            subscriber: None,
                 //            nested: Some(nested),
                 //        }
@@ -226,10 +770,46 @@ impl SubscriberTree {
         }
     }
 
+    fn render(&self, s: &mut String, depth: usize) {
+        let subscribed = |s: &mut String, subscriber: &Option<T>| {
+            if subscriber.is_some() {
+                s.push_str(" (subscribed)");
+            }
+        };
+        let child = |s: &mut String, key: &str| {
+            s.push('\n');
+            s.extend(iter::repeat_n("  ", depth + 1));
+            s.push_str(key);
+            s.push_str(": ");
+        };
+        match self {
+            Self::Empty => s.push_str("empty"),
+            Self::Leaf(..) => s.push_str("leaf"),
+            Self::IndexNode { subscriber, nested } => {
+                s.push_str("index");
+                subscribed(s, subscriber);
+                for (i, nested) in nested.iter().enumerate() {
+                    if let Some(nested) = nested {
+                        child(s, &i.to_string());
+                        nested.render(s, depth + 1);
+                    }
+                }
+            }
+            Self::WildcardNode { subscriber, nested } => {
+                s.push_str("wildcard");
+                subscribed(s, subscriber);
+                if let Some(nested) = nested {
+                    child(s, "*");
+                    nested.render(s, depth + 1);
+                }
+            }
+        }
+    }
+
     /// Inserts `sub` under a `path` - returns `false` if it failed and `true` if it succeeded.
     /// Tree state after `false` is returned in undefined
     #[instrument(level = "trace", skip_all)]
-    fn insert(&mut self, path: &[Option<usize>], sub: Subscriber) -> bool {
+    fn insert(&mut self, path: &[Option<usize>], sub: T) -> bool {
         match self {
             Self::Empty => {
                 *self = Self::from((path, sub));
@@ -302,6 +882,24 @@ impl SubscriberTree {
             },
         }
     }
+
+    fn map_subscriber<U>(self, f: &mut impl FnMut(T) -> U) -> SubscriberNode<U> {
+        match self {
+            Self::Empty => SubscriberNode::Empty,
+            Self::Leaf(subscriber) => SubscriberNode::Leaf(f(subscriber)),
+            Self::IndexNode { subscriber, nested } => SubscriberNode::IndexNode {
+                subscriber: subscriber.map(&mut *f),
+                nested: nested
+                    .into_iter()
+                    .map(|nested| nested.map(|nested| nested.map_subscriber(f)))
+                    .collect(),
+            },
+            Self::WildcardNode { subscriber, nested } => SubscriberNode::WildcardNode {
+                subscriber: subscriber.map(&mut *f),
+                nested: nested.map(|nested| Box::new(nested.map_subscriber(f))),
+            },
+        }
+    }
 }
 
 pub struct Reader {
@@ -319,9 +917,7 @@ impl wrpc_transport::Index<Self> for Reader {
             .lock()
             .map_err(|err| anyhow!(err.to_string()).context("failed to lock map"))?;
         trace!("taking index subscription");
-        let incoming = nested
-            .take(path)
-            .with_context(|| format!("unknown subscription for path `{path:?}`"))?;
+        let incoming = nested.try_take(path)?;
         Ok(Self {
             buffer: Bytes::default(),
             incoming,
@@ -482,6 +1078,67 @@ impl RootParamWriter {
             buffer,
         }
     }
+
+    /// Constructs a [`RootParamWriter`] for an invocation with a completed handshake
+    fn accepted(tx: SubjectWriter, buffer: Bytes) -> Self {
+        if buffer.is_empty() {
+            Self::Active(tx)
+        } else {
+            Self::Draining { tx, buffer }
+        }
+    }
+}
+
+/// Awaits handshake acknowledgement on `sub`, retransmitting the handshake using `publish`
+/// according to `redelivery`. Returns the reply subject of the acknowledgement.
+#[instrument(level = "trace", skip_all)]
+async fn await_handshake<F, Fut>(
+    mut sub: Subscriber,
+    Redelivery {
+        mut timeout,
+        max_retries,
+        ..
+    }: Redelivery,
+    publish: F,
+) -> anyhow::Result<Subject>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut retries = 0;
+    loop {
+        let deadline = time::Instant::now() + timeout;
+        match time::timeout_at(deadline, sub.next()).await {
+            Ok(Some(Message {
+                status: Some(StatusCode::NO_RESPONDERS),
+                ..
+            })) => {
+                debug!("no responders to handshake, awaiting retransmission");
+                time::sleep_until(deadline).await;
+            }
+            Ok(Some(Message {
+                status,
+                description,
+                reply,
+                ..
+            })) => {
+                if let Some(err) = handshake_status_error(status, description.as_deref()) {
+                    return Err(anyhow!(err).context("handshake failed"));
+                }
+                return reply.context("peer did not specify a reply subject");
+            }
+            Ok(None) => bail!("handshake subscription unexpectedly closed"),
+            Err(_) => debug!("handshake acknowledgement timed out"),
+        }
+        ensure!(
+            retries < max_retries,
+            "handshake was not acknowledged after `{retries}` retries"
+        );
+        retries += 1;
+        timeout = timeout.saturating_mul(2);
+        debug!(retries, ?timeout, "retransmitting handshake");
+        publish().await?;
+    }
 }
 
 impl RootParamWriter {
@@ -493,32 +1150,20 @@ impl RootParamWriter {
                 trace!("polling for handshake response");
                 match sub.poll_next_unpin(cx) {
                     Poll::Ready(Some(Message {
-                        status: Some(StatusCode::NO_RESPONDERS),
-                        ..
-                    })) => Poll::Ready(Err(std::io::ErrorKind::NotConnected.into())),
-                    Poll::Ready(Some(Message {
-                        status: Some(StatusCode::TIMEOUT),
-                        ..
-                    })) => Poll::Ready(Err(std::io::ErrorKind::TimedOut.into())),
-                    Poll::Ready(Some(Message {
-                        status: Some(StatusCode::REQUEST_TERMINATED),
-                        ..
-                    })) => Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into())),
-                    Poll::Ready(Some(Message {
-                        status: Some(code),
+                        status,
                         description,
+                        reply,
                         ..
-                    })) if !code.is_success() => Poll::Ready(Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        if let Some(description) = description {
-                            format!("received a response with code `{code}` ({description})")
-                        } else {
-                            format!("received a response with code `{code}`")
-                        },
-                    ))),
-                    Poll::Ready(Some(Message {
-                        reply: Some(tx), ..
                     })) => {
+                        if let Some(err) = handshake_status_error(status, description.as_deref()) {
+                            return Poll::Ready(Err(err));
+                        }
+                        let Some(tx) = reply else {
+                            return Poll::Ready(Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidInput,
+                                "peer did not specify a reply subject",
+                            )));
+                        };
                         let Self::Handshaking {
                             tx: SubjectWriter { nats, .. },
                             indexed,
@@ -549,10 +1194,6 @@ impl RootParamWriter {
                             self.poll_active(cx)
                         }
                     }
-                    Poll::Ready(Some(..)) => Poll::Ready(Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        "peer did not specify a reply subject",
-                    ))),
                     Poll::Ready(None) => {
                         *self = Self::Corrupted;
                         Poll::Ready(Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe)))
@@ -845,24 +1486,19 @@ impl wrpc_transport::Invoke for Client {
                     .await
                     .context("failed to subscribe on handshake subject")
             },
-            try_join_all(paths.iter().map(|path| async {
+            subscribe_nested(&result_rx, paths, |subject| async {
                 self.nats
-                    .subscribe(Subject::from(subscribe_path(&result_rx, path.as_ref())))
+                    .subscribe(Subject::from(subject))
                     .await
                     .context("failed to subscribe on nested result subject")
-            }))
+            })
         )?;
-        let nested: SubscriberTree = zip(paths.iter(), nested).collect();
-        ensure!(
-            paths.is_empty() == nested.is_empty(),
-            "failed to construct subscription tree"
-        );
         let ServerInfo {
             mut max_payload, ..
         } = self.nats.server_info();
         max_payload = max_payload.saturating_sub(rx.len());
         let param_tx = Subject::from(invocation_subject(&self.prefix, instance, func));
-        if let Some(headers) = cx {
+        if let Some(headers) = &cx {
             // based on https://github.com/nats-io/nats.rs/blob/0942c473ce56163fdd1fbc62762f8164e3afa7bf/async-nats/src/header.rs#L215-L224
             max_payload = max_payload
                 .saturating_sub(b"NATS/1.0\r\n".len())
@@ -877,32 +1513,44 @@ impl wrpc_transport::Invoke for Client {
                         .saturating_sub(b"\r\n".len());
                 }
             }
-            trace!("publishing handshake");
-            self.nats
-                .publish_with_reply_and_headers(
-                    param_tx.clone(),
-                    rx,
-                    headers,
-                    params.split_to(max_payload.min(params.len())),
-                )
-                .await
+        }
+        let payload = params.split_to(max_payload.min(params.len()));
+        let publish = || {
+            let (nats, cx, param_tx, rx, payload) = (&self.nats, &cx, &param_tx, &rx, &payload);
+            async move {
+                trace!("publishing handshake");
+                if let Some(headers) = cx {
+                    nats.publish_with_reply_and_headers(
+                        param_tx.clone(),
+                        rx.clone(),
+                        headers.clone(),
+                        payload.clone(),
+                    )
+                    .await
+                } else {
+                    nats.publish_with_reply(param_tx.clone(), rx.clone(), payload.clone())
+                        .await
+                }
+                .context("failed to send handshake")
+            }
+        };
+        publish().await?;
+        let tx = if let Some(redelivery) = self.redelivery {
+            let tx = await_handshake(handshake_rx, redelivery, publish).await?;
+            trace!("handshake succeeded");
+            RootParamWriter::accepted(
+                SubjectWriter::new((*self.nats).clone(), Subject::from(param_subject(&tx))),
+                params,
+            )
         } else {
-            trace!("publishing handshake");
-            self.nats
-                .publish_with_reply(
-                    param_tx.clone(),
-                    rx,
-                    params.split_to(max_payload.min(params.len())),
-                )
-                .await
-        }
-        .context("failed to send handshake")?;
-        Ok((
-            ParamWriter::Root(RootParamWriter::new(
-                SubjectWriter::new((*self.nats).clone(), param_tx),
+            RootParamWriter::new(
+                SubjectWriter::new((*self.nats).clone(), param_tx.clone()),
                 handshake_rx,
                 params,
-            )),
+            )
+        };
+        Ok((
+            ParamWriter::Root(tx),
             Reader {
                 buffer: Bytes::default(),
                 incoming: result_rx,
@@ -910,46 +1558,73 @@ impl wrpc_transport::Invoke for Client {
             },
         ))
     }
-}
 
-impl wrpc_transport::Serve for Client {
-    type Context = Option<HeaderMap>;
-    type Outgoing = SubjectWriter;
-    type Incoming = Reader;
+    /// Waits until the connection to the NATS.io server is established.
+    ///
+    /// Flush is only processed by the connection handler once connected, which makes it a
+    /// convenient readiness probe. It fails if the handler is gone, e.g. after the client was
+    /// closed or the reconnection attempts were exhausted.
+    #[instrument(level = "trace", skip(self))]
+    async fn ready(&self) -> anyhow::Result<()> {
+        if self.nats.connection_state() == async_nats::connection::State::Connected {
+            return Ok(());
+        }
+        self.nats
+            .flush()
+            .await
+            .context("failed to establish NATS.io server connection")
+    }
+}
 
-    #[instrument(level = "trace", skip(self, paths))]
-    async fn serve(
+impl Client {
+    /// Accepts invocations received on `sub`
+    fn accept(
         &self,
-        instance: &str,
-        func: &str,
-        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
-    ) -> anyhow::Result<
-        impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>> + 'static,
-    > {
-        let subject = invocation_subject(&self.prefix, instance, func);
-        let sub = if let Some(group) = &self.queue_group {
-            debug!(subject, ?group, "queue-subscribing on invocation subject");
-            self.nats
-                .queue_subscribe(subject, group.to_string())
-                .await?
-        } else {
-            debug!(subject, "subscribing on invocation subject");
-            self.nats.subscribe(subject).await?
-        };
-        let paths = paths.into();
+        sub: impl Stream<Item = anyhow::Result<Message>> + Send + 'static,
+        paths: Arc<[Box<[Option<usize>]>]>,
+    ) -> impl Stream<Item = anyhow::Result<(Option<HeaderMap>, SubjectWriter, Reader)>> + 'static
+    {
         let nats = Arc::clone(&self.nats);
-        Ok(sub.then(
+        let dedupe = self.redelivery.map(
+            |Redelivery {
+                 dedupe_capacity, ..
+             }| { Arc::new(std::sync::Mutex::new(Dedupe::new(dedupe_capacity))) },
+        );
+        sub.filter_map(
             // NOTE: instrumenting this function causes a stack overflow
-            move |Message {
-                      reply: tx,
-                      payload,
-                      headers,
-                      ..
-                  }: Message| {
-                {
-                    let nats = Arc::clone(&nats);
-                    let paths = Arc::clone(&paths);
-                    async move {
+            move |msg: anyhow::Result<Message>| {
+                let nats = Arc::clone(&nats);
+                let paths = Arc::clone(&paths);
+                let dedupe = dedupe.clone();
+                async move {
+                    let Message {
+                        reply: tx,
+                        payload,
+                        headers,
+                        ..
+                    } = match msg {
+                        Ok(msg) => msg,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    if let (Some(dedupe), Some(tx)) = (&dedupe, &tx) {
+                        let rx = dedupe
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .get(tx.as_str())
+                            .map(String::from);
+                        if let Some(rx) = rx {
+                            debug!(?tx, "acknowledging retransmitted handshake");
+                            if let Err(err) = nats
+                                .publish_with_reply(tx.clone(), rx, Bytes::default())
+                                .await
+                                .context("failed to publish handshake accept")
+                            {
+                                return Some(Err(err));
+                            }
+                            return None;
+                        }
+                    }
+                    let res = async move {
                         let tx = tx.context("peer did not specify a reply subject")?;
                         let rx = nats.new_inbox();
                         let param_rx = Subject::from(param_subject(&rx));
@@ -963,23 +1638,23 @@ impl wrpc_transport::Serve for Client {
                                     .await
                                     .context("failed to subscribe on parameter subject")
                             },
-                            try_join_all(paths.iter().map(|path| async {
-                                let subject = subscribe_path(&param_rx, path.as_ref());
+                            subscribe_nested(&param_rx, &paths, |subject| async {
                                 trace!(?subject, "subscribing on nested parameter subject");
                                 nats.subscribe(Subject::from(subject))
                                     .await
                                     .context("failed to subscribe on nested parameter subject")
-                            }))
+                            })
                         )?;
-                        let nested: SubscriberTree = zip(paths.iter(), nested).collect();
-                        ensure!(
-                            paths.is_empty() == nested.is_empty(),
-                            "failed to construct subscription tree"
-                        );
                         trace!("publishing handshake response");
-                        nats.publish_with_reply(tx.clone(), rx, Bytes::default())
+                        nats.publish_with_reply(tx.clone(), rx.clone(), Bytes::default())
                             .await
                             .context("failed to publish handshake accept")?;
+                        if let Some(dedupe) = dedupe {
+                            dedupe
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .insert(tx.to_string(), rx);
+                        }
                         Ok((
                             headers,
                             SubjectWriter::new((*nats).clone(), Subject::from(result_subject(&tx))),
@@ -990,8 +1665,205 @@ impl wrpc_transport::Serve for Client {
                             },
                         ))
                     }
+                    .await;
+                    Some(res)
                 }
             },
-        ))
+        )
+    }
+}
+
+impl wrpc_transport::Serve for Client {
+    type Context = Option<HeaderMap>;
+    type Outgoing = SubjectWriter;
+    type Incoming = Reader;
+
+    #[instrument(level = "trace", skip(self, paths))]
+    async fn serve(
+        &self,
+        instance: &str,
+        func: &str,
+        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+    ) -> anyhow::Result<
+        impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>> + 'static,
+    > {
+        let subject = invocation_subject(&self.prefix, instance, func);
+        let sub = if let Some(group) = &self.queue_group {
+            debug!(subject, ?group, "queue-subscribing on invocation subject");
+            let sub = self
+                .nats
+                .queue_subscribe(subject, group.to_string())
+                .await?;
+            Either::Left(sub.map(Ok))
+        } else if let Some(subscriber) = &self.subscriber {
+            debug!(subject, "subscribing on invocation subject using cache");
+            Either::Right(subscriber.subscribe(subject).await?)
+        } else {
+            debug!(subject, "subscribing on invocation subject");
+            Either::Left(self.nats.subscribe(subject).await?.map(Ok))
+        };
+        Ok(self.accept(sub, paths.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_subscriber() {
+        let tree: SubscriberTree<u32> = [
+            (vec![], 0),
+            (vec![Some(1)], 1),
+            (vec![Some(1), None], 2),
+            (vec![Some(2), Some(0)], 3),
+        ]
+        .into_iter()
+        .collect();
+        let mut visited = vec![];
+        let mut tree = tree.map_subscriber(|v| {
+            visited.push(v);
+            format!("sub-{v}")
+        });
+        visited.sort_unstable();
+        assert_eq!(visited, [0, 1, 2, 3]);
+        assert_eq!(
+            tree,
+            [
+                (vec![], "sub-0".to_string()),
+                (vec![Some(1)], "sub-1".to_string()),
+                (vec![Some(1), None], "sub-2".to_string()),
+                (vec![Some(2), Some(0)], "sub-3".to_string()),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(tree.take(&[2, 0]).as_deref(), Some("sub-3"));
+        assert_eq!(tree.take(&[]).as_deref(), Some("sub-0"));
+    }
+
+    #[test]
+    fn future_stream() {
+        // `future<stream<T>>` parameter at index 1, subscriptions may be inserted in any order
+        for paths in [
+            [vec![Some(1)], vec![Some(1), Some(0)]],
+            [vec![Some(1), Some(0)], vec![Some(1)]],
+        ] {
+            let mut tree: SubscriberTree<Vec<Option<usize>>> =
+                paths.into_iter().map(|path| (path.clone(), path)).collect();
+            // the future subscription is taken first, the stream once the future resolves
+            assert_eq!(tree.take(&[1]), Some(vec![Some(1)]));
+            assert_eq!(tree.take(&[1, 0]), Some(vec![Some(1), Some(0)]));
+            assert_eq!(tree.take(&[1]), None);
+        }
+    }
+
+    #[test]
+    fn render() {
+        let mut tree: SubscriberTree<u32> = [
+            (vec![Some(0)], 0),
+            (vec![Some(2)], 1),
+            (vec![Some(2), None], 2),
+            (vec![Some(2), None, Some(1)], 3),
+        ]
+        .into_iter()
+        .collect();
+        let shape = "index
+  0: leaf
+  2: wildcard (subscribed)
+    *: index (subscribed)
+      1: leaf";
+        assert_eq!(tree.render(), shape);
+        assert_eq!(SubscriberTree::<u32>::default().render(), "empty");
+
+        let err = tree
+            .try_take(&[1])
+            .expect_err("missing subscription should fail");
+        assert_eq!(
+            err.to_string(),
+            format!("unknown subscription for path `[1]`, subscription tree:\n{shape}")
+        );
+        assert_eq!(tree.try_take(&[0]).unwrap(), 0);
+    }
+
+    #[test]
+    fn handshake_status() {
+        assert!(handshake_status_error(None, None).is_none());
+        assert!(handshake_status_error(Some(StatusCode::OK), None).is_none());
+        assert_eq!(
+            handshake_status_error(Some(StatusCode::NO_RESPONDERS), None).map(|err| err.kind()),
+            Some(std::io::ErrorKind::NotConnected)
+        );
+        assert_eq!(
+            handshake_status_error(Some(StatusCode::TIMEOUT), None).map(|err| err.kind()),
+            Some(std::io::ErrorKind::TimedOut)
+        );
+        assert_eq!(
+            handshake_status_error(Some(StatusCode::REQUEST_TERMINATED), None)
+                .map(|err| err.kind()),
+            Some(std::io::ErrorKind::UnexpectedEof)
+        );
+        let err = handshake_status_error(
+            Some(StatusCode::from_u16(500).expect("status code should be valid")),
+            Some("test"),
+        )
+        .expect("non-success status should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert_eq!(
+            err.to_string(),
+            "received a response with code `500` (test)"
+        );
+    }
+
+    #[test]
+    fn dedupe() {
+        let mut dedupe = Dedupe::new(2);
+        dedupe.insert("a".into(), "rx-a".into());
+        dedupe.insert("b".into(), "rx-b".into());
+        assert_eq!(dedupe.get("a"), Some("rx-a"));
+        assert_eq!(dedupe.get("b"), Some("rx-b"));
+
+        // oldest invocation is evicted
+        dedupe.insert("c".into(), "rx-c".into());
+        assert_eq!(dedupe.get("a"), None);
+        assert_eq!(dedupe.get("b"), Some("rx-b"));
+        assert_eq!(dedupe.get("c"), Some("rx-c"));
+
+        let mut dedupe = Dedupe::new(0);
+        dedupe.insert("a".into(), "rx-a".into());
+        assert_eq!(dedupe.get("a"), None);
+    }
+
+    #[tokio::test]
+    async fn subscribe_nested_dedup() -> anyhow::Result<()> {
+        let subscribed = std::sync::Mutex::new(vec![]);
+        let paths = [
+            vec![Some(0), None],
+            vec![Some(1)],
+            vec![Some(0), None],
+            vec![Some(1)],
+        ];
+        let tree = subscribe_nested("rx", &paths, |subject| {
+            subscribed.lock().unwrap().push(subject.clone());
+            async { Ok(subject) }
+        })
+        .await?;
+        assert_eq!(subscribed.into_inner().unwrap(), ["rx.0.*", "rx.1"]);
+        assert_eq!(
+            tree,
+            [
+                (vec![Some(0), None], "rx.0.*".to_string()),
+                (vec![Some(1)], "rx.1".to_string()),
+            ]
+            .into_iter()
+            .collect()
+        );
+
+        let tree = subscribe_nested("rx", &[] as &[Vec<Option<usize>>], |subject| async move {
+            bail!("unexpected subscription on `{subject}`")
+        })
+        .await?;
+        assert_eq!(tree, SubscriberTree::<String>::default());
+        Ok(())
     }
 }
@@ -53,10 +53,12 @@ pub enum IndexTree {
         nested: Vec<Option<IndexTree>>,
     },
     // TODO: Add partially-indexed `WildcardIndexNode`
+    /// Node matching any index. Subtrees are instantiated from `paths` on first use of an index.
     WildcardNode {
         tx: Option<oneshot::Sender<RecvStream>>,
         rx: Option<oneshot::Receiver<RecvStream>>,
-        nested: Option<Box<IndexTree>>,
+        paths: Vec<Box<[Option<usize>]>>,
+        nested: HashMap<usize, IndexTree>,
     },
 }
 
@@ -76,10 +78,12 @@ impl<'a>
     ) -> Self {
         match path {
             [] => Self::Leaf { tx, rx },
+            // channels are created for each index once it is used
             [None, path @ ..] => Self::WildcardNode {
                 tx: None,
                 rx: None,
-                nested: Some(Box::new(Self::from((path, tx, rx)))),
+                paths: vec![path.into()],
+                nested: HashMap::default(),
             },
             [Some(i), path @ ..] => Self::IndexNode {
                 tx: None,
@@ -134,6 +138,23 @@ impl<P: AsRef<[Option<usize>]>> FromIterator<P> for IndexTree {
 }
 
 impl IndexTree {
+    /// Returns `true` if all channels in this tree have been taken, in which case it can be pruned
+    fn is_exhausted(&self) -> bool {
+        match self {
+            Self::Empty => true,
+            Self::Leaf { tx, rx } => tx.is_none() && rx.is_none(),
+            Self::IndexNode { tx, rx, nested } => {
+                tx.is_none()
+                    && rx.is_none()
+                    && nested
+                        .iter()
+                        .all(|nested| nested.as_ref().is_none_or(Self::is_exhausted))
+            }
+            // subtrees are instantiated for any index on first use
+            Self::WildcardNode { .. } => false,
+        }
+    }
+
     #[instrument(level = "trace", skip(self))]
     fn take_rx(&mut self, path: &[usize]) -> Option<oneshot::Receiver<RecvStream>> {
         let Some((i, path)) = path.split_first() else {
@@ -147,9 +168,9 @@ impl IndexTree {
                     }
                     rx
                 }
-                Self::WildcardNode { tx, rx, nested } => {
+                Self::WildcardNode { tx, rx, paths, .. } => {
                     let rx = rx.take();
-                    if nested.is_none() && tx.is_none() {
+                    if paths.is_empty() && tx.is_none() {
                         *self = Self::Empty;
                     }
                     rx
@@ -157,14 +178,27 @@ impl IndexTree {
             };
         };
         match self {
-            Self::Empty | Self::Leaf { .. } | Self::WildcardNode { .. } => None,
-            Self::IndexNode { ref mut nested, .. } => nested
-                .get_mut(*i)
-                .and_then(|nested| nested.as_mut().and_then(|nested| nested.take_rx(path))),
-            // TODO: Demux the subscription
-            //Self::WildcardNode { ref mut nested, .. } => {
-            //    nested.as_mut().and_then(|nested| nested.take(path))
-            //}
+            Self::Empty | Self::Leaf { .. } => None,
+            Self::IndexNode { ref mut nested, .. } => {
+                let slot = nested.get_mut(*i)?;
+                let rx = slot.as_mut()?.take_rx(path);
+                if slot.as_ref().is_some_and(Self::is_exhausted) {
+                    *slot = None;
+                }
+                rx
+            }
+            Self::WildcardNode {
+                ref paths,
+                ref mut nested,
+                ..
+            } => {
+                let tree = nested.entry(*i).or_insert_with(|| paths.iter().collect());
+                let rx = tree.take_rx(path);
+                if tree.is_exhausted() {
+                    nested.remove(i);
+                }
+                rx
+            }
         }
     }
 
@@ -181,9 +215,9 @@ impl IndexTree {
                     }
                     tx
                 }
-                Self::WildcardNode { tx, rx, nested } => {
+                Self::WildcardNode { tx, rx, paths, .. } => {
                     let tx = tx.take();
-                    if nested.is_none() && rx.is_none() {
+                    if paths.is_empty() && rx.is_none() {
                         *self = Self::Empty;
                     }
                     tx
@@ -191,14 +225,27 @@ impl IndexTree {
             };
         };
         match self {
-            Self::Empty | Self::Leaf { .. } | Self::WildcardNode { .. } => None,
-            Self::IndexNode { ref mut nested, .. } => nested
-                .get_mut(*i)
-                .and_then(|nested| nested.as_mut().and_then(|nested| nested.take_tx(path))),
-            // TODO: Demux the subscription
-            //Self::WildcardNode { ref mut nested, .. } => {
-            //    nested.as_mut().and_then(|nested| nested.take(path))
-            //}
+            Self::Empty | Self::Leaf { .. } => None,
+            Self::IndexNode { ref mut nested, .. } => {
+                let slot = nested.get_mut(*i)?;
+                let tx = slot.as_mut()?.take_tx(path);
+                if slot.as_ref().is_some_and(Self::is_exhausted) {
+                    *slot = None;
+                }
+                tx
+            }
+            Self::WildcardNode {
+                ref paths,
+                ref mut nested,
+                ..
+            } => {
+                let tree = nested.entry(*i).or_insert_with(|| paths.iter().collect());
+                let tx = tree.take_tx(path);
+                if tree.is_exhausted() {
+                    nested.remove(i);
+                }
+                tx
+            }
         }
     }
 
@@ -233,7 +280,8 @@ impl IndexTree {
                     *self = Self::WildcardNode {
                         tx,
                         rx,
-                        nested: Some(Box::new(Self::from((path, sender, receiver)))),
+                        paths: vec![path.into()],
+                        nested: HashMap::default(),
                     };
                 }
                 true
@@ -266,6 +314,7 @@ impl IndexTree {
             Self::WildcardNode {
                 ref mut tx,
                 ref mut rx,
+                ref mut paths,
                 ref mut nested,
             } => match (&tx, &rx, path) {
                 (None, None, []) => {
@@ -274,12 +323,11 @@ impl IndexTree {
                     true
                 }
                 (_, _, [None, path @ ..]) => {
-                    if let Some(nested) = nested {
-                        nested.insert(path, sender, receiver)
-                    } else {
-                        *nested = Some(Box::new(Self::from((path, sender, receiver))));
-                        true
-                    }
+                    paths.push(path.into());
+                    nested.values_mut().all(|nested| {
+                        let (tx, rx) = oneshot::channel();
+                        nested.insert(path, Some(tx), Some(rx))
+                    })
                 }
                 _ => false,
             },
@@ -802,3 +850,31 @@ impl wrpc_transport::Serve for Server {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn index_tree_prune() {
+        let mut tree = IndexTree::from_iter([[Some(0), None, Some(1)].as_slice(), &[Some(1)]]);
+        for i in 0..0x100 {
+            assert!(tree.take_rx(&[0, i, 1]).is_some());
+            assert!(tree.take_tx(&[0, i, 1]).is_some());
+        }
+        let IndexTree::IndexNode { nested, .. } = &tree else {
+            panic!("root should be an index node");
+        };
+        let Some(IndexTree::WildcardNode { nested, .. }) = &nested[0] else {
+            panic!("`[0]` should be a wildcard node");
+        };
+        assert!(nested.is_empty(), "exhausted subtrees should be pruned");
+
+        assert!(tree.take_tx(&[1]).is_some());
+        assert!(tree.take_rx(&[1]).is_some());
+        let IndexTree::IndexNode { nested, .. } = &tree else {
+            panic!("root should be an index node");
+        };
+        assert!(nested[1].is_none(), "exhausted subtree should be pruned");
+    }
+}
@@ -0,0 +1,923 @@
+//! wRPC transport over [Redis](https://redis.io) pub/sub.
+//!
+//! Redis pub/sub provides at-most-once delivery: messages published on a channel without
+//! subscribers are dropped and nothing is ever redelivered. To avoid losing data, each side of
+//! an invocation subscribes on its inbox before revealing it to the peer:
+//!
+//! 1. The client subscribes on its inbox and publishes the inbox name followed by the parameters
+//!    on the invocation channel.
+//! 2. The server subscribes on a fresh inbox and publishes its name on the client inbox.
+//! 3. Nested parameters are then sent to the server inbox and results to the client inbox.
+//!
+//! Every server subscribed on the invocation channel would receive the invocation and respond
+//! to it, there is no load balancing between servers. Invocations therefore fail unless exactly
+//! one server is subscribed on the invocation channel.
+//!
+//! Messages are buffered per channel until read. Once more than [`CHANNEL_CAPACITY`] messages
+//! are buffered on a channel, it is closed and reading from it fails, since dropping any
+//! further message would silently corrupt the stream.
+
+#![allow(clippy::type_complexity)]
+
+use core::fmt;
+use core::mem;
+use core::pin::Pin;
+use core::str;
+use core::task::{ready, Context, Poll};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use anyhow::{anyhow, ensure, Context as _};
+use bytes::{BufMut as _, Bytes, BytesMut};
+use futures::future::{BoxFuture, Shared};
+use futures::{stream, FutureExt as _, Stream, StreamExt as _};
+use redis::aio::{MultiplexedConnection, PubSubSink, PubSubStream};
+use redis::AsyncCommands as _;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio_util::codec::{Decoder as _, Encoder as _};
+use tokio_util::task::AbortOnDropHandle;
+use tracing::{debug, instrument, trace, warn};
+use wasm_tokio::{CoreNameDecoder, CoreNameEncoder};
+
+pub const PROTOCOL: &str = "wrpc.0.0.1";
+
+/// Maximum number of messages buffered per channel until they are read
+pub const CHANNEL_CAPACITY: usize = 1024;
+
+#[must_use]
+#[inline]
+pub fn param_channel(prefix: &str) -> String {
+    format!("{prefix}.params")
+}
+
+#[must_use]
+#[inline]
+pub fn result_channel(prefix: &str) -> String {
+    format!("{prefix}.results")
+}
+
+#[must_use]
+#[inline]
+pub fn index_channel(prefix: &str, path: &[usize]) -> String {
+    let mut s = String::with_capacity(prefix.len() + path.len() * 2);
+    if !prefix.is_empty() {
+        s.push_str(prefix);
+    }
+    for p in path {
+        if !s.is_empty() {
+            s.push('.');
+        }
+        s.push_str(&p.to_string());
+    }
+    s
+}
+
+#[must_use]
+#[inline]
+pub fn invocation_channel(prefix: &str, instance: &str, func: &str) -> String {
+    let mut s =
+        String::with_capacity(prefix.len() + PROTOCOL.len() + instance.len() + func.len() + 3);
+    if !prefix.is_empty() {
+        s.push_str(prefix);
+        s.push('.');
+    }
+    s.push_str(PROTOCOL);
+    s.push('.');
+    if !instance.is_empty() {
+        s.push_str(instance);
+        s.push('.');
+    }
+    s.push_str(func);
+    s
+}
+
+fn new_inbox() -> String {
+    format!("_INBOX.{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Encodes an invocation message, which consists of the reply inbox name followed by parameters
+fn encode_invocation(inbox: &str, params: &[u8]) -> anyhow::Result<Bytes> {
+    let mut buf = BytesMut::with_capacity(5 + inbox.len() + params.len());
+    CoreNameEncoder
+        .encode(inbox, &mut buf)
+        .context("failed to encode inbox name")?;
+    buf.put_slice(params);
+    Ok(buf.freeze())
+}
+
+/// Decodes an invocation message produced by [`encode_invocation`]
+fn decode_invocation(payload: &[u8]) -> anyhow::Result<(String, Bytes)> {
+    let mut buf = BytesMut::from(payload);
+    let inbox = CoreNameDecoder::default()
+        .decode(&mut buf)
+        .context("failed to decode inbox name")?
+        .context("invocation message is truncated")?;
+    Ok((inbox.to_string(), buf.freeze()))
+}
+
+/// Sending half of a bounded per-channel message buffer, which is closed with an error once
+/// the buffer is full
+#[derive(Debug)]
+struct ChannelSender(Option<mpsc::Sender<std::io::Result<Bytes>>>);
+
+fn channel() -> (ChannelSender, mpsc::Receiver<std::io::Result<Bytes>>) {
+    // one slot is reserved for the overflow error
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY + 1);
+    (ChannelSender(Some(tx)), rx)
+}
+
+impl ChannelSender {
+    fn send(&mut self, channel: &str, payload: Bytes) {
+        let Some(tx) = self.0.as_ref() else {
+            trace!(channel, "channel overflowed, discard message");
+            return;
+        };
+        // messages are only ever sent by the router task, so capacity cannot shrink concurrently
+        let res = if tx.capacity() > 1 {
+            tx.try_send(Ok(payload))
+        } else {
+            warn!(channel, "channel buffer full, close channel");
+            let res = tx.try_send(Err(std::io::Error::other(format!(
+                "more than {CHANNEL_CAPACITY} messages buffered on `{channel}`"
+            ))));
+            self.0 = None;
+            res
+        };
+        match res {
+            Ok(()) => {}
+            Err(TrySendError::Closed(..)) => trace!(channel, "receiver dropped, discard message"),
+            Err(TrySendError::Full(..)) => warn!(channel, "channel buffer full, discard message"),
+        }
+    }
+
+    /// Closes the channel with an error, which is received after all buffered messages
+    fn close(&mut self, channel: &str) {
+        let Some(tx) = self.0.take() else {
+            return;
+        };
+        trace!(channel, "close channel");
+        // a slot is always reserved for an error, so this only fails if the receiver was dropped
+        let _ = tx.try_send(Err(std::io::Error::new(
+            std::io::ErrorKind::BrokenPipe,
+            "Redis pub/sub connection closed",
+        )));
+    }
+}
+
+type Slot = (
+    ChannelSender,
+    Option<mpsc::Receiver<std::io::Result<Bytes>>>,
+);
+
+/// Demultiplexes messages received via a pattern subscription by channel name.
+///
+/// Messages received before the channel is taken are buffered.
+#[derive(Debug, Default)]
+struct Mailbox {
+    slots: Mutex<HashMap<String, Slot>>,
+    closed: AtomicBool,
+}
+
+impl Mailbox {
+    fn push(&self, channel: &str, payload: Bytes) {
+        let mut slots = self.slots.lock().unwrap_or_else(PoisonError::into_inner);
+        let (tx, _) = slots.entry(channel.to_string()).or_insert_with(|| {
+            let (tx, rx) = self::channel();
+            (tx, Some(rx))
+        });
+        tx.send(channel, payload);
+    }
+
+    fn take(&self, channel: &str) -> anyhow::Result<mpsc::Receiver<std::io::Result<Bytes>>> {
+        let mut slots = self.slots.lock().unwrap_or_else(PoisonError::into_inner);
+        let (_, rx) = slots.entry(channel.to_string()).or_insert_with(|| {
+            let (mut tx, rx) = self::channel();
+            if self.closed.load(Ordering::Relaxed) {
+                tx.close(channel);
+            }
+            (tx, Some(rx))
+        });
+        rx.take()
+            .with_context(|| format!("channel `{channel}` already taken"))
+    }
+
+    /// Closes all channels, including ones taken later
+    fn close(&self) {
+        let mut slots = self.slots.lock().unwrap_or_else(PoisonError::into_inner);
+        self.closed.store(true, Ordering::Relaxed);
+        for (channel, (tx, _)) in slots.iter_mut() {
+            tx.close(channel);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Routes {
+    channels: Mutex<HashMap<String, ChannelSender>>,
+    patterns: Mutex<HashMap<String, Arc<Mailbox>>>,
+}
+
+impl Routes {
+    fn route(&self, msg: &redis::Msg) {
+        let channel = msg.get_channel_name();
+        let payload = Bytes::copy_from_slice(msg.get_payload_bytes());
+        match msg.get_pattern::<Option<String>>() {
+            Ok(Some(pattern)) => {
+                let mailbox = self
+                    .patterns
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .get(&pattern)
+                    .map(Arc::clone);
+                if let Some(mailbox) = mailbox {
+                    mailbox.push(channel, payload);
+                } else {
+                    trace!(pattern, channel, "no route for pattern, discard message");
+                }
+            }
+            Ok(None) => {
+                let mut channels = self.channels.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some(tx) = channels.get_mut(channel) {
+                    tx.send(channel, payload);
+                } else {
+                    trace!(channel, "no route for channel, discard message");
+                }
+            }
+            Err(err) => warn!(?err, channel, "failed to parse message pattern"),
+        }
+    }
+
+    /// Removes all routes and closes their channels, so that consumers do not wait for
+    /// messages, which will never arrive
+    fn close(&self) {
+        let channels =
+            mem::take(&mut *self.channels.lock().unwrap_or_else(PoisonError::into_inner));
+        for (channel, mut tx) in channels {
+            tx.close(&channel);
+        }
+        let patterns =
+            mem::take(&mut *self.patterns.lock().unwrap_or_else(PoisonError::into_inner));
+        for mailbox in patterns.into_values() {
+            mailbox.close();
+        }
+    }
+}
+
+async fn route(routes: Arc<Routes>, mut stream: PubSubStream) {
+    while let Some(msg) = stream.next().await {
+        routes.route(&msg);
+    }
+    debug!("Redis pub/sub stream finished");
+    routes.close();
+}
+
+/// Pattern subscription on an inbox, unsubscribed on drop
+struct Inbox {
+    pattern: String,
+    mailbox: Arc<Mailbox>,
+    routes: Arc<Routes>,
+    sink: PubSubSink,
+}
+
+impl fmt::Debug for Inbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Inbox")
+            .field("pattern", &self.pattern)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Inbox {
+    fn drop(&mut self) {
+        self.routes
+            .patterns
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.pattern);
+        if let Ok(rt) = tokio::runtime::Handle::try_current() {
+            let mut sink = self.sink.clone();
+            let pattern = self.pattern.clone();
+            rt.spawn(async move {
+                if let Err(err) = sink.punsubscribe(&pattern).await {
+                    debug!(?err, pattern, "failed to unsubscribe from inbox");
+                }
+            });
+        }
+    }
+}
+
+/// Channel subscription, unsubscribed on drop
+struct Subscription {
+    channel: String,
+    routes: Arc<Routes>,
+    sink: PubSubSink,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.routes
+            .channels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.channel);
+        if let Ok(rt) = tokio::runtime::Handle::try_current() {
+            let mut sink = self.sink.clone();
+            let channel = self.channel.clone();
+            rt.spawn(async move {
+                if let Err(err) = sink.unsubscribe(&channel).await {
+                    debug!(?err, channel, "failed to unsubscribe from channel");
+                }
+            });
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    conn: MultiplexedConnection,
+    sink: PubSubSink,
+    routes: Arc<Routes>,
+    prefix: Arc<str>,
+    router: Arc<AbortOnDropHandle<()>>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("conn", &self.conn)
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Client {
+    /// Connects to Redis server using `redis` and constructs a new [`Client`].
+    ///
+    /// A dedicated pub/sub connection is established, which is shared by all clones of the
+    /// returned [`Client`].
+    #[instrument(level = "trace", skip_all)]
+    pub async fn new(redis: &redis::Client, prefix: impl Into<Arc<str>>) -> anyhow::Result<Self> {
+        let conn = redis
+            .get_multiplexed_async_connection()
+            .await
+            .context("failed to establish Redis connection")?;
+        let (sink, stream) = redis
+            .get_async_pubsub()
+            .await
+            .context("failed to establish Redis pub/sub connection")?
+            .split();
+        let routes = Arc::default();
+        let router = tokio::spawn(route(Arc::clone(&routes), stream));
+        Ok(Self {
+            conn,
+            sink,
+            routes,
+            prefix: prefix.into(),
+            router: Arc::new(AbortOnDropHandle::new(router)),
+        })
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    async fn subscribe_inbox(&self, inbox: &str) -> anyhow::Result<Arc<Inbox>> {
+        let pattern = format!("{inbox}*");
+        let mailbox = Arc::<Mailbox>::default();
+        self.routes
+            .patterns
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(pattern.clone(), Arc::clone(&mailbox));
+        let inbox = Arc::new(Inbox {
+            pattern,
+            mailbox,
+            routes: Arc::clone(&self.routes),
+            sink: self.sink.clone(),
+        });
+        self.sink
+            .clone()
+            .psubscribe(&inbox.pattern)
+            .await
+            .context("failed to subscribe on inbox")?;
+        Ok(inbox)
+    }
+}
+
+pub struct Reader {
+    buffer: Bytes,
+    incoming: mpsc::Receiver<std::io::Result<Bytes>>,
+    channel: String,
+    inbox: Arc<Inbox>,
+}
+
+impl wrpc_transport::Index<Self> for Reader {
+    #[instrument(level = "trace", skip(self))]
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let channel = index_channel(&self.channel, path);
+        let incoming = self.inbox.mailbox.take(&channel)?;
+        Ok(Self {
+            buffer: Bytes::default(),
+            incoming,
+            channel,
+            inbox: Arc::clone(&self.inbox),
+        })
+    }
+}
+
+impl AsyncRead for Reader {
+    #[instrument(level = "trace", skip_all, ret, fields(channel = self.channel))]
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let cap = buf.remaining();
+        if cap == 0 {
+            trace!("attempt to read empty buffer");
+            return Poll::Ready(Ok(()));
+        }
+
+        if self.buffer.is_empty() {
+            trace!("polling for next message");
+            match ready!(self.incoming.poll_recv(cx)) {
+                Some(Ok(payload)) => {
+                    trace!(?payload, "received message");
+                    self.buffer = payload;
+                }
+                Some(Err(err)) => {
+                    trace!(?err, "subscription failed");
+                    return Poll::Ready(Err(err));
+                }
+                None => {
+                    trace!("subscription finished");
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        }
+        let n = cap.min(self.buffer.len());
+        trace!(n, cap, len = self.buffer.len(), "reading buffer");
+        buf.put_slice(&self.buffer.split_to(n));
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Publishes written buffers on a Redis channel, an empty message signals end of stream.
+///
+/// At most one publish is in flight at a time, which preserves message order. If the writer
+/// is dropped without being shut down, e.g. because a handler failed, the stream is shut down
+/// in the background, so that the reader does not wait for messages forever.
+#[derive(Debug)]
+pub struct ChannelWriter {
+    conn: MultiplexedConnection,
+    channel: String,
+    publish: Option<JoinHandle<redis::RedisResult<()>>>,
+    shutdown: bool,
+}
+
+impl ChannelWriter {
+    fn new(conn: MultiplexedConnection, channel: String) -> Self {
+        Self {
+            conn,
+            channel,
+            publish: None,
+            shutdown: false,
+        }
+    }
+
+    fn start_publish(&mut self, payload: Bytes) {
+        let mut conn = self.conn.clone();
+        let channel = self.channel.clone();
+        self.publish = Some(tokio::spawn(async move {
+            conn.publish::<_, _, ()>(channel, payload.as_ref()).await
+        }));
+    }
+
+    fn poll_publish(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let Some(publish) = self.publish.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+        let res = ready!(publish.poll_unpin(cx));
+        self.publish = None;
+        match res {
+            Ok(Ok(())) => Poll::Ready(Ok(())),
+            Ok(Err(err)) => Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                err,
+            ))),
+            Err(err) => Poll::Ready(Err(std::io::Error::other(err))),
+        }
+    }
+}
+
+impl Drop for ChannelWriter {
+    fn drop(&mut self) {
+        if self.shutdown {
+            return;
+        }
+        if let Ok(rt) = tokio::runtime::Handle::try_current() {
+            let mut conn = self.conn.clone();
+            let channel = self.channel.clone();
+            let publish = self.publish.take();
+            rt.spawn(async move {
+                if let Some(publish) = publish {
+                    // the empty message must not overtake the last written buffer
+                    let _ = publish.await;
+                }
+                trace!(
+                    channel,
+                    "publishing empty message to shut down dropped stream"
+                );
+                if let Err(err) = conn.publish::<_, _, ()>(&channel, b"".as_slice()).await {
+                    debug!(?err, channel, "failed to shut down dropped stream");
+                }
+            });
+        }
+    }
+}
+
+impl wrpc_transport::Index<Self> for ChannelWriter {
+    #[instrument(level = "trace", skip(self))]
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            self.conn.clone(),
+            index_channel(&self.channel, path),
+        ))
+    }
+}
+
+impl AsyncWrite for ChannelWriter {
+    #[instrument(level = "trace", skip_all, ret, fields(channel = self.channel, buf = format!("{buf:02x?}")))]
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        ready!(self.poll_publish(cx))?;
+        trace!("starting publish");
+        self.start_publish(Bytes::copy_from_slice(buf));
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    #[instrument(level = "trace", skip_all, ret, fields(channel = self.channel))]
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_publish(cx)
+    }
+
+    #[instrument(level = "trace", skip_all, ret, fields(channel = self.channel))]
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        ready!(self.poll_publish(cx))?;
+        if !self.shutdown {
+            trace!("publishing empty message to shut down stream");
+            self.shutdown = true;
+            self.start_publish(Bytes::default());
+            ready!(self.poll_publish(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Writes parameters to the server inbox, once it is known from the handshake
+pub struct ParamWriter {
+    conn: MultiplexedConnection,
+    handshake: Shared<BoxFuture<'static, Result<Arc<str>, Arc<str>>>>,
+    path: Box<[usize]>,
+    writer: Option<ChannelWriter>,
+    _inbox: Arc<Inbox>,
+}
+
+impl ParamWriter {
+    fn poll_writer(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<&mut ChannelWriter>> {
+        let writer = if let Some(writer) = self.writer.take() {
+            writer
+        } else {
+            trace!("awaiting handshake");
+            let inbox = ready!(self.handshake.poll_unpin(cx)).map_err(|err| {
+                std::io::Error::new(std::io::ErrorKind::BrokenPipe, err.to_string())
+            })?;
+            let channel = index_channel(&param_channel(&inbox), &self.path);
+            ChannelWriter::new(self.conn.clone(), channel)
+        };
+        Poll::Ready(Ok(self.writer.insert(writer)))
+    }
+}
+
+impl wrpc_transport::Index<Self> for ParamWriter {
+    #[instrument(level = "trace", skip(self))]
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        Ok(Self {
+            conn: self.conn.clone(),
+            handshake: self.handshake.clone(),
+            path: self.path.iter().chain(path).copied().collect(),
+            writer: None,
+            _inbox: Arc::clone(&self._inbox),
+        })
+    }
+}
+
+impl AsyncWrite for ParamWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let w = ready!(self.poll_writer(cx))?;
+        Pin::new(w).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let Some(w) = self.writer.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+        Pin::new(w).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let w = ready!(self.poll_writer(cx))?;
+        Pin::new(w).poll_shutdown(cx)
+    }
+}
+
+impl wrpc_transport::Invoke for Client {
+    type Context = ();
+    type Outgoing = ParamWriter;
+    type Incoming = Reader;
+
+    #[instrument(level = "trace", skip(self, _paths, params), fields(params = format!("{params:02x?}")))]
+    async fn invoke<P: AsRef<[Option<usize>]> + Send + Sync>(
+        &self,
+        cx: Self::Context,
+        instance: &str,
+        func: &str,
+        params: Bytes,
+        // the whole inbox is subscribed on, so nested paths are always covered
+        _paths: impl AsRef<[P]> + Send,
+    ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)> {
+        let rx = new_inbox();
+        let inbox = self.subscribe_inbox(&rx).await?;
+        let mut handshake = inbox.mailbox.take(&rx)?;
+        let results = result_channel(&rx);
+        let incoming = inbox.mailbox.take(&results)?;
+
+        let channel = invocation_channel(&self.prefix, instance, func);
+        let payload = encode_invocation(&rx, &params)?;
+        trace!(channel, "publishing invocation");
+        let receivers: usize = self
+            .conn
+            .clone()
+            .publish(&channel, payload.as_ref())
+            .await
+            .context("failed to publish invocation")?;
+        ensure!(receivers > 0, "no servers subscribed to `{channel}`");
+        ensure!(
+            receivers == 1,
+            "{receivers} servers subscribed to `{channel}`, exactly one is required"
+        );
+
+        let handshake = async move {
+            let payload = handshake
+                .recv()
+                .await
+                .ok_or_else(|| Arc::from("inbox subscription closed before handshake"))?
+                .map_err(|err| Arc::from(err.to_string()))?;
+            str::from_utf8(&payload)
+                .map(Arc::from)
+                .map_err(|err| Arc::from(format!("invalid server inbox: {err}")))
+        }
+        .boxed()
+        .shared();
+        Ok((
+            ParamWriter {
+                conn: self.conn.clone(),
+                handshake,
+                path: Box::default(),
+                writer: None,
+                _inbox: Arc::clone(&inbox),
+            },
+            Reader {
+                buffer: Bytes::default(),
+                incoming,
+                channel: results,
+                inbox,
+            },
+        ))
+    }
+
+    /// Fails if the pub/sub connection is closed, since no messages can be received then.
+    #[instrument(level = "trace", skip(self))]
+    async fn ready(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.router.is_finished(),
+            "Redis pub/sub connection closed"
+        );
+        Ok(())
+    }
+}
+
+impl wrpc_transport::Serve for Client {
+    type Context = ();
+    type Outgoing = ChannelWriter;
+    type Incoming = Reader;
+
+    #[instrument(level = "trace", skip(self, _paths))]
+    async fn serve(
+        &self,
+        instance: &str,
+        func: &str,
+        // the whole inbox is subscribed on, so nested paths are always covered
+        _paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+    ) -> anyhow::Result<
+        impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>>
+            + Send
+            + 'static,
+    > {
+        let channel = invocation_channel(&self.prefix, instance, func);
+        let (tx, rx) = self::channel();
+        {
+            let mut channels = self
+                .routes
+                .channels
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            ensure!(
+                !channels.contains_key(&channel),
+                "`{channel}` is already served by this client"
+            );
+            channels.insert(channel.clone(), tx);
+        }
+        let sub = Subscription {
+            channel,
+            routes: Arc::clone(&self.routes),
+            sink: self.sink.clone(),
+        };
+        debug!(channel = sub.channel, "subscribing on invocation channel");
+        self.sink
+            .clone()
+            .subscribe(&sub.channel)
+            .await
+            .context("failed to subscribe on invocation channel")?;
+        let invocations = stream::unfold((rx, sub), |(mut rx, sub)| async move {
+            let payload = rx.recv().await?;
+            Some((payload, (rx, sub)))
+        });
+        let clt = self.clone();
+        Ok(invocations.then(move |payload| {
+            let clt = clt.clone();
+            async move {
+                let (tx, params) = decode_invocation(&payload?)?;
+                let rx = new_inbox();
+                let inbox = clt.subscribe_inbox(&rx).await?;
+                let params_rx = param_channel(&rx);
+                let incoming = inbox.mailbox.take(&params_rx)?;
+                trace!(tx, "publishing handshake response");
+                clt.conn
+                    .clone()
+                    .publish::<_, _, ()>(&tx, rx.as_bytes())
+                    .await
+                    .map_err(|err| anyhow!(err).context("failed to publish handshake"))?;
+                Ok((
+                    (),
+                    ChannelWriter::new(clt.conn.clone(), result_channel(&tx)),
+                    Reader {
+                        buffer: params,
+                        incoming,
+                        channel: params_rx,
+                        inbox,
+                    },
+                ))
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels() {
+        assert_eq!(
+            invocation_channel("test", "wrpc-examples:hello/handler", "hello"),
+            "test.wrpc.0.0.1.wrpc-examples:hello/handler.hello"
+        );
+        assert_eq!(invocation_channel("", "", "f"), "wrpc.0.0.1.f");
+        assert_eq!(index_channel("rx.params", &[1, 0]), "rx.params.1.0");
+        assert_eq!(index_channel("", &[1, 0]), "1.0");
+        assert_eq!(param_channel("rx"), "rx.params");
+        assert_eq!(result_channel("rx"), "rx.results");
+    }
+
+    #[test]
+    fn invocation() -> anyhow::Result<()> {
+        let payload = encode_invocation("_INBOX.abc", b"\x01\x02")?;
+        let (inbox, params) = decode_invocation(&payload)?;
+        assert_eq!(inbox, "_INBOX.abc");
+        assert_eq!(params, b"\x01\x02".as_slice());
+
+        assert!(decode_invocation(&payload[..3]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mailbox() -> anyhow::Result<()> {
+        let mailbox = Mailbox::default();
+        mailbox.push("rx.results", Bytes::from("before"));
+        let mut rx = mailbox.take("rx.results")?;
+        mailbox.push("rx.results", Bytes::from("after"));
+        assert_eq!(
+            rx.recv().await.transpose()?.as_deref(),
+            Some(b"before".as_slice())
+        );
+        assert_eq!(
+            rx.recv().await.transpose()?.as_deref(),
+            Some(b"after".as_slice())
+        );
+        assert!(mailbox.take("rx.results").is_err());
+
+        // messages on channels, which are taken later, are buffered
+        mailbox.push("rx.results.0", Bytes::from("nested"));
+        let mut rx = mailbox.take("rx.results.0")?;
+        assert_eq!(
+            rx.recv().await.transpose()?.as_deref(),
+            Some(b"nested".as_slice())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn overflow() -> anyhow::Result<()> {
+        let mailbox = Mailbox::default();
+        for i in 0..=CHANNEL_CAPACITY {
+            mailbox.push("rx.results", Bytes::from(i.to_string()));
+        }
+        let mut rx = mailbox.take("rx.results")?;
+        for i in 0..CHANNEL_CAPACITY {
+            assert_eq!(
+                rx.recv().await.transpose()?.as_deref(),
+                Some(i.to_string().as_bytes())
+            );
+        }
+        // the channel is closed once full, even if there is room again
+        mailbox.push("rx.results", Bytes::from("after"));
+        let err = rx
+            .recv()
+            .await
+            .context("overflow error missing")?
+            .expect_err("overflowed channel should fail");
+        assert_eq!(
+            err.to_string(),
+            format!("more than {CHANNEL_CAPACITY} messages buffered on `rx.results`")
+        );
+        assert!(rx.recv().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn close() -> anyhow::Result<()> {
+        let routes = Routes::default();
+        let (tx, mut invocations) = channel();
+        routes
+            .channels
+            .lock()
+            .unwrap()
+            .insert("invocations".into(), tx);
+        let mailbox = Arc::<Mailbox>::default();
+        routes
+            .patterns
+            .lock()
+            .unwrap()
+            .insert("rx*".into(), Arc::clone(&mailbox));
+        mailbox.push("rx.results", Bytes::from("before"));
+        let mut results = mailbox.take("rx.results")?;
+
+        routes.close();
+        assert!(routes.channels.lock().unwrap().is_empty());
+        assert!(routes.patterns.lock().unwrap().is_empty());
+        let err = invocations
+            .recv()
+            .await
+            .context("close error missing")?
+            .expect_err("closed channel should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+        assert!(invocations.recv().await.is_none());
+
+        // buffered messages are still received before the error
+        assert_eq!(
+            results.recv().await.transpose()?.as_deref(),
+            Some(b"before".as_slice())
+        );
+        assert!(matches!(results.recv().await, Some(Err(..))));
+        assert!(results.recv().await.is_none());
+
+        // channels taken after closing fail immediately
+        let mut nested = mailbox.take("rx.results.0")?;
+        assert!(matches!(nested.recv().await, Some(Err(..))));
+        assert!(nested.recv().await.is_none());
+        Ok(())
+    }
+}
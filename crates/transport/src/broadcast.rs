@@ -0,0 +1,197 @@
+//! Fan-out transmission of a single encoded value to multiple outgoing streams
+
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use tokio::io::{AsyncWrite, AsyncWriteExt as _};
+use tracing::{debug, instrument, trace};
+
+use crate::{encode_value, Encode, Index};
+
+/// Outgoing byte stream, which writes all data to each of the wrapped streams.
+///
+/// Writes are buffered and accepted once all streams have consumed the previously written
+/// buffer, so the slowest stream determines the throughput. Indexing returns a [Broadcast]
+/// of the indexed streams, so nested asynchronous values are transmitted to all streams as well.
+pub struct Broadcast<W> {
+    outgoing: Vec<(W, usize)>,
+    buf: Bytes,
+}
+
+impl<W> Broadcast<W> {
+    /// Constructs a new [`Broadcast`] writing to all of `outgoing`
+    pub fn new(outgoing: impl IntoIterator<Item = W>) -> Self {
+        Self {
+            outgoing: outgoing.into_iter().map(|w| (w, 0)).collect(),
+            buf: Bytes::default(),
+        }
+    }
+
+    /// Returns the wrapped streams
+    pub fn into_inner(self) -> Vec<W> {
+        self.outgoing.into_iter().map(|(w, _)| w).collect()
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Broadcast<W> {
+    /// Encodes `value` once and transmits it to all wrapped streams
+    #[instrument(level = "trace", skip_all)]
+    pub async fn transmit<T>(mut self, value: T) -> anyhow::Result<()>
+    where
+        T: Encode<Self>,
+        <T::Encoder as tokio_util::codec::Encoder<T>>::Error:
+            std::error::Error + Send + Sync + 'static,
+        Self: Index<Self>,
+    {
+        let (buf, tx) = encode_value(value).context("failed to encode value")?;
+        debug!("transmitting sync value");
+        self.write_all(&buf)
+            .await
+            .context("failed to transmit sync value")?;
+        self.shutdown()
+            .await
+            .context("failed to shutdown synchronous value channel")?;
+        if let Some(tx) = tx {
+            debug!("transmitting async value");
+            tx(Arc::new(self), Vec::with_capacity(8))
+                .await
+                .context("failed to transmit async value")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered data to all streams, returns [`Poll::Ready`] once all of it is written
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let mut pending = false;
+        for (w, n) in &mut self.outgoing {
+            while *n < self.buf.len() {
+                match Pin::new(&mut *w).poll_write(cx, &self.buf[*n..]) {
+                    Poll::Ready(Ok(0)) => {
+                        return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()))
+                    }
+                    Poll::Ready(Ok(written)) => *n += written,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => {
+                        pending = true;
+                        break;
+                    }
+                }
+            }
+        }
+        if pending {
+            return Poll::Pending;
+        }
+        trace!(n = self.buf.len(), "wrote buffer to all streams");
+        self.buf.clear();
+        for (_, n) in &mut self.outgoing {
+            *n = 0;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: Index<W>> Index<Self> for Broadcast<W> {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let outgoing = self
+            .outgoing
+            .iter()
+            .map(|(w, _)| w.index(path))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self::new(outgoing))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Broadcast<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        ready!(self.poll_write_buf(cx))?;
+        self.buf = Bytes::copy_from_slice(buf);
+        // data is buffered at this point, any errors will be returned on next call
+        if let Poll::Ready(Err(err)) = self.poll_write_buf(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        ready!(self.poll_write_buf(cx))?;
+        for (w, _) in &mut self.outgoing {
+            ready!(Pin::new(w).poll_flush(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        ready!(self.poll_write_buf(cx))?;
+        for (w, _) in &mut self.outgoing {
+            ready!(Pin::new(w).poll_shutdown(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use core::future::Future;
+
+    use futures::{stream, Stream, StreamExt as _, TryStreamExt as _};
+    use tokio::join;
+    use tokio_util::codec::FramedRead;
+
+    use crate::{Decode, Deferred as _, RecordingOutgoing, ReplayIncoming};
+
+    use super::*;
+
+    type Value = (
+        String,
+        Vec<u32>,
+        Pin<Box<dyn Stream<Item = Vec<u32>> + Send>>,
+        Pin<Box<dyn Future<Output = String> + Send>>,
+    );
+
+    #[test_log::test(tokio::test)]
+    async fn broadcast() -> anyhow::Result<()> {
+        let (w0, r0) = RecordingOutgoing::new(vec![]);
+        let (w1, r1) = RecordingOutgoing::new(vec![]);
+        let (w2, r2) = RecordingOutgoing::new(vec![]);
+        let v: Value = (
+            "test".into(),
+            vec![1, 2, 3],
+            Box::pin(stream::iter([vec![4, 5], vec![6]])),
+            Box::pin(async { "future".into() }),
+        );
+        Broadcast::new([w0, w1, w2]).transmit(v).await?;
+        let (r0, r1, r2) = join!(r0, r1, r2);
+        let recorded = [r0?, r1?, r2?];
+        assert_eq!(recorded[0], recorded[1]);
+        assert_eq!(recorded[0], recorded[2]);
+
+        for recorded in recorded {
+            let r = ReplayIncoming::new(recorded.as_slice()).await?;
+            let mut dec = FramedRead::new(r, <Value as Decode<ReplayIncoming>>::Decoder::default());
+            let (s, v, st, fut) = dec.try_next().await?.context("value missing")?;
+            assert_eq!(s, "test");
+            assert_eq!(v, [1, 2, 3]);
+            let io = dec
+                .decoder_mut()
+                .take_deferred()
+                .context("value should have async components")?;
+            let (res, items, fut) = join!(
+                io(Arc::new(dec.into_inner()), Vec::default()),
+                st.collect::<Vec<_>>(),
+                fut,
+            );
+            res?;
+            assert_eq!(items.concat(), [4, 5, 6]);
+            assert_eq!(fut, "future");
+        }
+        Ok(())
+    }
+}
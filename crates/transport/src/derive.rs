@@ -0,0 +1,258 @@
+//! Support for `#[derive(Encode, Decode)]`
+//!
+//! Derived implementations encode structs as tuples of their fields in declaration order, which
+//! is the encoding of WIT `record` and `tuple` types. Named structs correspond to records and
+//! tuple structs to tuples, unit structs are encoded as zero bytes.
+//!
+//! Structs with exactly one field can be annotated with `#[wrpc(transparent)]`, in which case
+//! they are encoded exactly as the field, e.g. to define newtypes:
+//!
+//! ```
+//! # use wrpc_transport::{Decode, Encode};
+//! #[derive(Encode, Decode)]
+//! #[wrpc(transparent)]
+//! struct UserId(u64);
+//! ```
+//!
+//! The attribute is rejected on structs with any other number of fields:
+//!
+//! ```compile_fail
+//! # use wrpc_transport::Encode;
+//! #[derive(Encode)]
+//! #[wrpc(transparent)]
+//! struct Point(u32, u32);
+//! ```
+//!
+//! The derive macros implement the conversion traits of this module and use [`StructEncoder`]
+//! and [`StructDecoder`] as the codecs of the struct, which delegate to the codecs of the tuple
+//! or, for transparent structs, the field.
+//!
+//! Enums without fields are encoded as WIT `enum` values, i.e. as the discriminant of the variant.
+//! Discriminants follow the Rust rules - a variant without an explicit discriminant uses the
+//! discriminant of the previous variant plus one, starting at zero. This allows discriminant
+//! sets with gaps, e.g. after a case was removed:
+//!
+//! ```
+//! # use wrpc_transport::{Decode, Encode};
+//! #[derive(Encode, Decode)]
+//! enum Level {
+//!     Debug,
+//!     Info,
+//!     Error = 5,
+//! }
+//! ```
+//!
+//! Decoding an unknown discriminant fails with an [`std::io::ErrorKind::InvalidInput`] error.
+//! Enum variants with fields are not supported:
+//!
+//! ```compile_fail
+//! # use wrpc_transport::Encode;
+//! #[derive(Encode)]
+//! enum Shape {
+//!     Circle(f32),
+//! }
+//! ```
+//!
+//! The derive macros implement [`IntoEnumDiscriminant`] and [`FromEnumDiscriminant`] for enums
+//! and use [`EnumEncoder`] and [`EnumDecoder`] as their codecs.
+
+use core::marker::PhantomData;
+
+use bytes::BytesMut;
+use wasm_tokio::{Leb128DecoderU32, Leb128Encoder};
+
+use crate::{Deferred, DeferredFn};
+
+/// Re-exported for bounds of derived implementations
+pub use tokio_util::codec::Encoder;
+
+/// Conversion of a struct into a tuple of its fields, implemented by `#[derive(Encode)]`
+pub trait IntoStructFields {
+    /// Tuple of the struct fields, or the only field of a transparent struct
+    type Fields;
+
+    /// Converts `self` into a tuple of its fields
+    fn into_fields(self) -> Self::Fields;
+}
+
+/// Conversion of a struct reference into a tuple of references to its fields, implemented by
+/// `#[derive(Encode)]`
+pub trait StructFieldRefs<'a> {
+    /// Tuple of references to the struct fields
+    type Refs;
+
+    /// Returns a tuple of references to fields of `self`
+    fn field_refs(&'a self) -> Self::Refs;
+}
+
+/// Construction of a struct from a tuple of its fields, implemented by `#[derive(Decode)]`
+pub trait FromStructFields {
+    /// Tuple of the struct fields
+    type Fields;
+
+    /// Constructs the struct from a tuple of its fields
+    fn from_fields(fields: Self::Fields) -> Self;
+}
+
+/// Encoder of a struct `T`, which encodes it as a tuple of its fields using `E`
+pub struct StructEncoder<T, E> {
+    enc: E,
+    _ty: PhantomData<fn(T)>,
+}
+
+impl<T, E: Default> Default for StructEncoder<T, E> {
+    fn default() -> Self {
+        Self {
+            enc: E::default(),
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T, E, W> Deferred<W> for StructEncoder<T, E>
+where
+    E: Deferred<W>,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.enc.take_deferred()
+    }
+}
+
+impl<T, E> Encoder<T> for StructEncoder<T, E>
+where
+    T: IntoStructFields,
+    E: Encoder<T::Fields>,
+{
+    type Error = E::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.enc.encode(item.into_fields(), dst)
+    }
+}
+
+impl<'a, T, E> Encoder<&'a T> for StructEncoder<T, E>
+where
+    T: StructFieldRefs<'a>,
+    E: Encoder<T::Refs>,
+{
+    type Error = E::Error;
+
+    fn encode(&mut self, item: &'a T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.enc.encode(item.field_refs(), dst)
+    }
+}
+
+/// Decoder of a struct `T`, which decodes it from a tuple of its fields using `D`
+pub struct StructDecoder<T, D> {
+    dec: D,
+    _ty: PhantomData<fn() -> T>,
+}
+
+impl<T, D: Default> Default for StructDecoder<T, D> {
+    fn default() -> Self {
+        Self {
+            dec: D::default(),
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T, D, R> Deferred<R> for StructDecoder<T, D>
+where
+    D: Deferred<R>,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        self.dec.take_deferred()
+    }
+}
+
+impl<T, D> tokio_util::codec::Decoder for StructDecoder<T, D>
+where
+    T: FromStructFields,
+    D: tokio_util::codec::Decoder<Item = T::Fields>,
+{
+    type Item = T;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(fields) = self.dec.decode(src)? else {
+            return Ok(None);
+        };
+        Ok(Some(T::from_fields(fields)))
+    }
+}
+
+/// Conversion of a fieldless enum into its discriminant, implemented by `#[derive(Encode)]`
+pub trait IntoEnumDiscriminant {
+    /// Returns the discriminant of `self`
+    fn discriminant(&self) -> u32;
+}
+
+/// Construction of a fieldless enum from its discriminant, implemented by `#[derive(Decode)]`
+pub trait FromEnumDiscriminant: Sized {
+    /// Returns the variant with discriminant `disc`, if any
+    fn from_discriminant(disc: u32) -> Option<Self>;
+}
+
+/// Encoder of a fieldless enum `T`, which encodes it as its discriminant
+pub struct EnumEncoder<T>(PhantomData<fn(T)>);
+
+impl<T> Default for EnumEncoder<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T, W> Deferred<W> for EnumEncoder<T> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        None
+    }
+}
+
+impl<T: IntoEnumDiscriminant> Encoder<T> for EnumEncoder<T> {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&item, dst)
+    }
+}
+
+impl<T: IntoEnumDiscriminant> Encoder<&T> for EnumEncoder<T> {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        Leb128Encoder.encode(item.discriminant(), dst)
+    }
+}
+
+/// Decoder of a fieldless enum `T`, which decodes it from its discriminant
+pub struct EnumDecoder<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for EnumDecoder<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T, R> Deferred<R> for EnumDecoder<T> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        None
+    }
+}
+
+impl<T: FromEnumDiscriminant> tokio_util::codec::Decoder for EnumDecoder<T> {
+    type Item = T;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(disc) = Leb128DecoderU32.decode(src)? else {
+            return Ok(None);
+        };
+        T::from_discriminant(disc).map(Some).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unknown enum discriminant `{disc}`"),
+            )
+        })
+    }
+}
@@ -0,0 +1,409 @@
+//! Per-invocation headers, like authentication tokens or trace context, carried in-band.
+//!
+//! [`HeaderInvoke`] prepends a header block to the synchronous parameter payload of each
+//! invocation and [`HeaderServe`] strips it before parameters are decoded, exposing the headers
+//! to handlers via the invocation context.
+//!
+//! The header block is encoded as `list<tuple<list<u8>, list<u8>>>` of name/value pairs.
+//! Headers are only transmitted on the root stream of an invocation - nested asynchronous
+//! parameters indexed from it carry no headers and are associated with the headers of the
+//! invocation they belong to. Results are never prefixed with headers.
+
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use bytes::{BufMut as _, Bytes, BytesMut};
+use futures::{Stream, StreamExt as _};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio_util::codec::FramedRead;
+use tracing::{instrument, trace};
+
+use crate::{encode_value, receive_value, Decode, Index, Invoke, Serve};
+
+/// Ordered list of header name/value pairs, names may repeat
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Headers(Vec<(Bytes, Bytes)>);
+
+impl Headers {
+    /// Constructs an empty set of [`Headers`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a header `name` with `value`
+    pub fn append(&mut self, name: impl Into<Bytes>, value: impl Into<Bytes>) {
+        self.0.push((name.into(), value.into()));
+    }
+
+    /// Returns the value of the first header called `name`, if any
+    #[must_use]
+    pub fn get(&self, name: impl AsRef<[u8]>) -> Option<&Bytes> {
+        let name = name.as_ref();
+        self.0.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+
+    /// Returns an iterator over all headers in order
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.0.iter().map(|(k, v)| (k, v))
+    }
+
+    /// Returns the number of headers
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if there are no headers
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<K: Into<Bytes>, V: Into<Bytes>> FromIterator<(K, V)> for Headers {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+/// [`Invoke`] implementation, which prepends [`Headers`] passed in the invocation context to
+/// parameters transmitted by the wrapped [`Invoke`] implementation.
+#[derive(Clone, Debug)]
+pub struct HeaderInvoke<T> {
+    inner: T,
+}
+
+impl<T> HeaderInvoke<T> {
+    /// Constructs a new [`HeaderInvoke`]
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the wrapped [`Invoke`] implementation
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Invoke> Invoke for HeaderInvoke<T> {
+    type Context = (Headers, T::Context);
+    type Outgoing = T::Outgoing;
+    type Incoming = T::Incoming;
+
+    #[instrument(level = "trace", skip(self, headers, cx, params, paths))]
+    async fn invoke<P: AsRef<[Option<usize>]> + Send + Sync>(
+        &self,
+        (headers, cx): Self::Context,
+        instance: &str,
+        func: &str,
+        params: Bytes,
+        paths: impl AsRef<[P]> + Send,
+    ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)> {
+        let (header_buf, _) = encode_value::<_, T::Outgoing>(headers.0.as_slice())
+            .context("failed to encode headers")?;
+        let mut buf = BytesMut::with_capacity(header_buf.len() + params.len());
+        buf.put(header_buf);
+        buf.put(params);
+        self.inner
+            .invoke(cx, instance, func, buf.freeze(), paths)
+            .await
+    }
+
+    async fn ready(&self) -> anyhow::Result<()> {
+        self.inner.ready().await
+    }
+}
+
+/// Incoming byte stream of an invocation accepted by [`HeaderServe`], with the header block
+/// stripped from the root stream.
+#[derive(Debug)]
+pub struct HeaderIncoming<T> {
+    buffer: Bytes,
+    inner: T,
+}
+
+impl<T> HeaderIncoming<T> {
+    /// Returns the wrapped stream, discarding any data buffered while reading the header block
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Index<T>> Index<Self> for HeaderIncoming<T> {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let inner = self.inner.index(path)?;
+        Ok(Self {
+            buffer: Bytes::default(),
+            inner,
+        })
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for HeaderIncoming<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.buffer.is_empty() {
+            return Pin::new(&mut self.inner).poll_read(cx, buf);
+        }
+        let n = buf.remaining().min(self.buffer.len());
+        trace!(n, "reading buffered parameter data");
+        buf.put_slice(&self.buffer.split_to(n));
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// [`Serve`] implementation, which reads [`Headers`] prepended by [`HeaderInvoke`] to parameters
+/// of invocations served by the wrapped [`Serve`] implementation and passes them to handlers
+/// in the invocation context.
+///
+/// Invocations are yielded once their header block is received. Header blocks of up to
+/// [`HeaderServe::MAX_PENDING`] invocations are received concurrently, so an invocation, which
+/// is slow to send its header block, does not delay the ones accepted after it. Invocations,
+/// which do not send their header block within the configured timeout, fail.
+#[derive(Clone, Debug)]
+pub struct HeaderServe<T> {
+    inner: T,
+    timeout: Duration,
+}
+
+impl<T> HeaderServe<T> {
+    /// Maximum number of invocations waiting for their header block at a time
+    pub const MAX_PENDING: usize = 256;
+
+    /// Constructs a new [`HeaderServe`], which fails invocations not sending their header block
+    /// within `timeout`
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    /// Returns the wrapped [`Serve`] implementation
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Serve> Serve for HeaderServe<T> {
+    type Context = (Headers, T::Context);
+    type Outgoing = T::Outgoing;
+    type Incoming = HeaderIncoming<T::Incoming>;
+
+    #[instrument(level = "trace", skip(self, paths))]
+    async fn serve(
+        &self,
+        instance: &str,
+        func: &str,
+        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+    ) -> anyhow::Result<
+        impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>>
+            + Send
+            + 'static,
+    > {
+        let timeout = self.timeout;
+        let invocations = self.inner.serve(instance, func, paths).await?;
+        Ok(invocations
+            .map(move |invocation| async move {
+                let (cx, outgoing, incoming) = invocation?;
+                let mut dec = FramedRead::new(
+                    incoming,
+                    <Vec<(Bytes, Bytes)> as Decode<T::Incoming>>::Decoder::default(),
+                );
+                let headers = tokio::time::timeout(timeout, receive_value(&mut dec))
+                    .await
+                    .with_context(|| format!("header block not received within {timeout:?}"))?
+                    .context("failed to receive header block")?;
+                trace!(headers = headers.len(), "received headers");
+                let buffer = mem::take(dec.read_buffer_mut()).freeze();
+                Ok((
+                    (Headers(headers), cx),
+                    outgoing,
+                    HeaderIncoming {
+                        buffer,
+                        inner: dec.into_inner(),
+                    },
+                ))
+            })
+            .buffer_unordered(Self::MAX_PENDING))
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use std::sync::Mutex;
+
+    use anyhow::{bail, ensure};
+    use futures::{stream, TryStreamExt as _};
+
+    use crate::{MockOutgoing, MockServe, ReplayIncoming, ServeExt as _};
+
+    use super::*;
+
+    /// [`Invoke`] implementation, which captures the parameters of the last invocation
+    #[derive(Default)]
+    struct CaptureInvoke(Mutex<Bytes>);
+
+    impl Invoke for CaptureInvoke {
+        type Context = ();
+        type Outgoing = MockOutgoing;
+        type Incoming = ReplayIncoming;
+
+        async fn invoke<P: AsRef<[Option<usize>]> + Send + Sync>(
+            &self,
+            (): Self::Context,
+            _instance: &str,
+            _func: &str,
+            params: Bytes,
+            _paths: impl AsRef<[P]> + Send,
+        ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)> {
+            *self.0.lock().unwrap() = params;
+            Ok((MockOutgoing::default(), ReplayIncoming::from(Bytes::new())))
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn roundtrip() -> anyhow::Result<()> {
+        let clt = HeaderInvoke::new(CaptureInvoke::default());
+        let srv = HeaderServe::new(MockServe::default(), Duration::from_secs(5));
+        let invocations = srv
+            .serve_values::<(u32, String), (String,)>("foo", "bar", [])
+            .await?;
+        let mut invocations = Box::pin(invocations);
+
+        let headers: Headers = [("authorization", "Bearer token"), ("traceparent", "00-01")]
+            .into_iter()
+            .collect();
+        let (params, _) = encode_value::<_, MockOutgoing>((42_u32, "test"))?;
+        clt.invoke((headers.clone(), ()), "foo", "bar", params, &[[]; 0])
+            .await?;
+        let params = clt.inner.0.lock().unwrap().clone();
+        srv.inner.invoke("foo", "bar", params)?;
+
+        let ((cx, ()), (a, b), _, _) = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        assert_eq!(cx, headers);
+        assert_eq!(
+            cx.get("authorization").map(Bytes::as_ref),
+            Some(b"Bearer token".as_slice())
+        );
+        assert_eq!(cx.get("missing"), None);
+        assert_eq!((a, b.as_str()), (42, "test"));
+
+        // empty header block
+        let (params, _) = encode_value::<_, MockOutgoing>((1_u32, "x"))?;
+        clt.invoke((Headers::new(), ()), "foo", "bar", params, &[[]; 0])
+            .await?;
+        let params = clt.inner.0.lock().unwrap().clone();
+        ensure!(params.starts_with(b"\x00"));
+        srv.inner.invoke("foo", "bar", params)?;
+        let ((cx, ()), (a, _), _, _) = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        assert!(cx.is_empty());
+        assert_eq!(a, 1);
+
+        // missing header block
+        srv.inner.invoke("foo", "bar", Bytes::new())?;
+        let Err(err) = invocations.try_next().await else {
+            bail!("invocation without a header block should fail")
+        };
+        assert!(
+            format!("{err:#}").contains("header block"),
+            "unexpected error: {err:#}"
+        );
+        Ok(())
+    }
+
+    /// Incoming stream, which replays a buffer and then never receives any more data
+    struct PendingIncoming(Bytes);
+
+    impl Index<Self> for PendingIncoming {
+        fn index(&self, _path: &[usize]) -> anyhow::Result<Self> {
+            Ok(Self(Bytes::new()))
+        }
+    }
+
+    impl AsyncRead for PendingIncoming {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            if self.0.is_empty() {
+                return Poll::Pending;
+            }
+            let n = buf.remaining().min(self.0.len());
+            buf.put_slice(&self.0.split_to(n));
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// [`Serve`] implementation, which accepts an invocation never sending its header block,
+    /// followed by one sending an empty header block
+    struct StalledServe;
+
+    impl Serve for StalledServe {
+        type Context = ();
+        type Outgoing = MockOutgoing;
+        type Incoming = PendingIncoming;
+
+        async fn serve(
+            &self,
+            _instance: &str,
+            _func: &str,
+            _paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+        ) -> anyhow::Result<
+            impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>>
+                + Send
+                + 'static,
+        > {
+            Ok(stream::iter([
+                Ok(((), MockOutgoing::default(), PendingIncoming(Bytes::new()))),
+                Ok((
+                    (),
+                    MockOutgoing::default(),
+                    PendingIncoming(Bytes::from_static(b"\x00")),
+                )),
+            ]))
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn stalled() -> anyhow::Result<()> {
+        let srv = HeaderServe::new(StalledServe, Duration::from_millis(50));
+        let invocations = srv.serve("foo", "bar", []).await?;
+        let mut invocations = Box::pin(invocations);
+
+        // a stalled header block does not delay invocations accepted after it
+        let ((cx, ()), _, _) = tokio::time::timeout(Duration::from_secs(5), invocations.try_next())
+            .await
+            .context("invocation blocked by a stalled header block")??
+            .context("invocation missing")?;
+        assert!(cx.is_empty());
+
+        let Err(err) = invocations.try_next().await else {
+            bail!("invocation without a header block should time out")
+        };
+        assert_eq!(
+            err.to_string(),
+            "header block not received within 50ms",
+            "unexpected error: {err:#}"
+        );
+        assert!(invocations.try_next().await?.is_none());
+        Ok(())
+    }
+}
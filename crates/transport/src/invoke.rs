@@ -1,16 +1,22 @@
+use core::fmt::{self, Display};
 use core::future::Future;
 use core::pin::pin;
 use core::time::Duration;
 
-use anyhow::Context as _;
-use bytes::{Bytes, BytesMut};
-use futures::TryStreamExt as _;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt as _};
+use anyhow::{anyhow, bail, ensure, Context as _};
+use bytes::Bytes;
+use futures::{stream, Stream};
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
 use tokio::{select, try_join};
-use tokio_util::codec::{Encoder as _, FramedRead};
+use tokio_util::codec::FramedRead;
+use tokio_util::task::AbortOnDropHandle;
 use tracing::{debug, instrument, trace, Instrument as _};
 
-use crate::{Deferred as _, Index, TupleDecode, TupleEncode};
+use crate::reflect::{FunctionSignatureTuple, REFLECT_FUNC, REFLECT_INSTANCE};
+use crate::{
+    encode_value, receive_value, Deferred as _, FunctionSignature, Index, ReceiveError,
+    TupleDecode, TupleEncode,
+};
 
 /// Client-side handle to a wRPC transport
 pub trait Invoke: Send + Sync {
@@ -18,6 +24,12 @@ pub trait Invoke: Send + Sync {
     type Context: Send + Sync;
 
     /// Outgoing multiplexed byte stream
+    ///
+    /// Writes are expected to apply backpressure, i.e. implementations should return
+    /// [`Poll::Pending`](core::task::Poll::Pending) from `poll_write` while the transport cannot
+    /// accept more data, rather than buffer it without bound. Transmission of asynchronous
+    /// values, like streams, stops polling the producer while written data is pending, which
+    /// propagates the backpressure to it.
     type Outgoing: AsyncWrite + Index<Self::Outgoing> + Send + Sync + Unpin + 'static;
 
     /// Incoming multiplexed byte stream
@@ -73,6 +85,14 @@ pub trait Invoke: Send + Sync {
     ) -> impl Future<Output = anyhow::Result<(Self::Outgoing, Self::Incoming)>> + Send
     where
         P: AsRef<[Option<usize>]> + Send + Sync;
+
+    /// Waits until the underlying transport is connected and invocations can be established
+    ///
+    /// Returns an error if the connection has permanently failed. Transports, which do not
+    /// maintain a connection, are always ready, which is the default.
+    fn ready(&self) -> impl Future<Output = anyhow::Result<()>> + Send {
+        async { Ok(()) }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -105,6 +125,12 @@ impl<T: Invoke> Invoke for Timeout<'_, T> {
         .await
         .context("invocation timed out")?
     }
+
+    async fn ready(&self) -> anyhow::Result<()> {
+        tokio::time::timeout(self.timeout, self.inner.ready())
+            .await
+            .context("transport did not become ready in time")?
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -135,10 +161,84 @@ impl<T: Invoke> Invoke for TimeoutOwned<T> {
             .invoke(cx, instance, func, params, paths)
             .await
     }
+
+    async fn ready(&self) -> anyhow::Result<()> {
+        self.inner.timeout(self.timeout).ready().await
+    }
+}
+
+/// Error returned by [`InvokeExt::try_invoke_values`], which allows callers to branch on the
+/// kind of failure, e.g. to only retry on [`InvokeError::Transport`]
+#[derive(Debug)]
+pub enum InvokeError {
+    /// Transport failed to establish the invocation or to transfer data
+    Transport(anyhow::Error),
+    /// Parameters could not be encoded
+    Encode(anyhow::Error),
+    /// Results could not be decoded, e.g. because the peer sent malformed data
+    Decode(anyhow::Error),
+    /// Invocation did not complete within the timeout, see [`InvokeExt::timeout`]
+    Timeout(anyhow::Error),
+}
+
+impl InvokeError {
+    /// Classifies an error returned by [`Invoke::invoke`]
+    fn invoke(err: anyhow::Error) -> Self {
+        if err.downcast_ref::<tokio::time::error::Elapsed>().is_some() {
+            Self::Timeout(err)
+        } else {
+            Self::Transport(err)
+        }
+    }
+
+    /// Classifies an error encountered while transferring data, using the I/O error kind
+    fn io(err: anyhow::Error, malformed: fn(anyhow::Error) -> Self) -> Self {
+        match err
+            .downcast_ref::<std::io::Error>()
+            .map(std::io::Error::kind)
+        {
+            None | Some(std::io::ErrorKind::InvalidData | std::io::ErrorKind::InvalidInput) => {
+                malformed(err)
+            }
+            Some(_) => Self::Transport(err),
+        }
+    }
+}
+
+impl Display for InvokeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(..) => write!(f, "transport error"),
+            Self::Encode(..) => write!(f, "failed to encode parameters"),
+            Self::Decode(..) => write!(f, "failed to decode results"),
+            Self::Timeout(..) => write!(f, "invocation timed out"),
+        }
+    }
+}
+
+impl std::error::Error for InvokeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(err) | Self::Encode(err) | Self::Decode(err) | Self::Timeout(err) => {
+                Some(err.as_ref())
+            }
+        }
+    }
 }
 
 pub trait InvokeExt: Invoke {
     /// Invoke function `func` on instance `instance` using typed `Params` and `Results`
+    ///
+    /// The returned future resolves once results are received. Transmission of
+    /// asynchronous parameters (e.g. streams) may still be in progress at that point,
+    /// it is driven by the optional I/O future returned alongside the results.
+    /// Dropping either future aborts any in-flight transmission.
+    /// Use [`Self::invoke_values_split`] to proceed as soon as the synchronous
+    /// parameters are sent.
+    ///
+    /// Results, which are encoded as zero bytes, like `()`, are decoded without waiting for any
+    /// data from the peer, so the returned future resolves as soon as the synchronous parameters
+    /// are sent, unless transmission of asynchronous parameters has already failed by then.
     #[instrument(level = "trace", skip(self, cx, params, paths))]
     fn invoke_values<P, Params, Results>(
         &self,
@@ -163,94 +263,265 @@ pub trait InvokeExt: Invoke {
             std::error::Error + Send + Sync + 'static,
     {
         async {
-            let mut buf = BytesMut::default();
-            let mut enc = Params::Encoder::default();
+            self.invoke_values_split(cx, instance, func, params, paths)
+                .await?
+                .await
+        }
+    }
+
+    /// Invoke function `func` on instance `instance` using typed `Params` and `Results`
+    /// This is like [`Self::invoke_values`], but the outer future resolves as soon as the
+    /// synchronous portion of the parameters is sent, before any asynchronous parameters
+    /// are transmitted. The inner future resolves once results are received.
+    ///
+    /// If transmission of asynchronous parameters fails before results are received, the inner
+    /// future fails with the transmission error, even if results become available at the same time.
+    /// Transmission errors occurring after results are received are returned by the I/O future.
+    #[instrument(level = "trace", skip(self, cx, params, paths))]
+    fn invoke_values_split<P, Params, Results>(
+        &self,
+        cx: Self::Context,
+        instance: &str,
+        func: &str,
+        params: Params,
+        paths: impl AsRef<[P]> + Send,
+    ) -> impl Future<
+        Output = anyhow::Result<
+            impl Future<
+                    Output = anyhow::Result<(
+                        Results,
+                        Option<impl Future<Output = anyhow::Result<()>> + Send + 'static>,
+                    )>,
+                > + Send,
+        >,
+    > + Send
+    where
+        P: AsRef<[Option<usize>]> + Send + Sync,
+        Params: TupleEncode<Self::Outgoing> + Send,
+        Results: TupleDecode<Self::Incoming> + Send,
+        <Params::Encoder as tokio_util::codec::Encoder<Params>>::Error:
+            std::error::Error + Send + Sync + 'static,
+        <Results::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        async {
+            let (incoming, tx) = self
+                .invoke_channels(cx, instance, func, params, paths)
+                .await?;
+            // abort the transmission task if the invocation is dropped
+            let mut tx = tx.map(|tx| AbortOnDropHandle::new(tokio::spawn(tx)));
+            Ok(async {
+                let mut dec = FramedRead::new(incoming, Results::Decoder::default());
+                let results = async {
+                    debug!("receiving sync results");
+                    receive_value(&mut dec)
+                        .await
+                        .context("failed to receive sync results")
+                };
+                let results = if let Some(mut fut) = tx.take() {
+                    let mut results = pin!(results);
+                    // transmission errors take precedence over results received concurrently
+                    select! {
+                        biased;
+                        res = &mut fut => {
+                            res??;
+                            results.await?
+                        }
+                        res = &mut results => {
+                            tx = Some(fut);
+                            res?
+                        }
+                    }
+                } else {
+                    results.await?
+                };
+                trace!("received sync results");
+                let rx = dec.decoder_mut().take_deferred();
+                Ok((
+                    results,
+                    (tx.is_some() || rx.is_some()).then_some(
+                        async {
+                            match (tx, rx) {
+                                (Some(tx), Some(rx)) => {
+                                    try_join!(
+                                        async {
+                                            debug!("receiving async results");
+                                            rx(dec.into_inner().into(), Vec::with_capacity(8))
+                                                .await
+                                                .context("receiving async results failed")
+                                        },
+                                        async {
+                                            tx.await
+                                                .context("transmitting async parameters failed")?
+                                        }
+                                    )?;
+                                }
+                                (Some(tx), None) => {
+                                    tx.await.context("transmitting async parameters failed")??;
+                                }
+                                (None, Some(rx)) => {
+                                    debug!("receiving async results");
+                                    rx(dec.into_inner().into(), Vec::with_capacity(8))
+                                        .await
+                                        .context("receiving async results failed")?;
+                                }
+                                _ => {}
+                            }
+                            Ok(())
+                        }
+                        .in_current_span(),
+                    ),
+                ))
+            }
+            .in_current_span())
+        }
+    }
+
+    /// Invoke function `func` on instance `instance` using typed `Params`
+    ///
+    /// This is the lower-level building block of [`Self::invoke_values`]. The returned future
+    /// resolves once the synchronous portion of the parameters is sent, with the incoming
+    /// result stream and, if `params` contain asynchronous values, a future transmitting them.
+    /// The caller is responsible for decoding the results and driving the transmission future
+    /// concurrently with it.
+    #[instrument(level = "trace", skip(self, cx, params, paths))]
+    fn invoke_channels<P, Params>(
+        &self,
+        cx: Self::Context,
+        instance: &str,
+        func: &str,
+        params: Params,
+        paths: impl AsRef<[P]> + Send,
+    ) -> impl Future<
+        Output = anyhow::Result<(
+            Self::Incoming,
+            Option<impl Future<Output = anyhow::Result<()>> + Send + 'static>,
+        )>,
+    > + Send
+    where
+        P: AsRef<[Option<usize>]> + Send + Sync,
+        Params: TupleEncode<Self::Outgoing> + Send,
+        <Params::Encoder as tokio_util::codec::Encoder<Params>>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        async {
             trace!("encoding parameters");
-            enc.encode(params, &mut buf)
-                .context("failed to encode parameters")?;
+            let (buf, tx) = encode_value(params).context("failed to encode parameters")?;
             debug!("invoking function");
             let (mut outgoing, incoming) = self
-                .invoke(cx, instance, func, buf.freeze(), paths)
+                .invoke(cx, instance, func, buf, paths)
                 .await
                 .context("failed to invoke function")?;
             outgoing
                 .shutdown()
                 .await
                 .context("failed to shutdown synchronous parameter channel")?;
-            let mut tx = enc.take_deferred().map(|tx| {
-                tokio::spawn(
+            trace!("sent sync parameters");
+            Ok((
+                incoming,
+                tx.map(|tx| {
                     async {
                         debug!("transmitting async parameters");
                         tx(outgoing.into(), Vec::with_capacity(8))
                             .await
                             .context("failed to write async parameters")
                     }
-                    .in_current_span(),
-                )
-            });
-
-            let mut dec = FramedRead::new(incoming, Results::Decoder::default());
-            let results = async {
-                debug!("receiving sync results");
-                dec.try_next()
-                    .await
-                    .context("failed to receive sync results")?
-                    .context("incomplete results")
-            };
-            let results = if let Some(mut fut) = tx.take() {
-                let mut results = pin!(results);
-                select! {
-                    res = &mut results => {
-                        tx = Some(fut);
-                        res?
-                    }
-                    res = &mut fut => {
-                        res??;
-                        results.await?
-                    }
-                }
-            } else {
-                results.await?
-            };
-            trace!("received sync results");
-            let rx = dec.decoder_mut().take_deferred();
-            Ok((
-                results,
-                (tx.is_some() || rx.is_some()).then_some(
-                    async {
-                        match (tx, rx) {
-                            (Some(tx), Some(rx)) => {
-                                try_join!(
-                                    async {
-                                        debug!("receiving async results");
-                                        rx(dec.into_inner().into(), Vec::with_capacity(8))
-                                            .await
-                                            .context("receiving async results failed")
-                                    },
-                                    async {
-                                        tx.await.context("transmitting async parameters failed")?
-                                    }
-                                )?;
-                            }
-                            (Some(tx), None) => {
-                                tx.await.context("transmitting async parameters failed")??;
-                            }
-                            (None, Some(rx)) => {
-                                debug!("receiving async results");
-                                rx(dec.into_inner().into(), Vec::with_capacity(8))
-                                    .await
-                                    .context("receiving async results failed")?;
-                            }
-                            _ => {}
-                        }
-                        Ok(())
-                    }
-                    .in_current_span(),
-                ),
+                    .in_current_span()
+                }),
             ))
         }
     }
 
+    /// Invoke function `func` on instance `instance` using typed `Results`, reading the encoded
+    /// synchronous parameters from `params`
+    ///
+    /// This is like [`Self::invoke_values`], but parameters are not encoded into memory before
+    /// invoking the function. Instead, `params` must produce the encoding of the parameter
+    /// tuple, which is copied to the outgoing stream as it is read, subject to its backpressure.
+    /// Neither parameters nor results may contain asynchronous values, invocations returning
+    /// asynchronous results fail.
+    ///
+    /// See [`Self::invoke_values_bytes_reader`] for functions taking a single `list<u8>`, which
+    /// does not require callers to encode the parameter themselves.
+    #[instrument(level = "trace", skip(self, cx, params))]
+    fn invoke_values_reader<Results>(
+        &self,
+        cx: Self::Context,
+        instance: &str,
+        func: &str,
+        params: impl AsyncRead + Send + Unpin,
+    ) -> impl Future<Output = anyhow::Result<Results>> + Send
+    where
+        Results: TupleDecode<Self::Incoming> + Send,
+        <Results::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        async {
+            let mut params = params;
+            debug!("invoking function");
+            let (mut outgoing, incoming) = self
+                .invoke(cx, instance, func, Bytes::new(), &[[]; 0])
+                .await
+                .context("failed to invoke function")?;
+            debug!("transmitting sync parameters");
+            let n = tokio::io::copy(&mut params, &mut outgoing)
+                .await
+                .context("failed to transmit synchronous parameters")?;
+            outgoing
+                .shutdown()
+                .await
+                .context("failed to shutdown synchronous parameter channel")?;
+            trace!(n, "sent sync parameters");
+            receive_sync_results(incoming).await
+        }
+    }
+
+    /// Invoke function `func` on instance `instance` taking a single `list<u8>` parameter of
+    /// `len` bytes read from `params`, using typed `Results`
+    ///
+    /// This is like [`Self::invoke_values_reader`], but only the bytes of the list are read from
+    /// `params`, the length prefix is encoded by this function. This allows uploading large
+    /// byte payloads without buffering them entirely. Exactly `len` bytes are read from
+    /// `params`, the invocation fails if `params` ends before that.
+    #[instrument(level = "trace", skip(self, cx, params))]
+    fn invoke_values_bytes_reader<Results>(
+        &self,
+        cx: Self::Context,
+        instance: &str,
+        func: &str,
+        len: u32,
+        params: impl AsyncRead + Send + Unpin,
+    ) -> impl Future<Output = anyhow::Result<Results>> + Send
+    where
+        Results: TupleDecode<Self::Incoming> + Send,
+        <Results::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        async move {
+            let (prefix, _) = encode_value::<_, Self::Outgoing>(len)
+                .context("failed to encode parameter length")?;
+            let mut params = params.take(len.into());
+            debug!("invoking function");
+            let (mut outgoing, incoming) = self
+                .invoke(cx, instance, func, prefix, &[[]; 0])
+                .await
+                .context("failed to invoke function")?;
+            debug!("transmitting sync parameters");
+            let n = tokio::io::copy(&mut params, &mut outgoing)
+                .await
+                .context("failed to transmit synchronous parameters")?;
+            if n < len.into() {
+                bail!("parameter ended after {n} of {len} bytes");
+            }
+            outgoing
+                .shutdown()
+                .await
+                .context("failed to shutdown synchronous parameter channel")?;
+            trace!(n, "sent sync parameters");
+            receive_sync_results(incoming).await
+        }
+    }
+
     /// Invoke function `func` on instance `instance` using typed `Params` and `Results`
     /// This is like [`Self::invoke_values`], but it only results once all I/O is done
     #[instrument(level = "trace", skip_all)]
@@ -282,6 +553,228 @@ pub trait InvokeExt: Invoke {
         }
     }
 
+    /// Invoke function `func` on instance `instance` using typed `Params` and `Results`
+    ///
+    /// This is like [`Self::invoke_values_blocking`], but returns an [`InvokeError`], which
+    /// classifies the failure
+    #[instrument(level = "trace", skip_all)]
+    fn try_invoke_values<P, Params, Results>(
+        &self,
+        cx: Self::Context,
+        instance: &str,
+        func: &str,
+        params: Params,
+        paths: impl AsRef<[P]> + Send,
+    ) -> impl Future<Output = Result<Results, InvokeError>> + Send
+    where
+        P: AsRef<[Option<usize>]> + Send + Sync,
+        Params: TupleEncode<Self::Outgoing> + Send,
+        Results: TupleDecode<Self::Incoming> + Send,
+        <Params::Encoder as tokio_util::codec::Encoder<Params>>::Error:
+            std::error::Error + Send + Sync + 'static,
+        <Results::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        async {
+            trace!("encoding parameters");
+            let (buf, tx) =
+                encode_value(params).map_err(|err| InvokeError::Encode(anyhow::Error::new(err)))?;
+            debug!("invoking function");
+            let (mut outgoing, incoming) = self
+                .invoke(cx, instance, func, buf, paths)
+                .await
+                .map_err(InvokeError::invoke)?;
+            outgoing
+                .shutdown()
+                .await
+                .context("failed to shutdown synchronous parameter channel")
+                .map_err(InvokeError::Transport)?;
+            let tx = async {
+                if let Some(tx) = tx {
+                    debug!("transmitting async parameters");
+                    tx(outgoing.into(), Vec::with_capacity(8))
+                        .await
+                        .context("failed to write async parameters")
+                        .map_err(|err| InvokeError::io(err, InvokeError::Encode))?;
+                }
+                Ok(())
+            };
+            let rx = async {
+                let mut dec = FramedRead::new(incoming, Results::Decoder::default());
+                debug!("receiving sync results");
+                let results = receive_value(&mut dec).await.map_err(|err| match err {
+                    ReceiveError::Decode(err) => InvokeError::io(
+                        anyhow::Error::new(err).context("failed to receive sync results"),
+                        InvokeError::Decode,
+                    ),
+                    // the stream ending, even in the middle of the value, is a transport failure
+                    err => InvokeError::Transport(
+                        anyhow::Error::new(err).context("failed to receive sync results"),
+                    ),
+                })?;
+                if let Some(rx) = dec.decoder_mut().take_deferred() {
+                    debug!("receiving async results");
+                    rx(dec.into_inner().into(), Vec::with_capacity(8))
+                        .await
+                        .context("failed to receive async results")
+                        .map_err(|err| InvokeError::io(err, InvokeError::Decode))?;
+                }
+                Ok(results)
+            };
+            let (results, ()) = try_join!(rx, tx)?;
+            Ok(results)
+        }
+    }
+
+    /// Invoke function `func` on instance `instance`, which returns a single `result<T, E>`
+    ///
+    /// This is like [`Self::invoke_values_blocking`], but it unwraps the result tuple, such that
+    /// application errors returned by the peer are decoded as typed `Ok(Err(E))` and the outer
+    /// error is only returned if the invocation itself failed.
+    #[instrument(level = "trace", skip_all)]
+    fn invoke_values_result<P, Params, T, E>(
+        &self,
+        cx: Self::Context,
+        instance: &str,
+        func: &str,
+        params: Params,
+        paths: impl AsRef<[P]> + Send,
+    ) -> impl Future<Output = anyhow::Result<Result<T, E>>> + Send
+    where
+        P: AsRef<[Option<usize>]> + Send + Sync,
+        Params: TupleEncode<Self::Outgoing> + Send,
+        (Result<T, E>,): TupleDecode<Self::Incoming> + Send,
+        <Params::Encoder as tokio_util::codec::Encoder<Params>>::Error:
+            std::error::Error + Send + Sync + 'static,
+        <(Result<T, E>,) as crate::Decode<Self::Incoming>>::Decoder:
+            tokio_util::codec::Decoder<Error: std::error::Error + Send + Sync + 'static>,
+    {
+        async {
+            let (res,) = self
+                .invoke_values_blocking(cx, instance, func, params, paths)
+                .await?;
+            Ok(res)
+        }
+    }
+
+    /// Invoke function `func` on instance `instance`, which responds with a stream of `Results`,
+    /// served by [`ServeExt::serve_server_stream`](crate::ServeExt::serve_server_stream)
+    ///
+    /// Responses are transmitted back-to-back on the root result stream, each encoded as a
+    /// `Results` tuple, which is self-delimiting, and the end of the response stream is
+    /// signaled by shutdown of the result stream. Responses must not contain asynchronous values.
+    ///
+    /// The returned stream yields responses as they are received. It yields an error and ends if
+    /// a response cannot be decoded, if the result stream ends in the middle of a response or
+    /// if transmission of asynchronous parameters fails. Dropping the stream aborts any in-flight
+    /// transmission.
+    #[instrument(level = "trace", skip(self, cx, params, paths))]
+    fn invoke_server_stream<P, Params, Results>(
+        &self,
+        cx: Self::Context,
+        instance: &str,
+        func: &str,
+        params: Params,
+        paths: impl AsRef<[P]> + Send,
+    ) -> impl Future<
+        Output = anyhow::Result<impl Stream<Item = anyhow::Result<Results>> + Send + 'static>,
+    > + Send
+    where
+        P: AsRef<[Option<usize>]> + Send + Sync,
+        Params: TupleEncode<Self::Outgoing> + Send,
+        Results: TupleDecode<Self::Incoming> + Send + 'static,
+        <Params::Encoder as tokio_util::codec::Encoder<Params>>::Error:
+            std::error::Error + Send + Sync + 'static,
+        <Results::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        async {
+            let (incoming, tx) = self
+                .invoke_channels(cx, instance, func, params, paths)
+                .await?;
+            // abort the transmission task if the stream is dropped
+            let tx = tx.map(|tx| AbortOnDropHandle::new(tokio::spawn(tx)));
+            let dec = FramedRead::new(incoming, Results::Decoder::default());
+            Ok(stream::unfold(Some((dec, tx)), |st| {
+                async move {
+                    let (mut dec, mut tx) = st?;
+                    let next = if let Some(mut fut) = tx.take() {
+                        // transmission errors take precedence over results received concurrently
+                        let next = select! {
+                            biased;
+                            res = &mut fut => Err(res),
+                            res = receive_value(&mut dec) => Ok(res),
+                        };
+                        match next {
+                            Ok(next) => {
+                                tx = Some(fut);
+                                next
+                            }
+                            Err(res) => {
+                                if let Err(err) = res
+                                    .context("transmitting async parameters failed")
+                                    .and_then(|res| res)
+                                {
+                                    return Some((Err(err), None));
+                                }
+                                receive_value(&mut dec).await
+                            }
+                        }
+                    } else {
+                        receive_value(&mut dec).await
+                    };
+                    match next {
+                        Ok(results) => {
+                            trace!("received result stream element");
+                            if dec.decoder_mut().take_deferred().is_some() {
+                                return Some((
+                                    Err(anyhow!(
+                                        "async values are not supported in result streams"
+                                    )),
+                                    None,
+                                ));
+                            }
+                            Some((Ok(results), Some((dec, tx))))
+                        }
+                        Err(err) if err.is_eof() => {
+                            debug!("result stream ended");
+                            let tx = tx?;
+                            match tx.await {
+                                Ok(Ok(())) => None,
+                                Ok(Err(err)) => Some((Err(err), None)),
+                                Err(err) => Some((
+                                    Err(anyhow::Error::new(err)
+                                        .context("transmitting async parameters failed")),
+                                    None,
+                                )),
+                            }
+                        }
+                        Err(err) => Some((
+                            Err(anyhow::Error::new(err).context("failed to receive results")),
+                            None,
+                        )),
+                    }
+                }
+                .in_current_span()
+            }))
+        }
+    }
+
+    /// List functions served by the peer, which must serve [`Reflection`](crate::Reflection)
+    #[instrument(level = "trace", skip_all)]
+    fn list_functions(
+        &self,
+        cx: Self::Context,
+    ) -> impl Future<Output = anyhow::Result<Vec<FunctionSignature>>> + Send {
+        async {
+            let (sigs,): (Vec<FunctionSignatureTuple>,) = self
+                .invoke_values_blocking(cx, REFLECT_INSTANCE, REFLECT_FUNC, (), &[[]; 0])
+                .await
+                .context("failed to invoke reflection function")?;
+            Ok(sigs.into_iter().map(FunctionSignature::from).collect())
+        }
+    }
+
     /// Returns a [`Timeout`], wrapping [Self] with an implementation of [Invoke], which will
     /// error, if call to [`Invoke::invoke`] does not return within a supplied `timeout`
     fn timeout(&self, timeout: Duration) -> Timeout<'_, Self> {
@@ -305,17 +798,40 @@ pub trait InvokeExt: Invoke {
 
 impl<T: Invoke> InvokeExt for T {}
 
+/// Receives synchronous `Results` from `incoming`, failing if they contain asynchronous values
+async fn receive_sync_results<R, Results>(incoming: R) -> anyhow::Result<Results>
+where
+    R: AsyncRead + Unpin,
+    Results: TupleDecode<R>,
+    <Results::Decoder as tokio_util::codec::Decoder>::Error:
+        std::error::Error + Send + Sync + 'static,
+{
+    let mut dec = FramedRead::new(incoming, Results::Decoder::default());
+    debug!("receiving sync results");
+    let results = receive_value(&mut dec)
+        .await
+        .context("failed to receive sync results")?;
+    trace!("received sync results");
+    ensure!(
+        dec.decoder_mut().take_deferred().is_none(),
+        "asynchronous results are not supported"
+    );
+    Ok(results)
+}
+
 #[allow(dead_code)]
 #[cfg(test)]
 mod tests {
     use core::future::Future;
     use core::pin::Pin;
+    use core::task::{Context, Poll};
 
     use std::sync::Arc;
 
     use bytes::Bytes;
-    use futures::{Stream, StreamExt as _};
+    use futures::{future, stream, Stream, StreamExt as _, TryStreamExt as _};
     use send_future::SendFuture as _;
+    use tokio::sync::oneshot;
 
     use super::*;
 
@@ -373,6 +889,243 @@ mod tests {
         Ok(r0)
     }
 
+    struct PendingIo;
+
+    impl Index<Self> for PendingIo {
+        fn index(&self, _path: &[usize]) -> anyhow::Result<Self> {
+            Ok(Self)
+        }
+    }
+
+    impl AsyncRead for PendingIo {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Poll::Pending
+        }
+    }
+
+    impl AsyncWrite for PendingIo {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// [Invoke] implementation, which records parameters and never receives any results
+    #[derive(Clone, Default)]
+    struct PendingInvoke {
+        params: Arc<std::sync::Mutex<Option<Bytes>>>,
+    }
+
+    impl Invoke for PendingInvoke {
+        type Context = ();
+        type Outgoing = PendingIo;
+        type Incoming = PendingIo;
+
+        async fn invoke<P>(
+            &self,
+            (): Self::Context,
+            _instance: &str,
+            _func: &str,
+            params: Bytes,
+            _paths: impl AsRef<[P]> + Send,
+        ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)>
+        where
+            P: AsRef<[Option<usize>]> + Send + Sync,
+        {
+            *self.params.lock().unwrap() = Some(params);
+            Ok((PendingIo, PendingIo))
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn invoke_drop() {
+        let (guard, dropped) = oneshot::channel::<()>();
+        let st: Pin<Box<dyn Stream<Item = Bytes> + Send>> = Box::pin(stream::once(async move {
+            let _guard = guard;
+            future::pending().await
+        }));
+        let res = tokio::time::timeout(
+            Duration::from_millis(100),
+            PendingInvoke::default().invoke_values::<_, _, (u8,)>(
+                (),
+                "foo",
+                "bar",
+                (st,),
+                [[Some(0)]],
+            ),
+        )
+        .await;
+        assert!(res.is_err(), "invocation should not have completed");
+        tokio::time::timeout(Duration::from_secs(1), dropped)
+            .await
+            .expect("transmission task was not aborted")
+            .expect_err("guard should have been dropped");
+    }
+
+    /// [Invoke] implementation, which is not ready until `ready` is set and fails permanently
+    /// once it is set to `false`
+    #[derive(Clone)]
+    struct GatedInvoke {
+        inner: PendingInvoke,
+        ready: tokio::sync::watch::Receiver<Option<bool>>,
+    }
+
+    impl Invoke for GatedInvoke {
+        type Context = ();
+        type Outgoing = PendingIo;
+        type Incoming = PendingIo;
+
+        async fn invoke<P>(
+            &self,
+            cx: Self::Context,
+            instance: &str,
+            func: &str,
+            params: Bytes,
+            paths: impl AsRef<[P]> + Send,
+        ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)>
+        where
+            P: AsRef<[Option<usize>]> + Send + Sync,
+        {
+            self.inner.invoke(cx, instance, func, params, paths).await
+        }
+
+        async fn ready(&self) -> anyhow::Result<()> {
+            let ready = *self
+                .ready
+                .clone()
+                .wait_for(Option::is_some)
+                .await
+                .context("connection dropped")?;
+            anyhow::ensure!(ready == Some(true), "connection failed");
+            Ok(())
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn ready() -> anyhow::Result<()> {
+        /// Returns `true` if `fut` does not resolve within a short time
+        async fn is_pending(fut: impl Future<Output = anyhow::Result<()>>) -> bool {
+            tokio::time::timeout(Duration::from_millis(50), fut)
+                .await
+                .is_err()
+        }
+
+        // transports without a connection are always ready
+        tokio::time::timeout(Duration::from_secs(1), PendingInvoke::default().ready())
+            .await
+            .context("default readiness should not block")??;
+
+        let (tx, rx) = tokio::sync::watch::channel(None);
+        let gated = || GatedInvoke {
+            inner: PendingInvoke::default(),
+            ready: rx.clone(),
+        };
+        let clt = gated();
+        let owned = gated().timeout_owned(Duration::from_secs(1));
+        let headers = crate::HeaderInvoke::new(gated());
+        let tee = crate::TeeInvoke::new(gated(), gated());
+
+        // wrappers forward readiness of the wrapped transport
+        assert!(is_pending(clt.ready()).await, "transport is not connected");
+        assert!(is_pending(clt.timeout(Duration::from_secs(1)).ready()).await);
+        assert!(is_pending(owned.ready()).await);
+        assert!(is_pending(headers.ready()).await);
+        assert!(is_pending(tee.ready()).await);
+        // only the primary transport of a tee is awaited
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            crate::TeeInvoke::new(PendingInvoke::default(), gated()).ready(),
+        )
+        .await
+        .context("tee readiness should not wait for the secondary")??;
+
+        tx.send_replace(Some(true));
+        clt.ready().await?;
+        clt.timeout(Duration::from_secs(1)).ready().await?;
+        owned.ready().await?;
+        headers.ready().await?;
+        tee.ready().await?;
+
+        tx.send_replace(Some(false));
+        for res in [
+            clt.ready().await,
+            clt.timeout(Duration::from_secs(1)).ready().await,
+            owned.ready().await,
+            headers.ready().await,
+            tee.ready().await,
+        ] {
+            let err = res.expect_err("failed connection is ready");
+            assert!(
+                format!("{err:#}").ends_with("connection failed"),
+                "unexpected error: {err:#}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn invoke_unit_results() -> anyhow::Result<()> {
+        let clt = PendingInvoke::default();
+        let ((), io) = tokio::time::timeout(
+            Duration::from_secs(1),
+            clt.invoke_values::<_, _, ()>((), "foo", "bar", (42_u32,), &[[]; 0]),
+        )
+        .await
+        .context("invocation with unit results should not wait for results")??;
+        assert!(io.is_none());
+        assert_eq!(
+            clt.params.lock().unwrap().as_deref(),
+            Some(b"\x2a".as_slice())
+        );
+
+        let params: Pin<Box<dyn Stream<Item = Bytes> + Send>> =
+            Box::pin(stream::iter([Bytes::from("test")]));
+        let ((), io) = tokio::time::timeout(
+            Duration::from_secs(1),
+            clt.invoke_values::<_, _, ()>((), "foo", "bar", (params,), [[Some(0)]]),
+        )
+        .await
+        .context("invocation with unit results should not wait for results")??;
+        // transmission may complete before results are decoded
+        if let Some(io) = io {
+            io.await?;
+        }
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn invoke_encode_value() -> anyhow::Result<()> {
+        fn params() -> (u8, &'static str, Pin<Box<dyn Stream<Item = Bytes> + Send>>) {
+            (0x42, "test", Box::pin(stream::pending()))
+        }
+
+        let (buf, deferred) = encode_value::<_, PendingIo>(params())?;
+        assert!(deferred.is_some());
+
+        let clt = PendingInvoke::default();
+        let _results = clt
+            .invoke_values_split::<_, _, (u8,)>((), "foo", "bar", params(), [[Some(2)]])
+            .await?;
+        assert_eq!(clt.params.lock().unwrap().as_ref(), Some(&buf));
+        assert_eq!(buf.as_ref(), b"\x42\x04test\x00");
+        Ok(())
+    }
+
     trait Handler {
         fn foo() -> impl Future<Output = anyhow::Result<()>>;
     }
@@ -387,4 +1140,416 @@ mod tests {
             Ok(())
         }
     }
+
+    /// Incoming byte stream, which yields a fixed buffer
+    struct BytesIo(Bytes);
+
+    impl Index<Self> for BytesIo {
+        fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+            anyhow::bail!("unexpected index {path:?}")
+        }
+    }
+
+    impl AsyncRead for BytesIo {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            let n = buf.remaining().min(self.0.len());
+            buf.put_slice(&self.0.split_to(n));
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// [Invoke] implementation with a fixed behavior
+    enum MockInvoke {
+        Fail,
+        Hang,
+        Respond(&'static [u8]),
+    }
+
+    impl Invoke for MockInvoke {
+        type Context = ();
+        type Outgoing = PendingIo;
+        type Incoming = BytesIo;
+
+        async fn invoke<P>(
+            &self,
+            (): Self::Context,
+            _instance: &str,
+            _func: &str,
+            _params: Bytes,
+            _paths: impl AsRef<[P]> + Send,
+        ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)>
+        where
+            P: AsRef<[Option<usize>]> + Send + Sync,
+        {
+            match self {
+                Self::Fail => anyhow::bail!("connection refused"),
+                Self::Hang => future::pending().await,
+                Self::Respond(buf) => Ok((PendingIo, BytesIo(Bytes::from_static(buf)))),
+            }
+        }
+    }
+
+    /// Outgoing byte stream, which fails all nested writes
+    struct BrokenIo;
+
+    impl Index<Self> for BrokenIo {
+        fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+            anyhow::bail!("connection reset while indexing {path:?}")
+        }
+    }
+
+    impl AsyncWrite for BrokenIo {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Poll::Ready(Err(std::io::ErrorKind::ConnectionReset.into()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Incoming byte stream, which yields to the runtime once before returning a fixed buffer
+    struct YieldingIo {
+        yielded: bool,
+        io: BytesIo,
+    }
+
+    impl Index<Self> for YieldingIo {
+        fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+            anyhow::bail!("unexpected index {path:?}")
+        }
+    }
+
+    impl AsyncRead for YieldingIo {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            if !self.yielded {
+                self.yielded = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Pin::new(&mut self.io).poll_read(cx, buf)
+        }
+    }
+
+    /// [Invoke] implementation, which responds immediately, but fails to transmit async parameters
+    struct BrokenTransmitInvoke;
+
+    impl Invoke for BrokenTransmitInvoke {
+        type Context = ();
+        type Outgoing = BrokenIo;
+        type Incoming = YieldingIo;
+
+        async fn invoke<P>(
+            &self,
+            (): Self::Context,
+            _instance: &str,
+            _func: &str,
+            _params: Bytes,
+            _paths: impl AsRef<[P]> + Send,
+        ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)>
+        where
+            P: AsRef<[Option<usize>]> + Send + Sync,
+        {
+            Ok((
+                BrokenIo,
+                YieldingIo {
+                    yielded: false,
+                    io: BytesIo(Bytes::from_static(b"\x42")),
+                },
+            ))
+        }
+    }
+
+    /// Value, which always fails to encode
+    struct Unencodable;
+
+    #[derive(Default)]
+    struct UnencodableEncoder;
+
+    impl tokio_util::codec::Encoder<Unencodable> for UnencodableEncoder {
+        type Error = std::io::Error;
+
+        fn encode(&mut self, _: Unencodable, _: &mut bytes::BytesMut) -> std::io::Result<()> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "value cannot be encoded",
+            ))
+        }
+    }
+
+    impl<W> crate::Deferred<W> for UnencodableEncoder {
+        fn take_deferred(&mut self) -> Option<crate::DeferredFn<W>> {
+            None
+        }
+    }
+
+    impl<W> crate::Encode<W> for Unencodable {
+        type Encoder = UnencodableEncoder;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn try_invoke_values() -> anyhow::Result<()> {
+        let (v,) = MockInvoke::Respond(b"\x42")
+            .try_invoke_values::<_, _, (u8,)>((), "foo", "bar", (), &[[]; 0])
+            .await?;
+        assert_eq!(v, 0x42);
+
+        let err = MockInvoke::Respond(b"\x42")
+            .try_invoke_values::<_, _, (u8,)>((), "foo", "bar", (Unencodable,), &[[]; 0])
+            .await
+            .expect_err("encoding should fail");
+        assert!(matches!(err, InvokeError::Encode(..)), "{err:?}");
+
+        let err = MockInvoke::Fail
+            .try_invoke_values::<_, _, (u8,)>((), "foo", "bar", (), &[[]; 0])
+            .await
+            .expect_err("invocation should fail");
+        assert!(matches!(err, InvokeError::Transport(..)), "{err:?}");
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(err)),
+            "transport error: connection refused"
+        );
+
+        let err = MockInvoke::Hang
+            .timeout(Duration::from_millis(10))
+            .try_invoke_values::<_, _, (u8,)>((), "foo", "bar", (), &[[]; 0])
+            .await
+            .expect_err("invocation should time out");
+        assert!(matches!(err, InvokeError::Timeout(..)), "{err:?}");
+
+        let err = MockInvoke::Respond(b"\x01\xff")
+            .try_invoke_values::<_, _, (String,)>((), "foo", "bar", (), &[[]; 0])
+            .await
+            .expect_err("decoding should fail");
+        assert!(matches!(err, InvokeError::Decode(..)), "{err:?}");
+
+        for buf in [b"".as_slice(), b"\x04te"] {
+            let err = MockInvoke::Respond(buf)
+                .try_invoke_values::<_, _, (String,)>((), "foo", "bar", (), &[[]; 0])
+                .await
+                .expect_err("truncated results should fail");
+            assert!(matches!(err, InvokeError::Transport(..)), "{err:?}");
+        }
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn invoke_values_transmit_error_precedence() -> anyhow::Result<()> {
+        for _ in 0..32 {
+            let params: Pin<Box<dyn Stream<Item = Bytes> + Send>> = Box::pin(stream::pending());
+            let Err(err) = BrokenTransmitInvoke
+                .invoke_values::<_, _, (u8,)>((), "foo", "bar", (params,), &[[None]])
+                .await
+            else {
+                anyhow::bail!("transmission error should take precedence over results")
+            };
+            assert!(format!("{err:#}").contains("connection reset"), "{err:#}");
+        }
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn invoke_server_stream() -> anyhow::Result<()> {
+        let responses = MockInvoke::Respond(b"\x00\x02#0\x01\x02#1\x02\x02#2")
+            .invoke_server_stream::<_, _, (u32, String)>((), "foo", "bar", (3_u32,), &[[]; 0])
+            .await?;
+        let responses: Vec<_> = responses.try_collect().await?;
+        assert_eq!(
+            responses,
+            [(0, "#0".into()), (1, "#1".into()), (2, "#2".into())]
+        );
+
+        let responses = MockInvoke::Respond(b"")
+            .invoke_server_stream::<_, _, (u32, String)>((), "foo", "bar", (), &[[]; 0])
+            .await?;
+        let responses: Vec<_> = responses.try_collect().await?;
+        assert!(responses.is_empty());
+
+        // result stream ends in the middle of the second response
+        let responses = MockInvoke::Respond(b"\x00\x02#0\x01\x02#")
+            .invoke_server_stream::<_, _, (u32, String)>((), "foo", "bar", (), &[[]; 0])
+            .await?;
+        let responses: Vec<_> = responses.collect().await;
+        let [Ok((0, first)), Err(err)] = responses.as_slice() else {
+            anyhow::bail!("unexpected responses: {responses:?}")
+        };
+        assert_eq!(first, "#0");
+        assert!(
+            format!("{err:#}").starts_with("failed to receive results: "),
+            "{err:#}"
+        );
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn invoke_values_result() -> anyhow::Result<()> {
+        // application error, e.g. `record { code: u16, message: string }`
+        type AppError = (u16, String);
+
+        let res = MockInvoke::Respond(b"\x00\x2a")
+            .invoke_values_result::<_, _, u32, AppError>((), "foo", "bar", (), &[[]; 0])
+            .await?;
+        assert_eq!(res, Ok(42));
+
+        let res = MockInvoke::Respond(b"\x01\x94\x03\x09not found")
+            .invoke_values_result::<_, _, u32, AppError>((), "foo", "bar", (), &[[]; 0])
+            .await?;
+        assert_eq!(res, Err((404, "not found".into())));
+
+        let err = MockInvoke::Fail
+            .invoke_values_result::<_, _, u32, AppError>((), "foo", "bar", (), &[[]; 0])
+            .await
+            .expect_err("invocation should fail");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to invoke function: connection refused"
+        );
+
+        let err = MockInvoke::Respond(b"\x02")
+            .invoke_values_result::<_, _, u32, AppError>((), "foo", "bar", (), &[[]; 0])
+            .await
+            .expect_err("invalid result status should fail");
+        assert!(
+            format!("{err:#}").starts_with("failed to receive sync results: "),
+            "{err:#}"
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test_log::test(tokio::test)]
+    async fn invoke_reader() -> anyhow::Result<()> {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        use tokio::sync::mpsc;
+
+        use crate::{encode_value, BoundedOutgoing, MockOutgoing, ReplayIncoming};
+
+        const CHUNKS: usize = 256;
+        const CHUNK_SIZE: usize = 64 * 1024;
+        // generated chunk, copy buffer and chunks queued in the channel
+        const MAX_IN_FLIGHT: usize = 4 * CHUNK_SIZE;
+
+        /// [Invoke] implementation, which records the parameters passed to it, sends the ones
+        /// written to the outgoing stream over a bounded channel and responds with `results`
+        struct UploadInvoke {
+            params: std::sync::Mutex<Option<Bytes>>,
+            outgoing: std::sync::Mutex<Option<BoundedOutgoing>>,
+            results: Bytes,
+        }
+
+        impl UploadInvoke {
+            fn new(results: Bytes) -> (Self, mpsc::Receiver<(Arc<[usize]>, Bytes)>) {
+                let (outgoing, rx) = BoundedOutgoing::new(2);
+                let clt = Self {
+                    params: std::sync::Mutex::default(),
+                    outgoing: std::sync::Mutex::new(Some(outgoing)),
+                    results,
+                };
+                (clt, rx)
+            }
+        }
+
+        impl Invoke for UploadInvoke {
+            type Context = ();
+            type Outgoing = BoundedOutgoing;
+            type Incoming = ReplayIncoming;
+
+            async fn invoke<P>(
+                &self,
+                (): Self::Context,
+                _instance: &str,
+                _func: &str,
+                params: Bytes,
+                _paths: impl AsRef<[P]> + Send,
+            ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)>
+            where
+                P: AsRef<[Option<usize>]> + Send + Sync,
+            {
+                *self.params.lock().unwrap() = Some(params);
+                let outgoing = self
+                    .outgoing
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .context("invoked more than once")?;
+                Ok((outgoing, ReplayIncoming::from(self.results.clone())))
+            }
+        }
+
+        let len = CHUNKS * CHUNK_SIZE;
+        let produced = Arc::new(AtomicUsize::new(0));
+        let chunks = stream::iter(0..CHUNKS).map({
+            let produced = Arc::clone(&produced);
+            move |i| {
+                produced.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+                Ok::<_, std::io::Error>(Bytes::from(vec![i as u8; CHUNK_SIZE]))
+            }
+        });
+        let params = tokio_util::io::StreamReader::new(chunks);
+
+        let (results, _) = encode_value::<_, MockOutgoing>((u64::try_from(len)?,))?;
+        let (clt, mut rx) = UploadInvoke::new(results.clone());
+        let consume = async {
+            let mut consumed = 0;
+            while let Some((path, buf)) = rx.recv().await {
+                assert!(path.is_empty());
+                assert!(buf.iter().all(|b| *b == (consumed / CHUNK_SIZE) as u8));
+                consumed += buf.len();
+                let in_flight = produced.load(Ordering::Relaxed) - consumed;
+                assert!(
+                    in_flight <= MAX_IN_FLIGHT,
+                    "`{in_flight}` bytes buffered, parameters should be streamed"
+                );
+                // let the producer run ahead as far as it can
+                tokio::task::yield_now().await;
+            }
+            anyhow::Ok(consumed)
+        };
+        let (res, consumed) = tokio::join!(
+            clt.invoke_values_bytes_reader::<(u64,)>((), "foo", "bar", u32::try_from(len)?, params),
+            consume,
+        );
+        let (n,) = res?;
+        assert_eq!(n, len as u64);
+        assert_eq!(consumed?, len);
+        // the length prefix is encoded by the client
+        let (prefix, _) = encode_value::<_, MockOutgoing>(u32::try_from(len)?)?;
+        assert_eq!(clt.params.lock().unwrap().take(), Some(prefix));
+
+        // parameters ending early fail the invocation
+        let (clt, _rx) = UploadInvoke::new(results.clone());
+        let err = clt
+            .invoke_values_bytes_reader::<(u64,)>((), "foo", "bar", 4, b"abc".as_slice())
+            .await
+            .expect_err("short parameters should fail");
+        assert_eq!(err.to_string(), "parameter ended after 3 of 4 bytes");
+
+        // pre-encoded parameters are copied as-is
+        let (clt, mut rx) = UploadInvoke::new(results);
+        let (n,) = clt
+            .invoke_values_reader::<(u64,)>((), "foo", "bar", b"\x01\x2a".as_slice())
+            .await?;
+        assert_eq!(n, len as u64);
+        assert_eq!(clt.params.lock().unwrap().take(), Some(Bytes::new()));
+        let (_, buf) = rx.recv().await.context("parameters missing")?;
+        assert_eq!(buf, b"\x01\x2a".as_slice());
+        Ok(())
+    }
 }
@@ -1,18 +1,46 @@
 #![allow(clippy::type_complexity)]
 
+pub mod broadcast;
+#[cfg(feature = "derive")]
+pub mod derive;
 #[cfg(feature = "frame")]
 pub mod frame;
+pub mod headers;
 pub mod invoke;
+pub mod limit;
+#[cfg(feature = "test-util")]
+pub mod mock;
+pub mod receive;
+#[cfg(feature = "test-util")]
+pub mod record;
+pub mod reflect;
+#[cfg(feature = "test-util")]
+pub mod roundtrip;
 pub mod serve;
+pub mod tee;
 
 mod value;
 
+pub use broadcast::Broadcast;
 #[cfg(feature = "frame")]
 pub use frame::{Decoder as FrameDecoder, Encoder as FrameEncoder, FrameRef};
-pub use invoke::{Invoke, InvokeExt};
+pub use headers::{HeaderIncoming, HeaderInvoke, HeaderServe, Headers};
+pub use invoke::{Invoke, InvokeError, InvokeExt};
+pub use limit::{BoundedIo, BoundedServe, IdleIncoming, IdleServe, LimitedIncoming, LimitedServe};
+#[cfg(feature = "test-util")]
+pub use mock::{BoundedOutgoing, ChunkedIncoming, MockOutgoing, MockServe};
+pub use receive::{receive_value, ReceiveError};
+#[cfg(feature = "test-util")]
+pub use record::{RecordingOutgoing, ReplayIncoming};
+pub use reflect::{FunctionSignature, Reflection};
+#[cfg(feature = "test-util")]
+pub use roundtrip::{assert_roundtrip, assert_roundtrip_chunked};
 pub use send_future::SendFuture;
 pub use serve::{Serve, ServeExt};
+pub use tee::{TeeInvoke, TeeOutgoing};
 pub use value::*;
+#[cfg(feature = "derive")]
+pub use wrpc_transport_derive::{Decode, Encode};
 
 #[doc(hidden)]
 // This is an internal trait used as a workaround for
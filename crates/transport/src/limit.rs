@@ -0,0 +1,633 @@
+//! Limits on served invocations, like the amount of data received, the number in flight or the
+//! time spent waiting for data
+
+use core::future::Future as _;
+use core::num::NonZeroUsize;
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+use core::time::Duration;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures::{stream, Stream, StreamExt as _, TryStreamExt as _};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Sleep;
+use tracing::{instrument, trace};
+
+use crate::{Index, Serve};
+
+/// Incoming byte stream, which fails once the cumulative amount of data received by it
+/// and all streams indexed from it exceeds a byte budget.
+///
+/// Bytes received on nested streams, e.g. elements of an async `stream<u8>` parameter,
+/// are counted as they arrive, regardless of the structure of the payload.
+#[derive(Debug)]
+pub struct LimitedIncoming<T> {
+    inner: T,
+    limit: u64,
+    received: Arc<AtomicU64>,
+}
+
+impl<T> LimitedIncoming<T> {
+    /// Constructs a new [`LimitedIncoming`], which allows at most `limit` bytes to be received
+    pub fn new(inner: T, limit: u64) -> Self {
+        Self {
+            inner,
+            limit,
+            received: Arc::default(),
+        }
+    }
+
+    /// Returns the amount of bytes received so far by this stream and all streams indexed from it
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Returns the wrapped stream
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Index<T>> Index<Self> for LimitedIncoming<T> {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let inner = self.inner.index(path)?;
+        Ok(Self {
+            inner,
+            limit: self.limit,
+            received: Arc::clone(&self.received),
+        })
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for LimitedIncoming<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let n = (buf.filled().len() - filled).try_into().unwrap_or(u64::MAX);
+        let received = self
+            .received
+            .fetch_add(n, Ordering::Relaxed)
+            .saturating_add(n);
+        if received > self.limit {
+            return Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invocation payload exceeds {} bytes", self.limit),
+            )));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// [`Serve`] implementation, which limits the cumulative amount of data received per
+/// invocation served by the wrapped [`Serve`] implementation, see [`LimitedIncoming`].
+#[derive(Clone, Debug)]
+pub struct LimitedServe<T> {
+    inner: T,
+    limit: u64,
+}
+
+impl<T> LimitedServe<T> {
+    /// Constructs a new [`LimitedServe`], which allows at most `limit` bytes to be received
+    /// per invocation
+    pub fn new(inner: T, limit: u64) -> Self {
+        Self { inner, limit }
+    }
+
+    /// Returns the wrapped [`Serve`] implementation
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Serve> Serve for LimitedServe<T> {
+    type Context = T::Context;
+    type Outgoing = T::Outgoing;
+    type Incoming = LimitedIncoming<T::Incoming>;
+
+    #[instrument(level = "trace", skip(self, paths), fields(limit = self.limit))]
+    async fn serve(
+        &self,
+        instance: &str,
+        func: &str,
+        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+    ) -> anyhow::Result<
+        impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>>
+            + Send
+            + 'static,
+    > {
+        let limit = self.limit;
+        let invocations = self.inner.serve(instance, func, paths).await?;
+        Ok(invocations.map_ok(move |(cx, outgoing, incoming)| {
+            (cx, outgoing, LimitedIncoming::new(incoming, limit))
+        }))
+    }
+}
+
+/// Incoming byte stream, which fails if no data is received by it for longer than a timeout.
+///
+/// Each stream indexed from it, e.g. a nested `future` or `stream` subscription, has its own
+/// timer, so a peer, which never sends a value for a nested subscription, cannot keep a reader
+/// waiting indefinitely. The timer only runs while the stream is being read from and is reset
+/// each time data is received.
+#[derive(Debug)]
+pub struct IdleIncoming<T> {
+    inner: T,
+    timeout: Duration,
+    path: Arc<[usize]>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> IdleIncoming<T> {
+    /// Constructs a new [`IdleIncoming`], which fails reads after `timeout` without data
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            path: Arc::default(),
+            sleep: None,
+        }
+    }
+
+    /// Returns the wrapped stream
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Index<T>> Index<Self> for IdleIncoming<T> {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let inner = self.inner.index(path)?;
+        Ok(Self {
+            inner,
+            timeout: self.timeout,
+            path: [self.path.as_ref(), path].concat().into(),
+            sleep: None,
+        })
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for IdleIncoming<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if let Poll::Ready(res) = Pin::new(&mut self.inner).poll_read(cx, buf) {
+            self.sleep = None;
+            return Poll::Ready(res);
+        }
+        let timeout = self.timeout;
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        ready!(sleep.as_mut().poll(cx));
+        self.sleep = None;
+        trace!(path = ?self.path, ?timeout, "subscription idle");
+        Poll::Ready(Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("subscription `{:?}` idle for {timeout:?}", self.path),
+        )))
+    }
+}
+
+/// [`Serve`] implementation, which fails reads of invocation data, if none is received for
+/// longer than a timeout, see [`IdleIncoming`].
+#[derive(Clone, Debug)]
+pub struct IdleServe<T> {
+    inner: T,
+    timeout: Duration,
+}
+
+impl<T> IdleServe<T> {
+    /// Constructs a new [`IdleServe`], which fails reads after `timeout` without data
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    /// Returns the wrapped [`Serve`] implementation
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Serve> Serve for IdleServe<T> {
+    type Context = T::Context;
+    type Outgoing = T::Outgoing;
+    type Incoming = IdleIncoming<T::Incoming>;
+
+    #[instrument(level = "trace", skip(self, paths), fields(timeout = ?self.timeout))]
+    async fn serve(
+        &self,
+        instance: &str,
+        func: &str,
+        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+    ) -> anyhow::Result<
+        impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>>
+            + Send
+            + 'static,
+    > {
+        let timeout = self.timeout;
+        let invocations = self.inner.serve(instance, func, paths).await?;
+        Ok(invocations.map_ok(move |(cx, outgoing, incoming)| {
+            (cx, outgoing, IdleIncoming::new(incoming, timeout))
+        }))
+    }
+}
+
+/// Byte stream of an invocation accepted by [`BoundedServe`], which holds the invocation's
+/// in-flight slot until it and all streams indexed from it are dropped.
+#[derive(Debug)]
+pub struct BoundedIo<T> {
+    inner: T,
+    permit: Arc<OwnedSemaphorePermit>,
+}
+
+impl<T> BoundedIo<T> {
+    /// Returns the wrapped stream, the in-flight slot is released once all other streams of the
+    /// invocation are dropped
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Index<T>> Index<Self> for BoundedIo<T> {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let inner = self.inner.index(path)?;
+        Ok(Self {
+            inner,
+            permit: Arc::clone(&self.permit),
+        })
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for BoundedIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for BoundedIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// [`Serve`] implementation, which limits the number of in-flight invocations across all
+/// functions served by the wrapped [`Serve`] implementation.
+///
+/// An invocation is in flight from the moment it is accepted until its incoming and outgoing
+/// streams, including all streams indexed from them, are dropped. Each invocation stream
+/// returned by [`Serve::serve`] pulls an invocation from the wrapped transport first and only
+/// then waits for an in-flight slot, so a slot is never held by an invocation, which has not
+/// arrived yet. Once the limit is reached, each invocation stream holds at most one pulled
+/// invocation waiting for a slot and stops polling the wrapped transport, which applies
+/// backpressure at the transport instead of accumulating accepted, but not yet handled
+/// invocations in memory.
+///
+/// Note, that this does not limit the number of handlers executing in parallel, but
+/// the number of invocations accepted, including those not yet handled.
+#[derive(Clone, Debug)]
+pub struct BoundedServe<T> {
+    inner: T,
+    inflight: Arc<Semaphore>,
+}
+
+impl<T> BoundedServe<T> {
+    /// Constructs a new [`BoundedServe`], which allows at most `max_inflight` invocations
+    /// to be in flight at a time
+    pub fn new(inner: T, max_inflight: NonZeroUsize) -> Self {
+        Self {
+            inner,
+            inflight: Arc::new(Semaphore::new(max_inflight.get())),
+        }
+    }
+
+    /// Returns the number of invocations, which can be accepted before the limit is reached
+    pub fn available(&self) -> usize {
+        self.inflight.available_permits()
+    }
+
+    /// Returns the wrapped [`Serve`] implementation
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Serve> Serve for BoundedServe<T> {
+    type Context = T::Context;
+    type Outgoing = BoundedIo<T::Outgoing>;
+    type Incoming = BoundedIo<T::Incoming>;
+
+    #[instrument(level = "trace", skip(self, paths))]
+    async fn serve(
+        &self,
+        instance: &str,
+        func: &str,
+        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+    ) -> anyhow::Result<
+        impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>>
+            + Send
+            + 'static,
+    > {
+        let invocations = self.inner.serve(instance, func, paths).await?;
+        let inflight = Arc::clone(&self.inflight);
+        Ok(stream::unfold(
+            (Box::pin(invocations), inflight),
+            |(mut invocations, inflight)| async move {
+                let invocation = match invocations.next().await? {
+                    Ok(invocation) => invocation,
+                    Err(err) => return Some((Err(err), (invocations, inflight))),
+                };
+                trace!("pulled invocation, waiting for in-flight invocation slot");
+                // the semaphore is never closed
+                let permit = Arc::clone(&inflight).acquire_owned().await.ok()?;
+                let invocation = {
+                    let (cx, outgoing, incoming) = invocation;
+                    let permit = Arc::new(permit);
+                    (
+                        cx,
+                        BoundedIo {
+                            inner: outgoing,
+                            permit: Arc::clone(&permit),
+                        },
+                        BoundedIo {
+                            inner: incoming,
+                            permit,
+                        },
+                    )
+                };
+                Some((Ok(invocation), (invocations, inflight)))
+            },
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use anyhow::{bail, Context as _};
+    use bytes::Bytes;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    use crate::{
+        encode_value, MockOutgoing, MockServe, RecordingOutgoing, ReplayIncoming, ServeExt as _,
+    };
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn serve() -> anyhow::Result<()> {
+        let srv = LimitedServe::new(MockServe::default(), 1024);
+        let invocations = srv.serve_values::<(Bytes,), ()>("foo", "bar", []).await?;
+        let mut invocations = Box::pin(invocations);
+
+        let (params, _) = encode_value::<_, MockOutgoing>((Bytes::from(vec![0xff; 1000]),))?;
+        srv.inner.invoke("foo", "bar", params)?;
+        let (_, (v,), ..) = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        assert_eq!(v.len(), 1000);
+
+        let (params, _) = encode_value::<_, MockOutgoing>((Bytes::from(vec![0xff; 1025]),))?;
+        srv.inner.invoke("foo", "bar", params)?;
+        let Err(err) = invocations.try_next().await else {
+            bail!("invocation exceeding the payload budget should fail")
+        };
+        assert!(
+            format!("{err:#}").ends_with("invocation payload exceeds 1024 bytes"),
+            "unexpected error: {err:#}"
+        );
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn nested() -> anyhow::Result<()> {
+        let (w, record) = RecordingOutgoing::new(vec![]);
+        w.index(&[])?.write_all(b"root").await?;
+        w.index(&[0])?.write_all(b"nested").await?;
+        drop(w);
+        let recorded = record.await?;
+        let incoming = ReplayIncoming::new(recorded.as_slice()).await?;
+
+        let mut r = LimitedIncoming::new(incoming, 6);
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        assert_eq!(buf, b"root");
+        assert_eq!(r.received(), 4);
+
+        // nested streams share the budget of the invocation
+        let mut nested = r.index(&[0])?;
+        let mut buf = [0; 2];
+        nested.read_exact(&mut buf).await?;
+        assert_eq!(r.received(), 6);
+        let err = nested
+            .read_exact(&mut buf)
+            .await
+            .expect_err("read exceeding the payload budget should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "invocation payload exceeds 6 bytes");
+        Ok(())
+    }
+
+    /// Incoming stream, which replays `buf` on the root path and never receives any data on
+    /// nested paths
+    struct StalledIncoming(Option<Bytes>);
+
+    impl Index<Self> for StalledIncoming {
+        fn index(&self, _path: &[usize]) -> anyhow::Result<Self> {
+            Ok(Self(None))
+        }
+    }
+
+    impl AsyncRead for StalledIncoming {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            let Some(data) = self.0.as_mut() else {
+                return Poll::Pending;
+            };
+            let n = buf.remaining().min(data.len());
+            buf.put_slice(&data.split_to(n));
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn idle() -> anyhow::Result<()> {
+        use core::future::Future;
+
+        use tokio_util::codec::FramedRead;
+
+        use crate::{receive_value, Decode, Deferred as _};
+
+        type Params = (u32, Pin<Box<dyn Future<Output = u32> + Send>>);
+
+        const TIMEOUT: Duration = Duration::from_millis(50);
+
+        // `42` followed by a pending future, which is never resolved by the peer
+        let incoming = IdleIncoming::new(
+            StalledIncoming(Some(Bytes::from_static(b"\x2a\x00"))),
+            TIMEOUT,
+        );
+        let mut dec = FramedRead::new(
+            incoming,
+            <Params as Decode<IdleIncoming<StalledIncoming>>>::Decoder::default(),
+        );
+        let (v, fut) = receive_value(&mut dec).await?;
+        assert_eq!(v, 42);
+        let rx = dec
+            .decoder_mut()
+            .take_deferred()
+            .context("future should be deferred")?;
+        let err = tokio::time::timeout(
+            Duration::from_secs(5),
+            rx(dec.into_inner().into(), Vec::default()),
+        )
+        .await
+        .context("idle subscription did not time out")?
+        .expect_err("idle subscription should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(err.to_string(), "subscription `[1]` idle for 50ms");
+        drop(fut);
+
+        // timer only runs while a read is waiting for data
+        let mut r = IdleIncoming::new(StalledIncoming(Some(Bytes::from_static(b"foo"))), TIMEOUT);
+        tokio::time::sleep(TIMEOUT * 2).await;
+        let mut buf = [0; 3];
+        r.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"foo");
+
+        // timer is reset each time data is received, so a steady sender is never cut off, even if
+        // the whole transfer takes longer than the timeout
+        let (mut tx, rx) = tokio::io::duplex(64);
+        let mut r = IdleIncoming::new(rx, TIMEOUT);
+        let send = async move {
+            for _ in 0..10 {
+                tokio::time::sleep(TIMEOUT / 5).await;
+                tx.write_all(b"x").await?;
+            }
+            anyhow::Ok(())
+        };
+        let mut buf = vec![];
+        let start = tokio::time::Instant::now();
+        let (sent, received) = tokio::join!(send, r.read_to_end(&mut buf));
+        sent?;
+        received.context("idle timeout fired while data was flowing")?;
+        assert!(start.elapsed() > TIMEOUT);
+        assert_eq!(buf, b"x".repeat(10));
+        Ok(())
+    }
+
+    /// [`Serve`] implementation, which counts invocations pulled from the wrapped [`MockServe`]
+    #[derive(Default)]
+    struct CountingServe {
+        inner: MockServe,
+        pulled: Arc<AtomicU64>,
+    }
+
+    impl Serve for CountingServe {
+        type Context = ();
+        type Outgoing = MockOutgoing;
+        type Incoming = ReplayIncoming;
+
+        async fn serve(
+            &self,
+            instance: &str,
+            func: &str,
+            paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+        ) -> anyhow::Result<
+            impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>>
+                + Send
+                + 'static,
+        > {
+            let pulled = Arc::clone(&self.pulled);
+            let invocations = self.inner.serve(instance, func, paths).await?;
+            Ok(invocations.inspect(move |_| {
+                pulled.fetch_add(1, Ordering::Relaxed);
+            }))
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn bounded() -> anyhow::Result<()> {
+        use futures::FutureExt as _;
+
+        let srv = BoundedServe::new(CountingServe::default(), NonZeroUsize::new(2).unwrap());
+        let invocations = srv.serve("foo", "bar", []).await?;
+        let mut invocations = Box::pin(invocations);
+        for _ in 0..4 {
+            srv.inner.inner.invoke("foo", "bar", Bytes::default())?;
+        }
+        let pulled = || srv.inner.pulled.load(Ordering::Relaxed);
+
+        let (_, tx, rx) = invocations
+            .try_next()
+            .await?
+            .context("first invocation missing")?;
+        let (_, tx2, rx2) = invocations
+            .try_next()
+            .await?
+            .context("second invocation missing")?;
+        assert_eq!(pulled(), 2);
+        assert_eq!(srv.available(), 0);
+
+        // the next invocation is pulled, but waits for a slot, the transport is not polled further
+        assert!(invocations.try_next().now_or_never().is_none());
+        assert_eq!(pulled(), 3);
+        assert!(invocations.try_next().now_or_never().is_none());
+        assert_eq!(pulled(), 3, "transport polled beyond the in-flight limit");
+
+        // an invocation remains in flight while any of its streams is alive
+        let nested = rx.index(&[0])?;
+        drop((tx, rx));
+        assert!(invocations.try_next().now_or_never().is_none());
+        assert_eq!(pulled(), 3);
+        drop(nested);
+        let (_, tx3, rx3) = invocations
+            .try_next()
+            .await?
+            .context("third invocation missing")?;
+        assert_eq!(pulled(), 3);
+        assert_eq!(srv.available(), 0);
+        assert!(invocations.try_next().now_or_never().is_none());
+        assert_eq!(pulled(), 4);
+
+        drop((tx2, rx2, tx3, rx3));
+        let _fourth = invocations
+            .try_next()
+            .await?
+            .context("fourth invocation missing")?;
+        assert_eq!(pulled(), 4);
+        assert_eq!(srv.available(), 1);
+
+        // no slot is held while waiting for the next invocation to arrive
+        assert!(invocations.try_next().now_or_never().is_none());
+        assert_eq!(srv.available(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,683 @@
+//! In-memory [`Serve`] implementation for unit testing handlers without a transport
+
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context as _;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt as _};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::sync::PollSender;
+use tracing::{instrument, trace};
+
+use crate::{Index, ReplayIncoming, Serve};
+
+#[derive(Debug, Default)]
+struct Captured {
+    buffers: HashMap<Box<[usize]>, BytesMut>,
+    shutdown: HashSet<Box<[usize]>>,
+}
+
+/// Outgoing byte stream, which captures all data written to each structural path in memory
+#[derive(Clone, Debug, Default)]
+pub struct MockOutgoing {
+    path: Arc<[usize]>,
+    captured: Arc<Mutex<Captured>>,
+}
+
+impl MockOutgoing {
+    /// Returns all data written to `path` so far
+    pub fn bytes(&self, path: &[usize]) -> Bytes {
+        let captured = self.captured.lock().unwrap();
+        captured
+            .buffers
+            .get(path)
+            .map(|buf| Bytes::copy_from_slice(buf))
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if the stream at `path` was shut down
+    pub fn is_shutdown(&self, path: &[usize]) -> bool {
+        let captured = self.captured.lock().unwrap();
+        captured.shutdown.contains(path)
+    }
+}
+
+impl Index<Self> for MockOutgoing {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: [self.path.as_ref(), path].concat().into(),
+            captured: Arc::clone(&self.captured),
+        })
+    }
+}
+
+impl AsyncWrite for MockOutgoing {
+    #[instrument(level = "trace", skip_all, fields(path = ?self.path, n = buf.len()))]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let mut captured = self.captured.lock().unwrap();
+        if captured.shutdown.contains(self.path.as_ref()) {
+            return Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "stream was shut down",
+            )));
+        }
+        captured
+            .buffers
+            .entry(Box::from(self.path.as_ref()))
+            .or_default()
+            .extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let mut captured = self.captured.lock().unwrap();
+        captured.shutdown.insert(Box::from(self.path.as_ref()));
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Outgoing byte stream, which sends data written to each structural path over a bounded
+/// channel.
+///
+/// Each write is sent as a single chunk along with the path it was written to. Writes block
+/// once the channel is full until the receiver makes room, which allows tests to verify that
+/// transmission respects backpressure of the transport.
+#[derive(Clone, Debug)]
+pub struct BoundedOutgoing {
+    path: Arc<[usize]>,
+    tx: PollSender<(Arc<[usize]>, Bytes)>,
+}
+
+impl BoundedOutgoing {
+    /// Constructs a new [`BoundedOutgoing`] with channel capacity of `capacity` chunks and returns
+    /// it along with the receiver of written chunks
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<(Arc<[usize]>, Bytes)>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (
+            Self {
+                path: Arc::default(),
+                tx: PollSender::new(tx),
+            },
+            rx,
+        )
+    }
+}
+
+impl Index<Self> for BoundedOutgoing {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: [self.path.as_ref(), path].concat().into(),
+            tx: self.tx.clone(),
+        })
+    }
+}
+
+impl AsyncWrite for BoundedOutgoing {
+    #[instrument(level = "trace", skip_all, fields(path = ?self.path, n = buf.len()))]
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        ready!(self.tx.poll_reserve(cx)).map_err(|_| bounded_receiver_closed())?;
+        let path = Arc::clone(&self.path);
+        self.tx
+            .send_item((path, Bytes::copy_from_slice(buf)))
+            .map_err(|_| bounded_receiver_closed())?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.tx.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn bounded_receiver_closed() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::BrokenPipe, "chunk receiver closed")
+}
+
+#[derive(Clone, Debug)]
+enum ChunkSizes {
+    Cycle { sizes: Arc<[usize]>, i: usize },
+    Random { state: u64, max: usize },
+}
+
+impl ChunkSizes {
+    fn next(&mut self) -> usize {
+        match self {
+            Self::Cycle { sizes, i } => {
+                let n = sizes[*i % sizes.len()];
+                *i = i.wrapping_add(1);
+                n
+            }
+            Self::Random { state, max } => {
+                // xorshift64
+                *state ^= *state << 13;
+                *state ^= *state >> 7;
+                *state ^= *state << 17;
+                let n = *state % u64::try_from(*max).unwrap_or(u64::MAX);
+                usize::try_from(n).unwrap_or(usize::MAX).saturating_add(1)
+            }
+        }
+    }
+
+    fn restart(&self) -> Self {
+        match self {
+            Self::Cycle { sizes, .. } => Self::Cycle {
+                sizes: Arc::clone(sizes),
+                i: 0,
+            },
+            Self::Random { state, max } => Self::Random {
+                state: *state,
+                max: *max,
+            },
+        }
+    }
+}
+
+/// Incoming byte stream, which delivers data of the wrapped stream in chunks of limited size,
+/// yielding to the executor between chunks.
+///
+/// This simulates data arriving split at awkward boundaries, e.g. a value split across several
+/// transport messages, which exercises resumption of decoders on partial input.
+/// Indexed streams are chunked the same way.
+#[derive(Clone, Debug)]
+pub struct ChunkedIncoming<T> {
+    inner: T,
+    sizes: ChunkSizes,
+    remaining: usize,
+    yielded: bool,
+}
+
+impl<T> ChunkedIncoming<T> {
+    /// Wraps `inner`, delivering its data in chunks of `sizes`, which are cycled through.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sizes` is empty or contains zero
+    pub fn new(inner: T, sizes: impl Into<Arc<[usize]>>) -> Self {
+        let sizes = sizes.into();
+        assert!(
+            !sizes.is_empty() && !sizes.contains(&0),
+            "chunk sizes must be non-empty and non-zero"
+        );
+        Self::with_sizes(inner, ChunkSizes::Cycle { sizes, i: 0 })
+    }
+
+    /// Wraps `inner`, delivering its data one byte at a time
+    pub fn bytewise(inner: T) -> Self {
+        Self::new(inner, [1])
+    }
+
+    /// Wraps `inner`, delivering its data in chunks of pseudo-random size between `1` and
+    /// `max`. The sizes are fully determined by `seed`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero
+    pub fn random(inner: T, seed: u64, max: usize) -> Self {
+        assert!(max > 0, "maximum chunk size must be non-zero");
+        Self::with_sizes(
+            inner,
+            ChunkSizes::Random {
+                // xorshift state must be non-zero
+                state: seed | 1,
+                max,
+            },
+        )
+    }
+
+    fn with_sizes(inner: T, mut sizes: ChunkSizes) -> Self {
+        let remaining = sizes.next();
+        Self {
+            inner,
+            sizes,
+            remaining,
+            yielded: false,
+        }
+    }
+
+    /// Returns the wrapped stream
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Index<T>> Index<Self> for ChunkedIncoming<T> {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let inner = self.inner.index(path)?;
+        Ok(Self::with_sizes(inner, self.sizes.restart()))
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ChunkedIncoming<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.remaining == 0 {
+            if !self.yielded {
+                // simulate the next chunk arriving separately
+                self.yielded = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.yielded = false;
+            self.remaining = self.sizes.next();
+        }
+        let mut chunk = vec![0; self.remaining.min(buf.remaining())];
+        let mut chunk = ReadBuf::new(&mut chunk);
+        ready!(Pin::new(&mut self.inner).poll_read(cx, &mut chunk))?;
+        let n = chunk.filled().len();
+        trace!(n, "read chunk");
+        buf.put_slice(chunk.filled());
+        self.remaining -= n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// [`Serve`] implementation, which accepts invocations made via [`MockServe::invoke`]
+///
+/// Parameters are provided as pre-encoded bytes and results written by the handler are captured
+/// in the returned [`MockOutgoing`], so tests can assert on exactly what a handler transmitted.
+#[derive(Clone, Debug, Default)]
+pub struct MockServe {
+    handlers: Arc<Mutex<HashMap<(String, String), mpsc::UnboundedSender<MockInvocation>>>>,
+}
+
+type MockInvocation = ((), MockOutgoing, ReplayIncoming);
+
+impl MockServe {
+    /// Invokes function `func` from instance `instance` with encoded `params`.
+    ///
+    /// `params` are available on the root path of the incoming stream.
+    /// Returned [`MockOutgoing`] captures the data transmitted by the handler.
+    /// This fails if `func` is not currently being served.
+    #[instrument(level = "trace", skip(self, params))]
+    pub fn invoke(
+        &self,
+        instance: &str,
+        func: &str,
+        params: impl Into<Bytes>,
+    ) -> anyhow::Result<MockOutgoing> {
+        let handlers = self.handlers.lock().unwrap();
+        let tx = handlers
+            .get(&(instance.to_string(), func.to_string()))
+            .with_context(|| format!("`{instance}.{func}` is not served"))?;
+        let outgoing = MockOutgoing::default();
+        trace!("sending invocation");
+        tx.send(((), outgoing.clone(), ReplayIncoming::from(params.into())))
+            .ok()
+            .with_context(|| format!("`{instance}.{func}` is no longer served"))?;
+        Ok(outgoing)
+    }
+}
+
+impl Serve for MockServe {
+    type Context = ();
+    type Outgoing = MockOutgoing;
+    type Incoming = ReplayIncoming;
+
+    #[instrument(level = "trace", skip(self, _paths))]
+    async fn serve(
+        &self,
+        instance: &str,
+        func: &str,
+        _paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+    ) -> anyhow::Result<
+        impl Stream<Item = anyhow::Result<(Self::Context, Self::Outgoing, Self::Incoming)>>
+            + Send
+            + 'static,
+    > {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut handlers = self.handlers.lock().unwrap();
+        handlers.insert((instance.to_string(), func.to_string()), tx);
+        Ok(UnboundedReceiverStream::new(rx).map(Ok))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+    use futures::TryStreamExt as _;
+
+    use tokio_util::codec::FramedRead;
+
+    use crate::{encode_value, ServeExt as _};
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn handler() -> anyhow::Result<()> {
+        let srv = MockServe::default();
+        let invocations = srv
+            .serve_fn("foo", "bar", [], |(a, b): (u32, String)| async move {
+                if b.is_empty() {
+                    bail!("empty name")
+                }
+                Ok((format!("{b}{a}"),))
+            })
+            .await?;
+        let mut invocations = Box::pin(invocations);
+
+        let (params, _) = encode_value::<_, MockOutgoing>((42_u32, "test"))?;
+        let outgoing = srv.invoke("foo", "bar", params)?;
+        let fut = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        fut.await?;
+        assert_eq!(outgoing.bytes(&[]).as_ref(), b"\x06test42");
+        assert!(outgoing.is_shutdown(&[]));
+
+        let (params, _) = encode_value::<_, MockOutgoing>((42_u32, ""))?;
+        let outgoing = srv.invoke("foo", "bar", params)?;
+        let fut = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        let err = fut.await.expect_err("handler should fail");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to handle `foo.bar` invocation: empty name"
+        );
+        assert!(outgoing.bytes(&[]).is_empty());
+        assert!(
+            outgoing.is_shutdown(&[]),
+            "result stream should be shut down to let the invoker fail"
+        );
+
+        let err = srv
+            .invoke("foo", "baz", Bytes::new())
+            .expect_err("unknown function should fail");
+        assert_eq!(err.to_string(), "`foo.baz` is not served");
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn handler_panic() -> anyhow::Result<()> {
+        let srv = MockServe::default();
+        let invocations = srv
+            .serve_fn("foo", "bar", [], |(): ()| async move {
+                if true {
+                    panic!("test panic")
+                }
+                anyhow::Ok((0_u32,))
+            })
+            .await?;
+        let mut invocations = Box::pin(invocations);
+
+        let outgoing = srv.invoke("foo", "bar", Bytes::new())?;
+        let fut = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        let err = fut.await.expect_err("handler should panic");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to handle `foo.bar` invocation: handler panicked: test panic"
+        );
+        assert!(outgoing.bytes(&[]).is_empty());
+        assert!(
+            outgoing.is_shutdown(&[]),
+            "result stream should be shut down to let the invoker fail"
+        );
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn malformed_params() -> anyhow::Result<()> {
+        let srv = MockServe::default();
+        let invocations = srv
+            .serve_fn("foo", "bar", [], |(a, b): (u32, String)| async move {
+                Ok((format!("{b}{a}"),))
+            })
+            .await?;
+        let mut invocations = Box::pin(invocations);
+
+        // string is not valid UTF-8
+        let malformed = srv.invoke("foo", "bar", b"\x2a\x02\xff\xfe".as_slice())?;
+        let (params, _) = encode_value::<_, MockOutgoing>((42_u32, "test"))?;
+        let valid = srv.invoke("foo", "bar", params)?;
+
+        let err = match invocations.next().await.context("invocation missing")? {
+            Ok(_) => bail!("malformed invocation should fail"),
+            Err(err) => err,
+        };
+        assert_eq!(err.to_string(), "failed to receive sync parameters");
+        assert!(malformed.bytes(&[]).is_empty());
+        assert!(malformed.is_shutdown(&[]));
+
+        let fut = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        fut.await?;
+        assert_eq!(valid.bytes(&[]).as_ref(), b"\x06test42");
+        assert!(valid.is_shutdown(&[]));
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn server_stream() -> anyhow::Result<()> {
+        let srv = MockServe::default();
+        let invocations = srv
+            .serve_server_stream("foo", "bar", [], |(n,): (u32,)| {
+                futures::stream::iter(0..n).map(move |i| {
+                    if i == 2 && n > 3 {
+                        bail!("too many responses")
+                    }
+                    Ok((i, format!("#{i}")))
+                })
+            })
+            .await?;
+        let mut invocations = Box::pin(invocations);
+
+        let outgoing = srv.invoke("foo", "bar", b"\x03".as_slice())?;
+        let fut = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        fut.await?;
+        assert_eq!(
+            outgoing.bytes(&[]).as_ref(),
+            b"\x00\x02#0\x01\x02#1\x02\x02#2"
+        );
+        assert!(outgoing.is_shutdown(&[]));
+
+        let outgoing = srv.invoke("foo", "bar", b"\x04".as_slice())?;
+        let fut = invocations
+            .try_next()
+            .await?
+            .context("invocation missing")?;
+        let err = fut.await.expect_err("handler should fail");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to handle `foo.bar` invocation: too many responses"
+        );
+        assert_eq!(outgoing.bytes(&[]).as_ref(), b"\x00\x02#0\x01\x02#1");
+        assert!(outgoing.is_shutdown(&[]));
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn bounded_backpressure() -> anyhow::Result<()> {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use core::time::Duration;
+
+        use bytes::Buf as _;
+        use futures::{stream, Stream};
+
+        const ITEMS: usize = 1024;
+        const ITEM_SIZE: usize = 1024;
+
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let items: Pin<Box<dyn Stream<Item = Bytes> + Send>> = Box::pin({
+            let pulled = Arc::clone(&pulled);
+            stream::iter(0..ITEMS).then(move |i| {
+                let pulled = Arc::clone(&pulled);
+                async move {
+                    tokio::task::yield_now().await;
+                    pulled.fetch_add(1, Ordering::Relaxed);
+                    Bytes::from(vec![i as u8; ITEM_SIZE])
+                }
+            })
+        });
+        let (buf, deferred) = encode_value::<_, BoundedOutgoing>(items)?;
+        assert_eq!(buf.as_ref(), b"\x00");
+        let deferred = deferred.context("stream should be deferred")?;
+
+        let (w, mut rx) = BoundedOutgoing::new(1);
+        let tx = tokio::spawn(deferred(Arc::new(w), Vec::default()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let n = pulled.load(Ordering::Relaxed);
+        assert!(
+            n > 0 && n < ITEMS / 8,
+            "stream should not be drained while the channel is full, pulled `{n}` items"
+        );
+        assert!(!tx.is_finished());
+
+        // all items are transmitted, in order, once the receiver catches up
+        let mut payload = BytesMut::default();
+        while let Some((path, chunk)) = rx.recv().await {
+            assert!(path.is_empty());
+            payload.extend_from_slice(&chunk);
+        }
+        tx.await??;
+        let mut items = Vec::with_capacity(ITEMS);
+        let mut payload = payload.freeze();
+        loop {
+            let (n, len) = crate::decode_value_counted::<u32, ReplayIncoming>(&payload[..])?;
+            payload.advance(len);
+            if n == 0 {
+                break;
+            }
+            items.extend(payload.split_to(n.try_into()?));
+        }
+        assert!(payload.is_empty());
+        assert_eq!(items.len(), ITEMS * ITEM_SIZE);
+        for (i, chunk) in items.chunks(ITEM_SIZE).enumerate() {
+            assert!(chunk.iter().all(|b| *b == i as u8), "item `{i}` corrupted");
+        }
+        Ok(())
+    }
+
+    /// Asserts that `v` is decoded from single-byte and pseudo-randomly sized chunks
+    async fn assert_chunked<T>(v: T) -> anyhow::Result<()>
+    where
+        for<'a> &'a T: crate::Encode<MockOutgoing>,
+        T: crate::Decode<ChunkedIncoming<ReplayIncoming>> + PartialEq + core::fmt::Debug,
+        <T::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        let Ok((buf, _)) = encode_value::<_, MockOutgoing>(&v) else {
+            bail!("failed to encode `{v:?}`")
+        };
+        let chunkings = (0..4)
+            .map(|seed| {
+                (
+                    format!("random chunking with seed `{seed}`"),
+                    ChunkedIncoming::random(ReplayIncoming::from(buf.clone()), seed, 3),
+                )
+            })
+            .chain([(
+                "single-byte chunking".to_string(),
+                ChunkedIncoming::bytewise(ReplayIncoming::from(buf.clone())),
+            )]);
+        for (name, r) in chunkings {
+            let mut dec = FramedRead::new(r, T::Decoder::default());
+            let got = dec
+                .try_next()
+                .await
+                .with_context(|| format!("failed to decode `{v:?}` with {name}"))?
+                .with_context(|| format!("`{v:?}` missing with {name}"))?;
+            assert_eq!(got, v, "{name}");
+            assert!(dec.read_buffer().is_empty());
+        }
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn chunked_primitives() -> anyhow::Result<()> {
+        assert_chunked(true).await?;
+        assert_chunked(0x42_u8).await?;
+        assert_chunked(-0x42_i8).await?;
+        assert_chunked(u16::MAX).await?;
+        assert_chunked(-0x2000_i16).await?;
+        assert_chunked(u32::MAX).await?;
+        assert_chunked(-0x800_0000_i32).await?;
+        assert_chunked(u64::MAX).await?;
+        assert_chunked(i64::MIN / 2).await?;
+        assert_chunked(f32::MIN).await?;
+        assert_chunked(f64::MAX).await?;
+        assert_chunked('🦀').await?;
+        assert_chunked(String::from("test 🦀")).await?;
+        assert_chunked(Bytes::from_static(b"test")).await?;
+        assert_chunked(vec![u32::MAX, 0, 0x80]).await?;
+        assert_chunked(Some(f32::MAX)).await?;
+        assert_chunked(Ok::<_, String>(u64::MAX)).await?;
+        assert_chunked((0x80_u16, 1.5_f64, String::from("test"))).await?;
+        Ok(())
+    }
+
+    /// Returns sizes of chunks yielded by `r`
+    async fn chunk_sizes(mut r: impl AsyncRead + Unpin) -> std::io::Result<Vec<usize>> {
+        use tokio::io::AsyncReadExt as _;
+
+        let mut sizes = vec![];
+        let mut buf = [0; 16];
+        loop {
+            match r.read(&mut buf).await? {
+                0 => return Ok(sizes),
+                n => sizes.push(n),
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn chunked_sizes() -> anyhow::Result<()> {
+        let buf = Bytes::from_static(b"0123456789");
+        let sizes = chunk_sizes(ChunkedIncoming::new(
+            ReplayIncoming::from(buf.clone()),
+            [3, 1],
+        ))
+        .await?;
+        assert_eq!(sizes, [3, 1, 3, 1, 2]);
+
+        let sizes =
+            chunk_sizes(ChunkedIncoming::bytewise(ReplayIncoming::from(buf.clone()))).await?;
+        assert_eq!(sizes, [1; 10]);
+
+        let a = chunk_sizes(ChunkedIncoming::random(
+            ReplayIncoming::from(buf.clone()),
+            42,
+            4,
+        ))
+        .await?;
+        let b = chunk_sizes(ChunkedIncoming::random(ReplayIncoming::from(buf), 42, 4)).await?;
+        assert_eq!(a, b, "chunk sizes should be determined by the seed");
+        assert_eq!(a.iter().sum::<usize>(), 10);
+        assert!(a.iter().all(|n| (1..=4).contains(n)), "{a:?}");
+        Ok(())
+    }
+}
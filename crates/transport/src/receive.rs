@@ -0,0 +1,209 @@
+//! Receiving values from byte streams, distinguishing a stream closed on a value boundary from a
+//! truncated value.
+
+use core::fmt::{self, Display};
+use core::future::poll_fn;
+use core::mem;
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+
+use bytes::BytesMut;
+use tokio::io::AsyncRead;
+use tokio_util::codec::{Decoder, FramedRead};
+use tokio_util::io::poll_read_buf;
+
+/// Error returned by [`receive_value`]
+#[derive(Debug)]
+pub enum ReceiveError<E> {
+    /// Stream ended on a value boundary, before any bytes of the value were received
+    Eof,
+    /// Stream ended after `received` bytes of the value were received, but before the value
+    /// was complete
+    UnexpectedEof {
+        /// Number of bytes of the incomplete value received
+        received: usize,
+    },
+    /// Reading from the stream failed
+    Io(std::io::Error),
+    /// Decoding the value failed, e.g. because it is malformed
+    Decode(E),
+}
+
+impl<E> ReceiveError<E> {
+    /// Returns `true` if the stream was closed cleanly on a value boundary
+    pub fn is_eof(&self) -> bool {
+        matches!(self, Self::Eof)
+    }
+
+    /// Returns `true` if the stream was closed in the middle of a value
+    pub fn is_unexpected_eof(&self) -> bool {
+        matches!(self, Self::UnexpectedEof { .. })
+    }
+}
+
+impl<E: Display> Display for ReceiveError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eof => write!(f, "stream ended before a value was received"),
+            Self::UnexpectedEof { received } => write!(
+                f,
+                "stream ended after receiving `{received}` bytes of an incomplete value"
+            ),
+            Self::Io(err) => write!(f, "failed to read from stream: {err}"),
+            Self::Decode(err) => write!(f, "failed to decode value: {err}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ReceiveError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Eof | Self::UnexpectedEof { .. } => None,
+            Self::Io(err) => Some(err),
+            Self::Decode(err) => Some(err),
+        }
+    }
+}
+
+impl<E: Into<std::io::Error>> From<ReceiveError<E>> for std::io::Error {
+    fn from(err: ReceiveError<E>) -> Self {
+        match err {
+            ReceiveError::Eof => std::io::ErrorKind::UnexpectedEof.into(),
+            ReceiveError::UnexpectedEof { received } => Self::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("stream ended after receiving `{received}` bytes of an incomplete value"),
+            ),
+            ReceiveError::Io(err) => err,
+            ReceiveError::Decode(err) => err.into(),
+        }
+    }
+}
+
+/// Receives the next value from `framed`.
+///
+/// Unlike [`FramedRead`] used as a [`Stream`](futures::Stream), which reports an incomplete value
+/// at the end of the stream using a decoder error, this function fails with
+/// [`ReceiveError::Eof`] if the stream ended before any bytes of the value were received and
+/// with [`ReceiveError::UnexpectedEof`] if the stream ended in the middle of the value.
+///
+/// This function is cancel-safe in the sense that no buffered data is lost, however bytes of
+/// the value received before cancellation are not accounted for by a subsequent call.
+pub async fn receive_value<R, D>(
+    framed: &mut FramedRead<R, D>,
+) -> Result<D::Item, ReceiveError<D::Error>>
+where
+    R: AsyncRead + Unpin,
+    D: Decoder,
+{
+    let mut received = framed.read_buffer().len();
+    poll_fn(|cx| {
+        let mut buf = mem::take(framed.read_buffer_mut());
+        let res = poll_receive(framed, &mut buf, &mut received, cx);
+        *framed.read_buffer_mut() = buf;
+        res
+    })
+    .await
+}
+
+fn poll_receive<R, D>(
+    framed: &mut FramedRead<R, D>,
+    buf: &mut BytesMut,
+    received: &mut usize,
+    cx: &mut Context<'_>,
+) -> Poll<Result<D::Item, ReceiveError<D::Error>>>
+where
+    R: AsyncRead + Unpin,
+    D: Decoder,
+{
+    loop {
+        if let Some(v) = framed
+            .decoder_mut()
+            .decode(buf)
+            .map_err(ReceiveError::Decode)?
+        {
+            return Poll::Ready(Ok(v));
+        }
+        let n =
+            ready!(poll_read_buf(Pin::new(framed.get_mut()), cx, buf)).map_err(ReceiveError::Io)?;
+        if n == 0 {
+            if *received == 0 {
+                return Poll::Ready(Err(ReceiveError::Eof));
+            }
+            return Poll::Ready(Err(ReceiveError::UnexpectedEof {
+                received: *received,
+            }));
+        }
+        *received = received.saturating_add(n);
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use anyhow::bail;
+    use bytes::Bytes;
+
+    use crate::{encode_value, ChunkedIncoming, Decode, MockOutgoing, ReplayIncoming};
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn clean_close() -> anyhow::Result<()> {
+        let (a, _) = encode_value::<_, MockOutgoing>((42_u32, "foo"))?;
+        let (b, _) = encode_value::<_, MockOutgoing>((7_u32, "bar"))?;
+        let mut buf = BytesMut::from(a.as_ref());
+        buf.extend_from_slice(&b);
+        let mut framed = FramedRead::new(
+            ChunkedIncoming::new(ReplayIncoming::from(buf.freeze()), [1]),
+            <(u32, String) as Decode<ReplayIncoming>>::Decoder::default(),
+        );
+        assert_eq!(receive_value(&mut framed).await?, (42, "foo".into()));
+        assert_eq!(receive_value(&mut framed).await?, (7, "bar".into()));
+        let err = receive_value(&mut framed)
+            .await
+            .expect_err("closed stream should fail");
+        assert!(err.is_eof(), "unexpected error: {err}");
+        assert!(!err.is_unexpected_eof());
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn truncated() -> anyhow::Result<()> {
+        let (buf, _) = encode_value::<_, MockOutgoing>((42_u32, "foo"))?;
+        let mut framed = FramedRead::new(
+            ReplayIncoming::from(buf.slice(..3)),
+            <(u32, String) as Decode<ReplayIncoming>>::Decoder::default(),
+        );
+        match receive_value(&mut framed).await {
+            Err(ReceiveError::UnexpectedEof { received: 3 }) => {}
+            Err(err) => bail!("unexpected error: {err}"),
+            Ok(v) => bail!("truncated value should fail, got {v:?}"),
+        }
+
+        // truncation after a complete value
+        let (a, _) = encode_value::<_, MockOutgoing>("foo")?;
+        let mut buf = BytesMut::from(a.as_ref());
+        buf.extend_from_slice(b"\x05ab");
+        let mut framed = FramedRead::new(
+            ReplayIncoming::from(buf.freeze()),
+            <String as Decode<ReplayIncoming>>::Decoder::default(),
+        );
+        assert_eq!(receive_value(&mut framed).await?, "foo");
+        let err = receive_value(&mut framed)
+            .await
+            .expect_err("truncated value should fail");
+        assert!(err.is_unexpected_eof(), "unexpected error: {err}");
+        assert_eq!(
+            std::io::Error::from(err).kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+
+        let mut framed = FramedRead::new(
+            ReplayIncoming::from(Bytes::new()),
+            <String as Decode<ReplayIncoming>>::Decoder::default(),
+        );
+        assert!(receive_value(&mut framed)
+            .await
+            .is_err_and(|err| err.is_eof()));
+        Ok(())
+    }
+}
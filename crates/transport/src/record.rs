@@ -0,0 +1,216 @@
+//! Recording and replay of wRPC traffic, e.g. for snapshot tests
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures::{SinkExt as _, TryStreamExt as _};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+use tokio_util::codec::{FramedRead, FramedWrite};
+use tracing::{instrument, trace};
+
+use crate::frame::{self, Frame};
+use crate::Index;
+
+/// Outgoing byte stream, which records each write as a [Frame] containing the structural path
+/// written to and the payload
+#[derive(Clone, Debug)]
+pub struct RecordingOutgoing {
+    path: Arc<[usize]>,
+    tx: mpsc::UnboundedSender<Frame>,
+}
+
+impl RecordingOutgoing {
+    /// Constructs a new [`RecordingOutgoing`] recording to `w`.
+    ///
+    /// Returned future writes recorded frames to `w` as they are produced and resolves with `w`
+    /// once all handles to the [`RecordingOutgoing`] and its indexed streams are dropped.
+    pub fn new<W>(w: W) -> (Self, impl Future<Output = std::io::Result<W>>)
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        (
+            Self {
+                path: Arc::default(),
+                tx,
+            },
+            async move {
+                let mut w = FramedWrite::new(w, frame::Encoder);
+                while let Some(frame) = rx.recv().await {
+                    trace!(?frame, "writing recorded frame");
+                    w.send(&frame).await?;
+                }
+                Ok(w.into_inner())
+            },
+        )
+    }
+}
+
+impl Index<Self> for RecordingOutgoing {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: [self.path.as_ref(), path].concat().into(),
+            tx: self.tx.clone(),
+        })
+    }
+}
+
+impl AsyncWrite for RecordingOutgoing {
+    #[instrument(level = "trace", skip_all, fields(path = ?self.path, n = buf.len()))]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let frame = Frame {
+            path: Arc::clone(&self.path),
+            data: Bytes::copy_from_slice(buf),
+        };
+        if self.tx.send(frame).is_err() {
+            return Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "recording stopped",
+            )));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Incoming byte stream, which replays frames recorded by [`RecordingOutgoing`]
+///
+/// All data recorded for a particular path is available in order, once indexed.
+/// Paths without any recorded data are empty.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayIncoming {
+    paths: Arc<HashMap<Box<[usize]>, Bytes>>,
+    path: Box<[usize]>,
+    buf: Bytes,
+}
+
+impl ReplayIncoming {
+    /// Reads all frames recorded in `r`
+    #[instrument(level = "trace", skip_all)]
+    pub async fn new(r: impl AsyncRead + Unpin) -> std::io::Result<Self> {
+        let mut r = FramedRead::new(r, frame::Decoder::default());
+        let mut paths = HashMap::<_, BytesMut>::default();
+        while let Some(Frame { path, data }) = r.try_next().await? {
+            trace!(?path, n = data.len(), "read recorded frame");
+            paths
+                .entry(Box::from(path.as_ref()))
+                .or_default()
+                .extend_from_slice(&data);
+        }
+        let paths: HashMap<_, _> = paths
+            .into_iter()
+            .map(|(path, buf)| (path, buf.freeze()))
+            .collect();
+        let buf = paths.get([].as_slice()).cloned().unwrap_or_default();
+        Ok(Self {
+            paths: Arc::new(paths),
+            path: Box::default(),
+            buf,
+        })
+    }
+}
+
+impl From<Bytes> for ReplayIncoming {
+    /// Constructs a new [`ReplayIncoming`] with `buf` available on the root path
+    fn from(buf: Bytes) -> Self {
+        Self {
+            paths: Arc::new(HashMap::from([(Box::default(), buf.clone())])),
+            path: Box::default(),
+            buf,
+        }
+    }
+}
+
+impl Index<Self> for ReplayIncoming {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let path: Box<[usize]> = [self.path.as_ref(), path].concat().into();
+        let buf = self.paths.get(&path).cloned().unwrap_or_default();
+        Ok(Self {
+            paths: Arc::clone(&self.paths),
+            path,
+            buf,
+        })
+    }
+}
+
+impl AsyncRead for ReplayIncoming {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let n = buf.remaining().min(self.buf.len());
+        buf.put_slice(&self.buf.split_to(n));
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context as _;
+    use futures::{stream, Stream, StreamExt as _};
+    use tokio::io::AsyncWriteExt as _;
+    use tokio::join;
+
+    use crate::{encode_value, Decode, Deferred as _};
+
+    use super::*;
+
+    type Value = (
+        String,
+        Pin<Box<dyn Future<Output = u32> + Send>>,
+        Pin<Box<dyn Stream<Item = Vec<u32>> + Send>>,
+    );
+
+    #[test_log::test(tokio::test)]
+    async fn replay() -> anyhow::Result<()> {
+        let (w, record) = RecordingOutgoing::new(vec![]);
+        let v: Value = (
+            "test".into(),
+            Box::pin(async { 42 }),
+            Box::pin(stream::iter([vec![1, 2], vec![3]])),
+        );
+        let (buf, io) = encode_value(v)?;
+        let mut root = w.index(&[])?;
+        root.write_all(&buf).await?;
+        let io = io.context("value should have async components")?;
+        io(Arc::new(w), Vec::default()).await?;
+        drop(root);
+        let recorded = record.await?;
+
+        let r = ReplayIncoming::new(recorded.as_slice()).await?;
+        let mut dec = FramedRead::new(r, <Value as Decode<ReplayIncoming>>::Decoder::default());
+        let (s, fut, st) = dec.try_next().await?.context("value missing")?;
+        assert_eq!(s, "test");
+        let io = dec
+            .decoder_mut()
+            .take_deferred()
+            .context("value should have async components")?;
+        let (res, v, items) = join!(
+            io(Arc::new(dec.into_inner()), Vec::default()),
+            fut,
+            st.collect::<Vec<_>>()
+        );
+        res?;
+        assert_eq!(v, 42);
+        assert_eq!(items.concat(), [1, 2, 3]);
+        Ok(())
+    }
+}
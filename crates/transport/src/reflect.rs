@@ -0,0 +1,119 @@
+//! Opt-in reflection protocol, which allows peers to discover served functions
+
+use core::future::Future;
+use core::pin::Pin;
+
+use std::sync::{Arc, RwLock};
+
+use futures::Stream;
+use tracing::instrument;
+
+use crate::{Serve, ServeExt as _};
+
+/// Instance name of the reflection function
+pub const REFLECT_INSTANCE: &str = "wrpc";
+
+/// Function name of the reflection function
+pub const REFLECT_FUNC: &str = "reflect";
+
+/// Wire representation of a [`FunctionSignature`], encoded as a `record`
+pub(crate) type FunctionSignatureTuple = (String, String, Vec<String>, Vec<String>);
+
+/// Signature of a served function
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FunctionSignature {
+    /// Name of the instance the function is exported from, e.g. `wasi:http/handler`
+    pub instance: String,
+    /// Name of the function
+    pub name: String,
+    /// WIT types of the function parameters
+    pub params: Vec<String>,
+    /// WIT types of the function results
+    pub results: Vec<String>,
+}
+
+impl From<FunctionSignature> for FunctionSignatureTuple {
+    fn from(
+        FunctionSignature {
+            instance,
+            name,
+            params,
+            results,
+        }: FunctionSignature,
+    ) -> Self {
+        (instance, name, params, results)
+    }
+}
+
+impl From<FunctionSignatureTuple> for FunctionSignature {
+    fn from((instance, name, params, results): FunctionSignatureTuple) -> Self {
+        Self {
+            instance,
+            name,
+            params,
+            results,
+        }
+    }
+}
+
+/// Registry of [`FunctionSignature`]s, which can be served to peers as
+/// `{REFLECT_INSTANCE}.{REFLECT_FUNC}` and queried using
+/// [`InvokeExt::list_functions`](crate::InvokeExt::list_functions)
+#[derive(Clone, Debug, Default)]
+pub struct Reflection(Arc<RwLock<Vec<FunctionSignature>>>);
+
+impl Reflection {
+    /// Registers a served function signature
+    pub fn register(&self, sig: FunctionSignature) {
+        self.0
+            .write()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(sig);
+    }
+
+    /// Returns all registered function signatures
+    pub fn list(&self) -> Vec<FunctionSignature> {
+        self.0.read().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+
+    /// Serve the reflection function on `srv`, returning a stream of reflection invocations,
+    /// like [`ServeExt::serve_fn`](crate::ServeExt::serve_fn)
+    #[instrument(level = "trace", skip_all)]
+    pub fn serve<'a, S: Serve>(
+        &self,
+        srv: &'a S,
+    ) -> impl Future<
+        Output = anyhow::Result<
+            Pin<
+                Box<
+                    dyn Stream<
+                            Item = anyhow::Result<
+                                Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'static>>,
+                            >,
+                        > + Send
+                        + 'static,
+                >,
+            >,
+        >,
+    > + Send
+           + 'a {
+        let reflection = self.clone();
+        let fut = srv.serve_fn(
+            REFLECT_INSTANCE,
+            REFLECT_FUNC,
+            Vec::<Box<[Option<usize>]>>::default(),
+            move |()| {
+                let sigs = reflection
+                    .list()
+                    .into_iter()
+                    .map(FunctionSignatureTuple::from)
+                    .collect::<Vec<_>>();
+                async move { Ok((sigs,)) }
+            },
+        );
+        async move {
+            let invocations = fut.await?;
+            Ok(Box::pin(invocations) as Pin<Box<dyn Stream<Item = _> + Send>>)
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Round-trip assertions for [`Encode`] and [`Decode`] implementations
+
+use core::fmt::Debug;
+
+use bytes::BytesMut;
+use tokio_util::codec::Decoder as _;
+
+use crate::{
+    decode_value, encode_value, Decode, Deferred as _, Encode, MockOutgoing, ReplayIncoming,
+};
+
+/// Asserts that `value` encodes and decodes back to an equal value, with the payload decoded
+/// from a single buffer as well as split into single-byte chunks, see
+/// [`assert_roundtrip_chunked`].
+///
+/// # Panics
+///
+/// Panics if encoding or decoding fails, if the value has asynchronous components or if the
+/// decoded value is not equal to `value`
+pub fn assert_roundtrip<T>(value: &T)
+where
+    for<'a> &'a T: Encode<MockOutgoing>,
+    T: Decode<ReplayIncoming> + PartialEq + Debug,
+    <T::Decoder as tokio_util::codec::Decoder>::Error: std::error::Error + Send + Sync + 'static,
+{
+    let Ok((buf, deferred)) = encode_value(value) else {
+        panic!("failed to encode `{value:?}`")
+    };
+    assert!(
+        deferred.is_none(),
+        "`{value:?}` has asynchronous components"
+    );
+    let decoded = decode_value::<T, ReplayIncoming>(buf.as_ref())
+        .unwrap_or_else(|err| panic!("failed to decode `{value:?}` from {buf:02x?}: {err:#}"));
+    assert_eq!(&decoded, value, "value decoded from {buf:02x?} differs");
+    assert_roundtrip_chunked(value);
+}
+
+/// Asserts that `value` encodes and decodes back to an equal value, with the payload split
+/// into single-byte chunks.
+///
+/// The decoder is fed one more byte at a time and must not return a value before the whole
+/// payload is available. This exercises resumption of decoders on partial input, which a
+/// payload received in a single chunk does not.
+///
+/// # Panics
+///
+/// Panics if encoding or decoding fails, if a value is decoded before the payload is complete,
+/// if the value has asynchronous components or if the decoded value is not equal to `value`
+pub fn assert_roundtrip_chunked<T>(value: &T)
+where
+    for<'a> &'a T: Encode<MockOutgoing>,
+    T: Decode<ReplayIncoming> + PartialEq + Debug,
+    <T::Decoder as tokio_util::codec::Decoder>::Error: Debug,
+{
+    let Ok((buf, deferred)) = encode_value(value) else {
+        panic!("failed to encode `{value:?}`")
+    };
+    assert!(
+        deferred.is_none(),
+        "`{value:?}` has asynchronous components"
+    );
+    let mut dec = T::Decoder::default();
+    let mut src = BytesMut::with_capacity(buf.len());
+    for (i, b) in buf.iter().enumerate() {
+        src.extend_from_slice(&[*b]);
+        let decoded = dec.decode(&mut src).unwrap_or_else(|err| {
+            panic!(
+                "failed to decode `{value:?}` from {buf:02x?} after {} bytes: {err:?}",
+                i + 1
+            )
+        });
+        if i + 1 < buf.len() {
+            assert!(
+                decoded.is_none(),
+                "`{value:?}` decoded from {buf:02x?} after only {} bytes",
+                i + 1
+            );
+            continue;
+        }
+        assert_eq!(
+            decoded.as_ref(),
+            Some(value),
+            "value decoded from {buf:02x?} in single-byte chunks differs"
+        );
+    }
+    if buf.is_empty() {
+        let decoded = dec
+            .decode(&mut src)
+            .unwrap_or_else(|err| panic!("failed to decode `{value:?}`: {err:?}"));
+        assert_eq!(decoded.as_ref(), Some(value), "decoded value differs");
+    }
+    assert!(
+        src.is_empty(),
+        "decoding `{value:?}` left {} trailing bytes",
+        src.len()
+    );
+    assert!(
+        dec.take_deferred().is_none(),
+        "decoded `{value:?}` has asynchronous components"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn primitives() {
+        assert_roundtrip(&true);
+        assert_roundtrip(&false);
+        for v in [0, 1, 0x7f, 0x80, u8::MAX] {
+            assert_roundtrip(&v);
+        }
+        for v in [0, 1, 0x80, u16::MAX] {
+            assert_roundtrip(&v);
+        }
+        for v in [0, 1, 0x80, 0x4000, u32::MAX] {
+            assert_roundtrip(&v);
+        }
+        for v in [0, 1, 0x80, u64::from(u32::MAX) + 1, u64::MAX] {
+            assert_roundtrip(&v);
+        }
+        for v in [0, -1, i8::MIN, i8::MAX] {
+            assert_roundtrip(&v);
+        }
+        for v in [0, -1, -0x40, 0x40, -0x2000, i16::MIN, i16::MAX] {
+            assert_roundtrip(&v);
+        }
+        for v in [0, -1, -0x40, 0x40, -0x800_0000, i32::MIN, i32::MAX] {
+            assert_roundtrip(&v);
+        }
+        for v in [0, -1, -0x40, 0x40, i64::MIN / 2, i64::MIN, i64::MAX] {
+            assert_roundtrip(&v);
+        }
+        for v in [0.0, -1.5, f32::MIN, f32::MAX, f32::INFINITY] {
+            assert_roundtrip(&v);
+        }
+        for v in [0.0, -1.5, f64::MIN, f64::MAX, f64::NEG_INFINITY] {
+            assert_roundtrip(&v);
+        }
+        for v in ['a', 'ß', '€', '🦀'] {
+            assert_roundtrip(&v);
+        }
+        for v in ["", "test", "🦀 wRPC"] {
+            assert_roundtrip(&String::from(v));
+        }
+    }
+
+    #[test]
+    fn compound() {
+        assert_roundtrip(&Bytes::from_static(b"test"));
+        assert_roundtrip(&Vec::<u32>::default());
+        assert_roundtrip(&vec![0_u32, 1, u32::MAX]);
+        assert_roundtrip(&vec![String::from("a"), String::new()]);
+        assert_roundtrip(&Some(42_u16));
+        assert_roundtrip(&None::<String>);
+        assert_roundtrip(&Ok::<_, String>(0x80_u32));
+        assert_roundtrip(&Err::<u32, _>(String::from("test")));
+        assert_roundtrip(&(1_u8, String::from("test"), vec![Some(-1_i32), None]));
+    }
+}
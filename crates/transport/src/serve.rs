@@ -1,22 +1,26 @@
 use core::future::Future;
-use core::pin::Pin;
+use core::panic::AssertUnwindSafe;
+use core::pin::{pin, Pin};
 
+use std::any::Any;
 use std::sync::Arc;
 
-use anyhow::{bail, Context as _};
-use futures::{SinkExt as _, Stream, TryStreamExt as _};
+use anyhow::{anyhow, bail, Context as _};
+use futures::{FutureExt as _, SinkExt as _, Stream, TryStreamExt as _};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt as _};
 use tokio_util::codec::{FramedRead, FramedWrite};
+use tokio_util::task::AbortOnDropHandle;
 use tracing::{debug, instrument, trace, Instrument as _, Span};
 
-use crate::{Deferred as _, Index, TupleDecode, TupleEncode};
+use crate::{receive_value, Deferred as _, Index, TupleDecode, TupleEncode};
 
 /// Server-side handle to a wRPC transport
 pub trait Serve: Sync {
     /// Transport-specific invocation context
     type Context: Send + Sync + 'static;
 
-    /// Outgoing multiplexed byte stream
+    /// Outgoing multiplexed byte stream, which is expected to apply backpressure, see
+    /// [`Invoke::Outgoing`](crate::Invoke::Outgoing)
     type Outgoing: AsyncWrite + Index<Self::Outgoing> + Send + Sync + Unpin + 'static;
 
     /// Incoming multiplexed byte stream
@@ -39,6 +43,11 @@ pub trait Serve: Sync {
 
 pub trait ServeExt: Serve {
     /// Serve function `func` from instance `instance` using typed `Params` and `Results`
+    ///
+    /// If receiving the sync parameters of an invocation fails, e.g. because they are malformed,
+    /// the result stream of that invocation is shut down, which causes the invoker to fail, and
+    /// the returned stream yields an error for it. Such errors do not terminate the returned
+    /// stream, which keeps yielding subsequent invocations.
     #[instrument(level = "trace", skip(self, paths))]
     fn serve_values<Params, Results>(
         &self,
@@ -74,60 +83,303 @@ pub trait ServeExt: Serve {
         async {
             let invocations = self.serve(instance, func, paths).await?;
             let span = Span::current();
-            Ok(invocations.and_then(move |(cx, outgoing, incoming)| {
+            Ok(invocations.and_then(move |(cx, mut outgoing, incoming)| {
                 async {
-                    let mut dec = FramedRead::new(incoming, Params::Decoder::default());
-                    debug!("receiving sync parameters");
-                    let Some(params) = dec
-                        .try_next()
-                        .await
-                        .context("failed to receive sync parameters")?
-                    else {
-                        bail!("incomplete sync parameters")
-                    };
-                    trace!("received sync parameters");
-                    let rx = dec.decoder_mut().take_deferred();
+                    let (params, rx) = receive_params(&mut outgoing, incoming).await?;
                     let span = Span::current();
-                    Ok((
-                        cx,
-                        params,
-                        rx.map(|f| f(dec.into_inner().into(), Vec::with_capacity(8))),
-                        move |results| {
-                            Box::pin(
-                                async {
-                                    let mut enc =
-                                        FramedWrite::new(outgoing, Results::Encoder::default());
-                                    debug!("transmitting sync results");
-                                    enc.send(results)
-                                        .await
-                                        .context("failed to transmit synchronous results")?;
-                                    let tx = enc.encoder_mut().take_deferred();
-                                    let mut outgoing = enc.into_inner();
-                                    outgoing
-                                        .shutdown()
-                                        .await
-                                        .context("failed to shutdown synchronous return channel")?;
-                                    if let Some(tx) = tx {
-                                        debug!("transmitting async results");
-                                        tx(outgoing.into(), Vec::with_capacity(8))
-                                            .await
-                                            .context("failed to write async results")?;
-                                    }
-                                    Ok(())
+                    Ok((cx, params, rx, move |results| {
+                        Box::pin(transmit_results(outgoing, results).instrument(span)) as Pin<_>
+                    }))
+                }
+                .instrument(span.clone())
+            }))
+        }
+    }
+
+    /// Serve function `func` from instance `instance` using `handler`
+    ///
+    /// The returned stream yields a future per accepted invocation, which calls `handler` with
+    /// the parameters received as in [`Self::serve_values`] and transmits the results.
+    /// If `handler` returns an error or panics, receipt of async parameters is aborted and the
+    /// result stream is shut down without transmitting results, which causes the invoker to fail.
+    /// The future then returns the error.
+    #[instrument(level = "trace", skip(self, paths, handler))]
+    fn serve_fn<Params, Results, F, Fut>(
+        &self,
+        instance: &str,
+        func: &str,
+        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+        handler: F,
+    ) -> impl Future<
+        Output = anyhow::Result<
+            impl Stream<
+                    Item = anyhow::Result<
+                        Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'static>>,
+                    >,
+                > + Send
+                + 'static,
+        >,
+    > + Send
+    where
+        Params: TupleDecode<Self::Incoming> + Send + 'static,
+        Results: TupleEncode<Self::Outgoing> + Send + 'static,
+        <Params::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+        <Results::Encoder as tokio_util::codec::Encoder<Results>>::Error:
+            std::error::Error + Send + Sync + 'static,
+        F: Fn(Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<Results>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let name: Arc<str> = format!("{instance}.{func}").into();
+        async move {
+            let invocations = self.serve(instance, func, paths).await?;
+            let span = Span::current();
+            Ok(invocations.and_then(move |(_, mut outgoing, incoming)| {
+                let handler = Arc::clone(&handler);
+                let name = Arc::clone(&name);
+                async move {
+                    let (params, rx) = receive_params(&mut outgoing, incoming).await?;
+                    Ok(Box::pin(
+                        async move {
+                            let rx = rx.map(|rx| {
+                                AbortOnDropHandle::new(tokio::spawn(rx.in_current_span()))
+                            });
+                            trace!("calling handler");
+                            let results = match AssertUnwindSafe(async { handler(params).await })
+                                .catch_unwind()
+                                .await
+                                .map_err(|err| {
+                                    anyhow!("handler panicked: {}", panic_message(&*err))
+                                })
+                                .and_then(|res| res)
+                            {
+                                Ok(results) => results,
+                                Err(err) => {
+                                    shutdown_failed(outgoing).await;
+                                    return Err(err).with_context(|| {
+                                        format!("failed to handle `{name}` invocation")
+                                    });
                                 }
-                                .instrument(span),
-                            ) as Pin<_>
-                        },
-                    ))
+                            };
+                            transmit_results(outgoing, results)
+                                .await
+                                .with_context(|| format!("failed to transmit `{name}` results"))?;
+                            if let Some(rx) = rx {
+                                trace!("receiving async parameters");
+                                rx.await
+                                    .with_context(|| {
+                                        format!("`{name}` async parameter receipt task failed")
+                                    })?
+                                    .with_context(|| {
+                                        format!("failed to receive `{name}` async parameters")
+                                    })?;
+                            }
+                            Ok(())
+                        }
+                        .in_current_span(),
+                    )
+                        as Pin<Box<dyn Future<Output = _> + Send>>)
                 }
                 .instrument(span.clone())
             }))
         }
     }
+
+    /// Serve function `func` from instance `instance` using `handler`, which responds with a
+    /// stream of `Results`, received by
+    /// [`InvokeExt::invoke_server_stream`](crate::InvokeExt::invoke_server_stream)
+    ///
+    /// Each element yielded by the stream returned by `handler` is transmitted as soon as it
+    /// is available as a `Results` tuple on the root result stream. Once the stream returned by
+    /// `handler` ends, the result stream is shut down, which signals the end of responses to
+    /// the invoker. Responses must not contain asynchronous values.
+    ///
+    /// The returned stream yields a future per accepted invocation, like [`Self::serve_fn`].
+    /// If the stream returned by `handler` yields an error, receipt of async parameters is
+    /// aborted, the result stream is shut down, so that the invoker does not wait for further
+    /// responses, and the future returns the error. Note, that the invoker cannot distinguish
+    /// this from the end of responses.
+    #[instrument(level = "trace", skip(self, paths, handler))]
+    fn serve_server_stream<Params, Results, F, St>(
+        &self,
+        instance: &str,
+        func: &str,
+        paths: impl Into<Arc<[Box<[Option<usize>]>]>> + Send,
+        handler: F,
+    ) -> impl Future<
+        Output = anyhow::Result<
+            impl Stream<
+                    Item = anyhow::Result<
+                        Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'static>>,
+                    >,
+                > + Send
+                + 'static,
+        >,
+    > + Send
+    where
+        Params: TupleDecode<Self::Incoming> + Send + 'static,
+        Results: TupleEncode<Self::Outgoing> + Send + 'static,
+        <Params::Decoder as tokio_util::codec::Decoder>::Error:
+            std::error::Error + Send + Sync + 'static,
+        <Results::Encoder as tokio_util::codec::Encoder<Results>>::Error:
+            std::error::Error + Send + Sync + 'static,
+        F: Fn(Params) -> St + Send + Sync + 'static,
+        St: Stream<Item = anyhow::Result<Results>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let name: Arc<str> = format!("{instance}.{func}").into();
+        async move {
+            let invocations = self.serve(instance, func, paths).await?;
+            Ok(invocations.map_ok(move |(_, mut outgoing, incoming)| {
+                let handler = Arc::clone(&handler);
+                let name = Arc::clone(&name);
+                Box::pin(
+                    async move {
+                        let (params, rx) = receive_params(&mut outgoing, incoming).await?;
+                        let rx =
+                            rx.map(|rx| AbortOnDropHandle::new(tokio::spawn(rx.in_current_span())));
+
+                        trace!("calling handler");
+                        let mut responses = pin!(handler(params));
+                        let mut enc = FramedWrite::new(outgoing, Results::Encoder::default());
+                        let res = async {
+                            while let Some(results) = AssertUnwindSafe(responses.try_next())
+                                .catch_unwind()
+                                .await
+                                .map_err(|err| {
+                                    anyhow!("handler panicked: {}", panic_message(&*err))
+                                })
+                                .and_then(|res| res)
+                                .with_context(|| format!("failed to handle `{name}` invocation"))?
+                            {
+                                debug!("transmitting result stream element");
+                                enc.send(results).await.with_context(|| {
+                                    format!("failed to transmit `{name}` results")
+                                })?;
+                                if enc.encoder_mut().take_deferred().is_some() {
+                                    bail!(
+                                        "async values are not supported in `{name}` result streams"
+                                    )
+                                }
+                            }
+                            Ok(())
+                        }
+                        .await;
+                        let mut outgoing = enc.into_inner();
+                        if let Err(err) = res {
+                            shutdown_failed(outgoing).await;
+                            return Err(err);
+                        }
+                        outgoing.shutdown().await.with_context(|| {
+                            format!("failed to shutdown `{name}` result stream")
+                        })?;
+                        if let Some(rx) = rx {
+                            trace!("receiving async parameters");
+                            rx.await
+                                .with_context(|| {
+                                    format!("`{name}` async parameter receipt task failed")
+                                })?
+                                .with_context(|| {
+                                    format!("failed to receive `{name}` async parameters")
+                                })?;
+                        }
+                        Ok(())
+                    }
+                    .in_current_span(),
+                ) as Pin<Box<dyn Future<Output = _> + Send>>
+            }))
+        }
+    }
 }
 
 impl<T: Serve> ServeExt for T {}
 
+/// Receives sync parameters of an invocation from `incoming` and returns them along with the
+/// future receiving async parameters, if any.
+///
+/// If receiving the parameters fails, `outgoing` is shut down, see [`shutdown_failed`].
+async fn receive_params<Params, O, I>(
+    outgoing: &mut O,
+    incoming: I,
+) -> anyhow::Result<(
+    Params,
+    Option<Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>>>,
+)>
+where
+    Params: TupleDecode<I>,
+    <Params::Decoder as tokio_util::codec::Decoder>::Error:
+        std::error::Error + Send + Sync + 'static,
+    O: AsyncWrite + Unpin,
+    I: AsyncRead + Unpin,
+{
+    let mut dec = FramedRead::new(incoming, Params::Decoder::default());
+    debug!("receiving sync parameters");
+    let params = match receive_value(&mut dec).await {
+        Ok(params) => params,
+        Err(err) => {
+            shutdown_failed(outgoing).await;
+            return Err(err).context("failed to receive sync parameters");
+        }
+    };
+    trace!("received sync parameters");
+    let rx = dec.decoder_mut().take_deferred();
+    Ok((
+        params,
+        rx.map(|f| f(dec.into_inner().into(), Vec::with_capacity(8))),
+    ))
+}
+
+/// Transmits `results` of an invocation on `outgoing`
+async fn transmit_results<Results, O>(outgoing: O, results: Results) -> anyhow::Result<()>
+where
+    Results: TupleEncode<O>,
+    <Results::Encoder as tokio_util::codec::Encoder<Results>>::Error:
+        std::error::Error + Send + Sync + 'static,
+    O: AsyncWrite + Index<O> + Send + Sync + Unpin + 'static,
+{
+    let mut enc = FramedWrite::new(outgoing, Results::Encoder::default());
+    debug!("transmitting sync results");
+    enc.send(results)
+        .await
+        .context("failed to transmit synchronous results")?;
+    let tx = enc.encoder_mut().take_deferred();
+    let mut outgoing = enc.into_inner();
+    outgoing
+        .shutdown()
+        .await
+        .context("failed to shutdown synchronous return channel")?;
+    if let Some(tx) = tx {
+        debug!("transmitting async results");
+        tx(outgoing.into(), Vec::with_capacity(8))
+            .await
+            .context("failed to write async results")?;
+    }
+    Ok(())
+}
+
+/// Shuts down the result stream `outgoing` of an invocation, which failed to be handled.
+///
+/// There is no way to transmit an error to the invoker, so this lets it fail instead of
+/// waiting for results, which it would do on transports, which only signal the end of
+/// results on shutdown.
+async fn shutdown_failed(mut outgoing: impl AsyncWrite + Unpin) {
+    if let Err(err) = outgoing.shutdown().await {
+        debug!(?err, "failed to shutdown synchronous return channel");
+    }
+}
+
+/// Extracts the message from a panic payload
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic payload"
+    }
+}
+
 #[allow(dead_code)]
 #[cfg(test)]
 mod tests {
@@ -209,4 +461,21 @@ mod tests {
             })) as Pin<Box<dyn Stream<Item = _>>>)
         }
     }
+
+    fn serve_fn_lifetime<T: Serve>(
+        s: &T,
+    ) -> impl Future<
+        Output = anyhow::Result<Pin<Box<dyn Stream<Item = anyhow::Result<()>> + 'static>>>,
+    > + crate::Captures<'_> {
+        let fut = s.serve_fn(
+            "foo",
+            "bar",
+            [Box::from([Some(42), None]), Box::from([None])],
+            |(v,): (Bytes,)| async move { Ok((v,)) },
+        );
+        async move {
+            let st = fut.await.unwrap();
+            Ok(Box::pin(st.and_then(|fut| fut)) as Pin<Box<dyn Stream<Item = _>>>)
+        }
+    }
 }
@@ -0,0 +1,417 @@
+//! Mirroring of outgoing wRPC traffic to a secondary transport, e.g. for migrations or capture.
+//!
+//! [`TeeInvoke`] establishes each invocation on both a primary and a secondary transport and
+//! transmits all outgoing data via [`TeeOutgoing`], which writes it to the primary and mirrors
+//! it to the secondary at the same structural path. Results are only received from the primary.
+//!
+//! The secondary never affects the primary: secondary invocations are established and written
+//! to by background tasks, which receive mirrored data over bounded channels. A stream, whose
+//! channel is full, because the secondary does not keep up, stops being mirrored, as does a
+//! stream, which fails to be written to the secondary. Both are logged.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+
+use anyhow::Context as _;
+use bytes::Bytes;
+use tokio::io::{AsyncWrite, AsyncWriteExt as _};
+use tokio::sync::mpsc;
+use tracing::{instrument, warn, Instrument as _};
+
+use crate::{Index, Invoke};
+
+/// Operation on the secondary stream, sent to the mirroring task
+#[derive(Debug)]
+enum Mirror {
+    Write(Bytes),
+    Index(Box<[usize]>, mpsc::Receiver<Mirror>),
+    Shutdown,
+}
+
+/// Applies operations received on `rx` to the secondary stream `w`, until the channel is
+/// closed, the stream is shut down or an error occurs
+fn mirror<T>(mut w: T, mut rx: mpsc::Receiver<Mirror>) -> Pin<Box<dyn Future<Output = ()> + Send>>
+where
+    T: AsyncWrite + Index<T> + Send + Unpin + 'static,
+{
+    Box::pin(async move {
+        while let Some(op) = rx.recv().await {
+            let res = match op {
+                Mirror::Write(buf) => match w.write_all(&buf).await {
+                    Ok(()) if rx.is_empty() => w.flush().await,
+                    res => res,
+                },
+                Mirror::Index(path, rx) => {
+                    match w.index(&path) {
+                        Ok(w) => {
+                            tokio::spawn(mirror(w, rx).in_current_span());
+                        }
+                        Err(err) => warn!(
+                            ?err,
+                            ?path,
+                            "failed to index secondary stream, stop mirroring"
+                        ),
+                    }
+                    Ok(())
+                }
+                Mirror::Shutdown => {
+                    if let Err(err) = w.shutdown().await {
+                        warn!(?err, "failed to shutdown secondary stream");
+                    }
+                    return;
+                }
+            };
+            if let Err(err) = res {
+                warn!(?err, "failed to mirror outgoing data, stop mirroring");
+                return;
+            }
+        }
+    })
+}
+
+/// Outgoing byte stream, which writes to `T` and mirrors all data accepted by it to a secondary
+/// stream in a background task
+#[derive(Debug)]
+pub struct TeeOutgoing<T> {
+    primary: T,
+    secondary: Option<mpsc::Sender<Mirror>>,
+}
+
+impl<T> TeeOutgoing<T> {
+    /// Maximum number of writes pending to be mirrored per stream, once exceeded, the stream
+    /// stops being mirrored
+    pub const CAPACITY: usize = 1024;
+
+    /// Constructs a new [`TeeOutgoing`] writing to `primary` and mirroring to `secondary`.
+    ///
+    /// This spawns the mirroring task and must be called within a Tokio runtime.
+    pub fn new<U>(primary: T, secondary: U) -> Self
+    where
+        U: AsyncWrite + Index<U> + Send + Unpin + 'static,
+    {
+        let (tx, rx) = mpsc::channel(Self::CAPACITY);
+        tokio::spawn(mirror(secondary, rx).in_current_span());
+        Self {
+            primary,
+            secondary: Some(tx),
+        }
+    }
+
+    /// Returns `true` if data is still mirrored to the secondary stream
+    pub fn is_mirroring(&self) -> bool {
+        self.secondary.as_ref().is_some_and(|tx| !tx.is_closed())
+    }
+
+    /// Sends `op` to the mirroring task without waiting, stops mirroring if that fails
+    fn forward(&mut self, op: Mirror) {
+        let Some(tx) = self.secondary.as_ref() else {
+            return;
+        };
+        match tx.try_send(op) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(..)) => {
+                warn!("secondary transport does not keep up, stop mirroring");
+                self.secondary = None;
+            }
+            Err(mpsc::error::TrySendError::Closed(..)) => {
+                self.secondary = None;
+            }
+        }
+    }
+}
+
+impl<T: Index<T>> Index<Self> for TeeOutgoing<T> {
+    fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+        let primary = self.primary.index(path)?;
+        let secondary = self.secondary.as_ref().and_then(|secondary| {
+            let (tx, rx) = mpsc::channel(Self::CAPACITY);
+            match secondary.try_send(Mirror::Index(path.into(), rx)) {
+                Ok(()) => Some(tx),
+                Err(mpsc::error::TrySendError::Full(..)) => {
+                    warn!(
+                        ?path,
+                        "secondary transport does not keep up, stop mirroring"
+                    );
+                    None
+                }
+                Err(mpsc::error::TrySendError::Closed(..)) => None,
+            }
+        });
+        Ok(Self { primary, secondary })
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for TeeOutgoing<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let n = ready!(Pin::new(&mut this.primary).poll_write(cx, buf))?;
+        if n > 0 {
+            this.forward(Mirror::Write(Bytes::copy_from_slice(&buf[..n])));
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().primary).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        this.forward(Mirror::Shutdown);
+        this.secondary = None;
+        Pin::new(&mut this.primary).poll_shutdown(cx)
+    }
+}
+
+/// [Invoke] implementation, which establishes each invocation using both `A` and `B`, but only
+/// receives results from `A`.
+///
+/// The invocation context is a tuple of contexts for `A` and `B`.
+/// Invocations are established using `B` in a background task, so the primary never waits for
+/// the secondary. An invocation, which fails to be established using `B`, is logged and not
+/// mirrored. Since an invocation, which never completes, holds on to up to
+/// [`TeeOutgoing::CAPACITY`] writes, consider wrapping `B` using
+/// [`InvokeExt::timeout_owned`](crate::InvokeExt::timeout_owned).
+#[derive(Clone, Debug)]
+pub struct TeeInvoke<A, B> {
+    primary: A,
+    secondary: B,
+}
+
+impl<A, B> TeeInvoke<A, B> {
+    /// Constructs a new [`TeeInvoke`] invoking `primary` and mirroring to `secondary`
+    pub fn new(primary: A, secondary: B) -> Self {
+        Self { primary, secondary }
+    }
+
+    /// Returns the wrapped [`Invoke`] implementations
+    pub fn into_inner(self) -> (A, B) {
+        (self.primary, self.secondary)
+    }
+}
+
+impl<A, B> Invoke for TeeInvoke<A, B>
+where
+    A: Invoke,
+    B: Invoke + Clone + 'static,
+    B::Context: 'static,
+{
+    type Context = (A::Context, B::Context);
+    type Outgoing = TeeOutgoing<A::Outgoing>;
+    type Incoming = A::Incoming;
+
+    #[instrument(level = "trace", skip(self, cx, secondary_cx, params, paths))]
+    async fn invoke<P: AsRef<[Option<usize>]> + Send + Sync>(
+        &self,
+        (cx, secondary_cx): Self::Context,
+        instance: &str,
+        func: &str,
+        params: Bytes,
+        paths: impl AsRef<[P]> + Send,
+    ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)> {
+        let paths = paths.as_ref();
+        let (tx, rx) = mpsc::channel(TeeOutgoing::<A::Outgoing>::CAPACITY);
+        tokio::spawn({
+            let secondary = self.secondary.clone();
+            let instance = instance.to_string();
+            let func = func.to_string();
+            let params = params.clone();
+            let paths = paths
+                .iter()
+                .map(|path| Box::from(path.as_ref()))
+                .collect::<Vec<Box<[Option<usize>]>>>();
+            async move {
+                match secondary
+                    .invoke(secondary_cx, &instance, &func, params, paths)
+                    .await
+                {
+                    Ok((outgoing, _)) => mirror(outgoing, rx).await,
+                    Err(err) => warn!(?err, "failed to mirror invocation"),
+                }
+            }
+            .in_current_span()
+        });
+        let (outgoing, incoming) = self
+            .primary
+            .invoke(cx, instance, func, params, paths)
+            .await?;
+        Ok((
+            TeeOutgoing {
+                primary: outgoing,
+                secondary: Some(tx),
+            },
+            incoming,
+        ))
+    }
+
+    async fn ready(&self) -> anyhow::Result<()> {
+        self.primary
+            .ready()
+            .await
+            .context("primary transport is not ready")
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use core::pin::Pin;
+    use core::time::Duration;
+
+    use bytes::Bytes;
+    use futures::{future, stream, Stream};
+
+    use super::*;
+    use crate::{BoundedOutgoing, InvokeExt as _, MockOutgoing, RecordingOutgoing, ReplayIncoming};
+
+    /// [Invoke] implementation, which records the parameters and all outgoing data and
+    /// responds with `42_u32`
+    #[derive(Clone)]
+    struct RecordingInvoke(RecordingOutgoing);
+
+    impl Invoke for RecordingInvoke {
+        type Context = ();
+        type Outgoing = RecordingOutgoing;
+        type Incoming = ReplayIncoming;
+
+        async fn invoke<P: AsRef<[Option<usize>]> + Send + Sync>(
+            &self,
+            (): Self::Context,
+            _instance: &str,
+            _func: &str,
+            params: Bytes,
+            _paths: impl AsRef<[P]> + Send,
+        ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)> {
+            let mut outgoing = self.0.clone();
+            outgoing.write_all(&params).await?;
+            Ok((outgoing, Bytes::from_static(b"\x2a").into()))
+        }
+    }
+
+    /// [Invoke] implementation, which never establishes an invocation
+    #[derive(Clone)]
+    struct PendingInvoke;
+
+    impl Invoke for PendingInvoke {
+        type Context = ();
+        type Outgoing = RecordingOutgoing;
+        type Incoming = ReplayIncoming;
+
+        async fn invoke<P: AsRef<[Option<usize>]> + Send + Sync>(
+            &self,
+            (): Self::Context,
+            _instance: &str,
+            _func: &str,
+            _params: Bytes,
+            _paths: impl AsRef<[P]> + Send,
+        ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)> {
+            future::pending().await
+        }
+    }
+
+    /// Waits until `tee` stops mirroring
+    async fn stopped_mirroring<T>(tee: &TeeOutgoing<T>) {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while tee.is_mirroring() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("mirroring did not stop");
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn tee() -> anyhow::Result<()> {
+        let (a, a_rec) = RecordingOutgoing::new(Vec::new());
+        let (b, b_rec) = RecordingOutgoing::new(Vec::new());
+        let clt = TeeInvoke::new(RecordingInvoke(a), RecordingInvoke(b));
+        let items: Pin<Box<dyn Stream<Item = Bytes> + Send>> =
+            Box::pin(stream::iter([Bytes::from("foo"), Bytes::from("bar")]));
+        let ((v,), io) = clt
+            .invoke_values::<_, _, (u32,)>(((), ()), "foo", "bar", (7_u32, items), [[Some(1)]])
+            .await?;
+        assert_eq!(v, 42);
+        if let Some(io) = io {
+            io.await?;
+        }
+        drop(clt);
+        let (a, b) = tokio::try_join!(a_rec, b_rec)?;
+        assert!(!a.is_empty());
+        assert_eq!(a, b, "both transports should receive identical frames");
+
+        // secondary failures do not affect the primary
+        let (broken, rec) = RecordingOutgoing::new(Vec::new());
+        drop(rec);
+        let primary = MockOutgoing::default();
+        let mut tee = TeeOutgoing::new(primary.clone(), broken.clone());
+        tee.write_all(b"foo").await?;
+        stopped_mirroring(&tee).await;
+        let mut nested = tee.index(&[0])?;
+        nested.write_all(b"bar").await?;
+        nested.shutdown().await?;
+        assert_eq!(primary.bytes(&[]), "foo");
+        assert_eq!(primary.bytes(&[0]), "bar");
+        assert!(primary.is_shutdown(&[0]));
+
+        let (primary, rec) = RecordingOutgoing::new(Vec::new());
+        let clt = TeeInvoke::new(RecordingInvoke(primary), RecordingInvoke(broken));
+        let ((v,), _) = clt
+            .invoke_values::<_, _, (u32,)>(((), ()), "foo", "bar", (7_u32,), &[[]; 0])
+            .await?;
+        assert_eq!(v, 42);
+        drop(clt);
+        assert!(!rec.await?.is_empty());
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn stalled_secondary() -> anyhow::Result<()> {
+        // secondary invocation is never established
+        let (primary, rec) = RecordingOutgoing::new(Vec::new());
+        let clt = TeeInvoke::new(RecordingInvoke(primary), PendingInvoke);
+        let items: Pin<Box<dyn Stream<Item = Bytes> + Send>> = Box::pin(stream::iter(
+            (0..=TeeOutgoing::<RecordingOutgoing>::CAPACITY).map(|_| Bytes::from("foo")),
+        ));
+        let ((v,), io) = tokio::time::timeout(
+            Duration::from_secs(1),
+            clt.invoke_values::<_, _, (u32,)>(((), ()), "foo", "bar", (7_u32, items), [[Some(1)]]),
+        )
+        .await
+        .context("primary invocation waited for the secondary")??;
+        assert_eq!(v, 42);
+        if let Some(io) = io {
+            tokio::time::timeout(Duration::from_secs(1), io)
+                .await
+                .context("primary transmission waited for the secondary")??;
+        }
+        drop(clt);
+        assert!(!rec.await?.is_empty());
+
+        // secondary stream never accepts data
+        let (secondary, _rx) = BoundedOutgoing::new(1);
+        let primary = MockOutgoing::default();
+        let mut tee = TeeOutgoing::new(primary.clone(), secondary);
+        let n = 2 * TeeOutgoing::<MockOutgoing>::CAPACITY;
+        tokio::time::timeout(Duration::from_secs(1), async {
+            for _ in 0..n {
+                tee.write_all(b"x").await?;
+                // allow the mirroring task to make progress, if it can
+                tokio::task::yield_now().await;
+            }
+            assert!(
+                !tee.is_mirroring(),
+                "data should be dropped once the channel is full"
+            );
+            tee.shutdown().await
+        })
+        .await
+        .context("primary writes waited for the secondary")??;
+        assert_eq!(primary.bytes(&[]).len(), n);
+        assert!(primary.is_shutdown(&[]));
+        Ok(())
+    }
+}
@@ -1,23 +1,30 @@
 use core::any::TypeId;
+use core::cell::Cell;
 use core::fmt::{self, Debug};
 use core::future::Future;
-use core::hash::{Hash, Hasher};
+use core::hash::{BuildHasher, Hash, Hasher};
 use core::iter::zip;
 use core::marker::PhantomData;
 use core::mem;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use core::ops::{Deref, DerefMut};
 use core::pin::Pin;
+use core::time::Duration;
 
+use anyhow::{bail, ensure, Context as _};
 use bytes::{Buf as _, BufMut as _, Bytes, BytesMut};
 use futures::stream::{self, FuturesUnordered};
-use futures::{Stream, StreamExt as _, TryStreamExt as _};
+use futures::{FutureExt as _, Stream, StreamExt as _, TryStreamExt as _};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, CString};
+use std::rc::Rc;
 use std::sync::Arc;
 use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
 use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinSet;
 use tokio::{select, try_join};
 use tokio_stream::wrappers::ReceiverStream;
-use tokio_util::codec::{Encoder as _, FramedRead};
+use tokio_util::codec::{Decoder as _, Encoder as _, FramedRead};
 use tokio_util::io::StreamReader;
 use tracing::{instrument, trace};
 use wasm_tokio::cm::{
@@ -248,6 +255,7 @@ impl_deferred_sync!(CoreVecDecoder<F32Codec>);
 impl_deferred_sync!(CoreVecDecoder<F64Codec>);
 impl_deferred_sync!(CoreVecDecoder<CoreNameDecoder>);
 impl_deferred_sync!(CoreVecDecoder<CoreVecDecoderBytes>);
+impl_deferred_sync!(CoreVecDecoder<BytesMutCodec>);
 impl_deferred_sync!(CoreVecDecoder<Utf8Codec>);
 impl_deferred_sync!(CoreVecDecoder<Leb128DecoderI8>);
 impl_deferred_sync!(CoreVecDecoder<Leb128DecoderU8>);
@@ -337,8 +345,10 @@ where
     I: IntoIterator<Item = Option<DeferredFn<T>>>,
     I::IntoIter: ExactSizeIterator,
 {
+    let idx = usize::try_from(idx)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
     let mut futs = FuturesUnordered::default();
-    for (i, f) in zip(0.., deferred) {
+    for (i, f) in zip(idx.., deferred) {
         if let Some(f) = f {
             path.push(i);
             futs.push(f(Arc::clone(&w), path.clone()));
@@ -349,10 +359,15 @@ where
     Ok(())
 }
 
+/// Defines how a value is encoded.
+///
+/// Types without asynchronous components also implement [Encode] by reference, which
+/// allows the same value to be transmitted multiple times without cloning it.
+/// Futures and streams are single-shot and can only be encoded by value.
 pub trait Encode<T>: Sized {
     type Encoder: tokio_util::codec::Encoder<Self> + Deferred<T> + Default + Send;
 
-    #[instrument(level = "trace", skip(self, enc))]
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip(self, enc)))]
     fn encode(
         self,
         enc: &mut Self::Encoder,
@@ -459,6 +474,298 @@ pub trait Decode<T>: Sized {
     type ListDecoder: tokio_util::codec::Decoder<Item = Vec<Self>> + Default + 'static;
 }
 
+/// Encode a value of type `T` into a single, fully-buffered payload.
+///
+/// Returns the synchronous portion of the encoding and, if `value` has asynchronous
+/// components, a [`DeferredFn`] transmitting them. This is useful for callers embedding wRPC
+/// in another protocol, which transmit the payload themselves.
+pub fn encode_value<T, W>(
+    value: T,
+) -> Result<(Bytes, Option<DeferredFn<W>>), <T::Encoder as tokio_util::codec::Encoder<T>>::Error>
+where
+    T: Encode<W>,
+{
+    let mut buf = BytesMut::default();
+    let mut enc = T::Encoder::default();
+    let deferred = value.encode(&mut enc, &mut buf)?;
+    Ok((buf.freeze(), deferred))
+}
+
+thread_local! {
+    /// Whether values are currently being encoded by [`encode_value_canonical`]
+    static CANONICAL: Cell<bool> = const { Cell::new(false) };
+}
+
+fn is_canonical() -> bool {
+    CANONICAL.with(Cell::get)
+}
+
+/// Restores previous [`CANONICAL`] mode on drop
+struct CanonicalGuard(bool);
+
+impl CanonicalGuard {
+    fn enter() -> Self {
+        Self(CANONICAL.with(|canonical| canonical.replace(true)))
+    }
+}
+
+impl Drop for CanonicalGuard {
+    fn drop(&mut self) {
+        CANONICAL.with(|canonical| canonical.set(self.0));
+    }
+}
+
+fn canonical_async_error(ty: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("`{ty}` values cannot be encoded canonically"),
+    )
+}
+
+/// Encode a value of type `T` into a canonical payload, suitable for hashing and signing.
+///
+/// Equal values always produce identical bytes:
+///
+/// - integers are encoded using minimal-length LEB128, `s*` values using signed LEB128
+/// - `f32` and `f64` are encoded as little-endian IEEE 754 bytes, NaN payloads are preserved as-is
+/// - `char` and `string` values are encoded as a LEB128 length in bytes followed by UTF-8 bytes
+/// - `list` and `map` values are encoded as a LEB128 element count followed by the elements,
+///   map entries are sorted by their encoded key bytes
+/// - `record` and `tuple` fields are encoded in order without separators
+/// - `option`, `result`, `variant` and `enum` values are encoded as a discriminant followed by the
+///   payload of the case, if any
+///
+/// `future` and `stream` values are rejected, since their encoding depends on the readiness of the
+/// value at the time of encoding.
+pub fn encode_value_canonical<T, W>(value: T) -> anyhow::Result<Bytes>
+where
+    T: Encode<W>,
+    <T::Encoder as tokio_util::codec::Encoder<T>>::Error: std::error::Error + Send + Sync + 'static,
+{
+    let _guard = CanonicalGuard::enter();
+    let (buf, deferred) = encode_value(value).context("failed to encode value")?;
+    ensure!(
+        deferred.is_none(),
+        "values with asynchronous components cannot be encoded canonically"
+    );
+    Ok(buf)
+}
+
+/// Value of type `T` along with its canonical encoding, see [`encode_value_canonical`]
+///
+/// Comparisons and hashing use the canonical encoding only, which provides a total order on
+/// values of any synchronous type, including floats and variants. The order matches the order of
+/// map entries in canonical encoding, so [`CanonicalValue`] can be used as the key of sorted maps
+/// and sets, which deduplicate values with identical encodings. Note, that this is an order of
+/// the encoded bytes, not of the values - e.g. `0x80_u32` is ordered before `0x7f_u32` and
+/// `-0.0_f64` is distinct from `0.0_f64`.
+#[derive(Clone, Debug)]
+pub struct CanonicalValue<T> {
+    value: T,
+    buf: Bytes,
+}
+
+impl<T> CanonicalValue<T> {
+    /// Encodes `value` canonically, see [`encode_value_canonical`]
+    ///
+    /// `future` and `stream` values cannot be cloned, so they are not supported.
+    pub fn new<W>(value: T) -> anyhow::Result<Self>
+    where
+        T: Encode<W> + Clone,
+        <T::Encoder as tokio_util::codec::Encoder<T>>::Error:
+            std::error::Error + Send + Sync + 'static,
+    {
+        let buf = encode_value_canonical(value.clone())?;
+        Ok(Self { value, buf })
+    }
+
+    /// Returns a reference to the value
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns the canonical encoding of the value
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.buf
+    }
+
+    /// Returns the value
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> PartialEq for CanonicalValue<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.buf == other.buf
+    }
+}
+
+impl<T> Eq for CanonicalValue<T> {}
+
+impl<T> PartialOrd for CanonicalValue<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for CanonicalValue<T> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.buf.cmp(&other.buf)
+    }
+}
+
+impl<T> Hash for CanonicalValue<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.buf.hash(state);
+    }
+}
+
+/// Size of the buffer, after which [`write_list`] writes encoded elements to the writer
+const WRITE_LIST_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Encode `items` as a `list<T>` and write it to `w` incrementally.
+///
+/// Unlike [`encode_value`], the whole encoding is never materialized in memory - elements are
+/// encoded into a buffer of bounded size, which is written to `w` each time it fills up. This is
+/// useful for transmitting large lists, which would otherwise double the memory required.
+///
+/// Returns a [`DeferredFn`] transmitting asynchronous components of `items`, if any.
+#[instrument(level = "trace", skip_all, fields(ty = "list"))]
+pub async fn write_list<T, W, I>(w: &mut W, items: I) -> std::io::Result<Option<DeferredFn<W>>>
+where
+    T: Encode<W>,
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    I: IntoIterator<Item = T>,
+    I::IntoIter: ExactSizeIterator,
+    <T::Encoder as tokio_util::codec::Encoder<T>>::Error: Into<std::io::Error>,
+{
+    let items = items.into_iter();
+    let n = u32::try_from(items.len())
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    let mut buf = BytesMut::with_capacity(WRITE_LIST_CHUNK_SIZE);
+    Leb128Encoder.encode(n, &mut buf)?;
+    let mut enc = T::Encoder::default();
+    // only keep track of elements with async components, since a `Vec<Option<_>>` for all
+    // elements would be larger than the encoding of e.g. a `list<u8>`
+    let mut deferred = Vec::default();
+    for (i, item) in items.enumerate() {
+        enc.encode(item, &mut buf).map_err(Into::into)?;
+        if let Some(f) = enc.take_deferred() {
+            deferred.push((i, f));
+        }
+        if buf.len() >= WRITE_LIST_CHUNK_SIZE {
+            trace!(n = buf.len(), "writing encoded list chunk");
+            w.write_all(&buf).await?;
+            buf.clear();
+        }
+    }
+    if !buf.is_empty() {
+        trace!(n = buf.len(), "writing encoded list chunk");
+        w.write_all(&buf).await?;
+    }
+    if deferred.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Box::new(move |w, mut path| {
+        Box::pin(async move {
+            let mut futs = FuturesUnordered::default();
+            for (i, f) in deferred {
+                path.push(i);
+                futs.push(f(Arc::clone(&w), path.clone()));
+                path.pop();
+            }
+            while let Some(()) = futs.try_next().await? {}
+            Ok(())
+        })
+    })))
+}
+
+/// Decode a value of type `T` from a single, fully-buffered payload.
+///
+/// This is useful for tooling, which has the whole payload in memory and does not need a
+/// transport. This function fails if the payload is incomplete, if it contains trailing bytes or
+/// if the value requires asynchronous I/O (e.g. a pending `future` or `stream`), which cannot be
+/// satisfied from a single buffer.
+#[instrument(level = "trace", skip_all)]
+pub fn decode_value<T, R>(buf: impl Into<BytesMut>) -> anyhow::Result<T>
+where
+    T: Decode<R>,
+    <T::Decoder as tokio_util::codec::Decoder>::Error: std::error::Error + Send + Sync + 'static,
+{
+    let (v, buf) = decode_value_prefix(buf.into())?;
+    ensure!(
+        buf.is_empty(),
+        "payload contains `{}` trailing bytes",
+        buf.len()
+    );
+    Ok(v)
+}
+
+/// Decode a value of type `T` from the beginning of `buf`, returning it along with the number
+/// of bytes it occupies.
+///
+/// This is like [`decode_value`], but any bytes following the value are left untouched. This is
+/// useful for callers embedding wRPC in another framed protocol, which need to advance their own
+/// cursor past the value.
+#[instrument(level = "trace", skip_all)]
+pub fn decode_value_counted<T, R>(buf: impl Into<BytesMut>) -> anyhow::Result<(T, usize)>
+where
+    T: Decode<R>,
+    <T::Decoder as tokio_util::codec::Decoder>::Error: std::error::Error + Send + Sync + 'static,
+{
+    let buf = buf.into();
+    let n = buf.len();
+    let (v, buf) = decode_value_prefix(buf)?;
+    Ok((v, n - buf.len()))
+}
+
+/// Decode a value of type `T` from the beginning of `buf`, returning it along with the remaining
+/// bytes
+fn decode_value_prefix<T, R>(mut buf: BytesMut) -> anyhow::Result<(T, BytesMut)>
+where
+    T: Decode<R>,
+    <T::Decoder as tokio_util::codec::Decoder>::Error: std::error::Error + Send + Sync + 'static,
+{
+    let mut dec = T::Decoder::default();
+    let Some(v) = dec.decode(&mut buf).context("failed to decode value")? else {
+        bail!("incomplete payload")
+    };
+    ensure!(
+        dec.take_deferred().is_none(),
+        "value requires asynchronous I/O, which cannot be performed on a single buffer"
+    );
+    Ok((v, buf))
+}
+
+/// Decodes a `list<u8>` from a fully-buffered `payload`, returning a [Bytes] view sharing
+/// the allocation of `payload` rather than copying the contents.
+///
+/// This is useful for proxies, which forward byte payloads without inspecting them.
+pub fn receive_bytes_zero_copy(payload: &Bytes) -> anyhow::Result<Bytes> {
+    // length prefix is at most 5 bytes long
+    let mut prefix = BytesMut::from(&payload[..payload.len().min(5)]);
+    let n = prefix.len();
+    let Some(len) = Leb128DecoderU32
+        .decode(&mut prefix)
+        .context("failed to decode list length")?
+    else {
+        bail!("incomplete payload")
+    };
+    let start = n - prefix.len();
+    let len = usize::try_from(len).context("list length does not fit in usize")?;
+    let end = start
+        .checked_add(len)
+        .context("list length overflows usize")?;
+    ensure!(payload.len() >= end, "incomplete payload");
+    ensure!(
+        payload.len() == end,
+        "payload contains `{}` trailing bytes",
+        payload.len() - end
+    );
+    Ok(payload.slice(start..end))
+}
+
 impl<T, W> Deferred<W> for OptionEncoder<T>
 where
     T: Deferred<W>,
@@ -495,1057 +802,4578 @@ where
 impl<T, R> Decode<R> for Option<T>
 where
     T: Decode<R>,
+    R: 'static,
 {
     type Decoder = OptionDecoder<T::Decoder>;
-    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
 }
 
-impl<O, E, W> Deferred<W> for ResultEncoder<O, E>
-where
-    O: Deferred<W>,
-    E: Deferred<W>,
-{
-    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
-        match (self.ok.take_deferred(), self.err.take_deferred()) {
-            (None, None) => None,
-            (Some(ok), None) => Some(ok),
-            (None, Some(err)) => Some(err),
-            (Some(ok), Some(_)) => {
-                if cfg!(debug_assertions) {
-                    panic!("both `result::ok` and `result::err` deferred function set")
-                }
-                Some(ok)
-            }
-        }
+/// Encoder for [`Box<T>`], which encodes the boxed value using `E`.
+///
+/// The inner encoder is allocated lazily, which allows for recursive types, e.g. a tree,
+/// to contain their own encoder.
+pub struct BoxEncoder<E>(Option<Box<E>>);
+
+impl<E> Default for BoxEncoder<E> {
+    fn default() -> Self {
+        Self(None)
     }
 }
 
-impl<O, E, W> Encode<W> for Result<O, E>
+impl<E: Default> BoxEncoder<E> {
+    fn inner(&mut self) -> &mut E {
+        self.0.get_or_insert_with(Box::default)
+    }
+}
+
+impl<T, E> tokio_util::codec::Encoder<Box<T>> for BoxEncoder<E>
 where
-    O: Encode<W>,
-    E: Encode<W>,
-    std::io::Error: From<<O::Encoder as tokio_util::codec::Encoder<O>>::Error>,
-    std::io::Error: From<<E::Encoder as tokio_util::codec::Encoder<E>>::Error>,
+    E: tokio_util::codec::Encoder<T> + Default,
 {
-    type Encoder = ResultEncoder<O::Encoder, E::Encoder>;
+    type Error = E::Error;
+
+    fn encode(&mut self, item: Box<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner().encode(*item, dst)
+    }
 }
 
-impl<'a, O, E, W> Encode<W> for &'a Result<O, E>
+impl<'a, T, E> tokio_util::codec::Encoder<&'a Box<T>> for BoxEncoder<E>
 where
-    O: Encode<W>,
-    O::Encoder: tokio_util::codec::Encoder<&'a O>,
-    E: Encode<W>,
-    E::Encoder: tokio_util::codec::Encoder<&'a E>,
-    std::io::Error: From<<O::Encoder as tokio_util::codec::Encoder<&'a O>>::Error>,
-    std::io::Error: From<<E::Encoder as tokio_util::codec::Encoder<&'a E>>::Error>,
+    E: tokio_util::codec::Encoder<&'a T> + Default,
 {
-    type Encoder = ResultEncoder<O::Encoder, E::Encoder>;
+    type Error = E::Error;
+
+    fn encode(&mut self, item: &'a Box<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner().encode(item, dst)
+    }
 }
 
-impl<O, E, W> Deferred<W> for ResultDecoder<O, E>
+impl<E, W> Deferred<W> for BoxEncoder<E>
 where
-    O: Deferred<W> + Default,
-    E: Deferred<W> + Default,
+    E: Deferred<W>,
 {
     fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
-        let (mut ok, mut err) = mem::take(self).into_inner();
-        match (ok.take_deferred(), err.take_deferred()) {
-            (None, None) => None,
-            (Some(ok), None) => Some(ok),
-            (None, Some(err)) => Some(err),
-            (Some(ok), Some(_)) => {
-                if cfg!(debug_assertions) {
-                    panic!("both `result::ok` and `result::err` deferred function set")
-                }
-                Some(ok)
-            }
-        }
+        self.0.as_mut()?.take_deferred()
     }
 }
 
-impl<O, E, R> Decode<R> for Result<O, E>
+impl<T, W> Encode<W> for Box<T>
 where
-    O: Decode<R>,
-    E: Decode<R>,
-    std::io::Error: From<<O::Decoder as tokio_util::codec::Decoder>::Error>,
-    std::io::Error: From<<E::Decoder as tokio_util::codec::Decoder>::Error>,
+    T: Encode<W>,
 {
-    type Decoder = ResultDecoder<O::Decoder, E::Decoder>;
-    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+    type Encoder = BoxEncoder<T::Encoder>;
 }
 
-pub struct ListEncoder<W> {
-    deferred: Option<DeferredFn<W>>,
+impl<'a, T, W> Encode<W> for &'a Box<T>
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'a T>,
+{
+    type Encoder = BoxEncoder<T::Encoder>;
 }
 
-impl<W> Default for ListEncoder<W> {
-    fn default() -> Self {
-        Self { deferred: None }
-    }
-}
+/// Decoder for [`Box<T>`], which decodes the value using `D` and boxes it.
+///
+/// The inner decoder is allocated lazily, which allows for recursive types, e.g. a tree,
+/// to contain their own decoder.
+pub struct BoxDecoder<D>(Option<Box<D>>);
 
-impl<W> Deferred<W> for ListEncoder<W> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
-        self.deferred.take()
+impl<D> Default for BoxDecoder<D> {
+    fn default() -> Self {
+        Self(None)
     }
 }
 
-impl<T, W> tokio_util::codec::Encoder<Vec<T>> for ListEncoder<W>
+impl<D> tokio_util::codec::Decoder for BoxDecoder<D>
 where
-    T: Encode<W>,
-    W: crate::Index<W> + Send + Sync + 'static,
+    D: tokio_util::codec::Decoder + Default,
 {
-    type Error = <T::Encoder as tokio_util::codec::Encoder<T>>::Error;
+    type Item = Box<D::Item>;
+    type Error = D::Error;
 
-    fn encode(&mut self, items: Vec<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let mut enc = T::Encoder::default();
-        self.deferred = T::encode_list_own(items, &mut enc, dst)?;
-        Ok(())
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let dec = self.0.get_or_insert_with(Box::default);
+        let Some(v) = dec.decode(src)? else {
+            return Ok(None);
+        };
+        Ok(Some(Box::new(v)))
     }
 }
 
-impl<'a, T, W> tokio_util::codec::Encoder<&'a Vec<T>> for ListEncoder<W>
+impl<D, R> Deferred<R> for BoxDecoder<D>
 where
-    T: Encode<W>,
-    T::Encoder: tokio_util::codec::Encoder<&'a T>,
-    W: crate::Index<W> + Send + Sync + 'static,
+    D: Deferred<R>,
 {
-    type Error = <T::Encoder as tokio_util::codec::Encoder<&'a T>>::Error;
-
-    fn encode(&mut self, items: &'a Vec<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let mut enc = T::Encoder::default();
-        self.deferred = T::encode_list_ref(items, &mut enc, dst)?;
-        Ok(())
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        self.0.as_mut()?.take_deferred()
     }
 }
 
-impl<'a, 'b, T, W> tokio_util::codec::Encoder<&'a &'b Vec<T>> for ListEncoder<W>
+impl<T, R> Decode<R> for Box<T>
 where
-    T: Encode<W>,
-    T::Encoder: tokio_util::codec::Encoder<&'b T>,
-    W: crate::Index<W> + Send + Sync + 'static,
+    T: Decode<R>,
+    R: 'static,
 {
-    type Error = <T::Encoder as tokio_util::codec::Encoder<&'b T>>::Error;
-
-    fn encode(&mut self, items: &'a &'b Vec<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let mut enc = T::Encoder::default();
-        self.deferred = T::encode_list_ref(items, &mut enc, dst)?;
-        Ok(())
-    }
+    type Decoder = BoxDecoder<T::Decoder>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
 }
 
-impl<'a, T, W> tokio_util::codec::Encoder<&'a [T]> for ListEncoder<W>
+/// Encoder for [`Rc<T>`](Rc), which encodes the shared value using `E`.
+///
+/// [`Rc<T>`](Rc) is not [`Send`], but neither [`Encode`] nor [`Decode`] require it of the value,
+/// only of its codec, which allows single-threaded guests to pass shared values directly.
+/// Lists and tuples of [`Rc<T>`](Rc) can be encoded, but not decoded, since their decoders
+/// require [`Send`] elements.
+/// Owned values are encoded in place if not shared and cloned otherwise.
+#[derive(Debug, Default)]
+pub struct RcEncoder<E>(E);
+
+impl<T, E> tokio_util::codec::Encoder<Rc<T>> for RcEncoder<E>
 where
-    T: Encode<W>,
-    T::Encoder: tokio_util::codec::Encoder<&'a T>,
-    W: crate::Index<W> + Send + Sync + 'static,
+    T: Clone,
+    E: tokio_util::codec::Encoder<T>,
 {
-    type Error = <T::Encoder as tokio_util::codec::Encoder<&'a T>>::Error;
+    type Error = E::Error;
 
-    fn encode(&mut self, items: &'a [T], dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let mut enc = T::Encoder::default();
-        self.deferred = T::encode_list_ref(items, &mut enc, dst)?;
-        Ok(())
+    fn encode(&mut self, item: Rc<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode(Rc::unwrap_or_clone(item), dst)
     }
 }
 
-impl<'a, 'b, T, W> tokio_util::codec::Encoder<&'a &'b [T]> for ListEncoder<W>
+impl<'a, T, E> tokio_util::codec::Encoder<&'a Rc<T>> for RcEncoder<E>
 where
-    T: Encode<W>,
-    T::Encoder: tokio_util::codec::Encoder<&'b T>,
-    W: crate::Index<W> + Send + Sync + 'static,
+    E: tokio_util::codec::Encoder<&'a T>,
 {
-    type Error = <T::Encoder as tokio_util::codec::Encoder<&'b T>>::Error;
+    type Error = E::Error;
 
-    fn encode(&mut self, items: &'a &'b [T], dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let mut enc = T::Encoder::default();
-        self.deferred = T::encode_list_ref(items, &mut enc, dst)?;
-        Ok(())
+    fn encode(&mut self, item: &'a Rc<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode(item, dst)
     }
 }
 
-impl<T, W> Encode<W> for Vec<T>
+impl<E, W> Deferred<W> for RcEncoder<E>
 where
-    T: Encode<W>,
-    W: crate::Index<W> + Send + Sync + 'static,
+    E: Deferred<W>,
 {
-    type Encoder = ListEncoder<W>;
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.0.take_deferred()
+    }
 }
 
-impl<'a, T, W> Encode<W> for &'a Vec<T>
+impl<T, W> Encode<W> for Rc<T>
 where
-    T: Encode<W>,
-    T::Encoder: tokio_util::codec::Encoder<&'a T>,
-    W: crate::Index<W> + Send + Sync + 'static,
+    T: Encode<W> + Clone,
 {
-    type Encoder = ListEncoder<W>;
+    type Encoder = RcEncoder<T::Encoder>;
 }
 
-impl<'a, T, W> Encode<W> for &'a [T]
+impl<'a, T, W> Encode<W> for &'a Rc<T>
 where
     T: Encode<W>,
     T::Encoder: tokio_util::codec::Encoder<&'a T>,
-    W: crate::Index<W> + Send + Sync + 'static,
 {
-    type Encoder = ListEncoder<W>;
+    type Encoder = RcEncoder<T::Encoder>;
 }
 
-pub struct ListDecoder<T, R>
+/// Decoder for [`Rc<T>`](Rc), which decodes the value using `D` and wraps it in an [`Rc`].
+#[derive(Debug, Default)]
+pub struct RcDecoder<D>(D);
+
+impl<D> tokio_util::codec::Decoder for RcDecoder<D>
 where
-    T: tokio_util::codec::Decoder,
+    D: tokio_util::codec::Decoder,
 {
-    dec: T,
-    ret: Vec<T::Item>,
-    cap: usize,
-    deferred: Vec<Option<DeferredFn<R>>>,
+    type Item = Rc<D::Item>;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(v) = self.0.decode(src)? else {
+            return Ok(None);
+        };
+        Ok(Some(Rc::new(v)))
+    }
 }
 
-impl<T, R> ListDecoder<T, R>
+impl<D, R> Deferred<R> for RcDecoder<D>
 where
-    T: tokio_util::codec::Decoder,
+    D: Deferred<R>,
 {
-    pub fn new(dec: T) -> Self {
-        Self {
-            dec,
-            ret: Vec::default(),
-            cap: 0,
-            deferred: vec![],
-        }
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        self.0.take_deferred()
     }
 }
 
-impl<T, R> Default for ListDecoder<T, R>
+impl<T, R> Decode<R> for Rc<T>
 where
-    T: tokio_util::codec::Decoder + Default,
+    T: Decode<R>,
+    R: 'static,
 {
-    fn default() -> Self {
-        Self::new(T::default())
-    }
+    type Decoder = RcDecoder<T::Decoder>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
 }
 
-impl<T, R> Deferred<R> for ListDecoder<T, R>
+/// Encoder for [`Arc<T>`](Arc), which encodes the shared value using `E`.
+///
+/// Shared values are encoded by reference via `&Arc<T>`, which never requires ownership.
+/// Owned values are encoded in place if not shared and cloned otherwise.
+#[derive(Debug, Default)]
+pub struct ArcEncoder<E>(E);
+
+impl<T, E> tokio_util::codec::Encoder<Arc<T>> for ArcEncoder<E>
 where
-    T: tokio_util::codec::Decoder,
-    R: crate::Index<R> + Send + Sync + 'static,
+    T: Clone,
+    E: tokio_util::codec::Encoder<T>,
 {
-    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-        let deferred = mem::take(&mut self.deferred);
-        if deferred.iter().any(Option::is_some) {
-            Some(Box::new(|r, path| {
-                Box::pin(handle_deferred(r, deferred, path, 0))
-            }))
-        } else {
-            None
-        }
+    type Error = E::Error;
+
+    fn encode(&mut self, item: Arc<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode(Arc::unwrap_or_clone(item), dst)
     }
 }
 
-impl<T, R> tokio_util::codec::Decoder for ListDecoder<T, R>
+impl<'a, T, E> tokio_util::codec::Encoder<&'a Arc<T>> for ArcEncoder<E>
 where
-    T: tokio_util::codec::Decoder + Deferred<R>,
+    E: tokio_util::codec::Encoder<&'a T>,
 {
-    type Item = Vec<T::Item>;
-    type Error = T::Error;
+    type Error = E::Error;
+
+    fn encode(&mut self, item: &'a Arc<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode(item, dst)
+    }
+}
+
+impl<E, W> Deferred<W> for ArcEncoder<E>
+where
+    E: Deferred<W>,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.0.take_deferred()
+    }
+}
+
+impl<T, W> Encode<W> for Arc<T>
+where
+    T: Encode<W> + Clone,
+{
+    type Encoder = ArcEncoder<T::Encoder>;
+}
+
+impl<'a, T, W> Encode<W> for &'a Arc<T>
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'a T>,
+{
+    type Encoder = ArcEncoder<T::Encoder>;
+}
+
+/// Decoder for [`Arc<T>`](Arc), which decodes the value using `D` and wraps it in an [`Arc`].
+#[derive(Debug, Default)]
+pub struct ArcDecoder<D>(D);
+
+impl<D> tokio_util::codec::Decoder for ArcDecoder<D>
+where
+    D: tokio_util::codec::Decoder,
+{
+    type Item = Arc<D::Item>;
+    type Error = D::Error;
 
-    #[instrument(level = "trace", skip(self), fields(ty = "list"))]
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if self.cap == 0 {
-            let Some(len) = Leb128DecoderU32.decode(src)? else {
-                return Ok(None);
-            };
-            if len == 0 {
-                return Ok(Some(Vec::default()));
-            }
-            let len = len
-                .try_into()
-                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
-            self.ret = Vec::with_capacity(len);
-            self.deferred = Vec::with_capacity(len);
-            self.cap = len;
-        }
-        while self.cap > 0 {
-            let Some(v) = self.dec.decode(src)? else {
-                return Ok(None);
-            };
-            self.ret.push(v);
-            self.deferred.push(self.dec.take_deferred());
-            self.cap -= 1;
-        }
-        Ok(Some(mem::take(&mut self.ret)))
+        let Some(v) = self.0.decode(src)? else {
+            return Ok(None);
+        };
+        Ok(Some(Arc::new(v)))
     }
 }
 
-impl<T, R> Decode<R> for Vec<T>
+impl<D, R> Deferred<R> for ArcDecoder<D>
 where
-    T: Decode<R> + Send,
-    T::ListDecoder: Deferred<R> + Send,
-    R: crate::Index<R> + Send + 'static,
+    D: Deferred<R>,
 {
-    type Decoder = T::ListDecoder;
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        self.0.take_deferred()
+    }
+}
+
+impl<T, R> Decode<R> for Arc<T>
+where
+    T: Decode<R>,
+    R: 'static,
+{
+    type Decoder = ArcDecoder<T::Decoder>;
     type ListDecoder = ListDecoder<Self::Decoder, R>;
 }
 
-macro_rules! impl_copy_codec {
-    ($t:ty, $c:tt) => {
-        impl<W> Encode<W> for $t {
-            type Encoder = $c;
+macro_rules! impl_num_wrapper_codec {
+    ($t:ident, $c:ident) => {
+        #[doc = concat!("Codec for [`", stringify!($t), "<T>`](core::num::", stringify!($t), "), which is encoded as the wrapped value using `C`.")]
+        #[derive(Debug, Default)]
+        pub struct $c<C>(C);
 
-            #[instrument(level = "trace", skip(items))]
-            fn encode_iter_own<I>(
-                items: I,
-                enc: &mut Self::Encoder,
+        impl<T, C> tokio_util::codec::Encoder<core::num::$t<T>> for $c<C>
+        where
+            C: tokio_util::codec::Encoder<T>,
+        {
+            type Error = C::Error;
+
+            fn encode(
+                &mut self,
+                core::num::$t(item): core::num::$t<T>,
                 dst: &mut BytesMut,
-                _idx: u64,
-            ) -> Result<
-                Option<DeferredFn<W>>,
-                <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error,
-            >
-            where
-                I: IntoIterator<Item = Self>,
-                I::IntoIter: ExactSizeIterator,
-            {
-                let items = items.into_iter();
-                dst.reserve(items.len());
-                for item in items {
-                    enc.encode(item, dst)?;
-                }
-                Ok(None)
+            ) -> Result<(), Self::Error> {
+                self.0.encode(item, dst)
             }
+        }
 
-            #[instrument(level = "trace", skip(items))]
-            fn encode_iter_ref<'a, I>(
-                items: I,
-                enc: &mut Self::Encoder,
+        impl<'a, T, C> tokio_util::codec::Encoder<&'a core::num::$t<T>> for $c<C>
+        where
+            C: tokio_util::codec::Encoder<&'a T>,
+        {
+            type Error = C::Error;
+
+            fn encode(
+                &mut self,
+                core::num::$t(item): &'a core::num::$t<T>,
                 dst: &mut BytesMut,
-                _idx: u64,
-            ) -> Result<
-                Option<DeferredFn<W>>,
-                <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error,
-            >
-            where
-                I: IntoIterator<Item = &'a Self>,
-                I::IntoIter: ExactSizeIterator,
-            {
-                let items = items.into_iter();
-                dst.reserve(items.len());
-                for item in items {
-                    enc.encode(*item, dst)?;
-                }
-                Ok(None)
+            ) -> Result<(), Self::Error> {
+                self.0.encode(item, dst)
             }
         }
 
-        impl<'b, W> Encode<W> for &'b $t {
-            type Encoder = $c;
+        impl<C> tokio_util::codec::Decoder for $c<C>
+        where
+            C: tokio_util::codec::Decoder,
+        {
+            type Item = core::num::$t<C::Item>;
+            type Error = C::Error;
 
-            #[instrument(level = "trace", skip(items))]
-            fn encode_iter_own<I>(
-                items: I,
-                enc: &mut Self::Encoder,
-                dst: &mut BytesMut,
-                _idx: u64,
-            ) -> Result<
-                Option<DeferredFn<W>>,
-                <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error,
-            >
-            where
-                I: IntoIterator<Item = Self>,
-                I::IntoIter: ExactSizeIterator,
-            {
-                let items = items.into_iter();
-                dst.reserve(items.len());
-                for item in items {
-                    enc.encode(*item, dst)?;
-                }
-                Ok(None)
+            fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+                let Some(v) = self.0.decode(src)? else {
+                    return Ok(None);
+                };
+                Ok(Some(core::num::$t(v)))
             }
+        }
 
-            #[instrument(level = "trace", skip(items))]
-            fn encode_iter_ref<'a, I>(
-                items: I,
-                enc: &mut Self::Encoder,
-                dst: &mut BytesMut,
-                _idx: u64,
-            ) -> Result<
-                Option<DeferredFn<W>>,
-                <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error,
-            >
-            where
-                I: IntoIterator<Item = &'a Self>,
-                I::IntoIter: ExactSizeIterator,
-                'b: 'a,
-            {
-                let items = items.into_iter();
-                dst.reserve(items.len());
-                for item in items {
-                    enc.encode(item, dst)?;
-                }
-                Ok(None)
+        impl<C, T> Deferred<T> for $c<C>
+        where
+            C: Deferred<T>,
+        {
+            fn take_deferred(&mut self) -> Option<DeferredFn<T>> {
+                self.0.take_deferred()
             }
         }
 
-        impl<R> Decode<R> for $t {
-            type Decoder = $c;
-            type ListDecoder = CoreVecDecoder<Self::Decoder>;
+        impl<T, W> Encode<W> for core::num::$t<T>
+        where
+            T: Encode<W>,
+        {
+            type Encoder = $c<T::Encoder>;
+        }
+
+        impl<'a, T, W> Encode<W> for &'a core::num::$t<T>
+        where
+            T: Encode<W>,
+            T::Encoder: tokio_util::codec::Encoder<&'a T>,
+        {
+            type Encoder = $c<T::Encoder>;
+        }
+
+        impl<T, R> Decode<R> for core::num::$t<T>
+        where
+            T: Decode<R>,
+            R: 'static,
+        {
+            type Decoder = $c<T::Decoder>;
+            type ListDecoder = ListDecoder<Self::Decoder, R>;
         }
     };
 }
 
-impl_copy_codec!(bool, BoolCodec);
-impl_copy_codec!(i8, S8Codec);
-impl_copy_codec!(i16, S16Codec);
-impl_copy_codec!(u16, U16Codec);
-impl_copy_codec!(i32, S32Codec);
-impl_copy_codec!(u32, U32Codec);
-impl_copy_codec!(i64, S64Codec);
-impl_copy_codec!(u64, U64Codec);
-impl_copy_codec!(f32, F32Codec);
-impl_copy_codec!(f64, F64Codec);
-impl_copy_codec!(char, Utf8Codec);
-
-impl<T> Encode<T> for u8 {
-    type Encoder = U8Codec;
+impl_num_wrapper_codec!(Wrapping, WrappingCodec);
+impl_num_wrapper_codec!(Saturating, SaturatingCodec);
 
-    #[instrument(level = "trace", skip(items))]
-    fn encode_iter_own<I>(
-        items: I,
-        enc: &mut Self::Encoder,
-        dst: &mut BytesMut,
-        _idx: u64,
-    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error>
-    where
-        I: IntoIterator<Item = Self>,
-        I::IntoIter: ExactSizeIterator,
-    {
-        let items = items.into_iter();
-        dst.reserve(items.len());
-        dst.extend(items);
-        Ok(None)
+impl<O, E, W> Deferred<W> for ResultEncoder<O, E>
+where
+    O: Deferred<W>,
+    E: Deferred<W>,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        match (self.ok.take_deferred(), self.err.take_deferred()) {
+            (None, None) => None,
+            (Some(ok), None) => Some(ok),
+            (None, Some(err)) => Some(err),
+            (Some(ok), Some(_)) => {
+                if cfg!(debug_assertions) {
+                    panic!("both `result::ok` and `result::err` deferred function set")
+                }
+                Some(ok)
+            }
+        }
     }
+}
 
-    #[instrument(level = "trace", skip(items))]
-    fn encode_iter_ref<'a, I>(
-        items: I,
-        enc: &mut Self::Encoder,
-        dst: &mut BytesMut,
-        _idx: u64,
-    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error>
-    where
-        I: IntoIterator<Item = &'a Self>,
-        I::IntoIter: ExactSizeIterator,
-    {
-        let items = items.into_iter();
-        dst.reserve(items.len());
-        dst.extend(items);
-        Ok(None)
-    }
+impl<O, E, W> Encode<W> for Result<O, E>
+where
+    O: Encode<W>,
+    E: Encode<W>,
+    std::io::Error: From<<O::Encoder as tokio_util::codec::Encoder<O>>::Error>,
+    std::io::Error: From<<E::Encoder as tokio_util::codec::Encoder<E>>::Error>,
+{
+    type Encoder = ResultEncoder<O::Encoder, E::Encoder>;
+}
 
-    #[instrument(level = "trace", skip(items), fields(ty = "list<u8>"))]
-    fn encode_list_own(
-        items: Vec<Self>,
-        enc: &mut Self::Encoder,
-        dst: &mut BytesMut,
-    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error>
-    {
-        CoreVecEncoderBytes.encode(items, dst)?;
-        Ok(None)
-    }
+impl<'a, O, E, W> Encode<W> for &'a Result<O, E>
+where
+    O: Encode<W>,
+    O::Encoder: tokio_util::codec::Encoder<&'a O>,
+    E: Encode<W>,
+    E::Encoder: tokio_util::codec::Encoder<&'a E>,
+    std::io::Error: From<<O::Encoder as tokio_util::codec::Encoder<&'a O>>::Error>,
+    std::io::Error: From<<E::Encoder as tokio_util::codec::Encoder<&'a E>>::Error>,
+{
+    type Encoder = ResultEncoder<O::Encoder, E::Encoder>;
+}
 
-    #[instrument(level = "trace", skip(items), fields(ty = "list<u8>"))]
-    fn encode_list_ref<'a>(
-        items: &'a [Self],
-        enc: &mut Self::Encoder,
-        dst: &mut BytesMut,
-    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error>
-    where
-        Self::Encoder: tokio_util::codec::Encoder<&'a Self>,
-    {
-        CoreVecEncoderBytes.encode(items, dst)?;
-        Ok(None)
+impl<O, E, W> Deferred<W> for ResultDecoder<O, E>
+where
+    O: Deferred<W> + Default,
+    E: Deferred<W> + Default,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        let (mut ok, mut err) = mem::take(self).into_inner();
+        match (ok.take_deferred(), err.take_deferred()) {
+            (None, None) => None,
+            (Some(ok), None) => Some(ok),
+            (None, Some(err)) => Some(err),
+            (Some(ok), Some(_)) => {
+                if cfg!(debug_assertions) {
+                    panic!("both `result::ok` and `result::err` deferred function set")
+                }
+                Some(ok)
+            }
+        }
     }
 }
 
-impl<'b, T> Encode<T> for &'b u8 {
-    type Encoder = U8Codec;
+impl<O, E, R> Decode<R> for Result<O, E>
+where
+    O: Decode<R>,
+    E: Decode<R>,
+    std::io::Error: From<<O::Decoder as tokio_util::codec::Decoder>::Error>,
+    std::io::Error: From<<E::Decoder as tokio_util::codec::Decoder>::Error>,
+    R: 'static,
+{
+    type Decoder = ResultDecoder<O::Decoder, E::Decoder>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
+}
 
-    #[instrument(level = "trace", skip(items))]
-    fn encode_iter_own<I>(
-        items: I,
-        enc: &mut Self::Encoder,
-        dst: &mut BytesMut,
-        _idx: u64,
-    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error>
-    where
-        I: IntoIterator<Item = Self>,
-        I::IntoIter: ExactSizeIterator,
-    {
-        let items = items.into_iter();
-        dst.reserve(items.len());
-        dst.extend(items);
-        Ok(None)
-    }
+/// Encoder of [`either::Either`] as a 2-case `variant { left(L), right(R) }`.
+///
+/// The encoding is identical to `result<L, R>`, with `left` encoded as `ok` and `right` as `err`.
+#[cfg(feature = "either")]
+#[derive(Debug, Default)]
+pub struct EitherEncoder<L, R>(ResultEncoder<L, R>);
 
-    #[instrument(level = "trace", skip(items))]
-    fn encode_iter_ref<'a, I>(
-        items: I,
-        enc: &mut Self::Encoder,
-        dst: &mut BytesMut,
-        _idx: u64,
-    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error>
-    where
-        I: IntoIterator<Item = &'a Self>,
-        I::IntoIter: ExactSizeIterator,
-        'b: 'a,
-    {
-        let items = items.into_iter();
-        dst.reserve(items.len());
-        dst.extend(items.map(|b| **b));
-        Ok(None)
+#[cfg(feature = "either")]
+impl<L, R, W> Deferred<W> for EitherEncoder<L, R>
+where
+    L: Deferred<W>,
+    R: Deferred<W>,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.0.take_deferred()
     }
 }
 
-#[derive(Debug, Default)]
-#[repr(transparent)]
-pub struct ListDecoderU8(CoreVecDecoderBytes);
-
-impl tokio_util::codec::Decoder for ListDecoderU8 {
-    type Item = Vec<u8>;
-    type Error = <CoreVecDecoderBytes as tokio_util::codec::Decoder>::Error;
+#[cfg(feature = "either")]
+impl<CL, L, CR, R> tokio_util::codec::Encoder<either::Either<L, R>> for EitherEncoder<CL, CR>
+where
+    ResultEncoder<CL, CR>: tokio_util::codec::Encoder<Result<L, R>>,
+{
+    type Error = <ResultEncoder<CL, CR> as tokio_util::codec::Encoder<Result<L, R>>>::Error;
 
-    #[instrument(level = "trace", skip(self), fields(ty = "list<u8>"))]
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let Some(buf) = self.0.decode(src)? else {
-            return Ok(None);
-        };
-        Ok(Some(buf.into()))
+    fn encode(&mut self, v: either::Either<L, R>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode(v.either(Ok, Err), dst)
     }
 }
 
-impl<R> Decode<R> for u8 {
-    type Decoder = U8Codec;
-    type ListDecoder = ListDecoderU8;
-}
+#[cfg(feature = "either")]
+impl<'a, CL, L, CR, R> tokio_util::codec::Encoder<&'a either::Either<L, R>>
+    for EitherEncoder<CL, CR>
+where
+    ResultEncoder<CL, CR>: tokio_util::codec::Encoder<Result<&'a L, &'a R>>,
+{
+    type Error = <ResultEncoder<CL, CR> as tokio_util::codec::Encoder<Result<&'a L, &'a R>>>::Error;
 
-impl<W> Encode<W> for &str {
-    type Encoder = CoreNameEncoder;
+    fn encode(
+        &mut self,
+        v: &'a either::Either<L, R>,
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        self.0.encode(v.as_ref().either(Ok, Err), dst)
+    }
 }
 
-impl<W> Encode<W> for &&str {
-    type Encoder = CoreNameEncoder;
+#[cfg(feature = "either")]
+impl<L, R, W> Encode<W> for either::Either<L, R>
+where
+    L: Encode<W>,
+    R: Encode<W>,
+    std::io::Error: From<<L::Encoder as tokio_util::codec::Encoder<L>>::Error>,
+    std::io::Error: From<<R::Encoder as tokio_util::codec::Encoder<R>>::Error>,
+{
+    type Encoder = EitherEncoder<L::Encoder, R::Encoder>;
 }
 
-impl<W> Encode<W> for String {
-    type Encoder = CoreNameEncoder;
+#[cfg(feature = "either")]
+impl<'a, L, R, W> Encode<W> for &'a either::Either<L, R>
+where
+    L: Encode<W>,
+    L::Encoder: tokio_util::codec::Encoder<&'a L>,
+    R: Encode<W>,
+    R::Encoder: tokio_util::codec::Encoder<&'a R>,
+    std::io::Error: From<<L::Encoder as tokio_util::codec::Encoder<&'a L>>::Error>,
+    std::io::Error: From<<R::Encoder as tokio_util::codec::Encoder<&'a R>>::Error>,
+{
+    type Encoder = EitherEncoder<L::Encoder, R::Encoder>;
 }
 
-impl<W> Encode<W> for &String {
-    type Encoder = CoreNameEncoder;
-}
+/// Decoder of [`either::Either`] encoded as a 2-case `variant { left(L), right(R) }`
+#[cfg(feature = "either")]
+#[derive(Debug, Default)]
+pub struct EitherDecoder<L, R>(ResultDecoder<L, R>);
 
-impl<R> Decode<R> for String {
-    type Decoder = CoreNameDecoder;
-    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+#[cfg(feature = "either")]
+impl<L, R, W> Deferred<W> for EitherDecoder<L, R>
+where
+    L: Deferred<W> + Default,
+    R: Deferred<W> + Default,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.0.take_deferred()
+    }
 }
 
-impl<W> Encode<W> for Bytes {
-    type Encoder = CoreVecEncoderBytes;
-}
+#[cfg(feature = "either")]
+impl<L, R> tokio_util::codec::Decoder for EitherDecoder<L, R>
+where
+    L: tokio_util::codec::Decoder,
+    R: tokio_util::codec::Decoder,
+    std::io::Error: From<L::Error>,
+    std::io::Error: From<R::Error>,
+{
+    type Item = either::Either<L::Item, R::Item>;
+    type Error = std::io::Error;
 
-impl<W> Encode<W> for &Bytes {
-    type Encoder = CoreVecEncoderBytes;
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.0.decode(src)? {
+            Some(Ok(v)) => Ok(Some(either::Either::Left(v))),
+            Some(Err(v)) => Ok(Some(either::Either::Right(v))),
+            None => Ok(None),
+        }
+    }
 }
 
-impl<R> Decode<R> for Bytes {
-    type Decoder = CoreVecDecoderBytes;
-    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+#[cfg(feature = "either")]
+impl<L, R, Rd> Decode<Rd> for either::Either<L, R>
+where
+    L: Decode<Rd>,
+    R: Decode<Rd>,
+    std::io::Error: From<<L::Decoder as tokio_util::codec::Decoder>::Error>,
+    std::io::Error: From<<R::Decoder as tokio_util::codec::Decoder>::Error>,
+    Rd: 'static,
+{
+    type Decoder = EitherDecoder<L::Decoder, R::Decoder>;
+    type ListDecoder = ListDecoder<Self::Decoder, Rd>;
 }
 
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-#[repr(transparent)]
-pub struct ResourceEncoder;
+/// Encoder of [`ControlFlow`](core::ops::ControlFlow) as a 2-case
+/// `variant { continue(C), break(B) }`.
+///
+/// The encoding is identical to `result<C, B>`, with `continue` encoded as `ok` and `break` as
+/// `err`.
+#[cfg(feature = "control-flow")]
+#[derive(Debug, Default)]
+pub struct ControlFlowEncoder<B, C>(ResultEncoder<C, B>);
 
-impl<T: ?Sized> tokio_util::codec::Encoder<ResourceOwn<T>> for ResourceEncoder {
-    type Error = std::io::Error;
+#[cfg(feature = "control-flow")]
+impl<B, C, W> Deferred<W> for ControlFlowEncoder<B, C>
+where
+    B: Deferred<W>,
+    C: Deferred<W>,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.0.take_deferred()
+    }
+}
 
-    #[instrument(level = "trace", skip(self, item), ret, fields(ty = "own"))]
-    fn encode(&mut self, item: ResourceOwn<T>, dst: &mut BytesMut) -> std::io::Result<()> {
-        CoreVecEncoderBytes.encode(item.repr, dst)
+#[cfg(feature = "control-flow")]
+impl<CB, B, CC, C> tokio_util::codec::Encoder<core::ops::ControlFlow<B, C>>
+    for ControlFlowEncoder<CB, CC>
+where
+    ResultEncoder<CC, CB>: tokio_util::codec::Encoder<Result<C, B>>,
+{
+    type Error = <ResultEncoder<CC, CB> as tokio_util::codec::Encoder<Result<C, B>>>::Error;
+
+    fn encode(
+        &mut self,
+        v: core::ops::ControlFlow<B, C>,
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        match v {
+            core::ops::ControlFlow::Continue(v) => self.0.encode(Ok(v), dst),
+            core::ops::ControlFlow::Break(v) => self.0.encode(Err(v), dst),
+        }
     }
 }
 
-impl<T: ?Sized> tokio_util::codec::Encoder<&ResourceOwn<T>> for ResourceEncoder {
-    type Error = std::io::Error;
+#[cfg(feature = "control-flow")]
+impl<'a, CB, B, CC, C> tokio_util::codec::Encoder<&'a core::ops::ControlFlow<B, C>>
+    for ControlFlowEncoder<CB, CC>
+where
+    ResultEncoder<CC, CB>: tokio_util::codec::Encoder<Result<&'a C, &'a B>>,
+{
+    type Error = <ResultEncoder<CC, CB> as tokio_util::codec::Encoder<Result<&'a C, &'a B>>>::Error;
 
-    #[instrument(level = "trace", skip(self, item), ret, fields(ty = "own"))]
-    fn encode(&mut self, item: &ResourceOwn<T>, dst: &mut BytesMut) -> std::io::Result<()> {
-        CoreVecEncoderBytes.encode(&item.repr, dst)
+    fn encode(
+        &mut self,
+        v: &'a core::ops::ControlFlow<B, C>,
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        match v {
+            core::ops::ControlFlow::Continue(v) => self.0.encode(Ok(v), dst),
+            core::ops::ControlFlow::Break(v) => self.0.encode(Err(v), dst),
+        }
     }
 }
 
-impl<T: ?Sized, W> Encode<W> for ResourceOwn<T> {
-    type Encoder = ResourceEncoder;
+#[cfg(feature = "control-flow")]
+impl<B, C, W> Encode<W> for core::ops::ControlFlow<B, C>
+where
+    B: Encode<W>,
+    C: Encode<W>,
+    std::io::Error: From<<B::Encoder as tokio_util::codec::Encoder<B>>::Error>,
+    std::io::Error: From<<C::Encoder as tokio_util::codec::Encoder<C>>::Error>,
+{
+    type Encoder = ControlFlowEncoder<B::Encoder, C::Encoder>;
 }
 
-impl<T: ?Sized, W> Encode<W> for &ResourceOwn<T> {
-    type Encoder = ResourceEncoder;
+#[cfg(feature = "control-flow")]
+impl<'a, B, C, W> Encode<W> for &'a core::ops::ControlFlow<B, C>
+where
+    B: Encode<W>,
+    B::Encoder: tokio_util::codec::Encoder<&'a B>,
+    C: Encode<W>,
+    C::Encoder: tokio_util::codec::Encoder<&'a C>,
+    std::io::Error: From<<B::Encoder as tokio_util::codec::Encoder<&'a B>>::Error>,
+    std::io::Error: From<<C::Encoder as tokio_util::codec::Encoder<&'a C>>::Error>,
+{
+    type Encoder = ControlFlowEncoder<B::Encoder, C::Encoder>;
 }
 
-impl<T: ?Sized> tokio_util::codec::Encoder<ResourceBorrow<T>> for ResourceEncoder {
-    type Error = std::io::Error;
+/// Decoder of [`ControlFlow`](core::ops::ControlFlow) encoded as a 2-case
+/// `variant { continue(C), break(B) }`
+#[cfg(feature = "control-flow")]
+#[derive(Debug, Default)]
+pub struct ControlFlowDecoder<B, C>(ResultDecoder<C, B>);
 
-    #[instrument(level = "trace", skip(self, item), ret, fields(ty = "borrow"))]
-    fn encode(&mut self, item: ResourceBorrow<T>, dst: &mut BytesMut) -> std::io::Result<()> {
-        CoreVecEncoderBytes.encode(item.repr, dst)
+#[cfg(feature = "control-flow")]
+impl<B, C, W> Deferred<W> for ControlFlowDecoder<B, C>
+where
+    B: Deferred<W> + Default,
+    C: Deferred<W> + Default,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.0.take_deferred()
     }
 }
 
-impl<T: ?Sized> tokio_util::codec::Encoder<&ResourceBorrow<T>> for ResourceEncoder {
+#[cfg(feature = "control-flow")]
+impl<B, C> tokio_util::codec::Decoder for ControlFlowDecoder<B, C>
+where
+    B: tokio_util::codec::Decoder,
+    C: tokio_util::codec::Decoder,
+    std::io::Error: From<B::Error>,
+    std::io::Error: From<C::Error>,
+{
+    type Item = core::ops::ControlFlow<B::Item, C::Item>;
     type Error = std::io::Error;
 
-    #[instrument(level = "trace", skip(self, item), ret, fields(ty = "borrow"))]
-    fn encode(&mut self, item: &ResourceBorrow<T>, dst: &mut BytesMut) -> std::io::Result<()> {
-        CoreVecEncoderBytes.encode(&item.repr, dst)
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.0.decode(src)? {
+            Some(Ok(v)) => Ok(Some(core::ops::ControlFlow::Continue(v))),
+            Some(Err(v)) => Ok(Some(core::ops::ControlFlow::Break(v))),
+            None => Ok(None),
+        }
     }
 }
 
-impl<T: ?Sized, W> Encode<W> for ResourceBorrow<T> {
-    type Encoder = ResourceEncoder;
+#[cfg(feature = "control-flow")]
+impl<B, C, R> Decode<R> for core::ops::ControlFlow<B, C>
+where
+    B: Decode<R>,
+    C: Decode<R>,
+    std::io::Error: From<<B::Decoder as tokio_util::codec::Decoder>::Error>,
+    std::io::Error: From<<C::Decoder as tokio_util::codec::Decoder>::Error>,
+    R: 'static,
+{
+    type Decoder = ControlFlowDecoder<B::Decoder, C::Decoder>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
 }
 
-impl<T: ?Sized, W> Encode<W> for &ResourceBorrow<T> {
-    type Encoder = ResourceEncoder;
+pub struct ListEncoder<W> {
+    deferred: Option<DeferredFn<W>>,
 }
 
-#[derive(Debug)]
-#[repr(transparent)]
-pub struct ResourceBorrowDecoder<T: ?Sized> {
-    dec: CoreVecDecoderBytes,
-    _ty: PhantomData<T>,
+impl<W> Default for ListEncoder<W> {
+    fn default() -> Self {
+        Self { deferred: None }
+    }
 }
 
-impl<T: ?Sized> Default for ResourceBorrowDecoder<T> {
-    fn default() -> Self {
-        Self {
-            dec: CoreVecDecoderBytes::default(),
-            _ty: PhantomData,
-        }
+impl<W> Deferred<W> for ListEncoder<W> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.deferred.take()
     }
 }
 
-impl<R, T: ?Sized> Deferred<R> for ResourceBorrowDecoder<T> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-        None
+impl<T, W> tokio_util::codec::Encoder<Vec<T>> for ListEncoder<W>
+where
+    T: Encode<W>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error = <T::Encoder as tokio_util::codec::Encoder<T>>::Error;
+
+    fn encode(&mut self, items: Vec<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut enc = T::Encoder::default();
+        self.deferred = T::encode_list_own(items, &mut enc, dst)?;
+        Ok(())
     }
 }
 
-impl<R, T: ?Sized> Deferred<R> for CoreVecDecoder<ResourceBorrowDecoder<T>> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-        None
+impl<'a, T, W> tokio_util::codec::Encoder<&'a Vec<T>> for ListEncoder<W>
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'a T>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error = <T::Encoder as tokio_util::codec::Encoder<&'a T>>::Error;
+
+    fn encode(&mut self, items: &'a Vec<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut enc = T::Encoder::default();
+        self.deferred = T::encode_list_ref(items, &mut enc, dst)?;
+        Ok(())
     }
 }
 
-impl<R, T: ?Sized + Send + 'static> Decode<R> for ResourceBorrow<T> {
-    type Decoder = ResourceBorrowDecoder<T>;
-    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+impl<'a, 'b, T, W> tokio_util::codec::Encoder<&'a &'b Vec<T>> for ListEncoder<W>
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'b T>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error = <T::Encoder as tokio_util::codec::Encoder<&'b T>>::Error;
+
+    fn encode(&mut self, items: &'a &'b Vec<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut enc = T::Encoder::default();
+        self.deferred = T::encode_list_ref(items, &mut enc, dst)?;
+        Ok(())
+    }
 }
 
-impl<T: ?Sized> tokio_util::codec::Decoder for ResourceBorrowDecoder<T> {
-    type Item = ResourceBorrow<T>;
-    type Error = std::io::Error;
+impl<'a, T, W> tokio_util::codec::Encoder<&'a [T]> for ListEncoder<W>
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'a T>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error = <T::Encoder as tokio_util::codec::Encoder<&'a T>>::Error;
 
-    #[instrument(level = "trace", skip(self), fields(ty = "borrow"))]
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let repr = self.dec.decode(src)?;
-        Ok(repr.map(Self::Item::from))
+    fn encode(&mut self, items: &'a [T], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut enc = T::Encoder::default();
+        self.deferred = T::encode_list_ref(items, &mut enc, dst)?;
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-#[repr(transparent)]
-pub struct ResourceOwnDecoder<T: ?Sized> {
-    dec: CoreVecDecoderBytes,
-    _ty: PhantomData<T>,
-}
+impl<'a, 'b, T, W> tokio_util::codec::Encoder<&'a &'b [T]> for ListEncoder<W>
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'b T>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error = <T::Encoder as tokio_util::codec::Encoder<&'b T>>::Error;
 
-impl<T: ?Sized> Default for ResourceOwnDecoder<T> {
-    fn default() -> Self {
-        Self {
-            dec: CoreVecDecoderBytes::default(),
-            _ty: PhantomData,
-        }
+    fn encode(&mut self, items: &'a &'b [T], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut enc = T::Encoder::default();
+        self.deferred = T::encode_list_ref(items, &mut enc, dst)?;
+        Ok(())
     }
 }
 
-impl<R, T: ?Sized> Deferred<R> for ResourceOwnDecoder<T> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-        None
-    }
+impl<T, W> Encode<W> for Vec<T>
+where
+    T: Encode<W>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Encoder = ListEncoder<W>;
 }
 
-impl<R, T: ?Sized> Deferred<R> for CoreVecDecoder<ResourceOwnDecoder<T>> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-        None
-    }
+impl<'a, T, W> Encode<W> for &'a Vec<T>
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'a T>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Encoder = ListEncoder<W>;
 }
 
-impl<R, T: ?Sized + Send + 'static> Decode<R> for ResourceOwn<T> {
-    type Decoder = ResourceOwnDecoder<T>;
-    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+impl<'a, T, W> Encode<W> for &'a [T]
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'a T>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Encoder = ListEncoder<W>;
 }
 
-impl<T: ?Sized> tokio_util::codec::Decoder for ResourceOwnDecoder<T> {
-    type Item = ResourceOwn<T>;
-    type Error = std::io::Error;
+pub struct ListDecoder<T, R>
+where
+    T: tokio_util::codec::Decoder,
+{
+    dec: T,
+    ret: Vec<T::Item>,
+    cap: usize,
+    deferred: Vec<Option<DeferredFn<R>>>,
+}
 
-    #[instrument(level = "trace", skip(self), fields(ty = "own"))]
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let repr = self.dec.decode(src)?;
-        Ok(repr.map(Self::Item::from))
+impl<T, R> ListDecoder<T, R>
+where
+    T: tokio_util::codec::Decoder,
+{
+    pub fn new(dec: T) -> Self {
+        Self {
+            dec,
+            ret: Vec::default(),
+            cap: 0,
+            deferred: vec![],
+        }
     }
 }
 
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-#[repr(transparent)]
-pub struct UnitCodec;
-
-impl tokio_util::codec::Encoder<()> for UnitCodec {
-    type Error = std::io::Error;
-
-    #[instrument(level = "trace", skip(self), ret)]
-    fn encode(&mut self, (): (), dst: &mut BytesMut) -> std::io::Result<()> {
-        Ok(())
+impl<T, R> Default for ListDecoder<T, R>
+where
+    T: tokio_util::codec::Decoder + Default,
+{
+    fn default() -> Self {
+        Self::new(T::default())
     }
 }
 
-impl tokio_util::codec::Encoder<&()> for UnitCodec {
-    type Error = std::io::Error;
-
-    #[instrument(level = "trace", skip(self), ret)]
-    fn encode(&mut self, (): &(), dst: &mut BytesMut) -> std::io::Result<()> {
-        Ok(())
+impl<T, R> Deferred<R> for ListDecoder<T, R>
+where
+    T: tokio_util::codec::Decoder,
+    R: crate::Index<R> + Send + Sync + 'static,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        let deferred = mem::take(&mut self.deferred);
+        if deferred.iter().any(Option::is_some) {
+            Some(Box::new(|r, path| {
+                Box::pin(handle_deferred(r, deferred, path, 0))
+            }))
+        } else {
+            None
+        }
     }
 }
 
-impl tokio_util::codec::Decoder for UnitCodec {
-    type Item = ();
-    type Error = std::io::Error;
+impl<T, R> tokio_util::codec::Decoder for ListDecoder<T, R>
+where
+    T: tokio_util::codec::Decoder + Deferred<R>,
+{
+    type Item = Vec<T::Item>;
+    type Error = T::Error;
 
-    #[instrument(level = "trace", skip(self))]
+    #[instrument(level = "trace", skip(self), fields(ty = "list"))]
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        Ok(Some(()))
+        if self.cap == 0 {
+            let Some(len) = Leb128DecoderU32.decode(src)? else {
+                return Ok(None);
+            };
+            if len == 0 {
+                return Ok(Some(Vec::default()));
+            }
+            let len = len
+                .try_into()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+            self.ret = Vec::with_capacity(len);
+            self.deferred = Vec::with_capacity(len);
+            self.cap = len;
+        }
+        while self.cap > 0 {
+            let Some(v) = self.dec.decode(src)? else {
+                return Ok(None);
+            };
+            self.ret.push(v);
+            self.deferred.push(self.dec.take_deferred());
+            self.cap -= 1;
+        }
+        Ok(Some(mem::take(&mut self.ret)))
     }
 }
 
-/// Marker trait for [Encode] tuple types
-pub trait TupleEncode<W>: Encode<W> {}
-
-/// Marker trait for [Decode] tuple types
-pub trait TupleDecode<R>: Decode<R> {}
-
-impl<W> Encode<W> for () {
-    type Encoder = UnitCodec;
+impl<T, R> Decode<R> for Vec<T>
+where
+    T: Decode<R> + Send,
+    T::ListDecoder: Deferred<R> + Send,
+    R: crate::Index<R> + Send + 'static,
+{
+    type Decoder = T::ListDecoder;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
 }
 
-impl<W> TupleEncode<W> for () {}
-
-impl<R> Decode<R> for () {
-    type Decoder = UnitCodec;
-    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+/// Byte-level layout of a value, which can be used to find the end of an encoded value
+/// without decoding it.
+///
+/// Only types with layout fully determined by the value bytes themselves can be described:
+/// there is no way to express `option`, `result`, `variant` or any asynchronous values (`future`
+/// and `stream`), since their payload depends on the discriminant or is transmitted out-of-band.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Framing {
+    /// Fixed-width value, e.g. `bool`, `u8`, `s8`, `f32` or `f64`
+    Fixed(usize),
+    /// LEB128-encoded integer, e.g. `u16`, `s32` or `u64`
+    Leb128,
+    /// Length-prefixed byte payload, i.e. `string` or `list<u8>`
+    Bytes,
+    /// `list` of values with the specified layout
+    List(Box<Framing>),
+    /// `tuple` or `record` of values with the specified layouts
+    Tuple(Vec<Framing>),
+}
+
+impl Framing {
+    /// Returns the length of the value at the start of `buf`, or `None` if `buf` does not contain
+    /// the complete value yet.
+    pub fn len(&self, buf: &[u8]) -> std::io::Result<Option<usize>> {
+        FramingScan::default().len(self, buf)
+    }
 }
 
-impl<R> TupleDecode<R> for () {}
+/// Maximum length of a LEB128-encoded integer, which is the length of a 128-bit integer
+const MAX_LEB128_LEN: usize = 19;
 
-macro_rules! impl_tuple_codec {
-    ($($vn:ident),+; $($vt:ident),+; $($cn:ident),+; $($ct:ident),+) => {
-        impl<W, $($ct),+> Deferred<W> for TupleEncoder::<($($ct),+,)>
-        where
-            W: crate::Index<W> + Send + Sync + 'static,
-            $($ct: Deferred<W> + Default + 'static),+
-        {
-            fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
-                let Self(($(mut $cn),+,)) = mem::take(self);
-                let deferred = [ $($cn.take_deferred()),+ ];
-                if deferred.iter().any(Option::is_some) {
-                    Some(Box::new(|r, path| Box::pin(handle_deferred(r, deferred, path, 0))))
-                } else {
-                    None
-                }
+/// Container entered by [`FramingScan`]
+#[derive(Debug)]
+enum ScanFrame {
+    /// `list` with `remaining` elements left to scan
+    List { remaining: usize },
+    /// `tuple` with fields starting at index `next` left to scan
+    Tuple { next: usize },
+}
+
+/// Progress of finding the end of a value with a given [`Framing`].
+///
+/// Once more bytes are buffered, the scan resumes at the first value, which was not complete,
+/// rather than at the start of the outermost value, so the total work of scanning a value
+/// received in many chunks is linear in its length.
+#[derive(Debug, Default)]
+struct FramingScan {
+    /// Length of the values scanned so far
+    offset: usize,
+    /// Containers entered, outermost first
+    stack: Vec<ScanFrame>,
+}
+
+impl FramingScan {
+    /// Returns the framing of the next value to scan, or `None` if all values of the innermost
+    /// container have been scanned
+    fn next<'a>(&self, mut framing: &'a Framing) -> Option<&'a Framing> {
+        for frame in &self.stack {
+            framing = match (framing, frame) {
+                (Framing::List(_), ScanFrame::List { remaining: 0 }) => return None,
+                (Framing::List(framing), ScanFrame::List { .. }) => framing,
+                (Framing::Tuple(framings), ScanFrame::Tuple { next }) => framings.get(*next)?,
+                _ => unreachable!("scanned containers must match the framing"),
+            };
+        }
+        Some(framing)
+    }
+
+    /// Marks the next value as scanned, returns `true` if it was the outermost value
+    fn complete(&mut self) -> bool {
+        match self.stack.last_mut() {
+            None => true,
+            Some(ScanFrame::List { remaining }) => {
+                *remaining -= 1;
+                false
+            }
+            Some(ScanFrame::Tuple { next }) => {
+                *next += 1;
+                false
             }
         }
+    }
 
-        impl<W, E, $($vt),+> Encode<W> for ($($vt),+,)
-        where
-            W: crate::Index<W> + Send + Sync + 'static,
-            E: From<std::io::Error>,
-            $(
-                $vt: Encode<W>,
-                $vt::Encoder: tokio_util::codec::Encoder<$vt, Error = E> + 'static,
-            )+
-        {
-            type Encoder = TupleEncoder::<($($vt::Encoder),+,)>;
+    /// Returns the length of the value with layout `framing` at the start of `buf`, or `None` if
+    /// `buf` does not contain the complete value yet, in which case the scan can be resumed
+    /// once more bytes are appended to `buf`.
+    fn len(&mut self, framing: &Framing, buf: &[u8]) -> std::io::Result<Option<usize>> {
+        loop {
+            let rest = &buf[self.offset..];
+            let n = match self.next(framing) {
+                None => {
+                    self.stack.pop();
+                    if self.complete() {
+                        return Ok(Some(self.offset));
+                    }
+                    continue;
+                }
+                Some(Framing::Fixed(n)) => {
+                    if rest.len() < *n {
+                        return Ok(None);
+                    }
+                    *n
+                }
+                Some(Framing::Leb128) => {
+                    let Some(i) = rest.iter().take(MAX_LEB128_LEN).position(|b| b & 0x80 == 0)
+                    else {
+                        if rest.len() >= MAX_LEB128_LEN {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                "LEB128 integer too long",
+                            ));
+                        }
+                        return Ok(None);
+                    };
+                    i + 1
+                }
+                Some(Framing::Bytes) => {
+                    let Some((len, n)) = decode_len(rest)? else {
+                        return Ok(None);
+                    };
+                    let Some(end) = n.checked_add(len) else {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "payload length overflows `usize`",
+                        ));
+                    };
+                    if rest.len() < end {
+                        return Ok(None);
+                    }
+                    end
+                }
+                Some(Framing::List(_)) => {
+                    let Some((len, n)) = decode_len(rest)? else {
+                        return Ok(None);
+                    };
+                    self.offset += n;
+                    self.stack.push(ScanFrame::List { remaining: len });
+                    continue;
+                }
+                Some(Framing::Tuple(_)) => {
+                    self.stack.push(ScanFrame::Tuple { next: 0 });
+                    continue;
+                }
+            };
+            self.offset += n;
+            if self.complete() {
+                return Ok(Some(self.offset));
+            }
         }
+    }
+}
 
-        impl<W, E, $($vt),+> TupleEncode<W> for ($($vt),+,)
-        where
-            W: crate::Index<W> + Send + Sync + 'static,
-            E: From<std::io::Error>,
-            $(
-                $vt: Encode<W>,
-                $vt::Encoder: tokio_util::codec::Encoder<$vt, Error = E> + 'static,
-            )+
-        {
-        }
+fn decode_len(buf: &[u8]) -> std::io::Result<Option<(usize, usize)>> {
+    let mut prefix = BytesMut::from(&buf[..buf.len().min(5)]);
+    let n = prefix.len();
+    let Some(len) = Leb128DecoderU32.decode(&mut prefix)? else {
+        return Ok(None);
+    };
+    let len = len
+        .try_into()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    Ok(Some((len, n - prefix.len())))
+}
+
+/// `list` decoder, which keeps the buffer aligned if decoding an element fails.
+///
+/// Each element is only decoded once all of its bytes, as determined by the element [`Framing`],
+/// are buffered. If decoding an element fails, the remaining elements of the list are skipped
+/// without being decoded and the element error is returned as the decoded item, so that the
+/// caller can continue decoding values following the list.
+///
+/// Limitations:
+/// - Element decoders must consume exactly the bytes described by the [`Framing`], a mismatch
+///   is treated as an element decoding failure.
+/// - Elements are buffered in full before being decoded, which means that memory usage is
+///   bounded by the largest element, rather than the decoder's internal state.
+/// - Elements cannot contain asynchronous values and errors in the framing itself, e.g. a
+///   malformed length prefix, are not recoverable and are returned as decoding errors.
+pub struct DrainListDecoder<T>
+where
+    T: tokio_util::codec::Decoder,
+{
+    dec: T,
+    framing: Framing,
+    scan: FramingScan,
+    ret: Vec<T::Item>,
+    cap: usize,
+    err: Option<std::io::Error>,
+}
 
-        impl<'a, W, E, $($vt),+> Encode<W> for &'a ($($vt),+,)
-        where
-            W: crate::Index<W> + Send + Sync + 'static,
-            E: From<std::io::Error>,
-            $(
-                $vt: Encode<W>,
-                $vt::Encoder: tokio_util::codec::Encoder<&'a $vt, Error = E> + 'static,
-            )+
-        {
-            type Encoder = TupleEncoder::<($($vt::Encoder),+,)>;
+impl<T> DrainListDecoder<T>
+where
+    T: tokio_util::codec::Decoder,
+{
+    /// Constructs a new [`DrainListDecoder`] using `dec` to decode elements with layout
+    /// described by `framing`
+    pub fn new(dec: T, framing: Framing) -> Self {
+        Self {
+            dec,
+            framing,
+            scan: FramingScan::default(),
+            ret: Vec::default(),
+            cap: 0,
+            err: None,
         }
+    }
+}
 
-        impl<R, $($vt),+> Deferred<R> for TupleDecoder::<($($vt::Decoder),+,), ($(Option<$vt>),+,)>
-        where
-            R: crate::Index<R> + Send + Sync + 'static,
-            $($vt: Decode<R>),+
-        {
-            fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-                let ($(mut $cn),+,) = mem::take(self).into_inner();
-                let deferred = [ $($cn.take_deferred()),+ ];
-                if deferred.iter().any(Option::is_some) {
-                    Some(Box::new(|r, path| Box::pin(handle_deferred(r, deferred, path, 0))))
-                } else {
-                    None
+impl<T> tokio_util::codec::Decoder for DrainListDecoder<T>
+where
+    T: tokio_util::codec::Decoder + Default,
+    std::io::Error: From<T::Error>,
+{
+    type Item = std::io::Result<Vec<T::Item>>;
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "list"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.cap == 0 {
+            let Some(len) = Leb128DecoderU32.decode(src)? else {
+                return Ok(None);
+            };
+            if len == 0 {
+                return Ok(Some(Ok(Vec::default())));
+            }
+            let len = len
+                .try_into()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+            self.ret = Vec::with_capacity(len);
+            self.cap = len;
+        }
+        while self.cap > 0 {
+            let Some(n) = self.scan.len(&self.framing, src)? else {
+                return Ok(None);
+            };
+            self.scan = FramingScan::default();
+            let mut buf = src.split_to(n);
+            self.cap -= 1;
+            if self.err.is_some() {
+                trace!(n, "skip list element");
+                continue;
+            }
+            match self.dec.decode_eof(&mut buf) {
+                Ok(Some(v)) if buf.is_empty() => self.ret.push(v),
+                Ok(_) => {
+                    self.dec = T::default();
+                    self.err = Some(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "list element does not match its framing",
+                    ));
+                }
+                Err(err) => {
+                    let err = err.into();
+                    trace!(?err, "failed to decode list element, skip remaining");
+                    self.dec = T::default();
+                    self.err = Some(err);
                 }
             }
         }
+        if let Some(err) = self.err.take() {
+            self.ret.clear();
+            return Ok(Some(Err(err)));
+        }
+        Ok(Some(Ok(mem::take(&mut self.ret))))
+    }
+}
 
-        impl<R, E, $($vt),+> Decode<R> for ($($vt),+,)
-        where
-            R: crate::Index<R> + Send + Sync + 'static,
-            E: From<std::io::Error>,
-            $(
-                $vt: Decode<R> + Send + 'static,
-                $vt::Decoder: tokio_util::codec::Decoder<Error = E> + Send + 'static,
-            )+
-        {
-            type Decoder = TupleDecoder::<($($vt::Decoder),+,), ($(Option<$vt>),+,)>;
-            type ListDecoder = ListDecoder<Self::Decoder, R>;
+#[cfg(feature = "smallvec")]
+impl<A, W> tokio_util::codec::Encoder<smallvec::SmallVec<A>> for ListEncoder<W>
+where
+    A: smallvec::Array,
+    A::Item: Encode<W>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error = <<A::Item as Encode<W>>::Encoder as tokio_util::codec::Encoder<A::Item>>::Error;
+
+    fn encode(
+        &mut self,
+        items: smallvec::SmallVec<A>,
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        let n = u32::try_from(items.len())
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        dst.reserve(5 + items.len());
+        Leb128Encoder.encode(n, dst)?;
+        let mut enc = <A::Item as Encode<W>>::Encoder::default();
+        self.deferred = A::Item::encode_iter_own(items, &mut enc, dst, 0)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<'a, A, W> tokio_util::codec::Encoder<&'a smallvec::SmallVec<A>> for ListEncoder<W>
+where
+    A: smallvec::Array,
+    A::Item: Encode<W>,
+    <A::Item as Encode<W>>::Encoder: tokio_util::codec::Encoder<&'a A::Item>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error =
+        <<A::Item as Encode<W>>::Encoder as tokio_util::codec::Encoder<&'a A::Item>>::Error;
+
+    fn encode(
+        &mut self,
+        items: &'a smallvec::SmallVec<A>,
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        self.encode(items.as_slice(), dst)
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<A, W> Encode<W> for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: Encode<W>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Encoder = ListEncoder<W>;
+}
+
+#[cfg(feature = "smallvec")]
+impl<'a, A, W> Encode<W> for &'a smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: Encode<W>,
+    <A::Item as Encode<W>>::Encoder: tokio_util::codec::Encoder<&'a A::Item>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Encoder = ListEncoder<W>;
+}
+
+/// Decoder of [`smallvec::SmallVec`], which is wire-compatible with [`Vec`]
+///
+/// Elements are decoded directly into the [`smallvec::SmallVec`], so lists, which fit in the
+/// inline buffer of `A` are stored inline without allocating, longer lists are spilled to
+/// the heap.
+#[cfg(feature = "smallvec")]
+pub struct SmallVecDecoder<A: smallvec::Array, T, R> {
+    dec: T,
+    ret: smallvec::SmallVec<A>,
+    cap: usize,
+    // only allocated once an element has deferred components
+    deferred: Vec<Option<DeferredFn<R>>>,
+}
+
+#[cfg(feature = "smallvec")]
+impl<A: smallvec::Array, T: Default, R> Default for SmallVecDecoder<A, T, R> {
+    fn default() -> Self {
+        Self {
+            dec: T::default(),
+            ret: smallvec::SmallVec::new(),
+            cap: 0,
+            deferred: Vec::default(),
         }
+    }
+}
 
-        impl<R, E, $($vt),+> TupleDecode<R> for ($($vt),+,)
-        where
-            R: crate::Index<R> + Send + Sync + 'static,
-            E: From<std::io::Error>,
-            $(
-                $vt: Decode<R> + Send + 'static,
-                $vt::Decoder: tokio_util::codec::Decoder<Error = E> + Send + 'static,
-            )+
-        {
+#[cfg(feature = "smallvec")]
+impl<A, T, R> Deferred<R> for SmallVecDecoder<A, T, R>
+where
+    A: smallvec::Array,
+    R: crate::Index<R> + Send + Sync + 'static,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        let deferred = mem::take(&mut self.deferred);
+        if deferred.iter().any(Option::is_some) {
+            Some(Box::new(|r, path| {
+                Box::pin(handle_deferred(r, deferred, path, 0))
+            }))
+        } else {
+            None
         }
-    };
+    }
 }
 
-impl_tuple_codec!(
-    v0;
-    V0;
-    c0;
-    C0
-);
+#[cfg(feature = "smallvec")]
+impl<A, T, R> tokio_util::codec::Decoder for SmallVecDecoder<A, T, R>
+where
+    A: smallvec::Array,
+    T: tokio_util::codec::Decoder<Item = A::Item> + Deferred<R>,
+{
+    type Item = smallvec::SmallVec<A>;
+    type Error = T::Error;
 
-impl_tuple_codec!(
-    v0, v1;
-    V0, V1;
-    c0, c1;
-    C0, C1
-);
+    #[instrument(level = "trace", skip(self), fields(ty = "list"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.cap == 0 {
+            let Some(len) = Leb128DecoderU32.decode(src)? else {
+                return Ok(None);
+            };
+            if len == 0 {
+                return Ok(Some(smallvec::SmallVec::new()));
+            }
+            let len = len
+                .try_into()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+            self.ret = smallvec::SmallVec::with_capacity(len);
+            self.cap = len;
+        }
+        while self.cap > 0 {
+            let Some(v) = self.dec.decode(src)? else {
+                return Ok(None);
+            };
+            if let Some(f) = self.dec.take_deferred() {
+                if self.deferred.is_empty() {
+                    self.deferred
+                        .resize_with(self.ret.len() + self.cap, || None);
+                }
+                self.deferred[self.ret.len()] = Some(f);
+            }
+            self.ret.push(v);
+            self.cap -= 1;
+        }
+        Ok(Some(mem::take(&mut self.ret)))
+    }
+}
 
-impl_tuple_codec!(
-    v0, v1, v2;
-    V0, V1, V2;
-    c0, c1, c2;
-    C0, C1, C2
-);
+#[cfg(feature = "smallvec")]
+impl<A, R> Decode<R> for smallvec::SmallVec<A>
+where
+    A: smallvec::Array + 'static,
+    A::Item: Decode<R> + Send,
+    R: crate::Index<R> + Send + Sync + 'static,
+{
+    type Decoder = SmallVecDecoder<A, <A::Item as Decode<R>>::Decoder, R>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3;
-    V0, V1, V2, V3;
-    c0, c1, c2, c3;
-    C0, C1, C2, C3
-);
+/// Encoder for fixed-size arrays, which are encoded as homogeneous tuples
+///
+/// This allows math types, like `vec3` (`[f32; 3]`) or `mat4` (`[f32; 16]`), to interoperate
+/// with interfaces defining them as tuples of floats.
+#[cfg(feature = "array")]
+pub struct ArrayEncoder<W> {
+    deferred: Option<DeferredFn<W>>,
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4;
-    V0, V1, V2, V3, V4;
-    c0, c1, c2, c3, c4;
-    C0, C1, C2, C3, C4
-);
+#[cfg(feature = "array")]
+impl<W> Default for ArrayEncoder<W> {
+    fn default() -> Self {
+        Self { deferred: None }
+    }
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5;
-    V0, V1, V2, V3, V4, V5;
-    c0, c1, c2, c3, c4, c5;
-    C0, C1, C2, C3, C4, C5
-);
+#[cfg(feature = "array")]
+impl<W> Deferred<W> for ArrayEncoder<W> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.deferred.take()
+    }
+}
+
+#[cfg(feature = "array")]
+impl<T, W, const N: usize> tokio_util::codec::Encoder<[T; N]> for ArrayEncoder<W>
+where
+    T: Encode<W>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error = <T::Encoder as tokio_util::codec::Encoder<T>>::Error;
+
+    #[instrument(level = "trace", skip(self, items), fields(ty = "array", n = N))]
+    fn encode(&mut self, items: [T; N], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut enc = T::Encoder::default();
+        self.deferred = T::encode_iter_own(items, &mut enc, dst, 0)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "array")]
+impl<'a, T, W, const N: usize> tokio_util::codec::Encoder<&'a [T; N]> for ArrayEncoder<W>
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'a T>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Error = <T::Encoder as tokio_util::codec::Encoder<&'a T>>::Error;
+
+    #[instrument(level = "trace", skip(self, items), fields(ty = "array", n = N))]
+    fn encode(&mut self, items: &'a [T; N], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut enc = T::Encoder::default();
+        self.deferred = T::encode_iter_ref(items, &mut enc, dst, 0)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "array")]
+impl<T, W, const N: usize> Encode<W> for [T; N]
+where
+    T: Encode<W>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Encoder = ArrayEncoder<W>;
+}
+
+#[cfg(feature = "array")]
+impl<'a, T, W, const N: usize> Encode<W> for &'a [T; N]
+where
+    T: Encode<W>,
+    T::Encoder: tokio_util::codec::Encoder<&'a T>,
+    W: crate::Index<W> + Send + Sync + 'static,
+{
+    type Encoder = ArrayEncoder<W>;
+}
+
+/// Decoder for fixed-size arrays, which are encoded as homogeneous tuples
+#[cfg(feature = "array")]
+pub struct ArrayDecoder<T, R, const N: usize>
+where
+    T: tokio_util::codec::Decoder,
+{
+    dec: T,
+    ret: Vec<T::Item>,
+    deferred: Vec<Option<DeferredFn<R>>>,
+}
+
+#[cfg(feature = "array")]
+impl<T, R, const N: usize> Default for ArrayDecoder<T, R, N>
+where
+    T: tokio_util::codec::Decoder + Default,
+{
+    fn default() -> Self {
+        Self {
+            dec: T::default(),
+            ret: Vec::with_capacity(N),
+            deferred: Vec::with_capacity(N),
+        }
+    }
+}
+
+#[cfg(feature = "array")]
+impl<T, R, const N: usize> Deferred<R> for ArrayDecoder<T, R, N>
+where
+    T: tokio_util::codec::Decoder,
+    R: crate::Index<R> + Send + Sync + 'static,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        let deferred = mem::take(&mut self.deferred);
+        if deferred.iter().any(Option::is_some) {
+            Some(Box::new(|r, path| {
+                Box::pin(handle_deferred(r, deferred, path, 0))
+            }))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "array")]
+impl<T, R, const N: usize> tokio_util::codec::Decoder for ArrayDecoder<T, R, N>
+where
+    T: tokio_util::codec::Decoder + Deferred<R>,
+{
+    type Item = [T::Item; N];
+    type Error = T::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "array", n = N))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        while self.ret.len() < N {
+            let Some(v) = self.dec.decode(src)? else {
+                return Ok(None);
+            };
+            self.ret.push(v);
+            self.deferred.push(self.dec.take_deferred());
+        }
+        let Ok(ret) = mem::replace(&mut self.ret, Vec::with_capacity(N)).try_into() else {
+            unreachable!("array decoder must contain exactly `{N}` elements")
+        };
+        Ok(Some(ret))
+    }
+}
+
+#[cfg(feature = "array")]
+impl<T, R, const N: usize> Decode<R> for [T; N]
+where
+    T: Decode<R> + Send,
+    R: crate::Index<R> + Send + Sync + 'static,
+{
+    type Decoder = ArrayDecoder<T::Decoder, R, N>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
+}
+
+/// Encoder for maps, which are encoded as `list<tuple<K, V>>`
+///
+/// By default, entries are encoded in iteration order of the map, which is not deterministic
+/// for [`HashMap`]. Use [`MapEncoder::new`] with `canonical` set to `true` to sort entries by
+/// encoded key bytes, producing reproducible output. Entries are always sorted when encoding
+/// using [`encode_value_canonical`].
+pub struct MapEncoder<W> {
+    deferred: Option<DeferredFn<W>>,
+    canonical: bool,
+}
+
+impl<W> MapEncoder<W> {
+    /// Constructs a new [`MapEncoder`], which sorts entries by encoded key bytes
+    /// if `canonical` is `true`
+    #[must_use]
+    pub fn new(canonical: bool) -> Self {
+        Self {
+            deferred: None,
+            canonical,
+        }
+    }
+}
+
+impl<W> Default for MapEncoder<W> {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl<W> Deferred<W> for MapEncoder<W> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.deferred.take()
+    }
+}
+
+impl<K, V, S, W> tokio_util::codec::Encoder<HashMap<K, V, S>> for MapEncoder<W>
+where
+    K: Encode<W>,
+    V: Encode<W>,
+    W: crate::Index<W> + Send + Sync + 'static,
+    std::io::Error: From<<K::Encoder as tokio_util::codec::Encoder<K>>::Error>,
+    std::io::Error: From<<V::Encoder as tokio_util::codec::Encoder<V>>::Error>,
+{
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, items), fields(ty = "map"))]
+    fn encode(&mut self, items: HashMap<K, V, S>, dst: &mut BytesMut) -> std::io::Result<()> {
+        let n = u32::try_from(items.len())
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        dst.reserve(5 + items.len());
+        Leb128Encoder.encode(n, dst)?;
+        let mut k_enc = K::Encoder::default();
+        let mut v_enc = V::Encoder::default();
+        let mut entries = Vec::with_capacity(items.len());
+        for (k, v) in items {
+            let mut buf = BytesMut::new();
+            let k_deferred = k.encode(&mut k_enc, &mut buf)?;
+            let k_len = buf.len();
+            let v_deferred = v.encode(&mut v_enc, &mut buf)?;
+            entries.push((buf, k_len, [k_deferred, v_deferred]));
+        }
+        if self.canonical || is_canonical() {
+            entries.sort_by(|(a, a_len, _), (b, b_len, _)| a[..*a_len].cmp(&b[..*b_len]));
+        }
+        let mut deferred = Vec::with_capacity(entries.len());
+        for (buf, _, entry) in entries {
+            dst.extend_from_slice(&buf);
+            if entry.iter().any(Option::is_some) {
+                let f: DeferredFn<W> =
+                    Box::new(|w, path| Box::pin(handle_deferred(w, entry, path, 0)));
+                deferred.push(Some(f));
+            } else {
+                deferred.push(None);
+            }
+        }
+        if deferred.iter().any(Option::is_some) {
+            self.deferred = Some(Box::new(|w, path| {
+                Box::pin(handle_deferred(w, deferred, path, 0))
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl<K, V, S, W> Encode<W> for HashMap<K, V, S>
+where
+    K: Encode<W>,
+    V: Encode<W>,
+    W: crate::Index<W> + Send + Sync + 'static,
+    std::io::Error: From<<K::Encoder as tokio_util::codec::Encoder<K>>::Error>,
+    std::io::Error: From<<V::Encoder as tokio_util::codec::Encoder<V>>::Error>,
+{
+    type Encoder = MapEncoder<W>;
+}
+
+/// Decoder for maps, which are encoded as `list<tuple<K, V>>`
+///
+/// Maps containing duplicate keys are rejected with [`std::io::ErrorKind::InvalidData`]
+pub struct MapDecoder<T, S> {
+    dec: T,
+    _hasher: PhantomData<fn() -> S>,
+}
+
+impl<T: Default, S> Default for MapDecoder<T, S> {
+    fn default() -> Self {
+        Self {
+            dec: T::default(),
+            _hasher: PhantomData,
+        }
+    }
+}
+
+impl<T, S, R> Deferred<R> for MapDecoder<T, S>
+where
+    T: Deferred<R>,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        self.dec.take_deferred()
+    }
+}
+
+impl<K, V, T, S> tokio_util::codec::Decoder for MapDecoder<T, S>
+where
+    T: tokio_util::codec::Decoder<Item = Vec<(K, V)>>,
+    T::Error: From<std::io::Error>,
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    type Item = HashMap<K, V, S>;
+    type Error = T::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "map"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(items) = self.dec.decode(src)? else {
+            return Ok(None);
+        };
+        let mut map = HashMap::with_capacity_and_hasher(items.len(), S::default());
+        for (k, v) in items {
+            if map.insert(k, v).is_some() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "map contains duplicate keys",
+                )
+                .into());
+            }
+        }
+        Ok(Some(map))
+    }
+}
+
+impl<K, V, S, R> Decode<R> for HashMap<K, V, S>
+where
+    (K, V): Decode<R> + Send,
+    <(K, V) as Decode<R>>::ListDecoder: Deferred<R> + Send,
+    K: Eq + Hash,
+    S: BuildHasher + Default + 'static,
+    R: crate::Index<R> + Send + 'static,
+{
+    type Decoder = MapDecoder<<(K, V) as Decode<R>>::ListDecoder, S>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
+}
+
+macro_rules! impl_copy_codec {
+    ($t:ty, $c:tt) => {
+        impl<W> Encode<W> for $t {
+            type Encoder = $c;
+
+            #[instrument(level = "trace", skip(items))]
+            fn encode_iter_own<I>(
+                items: I,
+                enc: &mut Self::Encoder,
+                dst: &mut BytesMut,
+                _idx: u64,
+            ) -> Result<
+                Option<DeferredFn<W>>,
+                <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error,
+            >
+            where
+                I: IntoIterator<Item = Self>,
+                I::IntoIter: ExactSizeIterator,
+            {
+                let items = items.into_iter();
+                dst.reserve(items.len());
+                for item in items {
+                    enc.encode(item, dst)?;
+                }
+                Ok(None)
+            }
+
+            #[instrument(level = "trace", skip(items))]
+            fn encode_iter_ref<'a, I>(
+                items: I,
+                enc: &mut Self::Encoder,
+                dst: &mut BytesMut,
+                _idx: u64,
+            ) -> Result<
+                Option<DeferredFn<W>>,
+                <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error,
+            >
+            where
+                I: IntoIterator<Item = &'a Self>,
+                I::IntoIter: ExactSizeIterator,
+            {
+                let items = items.into_iter();
+                dst.reserve(items.len());
+                for item in items {
+                    enc.encode(*item, dst)?;
+                }
+                Ok(None)
+            }
+        }
+
+        impl<'b, W> Encode<W> for &'b $t {
+            type Encoder = $c;
+
+            #[instrument(level = "trace", skip(items))]
+            fn encode_iter_own<I>(
+                items: I,
+                enc: &mut Self::Encoder,
+                dst: &mut BytesMut,
+                _idx: u64,
+            ) -> Result<
+                Option<DeferredFn<W>>,
+                <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error,
+            >
+            where
+                I: IntoIterator<Item = Self>,
+                I::IntoIter: ExactSizeIterator,
+            {
+                let items = items.into_iter();
+                dst.reserve(items.len());
+                for item in items {
+                    enc.encode(*item, dst)?;
+                }
+                Ok(None)
+            }
+
+            #[instrument(level = "trace", skip(items))]
+            fn encode_iter_ref<'a, I>(
+                items: I,
+                enc: &mut Self::Encoder,
+                dst: &mut BytesMut,
+                _idx: u64,
+            ) -> Result<
+                Option<DeferredFn<W>>,
+                <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error,
+            >
+            where
+                I: IntoIterator<Item = &'a Self>,
+                I::IntoIter: ExactSizeIterator,
+                'b: 'a,
+            {
+                let items = items.into_iter();
+                dst.reserve(items.len());
+                for item in items {
+                    enc.encode(item, dst)?;
+                }
+                Ok(None)
+            }
+        }
+
+        impl<R> Decode<R> for $t {
+            type Decoder = $c;
+            type ListDecoder = CoreVecDecoder<Self::Decoder>;
+        }
+    };
+}
+
+impl_copy_codec!(bool, BoolCodec);
+impl_copy_codec!(i8, S8Codec);
+impl_copy_codec!(u16, U16Codec);
+impl_copy_codec!(f32, F32Codec);
+impl_copy_codec!(f64, F64Codec);
+impl_copy_codec!(char, Utf8Codec);
+
+macro_rules! impl_unsigned_codec {
+    ($t:ty, $c:ident, $wc:ident) => {
+        #[doc = concat!("Codec for `", stringify!($t), "`, which writes values below `0x80` as a single byte without going through the generic LEB128 encoder")]
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+        #[repr(transparent)]
+        pub struct $c;
+
+        impl tokio_util::codec::Encoder<$t> for $c {
+            type Error = std::io::Error;
+
+            #[cfg_attr(feature = "trace", instrument(level = "trace", skip(self), ret, fields(ty = stringify!($t))))]
+            fn encode(&mut self, item: $t, dst: &mut BytesMut) -> std::io::Result<()> {
+                if item < 0x80 {
+                    dst.put_u8(item as u8);
+                    Ok(())
+                } else {
+                    $wc.encode(item, dst)
+                }
+            }
+        }
+
+        impl tokio_util::codec::Encoder<&$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(*item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Encoder<&&$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &&$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(**item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Decoder for $c {
+            type Item = $t;
+            type Error = std::io::Error;
+
+            fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+                $wc.decode(src)
+            }
+        }
+
+        impl_deferred_sync!($c);
+        impl_deferred_sync!(CoreVecDecoder<$c>);
+        impl_copy_codec!($t, $c);
+    };
+}
+
+impl_unsigned_codec!(u32, VarU32Codec, U32Codec);
+impl_unsigned_codec!(u64, VarU64Codec, U64Codec);
+
+macro_rules! impl_signed_codec {
+    ($t:ty, $c:ident, $wc:ident) => {
+        #[doc = concat!("Codec for `", stringify!($t), "`, which accepts all valid signed LEB128 encodings, including ones of maximum length, like the encoding of `", stringify!($t), "::MIN`")]
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+        #[repr(transparent)]
+        pub struct $c;
+
+        impl tokio_util::codec::Encoder<$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: $t, dst: &mut BytesMut) -> std::io::Result<()> {
+                $wc.encode(item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Encoder<&$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(*item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Encoder<&&$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &&$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(**item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Decoder for $c {
+            type Item = $t;
+            type Error = std::io::Error;
+
+            #[cfg_attr(feature = "trace", instrument(level = "trace", skip(self), fields(ty = stringify!($t))))]
+            fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+                let mut x: $t = 0;
+                let mut s = 0;
+                for (i, b) in src.iter().copied().enumerate() {
+                    let v = <$t>::from(b & 0x7f);
+                    // number of value bits remaining
+                    let n = <$t>::BITS - s;
+                    if n <= 7 {
+                        // last byte must not be continued and its unused bits must all be
+                        // equal to the sign bit
+                        let ext = (b & 0x7f) >> (n - 1);
+                        if b & 0x80 != 0 || (ext != 0 && ext != 0x7f >> (n - 1)) {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                concat!("varint overflows `", stringify!($t), "`"),
+                            ));
+                        }
+                        src.advance(i + 1);
+                        return Ok(Some(x | v << s));
+                    }
+                    x |= v << s;
+                    s += 7;
+                    if b & 0x80 == 0 {
+                        src.advance(i + 1);
+                        if b & 0x40 != 0 {
+                            // sign-extend
+                            x |= !0 << s;
+                        }
+                        return Ok(Some(x));
+                    }
+                }
+                src.reserve(1);
+                Ok(None)
+            }
+        }
+
+        impl_deferred_sync!($c);
+        impl_deferred_sync!(CoreVecDecoder<$c>);
+        impl_copy_codec!($t, $c);
+    };
+}
+
+impl_signed_codec!(i16, VarS16Codec, S16Codec);
+impl_signed_codec!(i32, VarS32Codec, S32Codec);
+impl_signed_codec!(i64, VarS64Codec, S64Codec);
+
+/// Instant of a monotonic clock in nanoseconds, as in `wasi:clocks/monotonic-clock.instant`
+///
+/// This is encoded as a `u64`, but is a distinct type, which prevents monotonic instants from
+/// being mixed up with wall-clock time or other `u64` values:
+///
+/// ```compile_fail
+/// # use wrpc_transport::MonotonicInstant;
+/// fn wall_clock_seconds(seconds: u64) {}
+///
+/// wall_clock_seconds(MonotonicInstant(42));
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct MonotonicInstant(pub u64);
+
+impl From<u64> for MonotonicInstant {
+    fn from(nanoseconds: u64) -> Self {
+        Self(nanoseconds)
+    }
+}
+
+impl From<MonotonicInstant> for u64 {
+    fn from(MonotonicInstant(nanoseconds): MonotonicInstant) -> Self {
+        nanoseconds
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct MonotonicInstantCodec;
+
+impl tokio_util::codec::Encoder<MonotonicInstant> for MonotonicInstantCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: MonotonicInstant, dst: &mut BytesMut) -> std::io::Result<()> {
+        VarU64Codec.encode(item.0, dst)
+    }
+}
+
+impl tokio_util::codec::Encoder<&MonotonicInstant> for MonotonicInstantCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: &MonotonicInstant, dst: &mut BytesMut) -> std::io::Result<()> {
+        self.encode(*item, dst)
+    }
+}
+
+impl tokio_util::codec::Encoder<&&MonotonicInstant> for MonotonicInstantCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: &&MonotonicInstant, dst: &mut BytesMut) -> std::io::Result<()> {
+        self.encode(**item, dst)
+    }
+}
+
+impl tokio_util::codec::Decoder for MonotonicInstantCodec {
+    type Item = MonotonicInstant;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(nanoseconds) = VarU64Codec.decode(src)? else {
+            return Ok(None);
+        };
+        Ok(Some(MonotonicInstant(nanoseconds)))
+    }
+}
+
+impl_deferred_sync!(MonotonicInstantCodec);
+impl_deferred_sync!(CoreVecDecoder<MonotonicInstantCodec>);
+impl_copy_codec!(MonotonicInstant, MonotonicInstantCodec);
+
+/// Implements encoding of references to a [Copy] type `$t` by `$c` in terms of encoding of `$t`
+macro_rules! impl_copy_ref_encoder {
+    ($t:ty, $c:ty) => {
+        impl tokio_util::codec::Encoder<&$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(*item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Encoder<&&$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &&$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(**item, dst)
+            }
+        }
+    };
+}
+
+/// Decodes an IPv4 address from the start of `buf`, returning it along with the number of bytes
+/// consumed
+fn decode_ipv4(buf: &[u8]) -> Option<(Ipv4Addr, usize)> {
+    let octets: [u8; 4] = buf.get(..4)?.try_into().ok()?;
+    Some((Ipv4Addr::from(octets), 4))
+}
+
+/// Decodes an IPv6 address from the start of `buf`, returning it along with the number of bytes
+/// consumed
+fn decode_ipv6(buf: &[u8]) -> std::io::Result<Option<(Ipv6Addr, usize)>> {
+    // each segment is at most 3 bytes long
+    let mut prefix = BytesMut::from(&buf[..buf.len().min(24)]);
+    let n = prefix.len();
+    let mut segments = [0; 8];
+    for segment in &mut segments {
+        let Some(v) = Leb128DecoderU16.decode(&mut prefix)? else {
+            return Ok(None);
+        };
+        *segment = v;
+    }
+    Ok(Some((Ipv6Addr::from(segments), n - prefix.len())))
+}
+
+/// Codec for [`Ipv4Addr`], which is encoded as `wasi:sockets/network.ipv4-address`, i.e.
+/// `tuple<u8, u8, u8, u8>` of octets in network order
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct Ipv4AddrCodec;
+
+impl tokio_util::codec::Encoder<Ipv4Addr> for Ipv4AddrCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: Ipv4Addr, dst: &mut BytesMut) -> std::io::Result<()> {
+        dst.extend_from_slice(&item.octets());
+        Ok(())
+    }
+}
+
+impl_copy_ref_encoder!(Ipv4Addr, Ipv4AddrCodec);
+
+impl tokio_util::codec::Decoder for Ipv4AddrCodec {
+    type Item = Ipv4Addr;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some((addr, n)) = decode_ipv4(src) else {
+            src.reserve(4);
+            return Ok(None);
+        };
+        src.advance(n);
+        Ok(Some(addr))
+    }
+}
+
+impl_deferred_sync!(Ipv4AddrCodec);
+impl_deferred_sync!(CoreVecDecoder<Ipv4AddrCodec>);
+impl_copy_codec!(Ipv4Addr, Ipv4AddrCodec);
+
+/// Codec for [`Ipv6Addr`], which is encoded as `wasi:sockets/network.ipv6-address`, i.e.
+/// a tuple of eight `u16` segments in network order
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct Ipv6AddrCodec;
+
+impl tokio_util::codec::Encoder<Ipv6Addr> for Ipv6AddrCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: Ipv6Addr, dst: &mut BytesMut) -> std::io::Result<()> {
+        dst.reserve(8);
+        for segment in item.segments() {
+            Leb128Encoder.encode(segment, dst)?;
+        }
+        Ok(())
+    }
+}
+
+impl_copy_ref_encoder!(Ipv6Addr, Ipv6AddrCodec);
+
+impl tokio_util::codec::Decoder for Ipv6AddrCodec {
+    type Item = Ipv6Addr;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some((addr, n)) = decode_ipv6(src)? else {
+            src.reserve(1);
+            return Ok(None);
+        };
+        src.advance(n);
+        Ok(Some(addr))
+    }
+}
+
+impl_deferred_sync!(Ipv6AddrCodec);
+impl_deferred_sync!(CoreVecDecoder<Ipv6AddrCodec>);
+impl_copy_codec!(Ipv6Addr, Ipv6AddrCodec);
+
+/// Codec for [`IpAddr`], which is encoded as `wasi:sockets/network.ip-address`, i.e.
+/// `variant { ipv4(ipv4-address), ipv6(ipv6-address) }`, see [`Ipv4AddrCodec`] and
+/// [`Ipv6AddrCodec`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct IpAddrCodec;
+
+impl tokio_util::codec::Encoder<IpAddr> for IpAddrCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: IpAddr, dst: &mut BytesMut) -> std::io::Result<()> {
+        match item {
+            IpAddr::V4(addr) => {
+                dst.reserve(5);
+                dst.put_u8(0);
+                Ipv4AddrCodec.encode(addr, dst)
+            }
+            IpAddr::V6(addr) => {
+                dst.reserve(9);
+                dst.put_u8(1);
+                Ipv6AddrCodec.encode(addr, dst)
+            }
+        }
+    }
+}
+
+impl_copy_ref_encoder!(IpAddr, IpAddrCodec);
+
+impl tokio_util::codec::Decoder for IpAddrCodec {
+    type Item = IpAddr;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some((disc, payload)) = src.split_first() else {
+            src.reserve(1);
+            return Ok(None);
+        };
+        let decoded = match disc {
+            0 => decode_ipv4(payload).map(|(addr, n)| (IpAddr::V4(addr), n)),
+            1 => decode_ipv6(payload)?.map(|(addr, n)| (IpAddr::V6(addr), n)),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("unknown `ip-address` discriminant `{disc}`"),
+                ))
+            }
+        };
+        let Some((addr, n)) = decoded else {
+            src.reserve(1);
+            return Ok(None);
+        };
+        src.advance(1 + n);
+        Ok(Some(addr))
+    }
+}
+
+impl_deferred_sync!(IpAddrCodec);
+impl_deferred_sync!(CoreVecDecoder<IpAddrCodec>);
+impl_copy_codec!(IpAddr, IpAddrCodec);
+
+/// Converts a `u128` duration in `unit` to a `u64` for encoding, failing on overflow
+fn duration_u64(d: Duration, v: u128, unit: &str) -> std::io::Result<u64> {
+    u64::try_from(v).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("duration `{d:?}` exceeds `u64` {unit}"),
+        )
+    })
+}
+
+/// Codec for [`Duration`], which is encoded as `wasi:clocks/monotonic-clock.duration`, i.e.
+/// a `u64` of nanoseconds.
+///
+/// Durations exceeding `u64::MAX` nanoseconds (roughly 584 years) fail to encode.
+/// See [`DurationMillis`] and [`DurationRecord`] for alternative encodings.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct DurationCodec;
+
+impl tokio_util::codec::Encoder<Duration> for DurationCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: Duration, dst: &mut BytesMut) -> std::io::Result<()> {
+        let nanoseconds = duration_u64(item, item.as_nanos(), "nanoseconds")?;
+        VarU64Codec.encode(nanoseconds, dst)
+    }
+}
+
+impl_copy_ref_encoder!(Duration, DurationCodec);
+
+impl tokio_util::codec::Decoder for DurationCodec {
+    type Item = Duration;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(nanoseconds) = VarU64Codec.decode(src)? else {
+            return Ok(None);
+        };
+        Ok(Some(Duration::from_nanos(nanoseconds)))
+    }
+}
+
+impl_deferred_sync!(DurationCodec);
+impl_deferred_sync!(CoreVecDecoder<DurationCodec>);
+impl_copy_codec!(Duration, DurationCodec);
+
+/// [`Duration`], which is encoded as a `u64` of milliseconds, for interoperability with peers
+/// representing durations this way.
+///
+/// Sub-millisecond precision is truncated on encoding, durations exceeding `u64::MAX`
+/// milliseconds fail to encode.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct DurationMillis(pub Duration);
+
+impl From<Duration> for DurationMillis {
+    fn from(d: Duration) -> Self {
+        Self(d)
+    }
+}
+
+impl From<DurationMillis> for Duration {
+    fn from(DurationMillis(d): DurationMillis) -> Self {
+        d
+    }
+}
+
+/// Codec for [`DurationMillis`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct DurationMillisCodec;
+
+impl tokio_util::codec::Encoder<DurationMillis> for DurationMillisCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: DurationMillis, dst: &mut BytesMut) -> std::io::Result<()> {
+        let milliseconds = duration_u64(item.0, item.0.as_millis(), "milliseconds")?;
+        VarU64Codec.encode(milliseconds, dst)
+    }
+}
+
+impl_copy_ref_encoder!(DurationMillis, DurationMillisCodec);
+
+impl tokio_util::codec::Decoder for DurationMillisCodec {
+    type Item = DurationMillis;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(milliseconds) = VarU64Codec.decode(src)? else {
+            return Ok(None);
+        };
+        Ok(Some(DurationMillis(Duration::from_millis(milliseconds))))
+    }
+}
+
+impl_deferred_sync!(DurationMillisCodec);
+impl_deferred_sync!(CoreVecDecoder<DurationMillisCodec>);
+impl_copy_codec!(DurationMillis, DurationMillisCodec);
+
+/// [`Duration`], which is encoded as `record { seconds: u64, nanoseconds: u32 }`, the same
+/// layout as `wasi:clocks/wall-clock.datetime`.
+///
+/// Unlike [`DurationCodec`], this covers the full range of [`Duration`]. Decoding fails if
+/// `nanoseconds` is not less than a second.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct DurationRecord(pub Duration);
+
+impl From<Duration> for DurationRecord {
+    fn from(d: Duration) -> Self {
+        Self(d)
+    }
+}
+
+impl From<DurationRecord> for Duration {
+    fn from(DurationRecord(d): DurationRecord) -> Self {
+        d
+    }
+}
+
+/// Codec for [`DurationRecord`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DurationRecordCodec {
+    seconds: Option<u64>,
+}
+
+impl tokio_util::codec::Encoder<DurationRecord> for DurationRecordCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: DurationRecord, dst: &mut BytesMut) -> std::io::Result<()> {
+        VarU64Codec.encode(item.0.as_secs(), dst)?;
+        VarU32Codec.encode(item.0.subsec_nanos(), dst)
+    }
+}
+
+impl_copy_ref_encoder!(DurationRecord, DurationRecordCodec);
+
+impl tokio_util::codec::Decoder for DurationRecordCodec {
+    type Item = DurationRecord;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let seconds = if let Some(seconds) = self.seconds {
+            seconds
+        } else {
+            let Some(seconds) = VarU64Codec.decode(src)? else {
+                return Ok(None);
+            };
+            self.seconds = Some(seconds);
+            seconds
+        };
+        let Some(nanoseconds) = VarU32Codec.decode(src)? else {
+            return Ok(None);
+        };
+        self.seconds = None;
+        if nanoseconds >= 1_000_000_000 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("duration nanoseconds value `{nanoseconds}` exceeds a second"),
+            ));
+        }
+        Ok(Some(DurationRecord(Duration::new(seconds, nanoseconds))))
+    }
+}
+
+impl_deferred_sync!(DurationRecordCodec);
+impl_deferred_sync!(CoreVecDecoder<DurationRecordCodec>);
+impl_copy_codec!(DurationRecord, DurationRecordCodec);
+
+macro_rules! impl_bit_int {
+    ($t:ident, $c:ident, $v:ty, $wc:ident, $sig:literal) => {
+        #[doc = concat!($sig, " integer of `BITS` bits, which is encoded as `", stringify!($v), "`.")]
+        ///
+        /// Decoding fails if the value received does not fit in `BITS` bits.
+        /// `BITS` must be in the range `1..=64`.
+        #[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        #[repr(transparent)]
+        pub struct $t<const BITS: u32>($v);
+
+        impl<const BITS: u32> $t<BITS> {
+            const VALID: () = assert!(BITS > 0 && BITS <= 64, "`BITS` must be in range `1..=64`");
+
+            /// Returns the wrapped value
+            #[must_use]
+            pub fn get(self) -> $v {
+                self.0
+            }
+
+            fn check(v: $v) -> std::io::Result<Self> {
+                let () = Self::VALID;
+                if Self::fits(v) {
+                    Ok(Self(v))
+                } else {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("value `{v}` does not fit in `{BITS}` bits"),
+                    ))
+                }
+            }
+        }
+
+        impl<const BITS: u32> TryFrom<$v> for $t<BITS> {
+            type Error = $v;
+
+            /// Wraps `v`, returning it back as an error if it does not fit in `BITS` bits
+            fn try_from(v: $v) -> Result<Self, Self::Error> {
+                Self::check(v).map_err(|_| v)
+            }
+        }
+
+        impl<const BITS: u32> From<$t<BITS>> for $v {
+            fn from($t(v): $t<BITS>) -> Self {
+                v
+            }
+        }
+
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+        pub struct $c<const BITS: u32>;
+
+        impl<T, const BITS: u32> Deferred<T> for $c<BITS> {
+            fn take_deferred(&mut self) -> Option<DeferredFn<T>> {
+                None
+            }
+        }
+
+        impl<T, const BITS: u32> Deferred<T> for CoreVecDecoder<$c<BITS>> {
+            fn take_deferred(&mut self) -> Option<DeferredFn<T>> {
+                None
+            }
+        }
+
+        impl<const BITS: u32> tokio_util::codec::Encoder<$t<BITS>> for $c<BITS> {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: $t<BITS>, dst: &mut BytesMut) -> std::io::Result<()> {
+                $wc.encode(item.0, dst)
+            }
+        }
+
+        impl<const BITS: u32> tokio_util::codec::Encoder<&$t<BITS>> for $c<BITS> {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &$t<BITS>, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(*item, dst)
+            }
+        }
+
+        impl<const BITS: u32> tokio_util::codec::Decoder for $c<BITS> {
+            type Item = $t<BITS>;
+            type Error = std::io::Error;
+
+            #[instrument(level = "trace", skip(self), fields(ty = stringify!($t), bits = BITS))]
+            fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+                let Some(v) = $wc.decode(src)? else {
+                    return Ok(None);
+                };
+                $t::check(v).map(Some)
+            }
+        }
+
+        impl<W, const BITS: u32> Encode<W> for $t<BITS> {
+            type Encoder = $c<BITS>;
+        }
+
+        impl<W, const BITS: u32> Encode<W> for &$t<BITS> {
+            type Encoder = $c<BITS>;
+        }
+
+        impl<R, const BITS: u32> Decode<R> for $t<BITS> {
+            type Decoder = $c<BITS>;
+            type ListDecoder = CoreVecDecoder<Self::Decoder>;
+        }
+    };
+}
+
+impl_bit_int!(UInt, UIntCodec, u64, VarU64Codec, "Unsigned");
+impl_bit_int!(SInt, SIntCodec, i64, S64Codec, "Signed");
+
+impl<const BITS: u32> UInt<BITS> {
+    fn fits(v: u64) -> bool {
+        BITS >= 64 || v >> BITS == 0
+    }
+}
+
+impl<const BITS: u32> SInt<BITS> {
+    fn fits(v: i64) -> bool {
+        BITS >= 64 || matches!(v >> (BITS - 1), 0 | -1)
+    }
+}
+
+macro_rules! impl_range_codec {
+    ($t:ident, $c:ident, $new:expr, $start:expr, $end:expr, $fmt:literal) => {
+        #[doc = concat!("Codec for [`", stringify!($t), "<u64>`](core::ops::", stringify!($t), "), which is encoded as a `record { start: u64, end: u64 }`.")]
+        ///
+        /// Decoding fails if `start` is greater than `end`.
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+        pub struct $c {
+            start: Option<u64>,
+        }
+
+        impl_deferred_sync!($c);
+        impl_deferred_sync!(CoreVecDecoder<$c>);
+
+        impl tokio_util::codec::Encoder<&core::ops::$t<u64>> for $c {
+            type Error = std::io::Error;
+
+            fn encode(
+                &mut self,
+                item: &core::ops::$t<u64>,
+                dst: &mut BytesMut,
+            ) -> std::io::Result<()> {
+                VarU64Codec.encode(($start)(item), dst)?;
+                VarU64Codec.encode(($end)(item), dst)
+            }
+        }
+
+        impl tokio_util::codec::Encoder<core::ops::$t<u64>> for $c {
+            type Error = std::io::Error;
+
+            fn encode(
+                &mut self,
+                item: core::ops::$t<u64>,
+                dst: &mut BytesMut,
+            ) -> std::io::Result<()> {
+                self.encode(&item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Decoder for $c {
+            type Item = core::ops::$t<u64>;
+            type Error = std::io::Error;
+
+            #[instrument(level = "trace", skip(self), fields(ty = stringify!($t)))]
+            fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+                let start = if let Some(start) = self.start {
+                    start
+                } else {
+                    let Some(start) = VarU64Codec.decode(src)? else {
+                        return Ok(None);
+                    };
+                    self.start = Some(start);
+                    start
+                };
+                let Some(end) = VarU64Codec.decode(src)? else {
+                    return Ok(None);
+                };
+                self.start = None;
+                if start > end {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(concat!("invalid range `", $fmt, "`, start exceeds end"), start, end),
+                    ));
+                }
+                Ok(Some(($new)(start, end)))
+            }
+        }
+
+        impl<W> Encode<W> for core::ops::$t<u64> {
+            type Encoder = $c;
+        }
+
+        impl<W> Encode<W> for &core::ops::$t<u64> {
+            type Encoder = $c;
+        }
+
+        impl<R> Decode<R> for core::ops::$t<u64> {
+            type Decoder = $c;
+            type ListDecoder = CoreVecDecoder<Self::Decoder>;
+        }
+    };
+}
+
+impl_range_codec!(
+    Range,
+    RangeCodec,
+    |start, end| start..end,
+    |r: &core::ops::Range<u64>| r.start,
+    |r: &core::ops::Range<u64>| r.end,
+    "{}..{}"
+);
+impl_range_codec!(
+    RangeInclusive,
+    RangeInclusiveCodec,
+    core::ops::RangeInclusive::new,
+    |r: &core::ops::RangeInclusive<u64>| *r.start(),
+    |r: &core::ops::RangeInclusive<u64>| *r.end(),
+    "{}..={}"
+);
+
+/// Sequence of `bool`s, which is encoded compactly by packing 8 values per byte.
+///
+/// The encoding is identical to that of `tuple<u32, list<u8>>`, where the `u32` is the number of
+/// values and the `list<u8>` contains the values packed least significant bit first. Unused bits
+/// of the last byte must be zero. This is distinct from `flags`, which is a fixed set of named
+/// values and is not prefixed by a count. Compared to `list<bool>`, this uses 8 times less space.
+///
+/// Decoding fails if the number of bytes does not match the number of values or if any of the
+/// unused bits are set.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BitVec(Vec<bool>);
+
+impl BitVec {
+    fn pack(&self) -> std::io::Result<(u32, Vec<u8>)> {
+        let n = u32::try_from(self.0.len())
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        let mut buf = vec![0; self.0.len().div_ceil(8)];
+        for (i, v) in self.0.iter().enumerate() {
+            if *v {
+                buf[i / 8] |= 1 << (i % 8);
+            }
+        }
+        Ok((n, buf))
+    }
+
+    fn unpack(n: u32, buf: &[u8]) -> std::io::Result<Self> {
+        let n = usize::try_from(n)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        if buf.len() != n.div_ceil(8) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "`{}` bytes cannot contain exactly `{n}` packed bits",
+                    buf.len()
+                ),
+            ));
+        }
+        if let Some(last) = buf.last() {
+            if n % 8 != 0 && last >> (n % 8) != 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "unused bits of packed bit vector are set",
+                ));
+            }
+        }
+        Ok(Self(
+            (0..n).map(|i| buf[i / 8] & (1 << (i % 8)) != 0).collect(),
+        ))
+    }
+}
+
+impl Deref for BitVec {
+    type Target = [bool];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<bool>> for BitVec {
+    fn from(v: Vec<bool>) -> Self {
+        Self(v)
+    }
+}
+
+impl From<BitVec> for Vec<bool> {
+    fn from(BitVec(v): BitVec) -> Self {
+        v
+    }
+}
+
+impl FromIterator<bool> for BitVec {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BitVecCodec {
+    n: Option<u32>,
+    dec: CoreVecDecoderBytes,
+}
+
+impl_deferred_sync!(BitVecCodec);
+impl_deferred_sync!(CoreVecDecoder<BitVecCodec>);
+
+impl tokio_util::codec::Encoder<&BitVec> for BitVecCodec {
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "bitvec"))]
+    fn encode(&mut self, item: &BitVec, dst: &mut BytesMut) -> std::io::Result<()> {
+        let (n, buf) = item.pack()?;
+        VarU32Codec.encode(n, dst)?;
+        CoreVecEncoderBytes.encode(buf, dst)
+    }
+}
+
+impl tokio_util::codec::Encoder<BitVec> for BitVecCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: BitVec, dst: &mut BytesMut) -> std::io::Result<()> {
+        self.encode(&item, dst)
+    }
+}
+
+impl tokio_util::codec::Decoder for BitVecCodec {
+    type Item = BitVec;
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "bitvec"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let n = if let Some(n) = self.n {
+            n
+        } else {
+            let Some(n) = VarU32Codec.decode(src)? else {
+                return Ok(None);
+            };
+            self.n = Some(n);
+            n
+        };
+        let Some(buf) = self.dec.decode(src)? else {
+            return Ok(None);
+        };
+        self.n = None;
+        BitVec::unpack(n, &buf).map(Some)
+    }
+}
+
+impl<W> Encode<W> for BitVec {
+    type Encoder = BitVecCodec;
+}
+
+impl<W> Encode<W> for &BitVec {
+    type Encoder = BitVecCodec;
+}
+
+impl<R> Decode<R> for BitVec {
+    type Decoder = BitVecCodec;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+/// Decoder of `wasi:clocks/wall-clock.datetime` records, i.e. `record { seconds: u64, nanoseconds: u32 }`
+#[cfg(any(feature = "chrono", feature = "time"))]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct DatetimeDecoder {
+    seconds: Option<u64>,
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+impl DatetimeDecoder {
+    fn decode(&mut self, src: &mut BytesMut) -> std::io::Result<Option<(i64, u32)>> {
+        let seconds = if let Some(seconds) = self.seconds {
+            seconds
+        } else {
+            let Some(seconds) = VarU64Codec.decode(src)? else {
+                return Ok(None);
+            };
+            self.seconds = Some(seconds);
+            seconds
+        };
+        let Some(nanoseconds) = VarU32Codec.decode(src)? else {
+            return Ok(None);
+        };
+        self.seconds = None;
+        let seconds = i64::try_from(seconds).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("datetime seconds value `{seconds}` is out of range"),
+            )
+        })?;
+        if nanoseconds >= 1_000_000_000 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("datetime nanoseconds value `{nanoseconds}` exceeds a second"),
+            ));
+        }
+        Ok(Some((seconds, nanoseconds)))
+    }
+}
+
+/// Encodes seconds and nanoseconds since the Unix epoch as a `wasi:clocks/wall-clock.datetime`
+#[cfg(any(feature = "chrono", feature = "time"))]
+fn encode_datetime(
+    seconds: i64,
+    nanoseconds: u32,
+    v: &impl fmt::Display,
+    dst: &mut BytesMut,
+) -> std::io::Result<()> {
+    let seconds = u64::try_from(seconds).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("`{v}` precedes the Unix epoch and cannot be encoded as a `datetime`"),
+        )
+    })?;
+    VarU64Codec.encode(seconds, dst)?;
+    VarU32Codec.encode(nanoseconds, dst)
+}
+
+macro_rules! impl_datetime_codec {
+    ($feature:literal, $t:ty, $c:ident, $parts:expr, $new:expr) => {
+        #[doc = concat!("Codec for [`", stringify!($t), "`], which is encoded as a `wasi:clocks/wall-clock.datetime`, i.e. `record { seconds: u64, nanoseconds: u32 }`.")]
+        ///
+        /// Timestamps preceding the Unix epoch cannot be represented and fail to encode.
+        /// Decoding fails if `nanoseconds` is not less than a second or if the timestamp is out of
+        /// range of the type.
+        #[cfg(feature = $feature)]
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+        pub struct $c(DatetimeDecoder);
+
+        #[cfg(feature = $feature)]
+        impl_deferred_sync!($c);
+        #[cfg(feature = $feature)]
+        impl_deferred_sync!(CoreVecDecoder<$c>);
+
+        #[cfg(feature = $feature)]
+        impl tokio_util::codec::Encoder<&$t> for $c {
+            type Error = std::io::Error;
+
+            #[instrument(level = "trace", skip(self), fields(ty = "datetime"))]
+            fn encode(&mut self, item: &$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                let (seconds, nanoseconds) = ($parts)(item);
+                encode_datetime(seconds, nanoseconds, item, dst)
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl tokio_util::codec::Encoder<$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: $t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(&item, dst)
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl tokio_util::codec::Decoder for $c {
+            type Item = $t;
+            type Error = std::io::Error;
+
+            #[instrument(level = "trace", skip(self), fields(ty = "datetime"))]
+            fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+                let Some((seconds, nanoseconds)) = self.0.decode(src)? else {
+                    return Ok(None);
+                };
+                ($new)(seconds, nanoseconds).map(Some).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            concat!("datetime `{}.{:09}` is out of range of `", stringify!($t), "`"),
+                            seconds, nanoseconds
+                        ),
+                    )
+                })
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl<W> Encode<W> for $t {
+            type Encoder = $c;
+        }
+
+        #[cfg(feature = $feature)]
+        impl<W> Encode<W> for &$t {
+            type Encoder = $c;
+        }
+
+        #[cfg(feature = $feature)]
+        impl<R> Decode<R> for $t {
+            type Decoder = $c;
+            type ListDecoder = CoreVecDecoder<Self::Decoder>;
+        }
+    };
+}
+
+impl_datetime_codec!(
+    "time",
+    time::OffsetDateTime,
+    OffsetDateTimeCodec,
+    |v: &time::OffsetDateTime| (v.unix_timestamp(), v.nanosecond()),
+    |seconds, nanoseconds| {
+        let v = time::OffsetDateTime::from_unix_timestamp(seconds).ok()?;
+        v.replace_nanosecond(nanoseconds).ok()
+    }
+);
+impl_datetime_codec!(
+    "chrono",
+    chrono::DateTime<chrono::Utc>,
+    ChronoDateTimeCodec,
+    // leap seconds are represented by `chrono` as nanoseconds exceeding a second, encode them as
+    // the first nanosecond of the following second
+    |v: &chrono::DateTime<chrono::Utc>| match v.timestamp_subsec_nanos() {
+        nanoseconds @ ..=999_999_999 => (v.timestamp(), nanoseconds),
+        _ => (v.timestamp().saturating_add(1), 0),
+    },
+    chrono::DateTime::from_timestamp
+);
+
+macro_rules! impl_size_codec {
+    ($t:ty, $c:ident, $wt:ty, $wc:ident) => {
+        #[doc = concat!("Codec for `", stringify!($t), "`, which is encoded as `", stringify!($wt), "`")]
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+        #[repr(transparent)]
+        pub struct $c;
+
+        impl tokio_util::codec::Encoder<$t> for $c {
+            type Error = std::io::Error;
+
+            #[cfg_attr(feature = "trace", instrument(level = "trace", skip(self), ret, fields(ty = stringify!($t))))]
+            fn encode(&mut self, item: $t, dst: &mut BytesMut) -> std::io::Result<()> {
+                let item = <$wt>::try_from(item)
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+                $wc.encode(item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Encoder<&$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(*item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Encoder<&&$t> for $c {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: &&$t, dst: &mut BytesMut) -> std::io::Result<()> {
+                self.encode(**item, dst)
+            }
+        }
+
+        impl tokio_util::codec::Decoder for $c {
+            type Item = $t;
+            type Error = std::io::Error;
+
+            #[cfg_attr(feature = "trace", instrument(level = "trace", skip(self), fields(ty = stringify!($t))))]
+            fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+                let Some(v) = $wc.decode(src)? else {
+                    return Ok(None);
+                };
+                let v = <$t>::try_from(v).map_err(|_| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        concat!("value overflows ", stringify!($t), " on this platform"),
+                    )
+                })?;
+                Ok(Some(v))
+            }
+        }
+
+        impl_deferred_sync!($c);
+        impl_deferred_sync!(CoreVecDecoder<$c>);
+        impl_copy_codec!($t, $c);
+    };
+}
+
+impl_size_codec!(usize, UsizeCodec, u64, VarU64Codec);
+impl_size_codec!(isize, IsizeCodec, i64, S64Codec);
+
+impl<T> Encode<T> for u8 {
+    type Encoder = U8Codec;
+
+    #[instrument(level = "trace", skip(items))]
+    fn encode_iter_own<I>(
+        items: I,
+        enc: &mut Self::Encoder,
+        dst: &mut BytesMut,
+        _idx: u64,
+    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error>
+    where
+        I: IntoIterator<Item = Self>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let items = items.into_iter();
+        dst.reserve(items.len());
+        dst.extend(items);
+        Ok(None)
+    }
+
+    #[instrument(level = "trace", skip(items))]
+    fn encode_iter_ref<'a, I>(
+        items: I,
+        enc: &mut Self::Encoder,
+        dst: &mut BytesMut,
+        _idx: u64,
+    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error>
+    where
+        I: IntoIterator<Item = &'a Self>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let items = items.into_iter();
+        dst.reserve(items.len());
+        dst.extend(items);
+        Ok(None)
+    }
+
+    #[instrument(level = "trace", skip(items), fields(ty = "list<u8>"))]
+    fn encode_list_own(
+        items: Vec<Self>,
+        enc: &mut Self::Encoder,
+        dst: &mut BytesMut,
+    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error>
+    {
+        CoreVecEncoderBytes.encode(items, dst)?;
+        Ok(None)
+    }
+
+    #[instrument(level = "trace", skip(items), fields(ty = "list<u8>"))]
+    fn encode_list_ref<'a>(
+        items: &'a [Self],
+        enc: &mut Self::Encoder,
+        dst: &mut BytesMut,
+    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error>
+    where
+        Self::Encoder: tokio_util::codec::Encoder<&'a Self>,
+    {
+        CoreVecEncoderBytes.encode(items, dst)?;
+        Ok(None)
+    }
+}
+
+impl<'b, T> Encode<T> for &'b u8 {
+    type Encoder = U8Codec;
+
+    #[instrument(level = "trace", skip(items))]
+    fn encode_iter_own<I>(
+        items: I,
+        enc: &mut Self::Encoder,
+        dst: &mut BytesMut,
+        _idx: u64,
+    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<Self>>::Error>
+    where
+        I: IntoIterator<Item = Self>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let items = items.into_iter();
+        dst.reserve(items.len());
+        dst.extend(items);
+        Ok(None)
+    }
+
+    #[instrument(level = "trace", skip(items))]
+    fn encode_iter_ref<'a, I>(
+        items: I,
+        enc: &mut Self::Encoder,
+        dst: &mut BytesMut,
+        _idx: u64,
+    ) -> Result<Option<DeferredFn<T>>, <Self::Encoder as tokio_util::codec::Encoder<&'a Self>>::Error>
+    where
+        I: IntoIterator<Item = &'a Self>,
+        I::IntoIter: ExactSizeIterator,
+        'b: 'a,
+    {
+        let items = items.into_iter();
+        dst.reserve(items.len());
+        dst.extend(items.map(|b| **b));
+        Ok(None)
+    }
+}
+
+/// Decoder of `list<u8>`, which copies the payload in bulk once fully received, rather than
+/// decoding it byte-by-byte like generic `list<T>` decoders
+#[derive(Debug, Default)]
+#[repr(transparent)]
+pub struct ListDecoderU8(CoreVecDecoderBytes);
+
+impl tokio_util::codec::Decoder for ListDecoderU8 {
+    type Item = Vec<u8>;
+    type Error = <CoreVecDecoderBytes as tokio_util::codec::Decoder>::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "list<u8>"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(buf) = self.0.decode(src)? else {
+            return Ok(None);
+        };
+        Ok(Some(buf.into()))
+    }
+}
+
+impl<R> Decode<R> for u8 {
+    type Decoder = U8Codec;
+    type ListDecoder = ListDecoderU8;
+}
+
+impl<W> Encode<W> for &str {
+    type Encoder = CoreNameEncoder;
+}
+
+impl<W> Encode<W> for &&str {
+    type Encoder = CoreNameEncoder;
+}
+
+impl<W> Encode<W> for String {
+    type Encoder = CoreNameEncoder;
+}
+
+impl<W> Encode<W> for &String {
+    type Encoder = CoreNameEncoder;
+}
+
+impl<R> Decode<R> for String {
+    type Decoder = CoreNameDecoder;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+/// `string` decoder, which does not validate that the decoded bytes are valid UTF-8.
+///
+/// This is intended for pass-through proxies, which forward opaque `string` payloads received
+/// from a trusted source. [`Decode`] implementation for [String] always uses the validating
+/// [`CoreNameDecoder`] and this decoder can only be constructed via [`Self::new`], which is `unsafe`.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct UncheckedStringDecoder(CoreVecDecoderBytes);
+
+impl UncheckedStringDecoder {
+    /// Constructs a new [`UncheckedStringDecoder`]
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that all payloads decoded using the returned decoder are valid
+    /// UTF-8, see [`String::from_utf8_unchecked`].
+    #[must_use]
+    pub unsafe fn new() -> Self {
+        Self(CoreVecDecoderBytes::default())
+    }
+}
+
+impl<R> Deferred<R> for UncheckedStringDecoder {
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        None
+    }
+}
+
+impl tokio_util::codec::Decoder for UncheckedStringDecoder {
+    type Item = String;
+    type Error = std::io::Error;
+
+    #[cfg_attr(
+        feature = "trace",
+        instrument(level = "trace", skip(self), fields(ty = "string"))
+    )]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(buf) = self.0.decode(src)? else {
+            return Ok(None);
+        };
+        // SAFETY: The caller of `UncheckedStringDecoder::new` guarantees that the payload is
+        // valid UTF-8
+        Ok(Some(unsafe { String::from_utf8_unchecked(buf.into()) }))
+    }
+}
+
+/// `string` of at most `N` bytes.
+///
+/// Encoding fails if the value is longer than `N` bytes and decoding fails as soon as a length
+/// header exceeding `N` is received, before the payload is buffered.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct BoundedString<const N: usize>(String);
+
+impl<const N: usize> BoundedString<N> {
+    /// Returns the wrapped [String]
+    #[must_use]
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl<const N: usize> Deref for BoundedString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> TryFrom<String> for BoundedString<N> {
+    type Error = String;
+
+    /// Wraps `s`, returning it back as an error if it is longer than `N` bytes
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s.len() > N {
+            Err(s)
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+
+impl<const N: usize> From<BoundedString<N>> for String {
+    fn from(BoundedString(s): BoundedString<N>) -> Self {
+        s
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BoundedStringCodec<const N: usize> {
+    dec: CoreNameDecoder,
+    checked: bool,
+}
+
+impl<const N: usize> BoundedStringCodec<N> {
+    fn check_len(len: usize) -> std::io::Result<()> {
+        if len > N {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("string length of `{len}` exceeds maximum of `{N}`"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<T, const N: usize> Deferred<T> for BoundedStringCodec<N> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<T>> {
+        None
+    }
+}
+
+impl<const N: usize> tokio_util::codec::Encoder<&BoundedString<N>> for BoundedStringCodec<N> {
+    type Error = std::io::Error;
+
+    #[cfg_attr(
+        feature = "trace",
+        instrument(level = "trace", skip(self), fields(ty = "string", max = N))
+    )]
+    fn encode(&mut self, item: &BoundedString<N>, dst: &mut BytesMut) -> std::io::Result<()> {
+        Self::check_len(item.0.len())?;
+        CoreNameEncoder.encode(item.0.as_str(), dst)
+    }
+}
+
+impl<const N: usize> tokio_util::codec::Encoder<BoundedString<N>> for BoundedStringCodec<N> {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: BoundedString<N>, dst: &mut BytesMut) -> std::io::Result<()> {
+        self.encode(&item, dst)
+    }
+}
+
+impl<const N: usize> tokio_util::codec::Decoder for BoundedStringCodec<N> {
+    type Item = BoundedString<N>;
+    type Error = std::io::Error;
+
+    #[cfg_attr(
+        feature = "trace",
+        instrument(level = "trace", skip(self), fields(ty = "string", max = N))
+    )]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if !self.checked {
+            // length prefix is at most 5 bytes long
+            let mut prefix = BytesMut::from(&src[..src.len().min(5)]);
+            let Some(len) = Leb128DecoderU32.decode(&mut prefix)? else {
+                return Ok(None);
+            };
+            let len = len
+                .try_into()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+            Self::check_len(len)?;
+            self.checked = true;
+        }
+        let Some(s) = self.dec.decode(src)? else {
+            return Ok(None);
+        };
+        self.checked = false;
+        Ok(Some(BoundedString(s)))
+    }
+}
+
+impl<W, const N: usize> Encode<W> for BoundedString<N> {
+    type Encoder = BoundedStringCodec<N>;
+}
+
+impl<W, const N: usize> Encode<W> for &BoundedString<N> {
+    type Encoder = BoundedStringCodec<N>;
+}
+
+impl<R, const N: usize> Decode<R> for BoundedString<N>
+where
+    R: crate::Index<R> + Send + Sync + 'static,
+{
+    type Decoder = BoundedStringCodec<N>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
+}
+
+impl<W> Encode<W> for Bytes {
+    type Encoder = CoreVecEncoderBytes;
+}
+
+impl<W> Encode<W> for &Bytes {
+    type Encoder = CoreVecEncoderBytes;
+}
+
+impl<R> Decode<R> for Bytes {
+    type Decoder = CoreVecDecoderBytes;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+/// Codec for [`BytesMut`], which is encoded as `list<u8>`.
+///
+/// Decoded payloads are split off the receive buffer without copying and remain mutable and
+/// growable, which allows handlers to modify received bytes in place.
+#[derive(Debug, Default)]
+pub struct BytesMutCodec(usize);
+
+impl_deferred_sync!(BytesMutCodec);
+
+impl tokio_util::codec::Encoder<BytesMut> for BytesMutCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: BytesMut, dst: &mut BytesMut) -> std::io::Result<()> {
+        self.encode(&item, dst)
+    }
+}
+
+impl tokio_util::codec::Encoder<&BytesMut> for BytesMutCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: &BytesMut, dst: &mut BytesMut) -> std::io::Result<()> {
+        CoreVecEncoderBytes.encode(item.as_ref(), dst)
+    }
+}
+
+impl tokio_util::codec::Decoder for BytesMutCodec {
+    type Item = BytesMut;
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "list<u8>"))]
+    fn decode(&mut self, src: &mut BytesMut) -> std::io::Result<Option<Self::Item>> {
+        if self.0 == 0 {
+            let Some(len) = Leb128DecoderU32.decode(src)? else {
+                return Ok(None);
+            };
+            if len == 0 {
+                return Ok(Some(BytesMut::default()));
+            }
+            self.0 = len
+                .try_into()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        }
+        let n = self.0.saturating_sub(src.len());
+        if n > 0 {
+            src.reserve(n);
+            return Ok(None);
+        }
+        Ok(Some(src.split_to(mem::take(&mut self.0))))
+    }
+}
+
+impl<W> Encode<W> for BytesMut {
+    type Encoder = BytesMutCodec;
+}
+
+impl<W> Encode<W> for &BytesMut {
+    type Encoder = BytesMutCodec;
+}
+
+impl<R> Decode<R> for BytesMut {
+    type Decoder = BytesMutCodec;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+/// Codec of C strings, which are encoded as length-prefixed bytes without the nul terminator.
+///
+/// The encoding is compatible with `list<u8>` and, if the bytes are valid UTF-8, `string`.
+/// Decoding fails if the payload contains a nul byte.
+#[derive(Debug, Default)]
+pub struct CStringCodec(CoreVecDecoderBytes);
+
+impl_deferred_sync!(CStringCodec);
+impl_deferred_sync!(CoreVecDecoder<CStringCodec>);
+
+impl tokio_util::codec::Encoder<&CStr> for CStringCodec {
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "cstring"))]
+    fn encode(&mut self, item: &CStr, dst: &mut BytesMut) -> std::io::Result<()> {
+        CoreVecEncoderBytes.encode(item.to_bytes(), dst)
+    }
+}
+
+impl tokio_util::codec::Encoder<&CString> for CStringCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: &CString, dst: &mut BytesMut) -> std::io::Result<()> {
+        self.encode(item.as_c_str(), dst)
+    }
+}
+
+impl tokio_util::codec::Encoder<CString> for CStringCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: CString, dst: &mut BytesMut) -> std::io::Result<()> {
+        self.encode(item.as_c_str(), dst)
+    }
+}
+
+impl tokio_util::codec::Decoder for CStringCodec {
+    type Item = CString;
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "cstring"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(buf) = self.0.decode(src)? else {
+            return Ok(None);
+        };
+        CString::new(Vec::from(buf))
+            .map(Some)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl<W> Encode<W> for CString {
+    type Encoder = CStringCodec;
+}
+
+impl<W> Encode<W> for &CString {
+    type Encoder = CStringCodec;
+}
+
+impl<W> Encode<W> for &CStr {
+    type Encoder = CStringCodec;
+}
+
+impl<R> Decode<R> for CString {
+    type Decoder = CStringCodec;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct ResourceEncoder;
+
+impl<T: ?Sized> tokio_util::codec::Encoder<ResourceOwn<T>> for ResourceEncoder {
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, item), ret, fields(ty = "own"))]
+    fn encode(&mut self, item: ResourceOwn<T>, dst: &mut BytesMut) -> std::io::Result<()> {
+        CoreVecEncoderBytes.encode(item.repr, dst)
+    }
+}
+
+impl<T: ?Sized> tokio_util::codec::Encoder<&ResourceOwn<T>> for ResourceEncoder {
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, item), ret, fields(ty = "own"))]
+    fn encode(&mut self, item: &ResourceOwn<T>, dst: &mut BytesMut) -> std::io::Result<()> {
+        CoreVecEncoderBytes.encode(&item.repr, dst)
+    }
+}
+
+impl<T: ?Sized, W> Encode<W> for ResourceOwn<T> {
+    type Encoder = ResourceEncoder;
+}
+
+impl<T: ?Sized, W> Encode<W> for &ResourceOwn<T> {
+    type Encoder = ResourceEncoder;
+}
+
+impl<T: ?Sized> tokio_util::codec::Encoder<ResourceBorrow<T>> for ResourceEncoder {
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, item), ret, fields(ty = "borrow"))]
+    fn encode(&mut self, item: ResourceBorrow<T>, dst: &mut BytesMut) -> std::io::Result<()> {
+        CoreVecEncoderBytes.encode(item.repr, dst)
+    }
+}
+
+impl<T: ?Sized> tokio_util::codec::Encoder<&ResourceBorrow<T>> for ResourceEncoder {
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, item), ret, fields(ty = "borrow"))]
+    fn encode(&mut self, item: &ResourceBorrow<T>, dst: &mut BytesMut) -> std::io::Result<()> {
+        CoreVecEncoderBytes.encode(&item.repr, dst)
+    }
+}
+
+impl<T: ?Sized, W> Encode<W> for ResourceBorrow<T> {
+    type Encoder = ResourceEncoder;
+}
+
+impl<T: ?Sized, W> Encode<W> for &ResourceBorrow<T> {
+    type Encoder = ResourceEncoder;
+}
+
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct ResourceBorrowDecoder<T: ?Sized> {
+    dec: CoreVecDecoderBytes,
+    _ty: PhantomData<T>,
+}
+
+impl<T: ?Sized> Default for ResourceBorrowDecoder<T> {
+    fn default() -> Self {
+        Self {
+            dec: CoreVecDecoderBytes::default(),
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<R, T: ?Sized> Deferred<R> for ResourceBorrowDecoder<T> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        None
+    }
+}
+
+impl<R, T: ?Sized> Deferred<R> for CoreVecDecoder<ResourceBorrowDecoder<T>> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        None
+    }
+}
+
+impl<R, T: ?Sized + Send + 'static> Decode<R> for ResourceBorrow<T> {
+    type Decoder = ResourceBorrowDecoder<T>;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+impl<T: ?Sized> tokio_util::codec::Decoder for ResourceBorrowDecoder<T> {
+    type Item = ResourceBorrow<T>;
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "borrow"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let repr = self.dec.decode(src)?;
+        Ok(repr.map(Self::Item::from))
+    }
+}
+
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct ResourceOwnDecoder<T: ?Sized> {
+    dec: CoreVecDecoderBytes,
+    _ty: PhantomData<T>,
+}
+
+impl<T: ?Sized> Default for ResourceOwnDecoder<T> {
+    fn default() -> Self {
+        Self {
+            dec: CoreVecDecoderBytes::default(),
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<R, T: ?Sized> Deferred<R> for ResourceOwnDecoder<T> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        None
+    }
+}
+
+impl<R, T: ?Sized> Deferred<R> for CoreVecDecoder<ResourceOwnDecoder<T>> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        None
+    }
+}
+
+impl<R, T: ?Sized + Send + 'static> Decode<R> for ResourceOwn<T> {
+    type Decoder = ResourceOwnDecoder<T>;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+impl<T: ?Sized> tokio_util::codec::Decoder for ResourceOwnDecoder<T> {
+    type Item = ResourceOwn<T>;
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "own"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let repr = self.dec.decode(src)?;
+        Ok(repr.map(Self::Item::from))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct UnitCodec;
+
+impl tokio_util::codec::Encoder<()> for UnitCodec {
+    type Error = std::io::Error;
+
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip(self), ret))]
+    fn encode(&mut self, (): (), _dst: &mut BytesMut) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl tokio_util::codec::Encoder<&()> for UnitCodec {
+    type Error = std::io::Error;
+
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip(self), ret))]
+    fn encode(&mut self, (): &(), _dst: &mut BytesMut) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl tokio_util::codec::Decoder for UnitCodec {
+    type Item = ();
+    type Error = std::io::Error;
+
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip(self)))]
+    fn decode(&mut self, _src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(Some(()))
+    }
+}
+
+/// Codec for [`Infallible`](core::convert::Infallible), e.g. the error type of a WIT `result<T>`
+/// without an error type mapped to `Result<T, Infallible>`.
+///
+/// Values of an uninhabited type cannot exist, so encoding is unreachable and decoding always
+/// fails, e.g. if a `result::err` is received for a `Result<T, Infallible>`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct InfallibleCodec;
+
+impl_deferred_sync!(InfallibleCodec);
+impl_deferred_sync!(CoreVecDecoder<InfallibleCodec>);
+
+impl tokio_util::codec::Encoder<core::convert::Infallible> for InfallibleCodec {
+    type Error = std::io::Error;
+
+    fn encode(
+        &mut self,
+        item: core::convert::Infallible,
+        _dst: &mut BytesMut,
+    ) -> std::io::Result<()> {
+        match item {}
+    }
+}
+
+impl tokio_util::codec::Encoder<&core::convert::Infallible> for InfallibleCodec {
+    type Error = std::io::Error;
+
+    fn encode(
+        &mut self,
+        item: &core::convert::Infallible,
+        _dst: &mut BytesMut,
+    ) -> std::io::Result<()> {
+        match *item {}
+    }
+}
+
+impl tokio_util::codec::Decoder for InfallibleCodec {
+    type Item = core::convert::Infallible;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, _src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "received a value of uninhabited type `Infallible`, e.g. a `result::err` for a result without an error type",
+        ))
+    }
+}
+
+impl<W> Encode<W> for core::convert::Infallible {
+    type Encoder = InfallibleCodec;
+}
+
+impl<W> Encode<W> for &core::convert::Infallible {
+    type Encoder = InfallibleCodec;
+}
+
+impl<R> Decode<R> for core::convert::Infallible {
+    type Decoder = InfallibleCodec;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+/// Marker trait for [Encode] tuple types
+pub trait TupleEncode<W>: Encode<W> {}
+
+/// Marker trait for [Decode] tuple types
+pub trait TupleDecode<R>: Decode<R> {}
+
+impl<W> Encode<W> for () {
+    type Encoder = UnitCodec;
+}
+
+impl<W> Encode<W> for &() {
+    type Encoder = UnitCodec;
+}
+
+impl<W> TupleEncode<W> for () {}
+
+impl<R> Decode<R> for () {
+    type Decoder = UnitCodec;
+    type ListDecoder = CoreVecDecoder<Self::Decoder>;
+}
+
+impl<R> TupleDecode<R> for () {}
+
+macro_rules! impl_tuple_codec {
+    ($($vn:ident),+; $($vt:ident),+; $($cn:ident),+; $($ct:ident),+) => {
+        impl<W, $($ct),+> Deferred<W> for TupleEncoder::<($($ct),+,)>
+        where
+            W: crate::Index<W> + Send + Sync + 'static,
+            $($ct: Deferred<W> + Default + 'static),+
+        {
+            fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+                let Self(($(mut $cn),+,)) = mem::take(self);
+                let deferred = [ $($cn.take_deferred()),+ ];
+                if deferred.iter().any(Option::is_some) {
+                    Some(Box::new(|r, path| Box::pin(handle_deferred(r, deferred, path, 0))))
+                } else {
+                    None
+                }
+            }
+        }
+
+        impl<W, E, $($vt),+> Encode<W> for ($($vt),+,)
+        where
+            W: crate::Index<W> + Send + Sync + 'static,
+            E: From<std::io::Error>,
+            $(
+                $vt: Encode<W>,
+                $vt::Encoder: tokio_util::codec::Encoder<$vt, Error = E> + 'static,
+            )+
+        {
+            type Encoder = TupleEncoder::<($($vt::Encoder),+,)>;
+        }
+
+        impl<W, E, $($vt),+> TupleEncode<W> for ($($vt),+,)
+        where
+            W: crate::Index<W> + Send + Sync + 'static,
+            E: From<std::io::Error>,
+            $(
+                $vt: Encode<W>,
+                $vt::Encoder: tokio_util::codec::Encoder<$vt, Error = E> + 'static,
+            )+
+        {
+        }
+
+        impl<'a, W, E, $($vt),+> Encode<W> for &'a ($($vt),+,)
+        where
+            W: crate::Index<W> + Send + Sync + 'static,
+            E: From<std::io::Error>,
+            $(
+                $vt: Encode<W>,
+                $vt::Encoder: tokio_util::codec::Encoder<&'a $vt, Error = E> + 'static,
+            )+
+        {
+            type Encoder = TupleEncoder::<($($vt::Encoder),+,)>;
+        }
+
+        impl<R, $($vt),+> Deferred<R> for TupleDecoder::<($($vt::Decoder),+,), ($(Option<$vt>),+,)>
+        where
+            R: crate::Index<R> + Send + Sync + 'static,
+            $($vt: Decode<R>),+
+        {
+            fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+                let ($(mut $cn),+,) = mem::take(self).into_inner();
+                let deferred = [ $($cn.take_deferred()),+ ];
+                if deferred.iter().any(Option::is_some) {
+                    Some(Box::new(|r, path| Box::pin(handle_deferred(r, deferred, path, 0))))
+                } else {
+                    None
+                }
+            }
+        }
+
+        impl<R, E, $($vt),+> Decode<R> for ($($vt),+,)
+        where
+            R: crate::Index<R> + Send + Sync + 'static,
+            E: From<std::io::Error>,
+            $(
+                $vt: Decode<R> + Send + 'static,
+                $vt::Decoder: tokio_util::codec::Decoder<Error = E> + Send + 'static,
+            )+
+        {
+            type Decoder = TupleDecoder::<($($vt::Decoder),+,), ($(Option<$vt>),+,)>;
+            type ListDecoder = ListDecoder<Self::Decoder, R>;
+        }
+
+        impl<R, E, $($vt),+> TupleDecode<R> for ($($vt),+,)
+        where
+            R: crate::Index<R> + Send + Sync + 'static,
+            E: From<std::io::Error>,
+            $(
+                $vt: Decode<R> + Send + 'static,
+                $vt::Decoder: tokio_util::codec::Decoder<Error = E> + Send + 'static,
+            )+
+        {
+        }
+    };
+}
+
+impl_tuple_codec!(
+    v0;
+    V0;
+    c0;
+    C0
+);
+
+impl_tuple_codec!(
+    v0, v1;
+    V0, V1;
+    c0, c1;
+    C0, C1
+);
+
+impl_tuple_codec!(
+    v0, v1, v2;
+    V0, V1, V2;
+    c0, c1, c2;
+    C0, C1, C2
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3;
+    V0, V1, V2, V3;
+    c0, c1, c2, c3;
+    C0, C1, C2, C3
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4;
+    V0, V1, V2, V3, V4;
+    c0, c1, c2, c3, c4;
+    C0, C1, C2, C3, C4
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5;
+    V0, V1, V2, V3, V4, V5;
+    c0, c1, c2, c3, c4, c5;
+    C0, C1, C2, C3, C4, C5
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6;
+    V0, V1, V2, V3, V4, V5, V6;
+    c0, c1, c2, c3, c4, c5, c6;
+    C0, C1, C2, C3, C4, C5, C6
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7;
+    V0, V1, V2, V3, V4, V5, V6, V7;
+    c0, c1, c2, c3, c4, c5, c6, c7;
+    C0, C1, C2, C3, C4, C5, C6, C7
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7, v8;
+    V0, V1, V2, V3, V4, V5, V6, V7, V8;
+    c0, c1, c2, c3, c4, c5, c6, c7, c8;
+    C0, C1, C2, C3, C4, C5, C6, C7, C8
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9;
+    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9;
+    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9;
+    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10;
+    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10;
+    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10;
+    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11;
+    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11;
+    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11;
+    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12;
+    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12;
+    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12;
+    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12, v13;
+    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12, V13;
+    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12, c13;
+    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12, C13
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12, v13, v14;
+    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12, V13, V14;
+    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12, c13, c14;
+    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12, C13, C14
+);
+
+impl_tuple_codec!(
+    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12, v13, v14, v15;
+    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12, V13, V14, V15;
+    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12, c13, c14, c15;
+    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12, C13, C14, C15
+);
+
+pub struct FutureEncoder<W> {
+    deferred: Option<DeferredFn<W>>,
+}
+
+impl<W> Default for FutureEncoder<W> {
+    fn default() -> Self {
+        Self { deferred: None }
+    }
+}
+
+impl<W> Deferred<W> for FutureEncoder<W> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.deferred.take()
+    }
+}
+
+impl<T, W, Fut> tokio_util::codec::Encoder<Fut> for FutureEncoder<W>
+where
+    T: Encode<W>,
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
+{
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, item), fields(ty = "future"))]
+    fn encode(&mut self, item: Fut, dst: &mut BytesMut) -> std::io::Result<()> {
+        if is_canonical() {
+            return Err(canonical_async_error("future"));
+        }
+        // TODO: Check if future is resolved
+        dst.reserve(1);
+        dst.put_u8(0x00);
+        self.deferred = Some(Box::new(|w, mut path| {
+            Box::pin(async move {
+                let mut root = w.index(&path).map_err(std::io::Error::other)?;
+                let item = item.await;
+                let mut enc = T::Encoder::default();
+                let mut buf = BytesMut::default();
+                enc.encode(item, &mut buf)?;
+                try_join!(root.write_all(&buf), async {
+                    if let Some(f) = enc.take_deferred() {
+                        path.push(0);
+                        f(w, path).await
+                    } else {
+                        Ok(())
+                    }
+                })?;
+                Ok(())
+            })
+        }));
+        Ok(())
+    }
+}
+
+impl<T, W> Encode<W> for Pin<Box<dyn Future<Output = T> + Send>>
+where
+    T: Encode<W> + 'static,
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
+{
+    type Encoder = FutureEncoder<W>;
+}
+
+pub struct FutureDecoder<T, R>
+where
+    T: Decode<R>,
+{
+    dec: OptionDecoder<T::Decoder>,
+    deferred: Option<DeferredFn<R>>,
+    _ty: PhantomData<T>,
+}
+
+impl<T, R> Default for FutureDecoder<T, R>
+where
+    T: Decode<R>,
+{
+    fn default() -> Self {
+        Self {
+            dec: OptionDecoder::default(),
+            deferred: None,
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T, R> Deferred<R> for FutureDecoder<T, R>
+where
+    T: Decode<R>,
+{
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        self.deferred.take()
+    }
+}
+
+impl<T, R> tokio_util::codec::Decoder for FutureDecoder<T, R>
+where
+    T: Decode<R> + Send + 'static,
+    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
+    std::io::Error: From<<T::Decoder as tokio_util::codec::Decoder>::Error>,
+{
+    type Item = Pin<Box<dyn Future<Output = T> + Send>>;
+    type Error = <T::Decoder as tokio_util::codec::Decoder>::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "future"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(item) = self.dec.decode(src)? else {
+            return Ok(None);
+        };
+        if let Some(item) = item {
+            self.deferred = self.dec.take_deferred();
+            return Ok(Some(Box::pin(async { item })));
+        }
+
+        // future is pending
+        let (tx, rx) = oneshot::channel();
+        let dec = mem::take(&mut self.dec).into_inner();
+        self.deferred = Some(Box::new(|r, mut path| {
+            Box::pin(async move {
+                let indexed = r.index(&path).map_err(std::io::Error::other)?;
+                let mut dec = FramedRead::new(indexed, dec);
+                trace!("receiving future element");
+                let Some(item) = dec.next().await else {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                };
+                let item = item?;
+                try_join!(
+                    async {
+                        tx.send(item).map_err(|_| {
+                            std::io::Error::new(
+                                std::io::ErrorKind::BrokenPipe,
+                                "future receiver closed",
+                            )
+                        })
+                    },
+                    async {
+                        if let Some(rx) = dec.decoder_mut().take_deferred() {
+                            path.push(0);
+                            rx(r, path).await
+                        } else {
+                            Ok(())
+                        }
+                    }
+                )?;
+                Ok(())
+            })
+        }));
+        return Ok(Some(Box::pin(async {
+            rx.await.expect("future I/O dropped")
+        })));
+    }
+}
+
+impl<T, R> Decode<R> for Pin<Box<dyn Future<Output = T> + Send>>
+where
+    T: Decode<R> + Send + 'static,
+    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
+    std::io::Error: From<<T::Decoder as tokio_util::codec::Decoder>::Error>,
+{
+    type Decoder = FutureDecoder<T, R>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
+}
+
+/// Flattens a batched `stream<list<u8>>` into a stream of [Bytes] chunks.
+///
+/// If `preserve_boundaries` is `true`, each `list<u8>` element is yielded as a separate chunk,
+/// otherwise all elements of a received batch are concatenated into a single chunk.
+/// Empty chunks are never yielded.
+pub fn flatten_byte_stream<S, B>(
+    items: S,
+    preserve_boundaries: bool,
+) -> impl Stream<Item = Bytes> + Send + 'static
+where
+    S: Stream<Item = Vec<B>> + Send + 'static,
+    B: AsRef<[u8]> + Into<Bytes>,
+{
+    items.flat_map(move |chunk| {
+        let chunks = if preserve_boundaries {
+            chunk
+                .into_iter()
+                .map(Into::into)
+                .filter(|buf: &Bytes| !buf.is_empty())
+                .collect()
+        } else {
+            let n = chunk.iter().map(|buf| buf.as_ref().len()).sum();
+            if n == 0 {
+                vec![]
+            } else {
+                let mut buf = BytesMut::with_capacity(n);
+                for item in chunk {
+                    buf.extend_from_slice(item.as_ref());
+                }
+                vec![buf.freeze()]
+            }
+        };
+        stream::iter(chunks)
+    })
+}
+
+/// Reassembles a stream of indexed `items`, which may arrive out of order, e.g. when elements of
+/// a logical stream are demultiplexed from several channels, into a stream of items in index
+/// order, starting at `0`.
+///
+/// Items arriving early are buffered until all preceding indices arrive. At most `window`
+/// items past the next expected index are accepted, an item arriving further ahead is considered
+/// to indicate a gap, which would otherwise cause unbounded buffering, and results in an error.
+/// Duplicate indices and `items` ending with a gap result in an error as well.
+/// The stream ends after the first error.
+pub fn reorder_indexed<S, T>(
+    items: S,
+    window: usize,
+) -> impl Stream<Item = std::io::Result<T>> + Send + 'static
+where
+    S: Stream<Item = (usize, T)> + Send + 'static,
+    T: Send + 'static,
+{
+    stream::unfold(
+        (
+            Box::pin(items),
+            BTreeMap::<usize, T>::default(),
+            0usize,
+            false,
+        ),
+        move |(mut items, mut buf, mut next, done)| async move {
+            loop {
+                if let Some(v) = buf.remove(&next) {
+                    trace!(i = next, "yielding buffered item");
+                    next += 1;
+                    return Some((Ok(v), (items, buf, next, done)));
+                }
+                if done {
+                    return None;
+                }
+                let err = match items.next().await {
+                    None if buf.is_empty() => return None,
+                    None => std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("stream ended before item `{next}` arrived"),
+                    ),
+                    Some((i, v)) if i == next => {
+                        next += 1;
+                        return Some((Ok(v), (items, buf, next, done)));
+                    }
+                    Some((i, _)) if i < next || buf.contains_key(&i) => std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("duplicate item `{i}`"),
+                    ),
+                    Some((i, _)) if i - next > window => std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "item `{i}` arrived more than `{window}` items ahead of item `{next}`"
+                        ),
+                    ),
+                    Some((i, v)) => {
+                        trace!(i, next, "buffering early item");
+                        buf.insert(i, v);
+                        continue;
+                    }
+                };
+                return Some((Err(err), (items, BTreeMap::default(), next, true)));
+            }
+        },
+    )
+}
+
+/// Spawns `task` on `tasks`.
+///
+/// If built with `--cfg tokio_unstable`, the task is named using `name`, which allows
+/// identifying the stream a task belongs to in e.g. `tokio-console`.
+fn spawn_named<T, F>(
+    tasks: &mut JoinSet<T>,
+    name: impl FnOnce() -> String,
+    task: F,
+) -> std::io::Result<()>
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    #[cfg(tokio_unstable)]
+    tasks.build_task().name(&name()).spawn(task)?;
+    #[cfg(not(tokio_unstable))]
+    {
+        let _ = name;
+        tasks.spawn(task);
+    }
+    Ok(())
+}
+
+/// Maximum number of immediately available chunks buffered by stream encoders, before
+/// falling back to asynchronous transmission
+const MAX_BUFFERED_STREAM_CHUNKS: usize = 1024;
+
+/// Size of encoded data buffered by stream encoders, after which the stream is not polled until
+/// the buffered data is written, which propagates backpressure of the transport to the producer
+const MAX_BUFFERED_STREAM_BYTES: usize = 64 * 1024;
+
+/// Polls `items` without blocking, returning all immediately available chunks and whether the
+/// stream has terminated.
+///
+/// Streams, which are fully buffered (e.g. [`stream::iter`]), can be encoded eagerly as a
+/// length-prefixed list, which avoids a round-trip over an indexed async stream. Chunks polled
+/// from a partially-ready stream must be transmitted before the remainder of the stream.
+fn poll_buffered<S: Stream + Unpin>(items: &mut S) -> (Vec<S::Item>, bool) {
+    let mut chunks = Vec::default();
+    while chunks.len() < MAX_BUFFERED_STREAM_CHUNKS {
+        match items.next().now_or_never() {
+            Some(Some(chunk)) => chunks.push(chunk),
+            Some(None) => return (chunks, true),
+            None => break,
+        }
+    }
+    (chunks, false)
+}
+
+pub struct StreamEncoder<W> {
+    deferred: Option<DeferredFn<W>>,
+}
+
+impl<W> Default for StreamEncoder<W> {
+    fn default() -> Self {
+        Self { deferred: None }
+    }
+}
+
+impl<W> Deferred<W> for StreamEncoder<W> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.deferred.take()
+    }
+}
+
+impl<T, W, S> tokio_util::codec::Encoder<S> for StreamEncoder<W>
+where
+    T: Encode<W> + Send + 'static,
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    S: Stream<Item = Vec<T>> + Send + Unpin + 'static,
+    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
+{
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, items), fields(ty = "stream"))]
+    fn encode(&mut self, mut items: S, dst: &mut BytesMut) -> std::io::Result<()> {
+        if is_canonical() {
+            return Err(canonical_async_error("stream"));
+        }
+        let (chunks, done) = poll_buffered(&mut items);
+        let mut items = if done {
+            let buffered: Vec<T> = chunks.into_iter().flatten().collect();
+            if !buffered.is_empty() {
+                trace!(n = buffered.len(), "encoding fully buffered stream");
+                let mut enc = ListEncoder::<W>::default();
+                enc.encode(buffered, dst)?;
+                self.deferred = enc.take_deferred();
+                return Ok(());
+            }
+            stream::iter(vec![]).left_stream()
+        } else {
+            stream::iter(chunks).chain(items).right_stream()
+        };
+        dst.reserve(1);
+        dst.put_u8(0x00);
+        self.deferred = Some(Box::new(|w, path| {
+            Box::pin(async move {
+                let mut root = w.index(&path).map_err(std::io::Error::other)?;
+                let mut enc = T::Encoder::default();
+                let mut buf = BytesMut::default();
+                let mut tasks = JoinSet::new();
+                let mut i = 0_u64;
+                loop {
+                    select! {
+                        chunk = items.next(), if buf.len() < MAX_BUFFERED_STREAM_BYTES => {
+                            let Some(chunk) = chunk else {
+                                trace!("writing stream end");
+                                buf.reserve(1);
+                                buf.put_u8(0x00);
+                                try_join!(
+                                    root.write_all(&buf),
+                                    async {
+                                        while let Some(res) = tasks.join_next().await {
+                                            trace!(?res, "receiver task finished");
+                                            res??;
+                                        }
+                                    Ok(())
+                                })?;
+                                return Ok(())
+                            };
+                            let n = u32::try_from(chunk.len()).map_err(|err| {
+                                std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+                            })?;
+                            let end = i.checked_add(n.into()).ok_or_else(|| {
+                                std::io::Error::new(
+                                    std::io::ErrorKind::InvalidInput,
+                                    "stream element index would overflow u64",
+                                )
+                            })?;
+                            trace!(n, "encoding chunk length");
+                            Leb128Encoder.encode(n, &mut buf)?;
+                            trace!(i, buf = format!("{buf:02x?}"), "writing stream chunk items");
+                            if let Some(deferred) = T::encode_iter_own(chunk, &mut enc, &mut buf, i)? {
+                                trace!("spawning transmit task");
+                                spawn_named(
+                                    &mut tasks,
+                                    || format!("wrpc stream transmit {path:?} from {i}"),
+                                    deferred(Arc::clone(&w), path.clone()),
+                                )?;
+                            }
+                            i = end;
+                        }
+                        Some(res) = tasks.join_next() => {
+                            trace!(?res, "receiver task finished");
+                            res??;
+                        }
+                        res = root.write(&buf), if !buf.is_empty() => {
+                            let n = res?;
+                            trace!(?buf, n, "wrote bytes from buffer");
+                            buf.advance(n);
+                        }
+                    }
+                }
+            })
+        }));
+        Ok(())
+    }
+}
+
+impl<T, W> Encode<W> for Pin<Box<dyn Stream<Item = Vec<T>> + Send>>
+where
+    T: Encode<W> + Send + 'static,
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
+{
+    type Encoder = StreamEncoder<W>;
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6;
-    V0, V1, V2, V3, V4, V5, V6;
-    c0, c1, c2, c3, c4, c5, c6;
-    C0, C1, C2, C3, C4, C5, C6
-);
+/// Typed `stream<T>` value backed by an arbitrary [`Stream`] of individual items.
+///
+/// This is wire-compatible with `Pin<Box<dyn Stream<Item = Vec<T>> + Send>>`, but does not
+/// require the caller to box the stream or group items in chunks. Items, which are immediately
+/// available, are transmitted together in a single chunk.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StreamItems<S>(pub S);
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7;
-    V0, V1, V2, V3, V4, V5, V6, V7;
-    c0, c1, c2, c3, c4, c5, c6, c7;
-    C0, C1, C2, C3, C4, C5, C6, C7
-);
+pub struct StreamItemsEncoder<W>(StreamEncoder<W>);
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7, v8;
-    V0, V1, V2, V3, V4, V5, V6, V7, V8;
-    c0, c1, c2, c3, c4, c5, c6, c7, c8;
-    C0, C1, C2, C3, C4, C5, C6, C7, C8
-);
+impl<W> Default for StreamItemsEncoder<W> {
+    fn default() -> Self {
+        Self(StreamEncoder::default())
+    }
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9;
-    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9;
-    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9;
-    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9
-);
+impl<W> Deferred<W> for StreamItemsEncoder<W> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.0.take_deferred()
+    }
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10;
-    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10;
-    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10;
-    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10
-);
+impl<T, W, S> tokio_util::codec::Encoder<StreamItems<S>> for StreamItemsEncoder<W>
+where
+    T: Encode<W> + Send + 'static,
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    S: Stream<Item = T> + Send + 'static,
+    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
+{
+    type Error = std::io::Error;
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11;
-    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11;
-    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11;
-    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11
-);
+    #[instrument(level = "trace", skip(self, items), fields(ty = "stream"))]
+    fn encode(
+        &mut self,
+        StreamItems(items): StreamItems<S>,
+        dst: &mut BytesMut,
+    ) -> std::io::Result<()> {
+        self.0.encode(
+            Box::pin(items.ready_chunks(MAX_BUFFERED_STREAM_CHUNKS)),
+            dst,
+        )
+    }
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12;
-    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12;
-    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12;
-    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12
-);
+impl<T, W, S> Encode<W> for StreamItems<S>
+where
+    T: Encode<W> + Send + 'static,
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    S: Stream<Item = T> + Send + 'static,
+    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
+{
+    type Encoder = StreamItemsEncoder<W>;
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12, v13;
-    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12, V13;
-    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12, c13;
-    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12, C13
-);
+pub struct StreamEncoderBytes<W> {
+    deferred: Option<DeferredFn<W>>,
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12, v13, v14;
-    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12, V13, V14;
-    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12, c13, c14;
-    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12, C13, C14
-);
+impl<W> Default for StreamEncoderBytes<W> {
+    fn default() -> Self {
+        Self { deferred: None }
+    }
+}
 
-impl_tuple_codec!(
-    v0, v1, v2, v3, v4, v5, v6, v7, v8, v9, v10, v11, v12, v13, v14, v15;
-    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12, V13, V14, V15;
-    c0, c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12, c13, c14, c15;
-    C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12, C13, C14, C15
-);
+impl<W> Deferred<W> for StreamEncoderBytes<W> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+        self.deferred.take()
+    }
+}
 
-pub struct FutureEncoder<W> {
+impl<W, S> tokio_util::codec::Encoder<S> for StreamEncoderBytes<W>
+where
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    S: Stream<Item = Bytes> + Send + Unpin + 'static,
+{
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, items), fields(ty = "stream<u8>"))]
+    fn encode(&mut self, mut items: S, dst: &mut BytesMut) -> std::io::Result<()> {
+        if is_canonical() {
+            return Err(canonical_async_error("stream"));
+        }
+        let (chunks, done) = poll_buffered(&mut items);
+        let mut items = if done {
+            let buffered = chunks.concat();
+            if !buffered.is_empty() {
+                trace!(n = buffered.len(), "encoding fully buffered byte stream");
+                return CoreVecEncoderBytes.encode(buffered, dst);
+            }
+            stream::iter(vec![]).left_stream()
+        } else {
+            stream::iter(chunks).chain(items).right_stream()
+        };
+        dst.reserve(1);
+        dst.put_u8(0x00);
+        self.deferred = Some(Box::new(|w, path| {
+            Box::pin(async move {
+                let mut root = w.index(&path).map_err(std::io::Error::other)?;
+                let mut buf = BytesMut::default();
+                loop {
+                    select! {
+                        chunk = items.next(), if buf.len() < MAX_BUFFERED_STREAM_BYTES => {
+                            let Some(chunk) = chunk else {
+                                trace!("writing stream end");
+                                buf.reserve(1);
+                                buf.put_u8(0x00);
+                                return root.write_all(&buf).await
+                            };
+                            let n = u32::try_from(chunk.len()).map_err(|err| {
+                                std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+                            })?;
+                            trace!(n, "encoding chunk length");
+                            Leb128Encoder.encode(n, &mut buf)?;
+                            buf.extend_from_slice(&chunk);
+                        }
+                        res = root.write(&buf), if !buf.is_empty() => {
+                            let n = res?;
+                            buf.advance(n);
+                        }
+                    }
+                }
+            })
+        }));
+        Ok(())
+    }
+}
+
+impl<W> Encode<W> for Pin<Box<dyn Stream<Item = Bytes> + Send>>
+where
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+{
+    type Encoder = StreamEncoderBytes<W>;
+}
+
+pub struct StreamEncoderRead<W> {
     deferred: Option<DeferredFn<W>>,
 }
 
-impl<W> Default for FutureEncoder<W> {
+impl<W> Default for StreamEncoderRead<W> {
     fn default() -> Self {
         Self { deferred: None }
     }
 }
 
-impl<W> Deferred<W> for FutureEncoder<W> {
+impl<W> Deferred<W> for StreamEncoderRead<W> {
     fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
         self.deferred.take()
     }
 }
 
-impl<T, W, Fut> tokio_util::codec::Encoder<Fut> for FutureEncoder<W>
+impl<W, S> tokio_util::codec::Encoder<S> for StreamEncoderRead<W>
+where
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+    S: AsyncRead + Send + Unpin + 'static,
+{
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self, items), fields(ty = "stream<u8>"))]
+    fn encode(&mut self, mut items: S, dst: &mut BytesMut) -> std::io::Result<()> {
+        if is_canonical() {
+            return Err(canonical_async_error("stream"));
+        }
+        // TODO: Check if reader is resolved
+        dst.reserve(1);
+        dst.put_u8(0x00);
+        self.deferred = Some(Box::new(|w, path| {
+            Box::pin(async move {
+                let mut root = w.index(&path).map_err(std::io::Error::other)?;
+                let mut buf = BytesMut::default();
+                let mut chunk = BytesMut::default();
+                loop {
+                    select! {
+                        res = items.read_buf(&mut chunk), if buf.len() < MAX_BUFFERED_STREAM_BYTES => {
+                            let n = res?;
+                            if n == 0 {
+                                trace!("writing stream end");
+                                buf.reserve(1);
+                                buf.put_u8(0x00);
+                                return root.write_all(&buf).await
+                            }
+                            let n = u32::try_from(n).map_err(|err| {
+                                std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+                            })?;
+                            trace!(n, "encoding chunk length");
+                            Leb128Encoder.encode(n, &mut buf)?;
+                            buf.extend_from_slice(&chunk);
+                            chunk.clear();
+                        }
+                        res = root.write(&buf), if !buf.is_empty() => {
+                            let n = res?;
+                            buf.advance(n);
+                        }
+                    }
+                }
+            })
+        }));
+        Ok(())
+    }
+}
+
+impl<W> Encode<W> for Pin<Box<dyn AsyncRead + Send>>
+where
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+{
+    type Encoder = StreamEncoderRead<W>;
+}
+
+impl<T, W> Encode<W> for std::io::Cursor<T>
+where
+    T: AsRef<[u8]> + Send + Unpin + 'static,
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+{
+    type Encoder = StreamEncoderRead<W>;
+}
+
+impl<W> Encode<W> for tokio::io::Empty
+where
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+{
+    type Encoder = StreamEncoderRead<W>;
+}
+
+#[cfg(feature = "io-std")]
+impl<W> Encode<W> for tokio::io::Stdin
+where
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+{
+    type Encoder = StreamEncoderRead<W>;
+}
+
+#[cfg(feature = "fs")]
+impl<W> Encode<W> for tokio::fs::File
+where
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+{
+    type Encoder = StreamEncoderRead<W>;
+}
+
+#[cfg(feature = "net")]
+impl<W> Encode<W> for tokio::net::TcpStream
+where
+    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
+{
+    type Encoder = StreamEncoderRead<W>;
+}
+
+#[cfg(all(unix, feature = "net"))]
+impl<W> Encode<W> for tokio::net::UnixStream
 where
-    T: Encode<W>,
     W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-    Fut: Future<Output = T> + Send + 'static,
-    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
 {
-    type Error = std::io::Error;
-
-    #[instrument(level = "trace", skip(self, item), fields(ty = "future"))]
-    fn encode(&mut self, item: Fut, dst: &mut BytesMut) -> std::io::Result<()> {
-        // TODO: Check if future is resolved
-        dst.reserve(1);
-        dst.put_u8(0x00);
-        self.deferred = Some(Box::new(|w, mut path| {
-            Box::pin(async move {
-                let mut root = w.index(&path).map_err(std::io::Error::other)?;
-                let item = item.await;
-                let mut enc = T::Encoder::default();
-                let mut buf = BytesMut::default();
-                enc.encode(item, &mut buf)?;
-                try_join!(root.write_all(&buf), async {
-                    if let Some(f) = enc.take_deferred() {
-                        path.push(0);
-                        f(w, path).await
-                    } else {
-                        Ok(())
-                    }
-                })?;
-                Ok(())
-            })
-        }));
-        Ok(())
-    }
+    type Encoder = StreamEncoderRead<W>;
 }
 
-impl<T, W> Encode<W> for Pin<Box<dyn Future<Output = T> + Send>>
+#[cfg(all(unix, feature = "net"))]
+impl<W> Encode<W> for tokio::net::unix::pipe::Receiver
 where
-    T: Encode<W> + 'static,
     W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
 {
-    type Encoder = FutureEncoder<W>;
+    type Encoder = StreamEncoderRead<W>;
 }
 
-pub struct FutureDecoder<T, R>
+pub struct StreamDecoder<T, R>
 where
     T: Decode<R>,
 {
-    dec: OptionDecoder<T::Decoder>,
+    dec: T::ListDecoder,
     deferred: Option<DeferredFn<R>>,
     _ty: PhantomData<T>,
 }
 
-impl<T, R> Default for FutureDecoder<T, R>
+impl<T, R> Default for StreamDecoder<T, R>
 where
     T: Decode<R>,
 {
     fn default() -> Self {
         Self {
-            dec: OptionDecoder::default(),
+            dec: T::ListDecoder::default(),
             deferred: None,
             _ty: PhantomData,
         }
     }
 }
 
-impl<T, R> Deferred<R> for FutureDecoder<T, R>
+impl<T, R> Deferred<R> for StreamDecoder<T, R>
 where
     T: Decode<R>,
 {
@@ -1554,668 +5382,2472 @@ where
     }
 }
 
-impl<T, R> tokio_util::codec::Decoder for FutureDecoder<T, R>
+/// Returns the error, with which stream receive tasks fail once the receiving end of the stream
+/// is dropped
+fn stream_receiver_closed() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::BrokenPipe, "stream receiver closed")
+}
+
+/// Receives the next chunk from `framed`, unless `tx` is closed first.
+///
+/// This ensures that stream receive tasks stop promptly once the receiving end of the stream is
+/// dropped, even if the peer does not send any more data.
+async fn next_stream_chunk<C, R, T>(
+    framed: &mut FramedRead<R, C>,
+    tx: &mpsc::Sender<T>,
+) -> std::io::Result<Option<Bytes>>
+where
+    C: tokio_util::codec::Decoder<Item = Bytes>,
+    R: AsyncRead + Unpin,
+    std::io::Error: From<C::Error>,
+{
+    trace!("receiving stream chunk");
+    select! {
+        chunk = framed.next() => Ok(chunk.transpose()?),
+        () = tx.closed() => {
+            trace!("stream receiver closed");
+            Err(stream_receiver_closed())
+        }
+    }
+}
+
+#[instrument(level = "trace", skip(dec, r, tx), ret)]
+async fn handle_deferred_stream<C, T, R>(
+    dec: C,
+    r: Arc<R>,
+    mut path: Vec<usize>,
+    tx: mpsc::Sender<Vec<T>>,
+) -> std::io::Result<()>
+where
+    C: tokio_util::codec::Decoder<Item = T> + Deferred<R>,
+    R: AsyncRead + crate::Index<R> + Send + Unpin + 'static,
+    std::io::Error: From<C::Error>,
+{
+    let dec = ListDecoder::new(dec);
+    let indexed = r.index(&path).map_err(std::io::Error::other)?;
+    let mut framed = FramedRead::new(indexed, dec);
+    let mut tasks = JoinSet::new();
+    let mut i = 0_usize;
+    loop {
+        trace!("receiving stream chunk");
+        select! {
+            chunk = framed.next() => {
+                let Some(chunk) = chunk else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "stream ended without end marker",
+                    ))
+                };
+                let chunk = chunk?;
+                if chunk.is_empty() {
+                    trace!("received stream end");
+                    while let Some(res) = tasks.join_next().await {
+                        res??;
+                    }
+                    return Ok(())
+                }
+                let end = i.checked_add(chunk.len()).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "stream element index would overflow usize",
+                    )
+                })?;
+                trace!(i, end, "received stream chunk");
+                tx.send(chunk).await.map_err(|_| stream_receiver_closed())?;
+                for (i, deferred) in zip(i.., mem::take(&mut framed.decoder_mut().deferred)) {
+                    if let Some(deferred) = deferred {
+                        trace!(i, "handling async read");
+                        path.push(i);
+                        trace!("spawning receive task");
+                        spawn_named(
+                            &mut tasks,
+                            || format!("wrpc stream receive {path:?}"),
+                            deferred(Arc::clone(&r), path.clone()),
+                        )?;
+                        path.pop();
+                    }
+                }
+                i = end;
+            },
+            Some(res) = tasks.join_next() => {
+                trace!(?res, "receiver task finished");
+                res??;
+            }
+            () = tx.closed() => {
+                trace!("stream receiver closed");
+                return Err(stream_receiver_closed())
+            }
+        }
+    }
+}
+
+impl<T, R> tokio_util::codec::Decoder for StreamDecoder<T, R>
 where
     T: Decode<R> + Send + 'static,
+    T::ListDecoder: Deferred<R>,
     R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
+    <T::Decoder as tokio_util::codec::Decoder>::Error: Send,
     std::io::Error: From<<T::Decoder as tokio_util::codec::Decoder>::Error>,
 {
-    type Item = Pin<Box<dyn Future<Output = T> + Send>>;
-    type Error = <T::Decoder as tokio_util::codec::Decoder>::Error;
+    type Item = Pin<Box<dyn Stream<Item = Vec<T>> + Send>>;
+    type Error = <<T as Decode<R>>::ListDecoder as tokio_util::codec::Decoder>::Error;
 
-    #[instrument(level = "trace", skip(self), fields(ty = "future"))]
+    #[instrument(level = "trace", skip(self), fields(ty = "stream"))]
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let Some(item) = self.dec.decode(src)? else {
+        let Some(chunk) = self.dec.decode(src)? else {
             return Ok(None);
         };
-        if let Some(item) = item {
+        if !chunk.is_empty() {
             self.deferred = self.dec.take_deferred();
-            return Ok(Some(Box::pin(async { item })));
+            return Ok(Some(Box::pin(stream::iter([chunk]))));
         }
 
-        // future is pending
-        let (tx, rx) = oneshot::channel();
-        let dec = mem::take(&mut self.dec).into_inner();
-        self.deferred = Some(Box::new(|r, mut path| {
-            Box::pin(async move {
-                let indexed = r.index(&path).map_err(std::io::Error::other)?;
-                let mut dec = FramedRead::new(indexed, dec);
-                trace!("receiving future element");
-                let Some(item) = dec.next().await else {
-                    return Err(std::io::ErrorKind::UnexpectedEof.into());
-                };
-                let item = item?;
-                try_join!(
-                    async {
-                        tx.send(item).map_err(|_| {
-                            std::io::Error::new(
-                                std::io::ErrorKind::BrokenPipe,
-                                "future receiver closed",
-                            )
-                        })
-                    },
-                    async {
-                        if let Some(rx) = dec.decoder_mut().take_deferred() {
-                            path.push(0);
-                            rx(r, path).await
-                        } else {
-                            Ok(())
-                        }
-                    }
-                )?;
-                Ok(())
-            })
+        // stream is pending
+        let (tx, rx) = mpsc::channel(128);
+        self.deferred = Some(Box::new(|r, path| {
+            Box::pin(
+                async move { handle_deferred_stream(T::Decoder::default(), r, path, tx).await },
+            )
         }));
-        return Ok(Some(Box::pin(async {
-            rx.await.expect("future I/O dropped")
-        })));
+        return Ok(Some(Box::pin(ReceiverStream::new(rx))));
     }
 }
 
-impl<T, R> Decode<R> for Pin<Box<dyn Future<Output = T> + Send>>
+impl<T, R> Decode<R> for Pin<Box<dyn Stream<Item = Vec<T>> + Send>>
 where
     T: Decode<R> + Send + 'static,
+    T::ListDecoder: Deferred<R> + Send,
     R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
+    <T::Decoder as tokio_util::codec::Decoder>::Error: Send,
     std::io::Error: From<<T::Decoder as tokio_util::codec::Decoder>::Error>,
 {
-    type Decoder = FutureDecoder<T, R>;
+    type Decoder = StreamDecoder<T, R>;
     type ListDecoder = ListDecoder<Self::Decoder, R>;
 }
 
-pub struct StreamEncoder<W> {
-    deferred: Option<DeferredFn<W>>,
+pub struct StreamDecoderBytes<R> {
+    dec: CoreVecDecoderBytes,
+    deferred: Option<DeferredFn<R>>,
 }
 
-impl<W> Default for StreamEncoder<W> {
+impl<R> Default for StreamDecoderBytes<R> {
     fn default() -> Self {
-        Self { deferred: None }
-    }
-}
-
-impl<W> Deferred<W> for StreamEncoder<W> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
-        self.deferred.take()
-    }
-}
-
-impl<T, W, S> tokio_util::codec::Encoder<S> for StreamEncoder<W>
-where
-    T: Encode<W> + Send + 'static,
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-    S: Stream<Item = Vec<T>> + Send + Unpin + 'static,
-    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
-{
-    type Error = std::io::Error;
-
-    #[instrument(level = "trace", skip(self, items), fields(ty = "stream"))]
-    fn encode(&mut self, mut items: S, dst: &mut BytesMut) -> std::io::Result<()> {
-        // TODO: Check if stream is resolved
-        dst.reserve(1);
-        dst.put_u8(0x00);
-        self.deferred = Some(Box::new(|w, path| {
-            Box::pin(async move {
-                let mut root = w.index(&path).map_err(std::io::Error::other)?;
-                let mut enc = T::Encoder::default();
-                let mut buf = BytesMut::default();
-                let mut tasks = JoinSet::new();
-                let mut i = 0_u64;
-                loop {
-                    select! {
-                        chunk = items.next() => {
-                            let Some(chunk) = chunk else {
-                                trace!("writing stream end");
-                                buf.reserve(1);
-                                buf.put_u8(0x00);
-                                try_join!(
-                                    root.write_all(&buf),
-                                    async {
-                                        while let Some(res) = tasks.join_next().await {
-                                            trace!(?res, "receiver task finished");
-                                            res??;
-                                        }
-                                    Ok(())
-                                })?;
-                                return Ok(())
-                            };
-                            let n = u32::try_from(chunk.len()).map_err(|err| {
-                                std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
-                            })?;
-                            let end = i.checked_add(n.into()).ok_or_else(|| {
-                                std::io::Error::new(
-                                    std::io::ErrorKind::InvalidInput,
-                                    "stream element index would overflow u64",
-                                )
-                            })?;
-                            trace!(n, "encoding chunk length");
-                            Leb128Encoder.encode(n, &mut buf)?;
-                            trace!(i, buf = format!("{buf:02x?}"), "writing stream chunk items");
-                            if let Some(deferred) = T::encode_iter_own(chunk, &mut enc, &mut buf, i)? {
-                                trace!("spawning transmit task");
-                                tasks.spawn(deferred(Arc::clone(&w), path.clone()));
-                            }
-                            i = end;
-                        }
-                        Some(res) = tasks.join_next() => {
-                            trace!(?res, "receiver task finished");
-                            res??;
-                        }
-                        res = root.write(&buf), if !buf.is_empty() => {
-                            let n = res?;
-                            trace!(?buf, n, "wrote bytes from buffer");
-                            buf.advance(n);
-                        }
+        Self {
+            dec: CoreVecDecoderBytes::default(),
+            deferred: None,
+        }
+    }
+}
+
+impl<R> Deferred<R> for StreamDecoderBytes<R> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+        self.deferred.take()
+    }
+}
+
+impl<R> tokio_util::codec::Decoder for StreamDecoderBytes<R>
+where
+    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
+{
+    type Item = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+    type Error = std::io::Error;
+
+    #[instrument(level = "trace", skip(self), fields(ty = "stream<u8>"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(chunk) = self.dec.decode(src)? else {
+            return Ok(None);
+        };
+        if !chunk.is_empty() {
+            return Ok(Some(Box::pin(stream::iter([chunk]))));
+        }
+
+        // stream is pending
+        let (tx, rx) = mpsc::channel(128);
+        let dec = mem::take(&mut self.dec);
+        self.deferred = Some(Box::new(|r, path| {
+            Box::pin(async move {
+                let indexed = r.index(&path).map_err(std::io::Error::other)?;
+                let mut framed = FramedRead::new(indexed, dec);
+                while let Some(chunk) = next_stream_chunk(&mut framed, &tx).await? {
+                    if chunk.is_empty() {
+                        trace!("received stream end");
+                        return Ok(());
                     }
+                    trace!(?chunk, "received byte stream chunk");
+                    tx.send(chunk).await.map_err(|_| stream_receiver_closed())?;
                 }
+                Ok(())
             })
         }));
-        Ok(())
+        return Ok(Some(Box::pin(ReceiverStream::new(rx))));
     }
 }
 
-impl<T, W> Encode<W> for Pin<Box<dyn Stream<Item = Vec<T>> + Send>>
+impl<R> Decode<R> for Pin<Box<dyn Stream<Item = Bytes> + Send>>
 where
-    T: Encode<W> + Send + 'static,
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-    std::io::Error: From<<T::Encoder as tokio_util::codec::Encoder<T>>::Error>,
+    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
 {
-    type Encoder = StreamEncoder<W>;
+    type Decoder = StreamDecoderBytes<R>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
 }
 
-pub struct StreamEncoderBytes<W> {
-    deferred: Option<DeferredFn<W>>,
+pub struct StreamDecoderRead<R> {
+    dec: CoreVecDecoderBytes,
+    deferred: Option<DeferredFn<R>>,
 }
 
-impl<W> Default for StreamEncoderBytes<W> {
+impl<R> Default for StreamDecoderRead<R> {
     fn default() -> Self {
-        Self { deferred: None }
+        Self {
+            dec: CoreVecDecoderBytes::default(),
+            deferred: None,
+        }
     }
 }
 
-impl<W> Deferred<W> for StreamEncoderBytes<W> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+impl<R> Deferred<R> for StreamDecoderRead<R> {
+    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
         self.deferred.take()
     }
 }
 
-impl<W, S> tokio_util::codec::Encoder<S> for StreamEncoderBytes<W>
+impl<R> tokio_util::codec::Decoder for StreamDecoderRead<R>
 where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-    S: Stream<Item = Bytes> + Send + Unpin + 'static,
+    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
 {
+    type Item = Pin<Box<dyn AsyncRead + Send>>;
     type Error = std::io::Error;
 
-    #[instrument(level = "trace", skip(self, items), fields(ty = "stream<u8>"))]
-    fn encode(&mut self, mut items: S, dst: &mut BytesMut) -> std::io::Result<()> {
-        // TODO: Check if reader is resolved
-        dst.reserve(1);
-        dst.put_u8(0x00);
-        self.deferred = Some(Box::new(|w, path| {
+    #[instrument(level = "trace", skip(self), fields(ty = "stream<u8>"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(chunk) = self.dec.decode(src)? else {
+            return Ok(None);
+        };
+        if !chunk.is_empty() {
+            return Ok(Some(Box::pin(std::io::Cursor::new(chunk))));
+        }
+
+        // stream is pending
+        let (tx, rx) = mpsc::channel(128);
+        let dec = mem::take(&mut self.dec);
+        self.deferred = Some(Box::new(|r, path| {
             Box::pin(async move {
-                let mut root = w.index(&path).map_err(std::io::Error::other)?;
-                let mut buf = BytesMut::default();
-                loop {
-                    select! {
-                        chunk = items.next() => {
-                            let Some(chunk) = chunk else {
-                                trace!("writing stream end");
-                                buf.reserve(1);
-                                buf.put_u8(0x00);
-                                return root.write_all(&buf).await
-                            };
-                            let n = u32::try_from(chunk.len()).map_err(|err| {
-                                std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
-                            })?;
-                            trace!(n, "encoding chunk length");
-                            Leb128Encoder.encode(n, &mut buf)?;
-                            buf.extend_from_slice(&chunk);
-                        }
-                        res = root.write(&buf), if !buf.is_empty() => {
-                            let n = res?;
-                            buf.advance(n);
-                        }
+                let indexed = r.index(&path).map_err(std::io::Error::other)?;
+                let mut framed = FramedRead::new(indexed, dec);
+                while let Some(chunk) = next_stream_chunk(&mut framed, &tx).await? {
+                    if chunk.is_empty() {
+                        trace!("received stream end");
+                        return Ok(());
                     }
+                    trace!(?chunk, "received byte stream chunk");
+                    tx.send(std::io::Result::Ok(chunk))
+                        .await
+                        .map_err(|_| stream_receiver_closed())?;
                 }
+                Ok(())
             })
         }));
+        return Ok(Some(Box::pin(StreamReader::new(ReceiverStream::new(rx)))));
+    }
+}
+
+impl<R> Decode<R> for Pin<Box<dyn AsyncRead + Send>>
+where
+    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
+{
+    type Decoder = StreamDecoderRead<R>;
+    type ListDecoder = ListDecoder<Self::Decoder, R>;
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+    use tokio::join;
+
+    use super::*;
+
+    struct NoopStream;
+
+    impl crate::Index<Self> for NoopStream {
+        fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+            panic!("index should not be called with path {path:?}")
+        }
+    }
+
+    impl AsyncRead for NoopStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+            _buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> core::task::Poll<std::io::Result<()>> {
+            panic!("read should not be called")
+        }
+    }
+
+    impl AsyncWrite for NoopStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+            _buf: &[u8],
+        ) -> core::task::Poll<std::io::Result<usize>> {
+            panic!("write should not be called")
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+        ) -> core::task::Poll<std::io::Result<()>> {
+            panic!("flush should not be called")
+        }
+
+        fn poll_shutdown(
+            self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+        ) -> core::task::Poll<std::io::Result<()>> {
+            panic!("shutdown should not be called")
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn codec() -> anyhow::Result<()> {
+        let mut buf = BytesMut::new();
+        let mut enc = <(u8, u32) as Encode<NoopStream>>::Encoder::default();
+        enc.encode((0x42, 0x42), &mut buf)?;
+        if let Some(_f) = Deferred::<NoopStream>::take_deferred(&mut enc) {
+            bail!("no deferred write should have been returned");
+        }
+        assert_eq!(buf.as_ref(), b"\x42\x42");
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn unsigned() -> anyhow::Result<()> {
+        for v in [0, 1, 0x7f, 0x80, 0x81, 0x3fff, 0x4000, u32::MAX] {
+            let mut buf = BytesMut::new();
+            VarU32Codec.encode(v, &mut buf)?;
+            let mut expected = BytesMut::new();
+            U32Codec.encode(v, &mut expected)?;
+            assert_eq!(buf, expected, "u32 `{v}` encoding mismatch");
+            assert_eq!(decode_value::<u32, NoopStream>(buf)?, v);
+        }
+        for v in [0, 0x7f, 0x80, u64::from(u32::MAX) + 1, u64::MAX] {
+            let mut buf = BytesMut::new();
+            VarU64Codec.encode(v, &mut buf)?;
+            let mut expected = BytesMut::new();
+            U64Codec.encode(v, &mut expected)?;
+            assert_eq!(buf, expected, "u64 `{v}` encoding mismatch");
+            assert_eq!(decode_value::<u64, NoopStream>(buf)?, v);
+        }
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn signed() -> anyhow::Result<()> {
+        for v in [0, -1, 0x3f, -0x40, 0x40, -0x41, i16::MIN, i16::MAX] {
+            let mut buf = BytesMut::new();
+            VarS16Codec.encode(v, &mut buf)?;
+            let mut expected = BytesMut::new();
+            S16Codec.encode(v, &mut expected)?;
+            assert_eq!(buf, expected, "s16 `{v}` encoding mismatch");
+            assert_eq!(decode_value::<i16, NoopStream>(buf)?, v);
+        }
+        for v in [0, -1, -0x40, 0x40, i32::MIN, i32::MAX] {
+            let mut buf = BytesMut::new();
+            VarS32Codec.encode(v, &mut buf)?;
+            assert_eq!(decode_value::<i32, NoopStream>(buf)?, v);
+        }
+        for v in [0, -1, -0x40, 0x40, i64::MIN, i64::MAX] {
+            let mut buf = BytesMut::new();
+            VarS64Codec.encode(v, &mut buf)?;
+            assert_eq!(decode_value::<i64, NoopStream>(buf)?, v);
+        }
+
+        // unused bits of the last byte must match the sign bit and it must not be continued
+        for buf in [b"\x80\x80\x02", b"\x80\x80\x7c", b"\xff\xff\x81"] {
+            let err = VarS16Codec
+                .decode(&mut BytesMut::from(&buf[..]))
+                .expect_err("overflowing value should fail to decode");
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+        assert!(VarS64Codec
+            .decode(&mut BytesMut::from(
+                &b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x7e"[..]
+            ))
+            .is_err());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn decode_lengths() -> anyhow::Result<()> {
+        /// Decodes `buf` both at once and byte-by-byte, as if refilled from the transport
+        fn decode<D: tokio_util::codec::Decoder<Error = std::io::Error>>(
+            mut dec: D,
+            buf: &[u8],
+        ) -> anyhow::Result<D::Item> {
+            let mut src = BytesMut::from(buf);
+            let v = dec.decode(&mut src)?.context("value incomplete")?;
+            ensure!(src.is_empty(), "`{}` bytes not consumed", src.len());
+
+            let mut src = BytesMut::new();
+            for b in &buf[..buf.len() - 1] {
+                src.put_u8(*b);
+                ensure!(dec.decode(&mut src)?.is_none(), "incomplete value decoded");
+            }
+            src.put_u8(buf[buf.len() - 1]);
+            ensure!(dec.decode(&mut src)?.is_some(), "value incomplete");
+            ensure!(src.is_empty(), "`{}` bytes not consumed", src.len());
+            Ok(v)
+        }
+
+        for n in 1..=10 {
+            for v in [
+                1_u64 << (7 * (n - 1)),
+                1_u64.checked_shl(7 * n).map_or(u64::MAX, |v| v - 1),
+            ] {
+                let mut buf = BytesMut::new();
+                VarU64Codec.encode(v, &mut buf)?;
+                assert_eq!(
+                    buf.len() as u32,
+                    n,
+                    "`{v}` should be encoded using `{n}` bytes"
+                );
+                assert_eq!(decode(VarU64Codec, &buf)?, v);
+                if let Ok(v) = u32::try_from(v) {
+                    assert_eq!(decode(VarU32Codec, &buf)?, v);
+                } else {
+                    assert!(decode(VarU32Codec, &buf).is_err(), "`{v}` overflows `u32`");
+                }
+            }
+        }
+
+        for n in 1..=10_u32 {
+            // smallest and largest magnitudes of each sign encoded using `n` bytes
+            let max = (1_i128 << (7 * n - 1)).min(1 << 63);
+            let min = if n == 1 { 0 } else { 1_i128 << (7 * n - 8) };
+            for v in [min, max - 1, -min - 1, -max] {
+                let v = i64::try_from(v)?;
+                let mut buf = BytesMut::new();
+                VarS64Codec.encode(v, &mut buf)?;
+                assert_eq!(
+                    buf.len() as u32,
+                    n,
+                    "`{v}` should be encoded using `{n}` bytes"
+                );
+                assert_eq!(decode(VarS64Codec, &buf)?, v);
+                if let Ok(v) = i32::try_from(v) {
+                    assert_eq!(decode(VarS32Codec, &buf)?, v);
+                } else {
+                    assert!(decode(VarS32Codec, &buf).is_err(), "`{v}` overflows `i32`");
+                }
+                if let Ok(v) = i16::try_from(v) {
+                    assert_eq!(decode(VarS16Codec, &buf)?, v);
+                } else {
+                    assert!(decode(VarS16Codec, &buf).is_err(), "`{v}` overflows `i16`");
+                }
+            }
+        }
+        for (v, n) in [(i16::MIN, 3), (i16::MAX, 3)] {
+            let mut buf = BytesMut::new();
+            VarS16Codec.encode(v, &mut buf)?;
+            assert_eq!(buf.len(), n, "`{v}` should be encoded using `{n}` bytes");
+            assert_eq!(decode(VarS16Codec, &buf)?, v);
+            assert_eq!(decode(VarS32Codec, &buf)?, v.into());
+            assert_eq!(decode(VarS64Codec, &buf)?, v.into());
+        }
+        for (v, n) in [(i32::MIN, 5), (i32::MAX, 5)] {
+            let mut buf = BytesMut::new();
+            VarS32Codec.encode(v, &mut buf)?;
+            assert_eq!(buf.len(), n, "`{v}` should be encoded using `{n}` bytes");
+            assert_eq!(decode(VarS32Codec, &buf)?, v);
+            assert_eq!(decode(VarS64Codec, &buf)?, v.into());
+        }
+        for (v, n) in [(i64::MIN, 10), (i64::MAX, 10)] {
+            let mut buf = BytesMut::new();
+            VarS64Codec.encode(v, &mut buf)?;
+            assert_eq!(buf.len(), n, "`{v}` should be encoded using `{n}` bytes");
+            assert_eq!(decode(VarS64Codec, &buf)?, v);
+        }
+
+        // overlong encodings are accepted as long as they fit in the maximum length
+        assert_eq!(decode(VarU32Codec, b"\x80\x00")?, 0);
+        assert_eq!(decode(VarU32Codec, b"\xff\x80\x80\x80\x00")?, 0x7f);
+        assert_eq!(
+            decode(VarU64Codec, b"\x81\x80\x80\x80\x80\x80\x80\x80\x80\x00")?,
+            1
+        );
+
+        // encodings exceeding the maximum length or value fail
+        assert!(decode(VarU32Codec, b"\x80\x80\x80\x80\x80\x00").is_err());
+        assert!(decode(VarU32Codec, b"\xff\xff\xff\xff\x1f").is_err());
+        assert!(decode(VarU64Codec, b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x00").is_err());
+        assert!(decode(VarU64Codec, b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x02").is_err());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn unsigned_many() -> anyhow::Result<()> {
+        // records with many small counters
+        let items: Vec<_> = (0..1 << 16)
+            .map(|i: u32| (i % 0x100, u64::from(i % 0x90)))
+            .collect();
+        let mut buf = BytesMut::new();
+        let start = std::time::Instant::now();
+        let mut enc = <&[(u32, u64)] as Encode<NoopStream>>::Encoder::default();
+        enc.encode(items.as_slice(), &mut buf)?;
+        trace!(elapsed = ?start.elapsed(), len = buf.len(), "encoded records");
+
+        let mut expected = BytesMut::new();
+        for (a, b) in &items {
+            U32Codec.encode(*a, &mut expected)?;
+            U64Codec.encode(*b, &mut expected)?;
+        }
+        // list length prefix
+        assert_eq!(&buf[..3], b"\x80\x80\x04");
+        assert_eq!(&buf[3..], expected);
+        assert_eq!(decode_value::<Vec<(u32, u64)>, NoopStream>(buf)?, items);
+        Ok(())
+    }
+
+    #[test_log::test]
+    #[ignore = "timing comparison, run explicitly"]
+    fn unsigned_list_decode() -> anyhow::Result<()> {
+        // compare with `--no-default-features` to measure the overhead of primitive codec tracing
+        let items: Vec<u32> = (0..1_000_000).collect();
+        let mut buf = BytesMut::new();
+        let mut enc = <Vec<u32> as Encode<NoopStream>>::Encoder::default();
+        enc.encode(items.clone(), &mut buf)?;
+        let start = std::time::Instant::now();
+        let decoded = decode_value::<Vec<u32>, NoopStream>(buf)?;
+        trace!(elapsed = ?start.elapsed(), "decoded `list<u32>`");
+        assert_eq!(decoded, items);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn size() -> anyhow::Result<()> {
+        let mut buf = BytesMut::new();
+        let mut enc = <(usize, isize) as Encode<NoopStream>>::Encoder::default();
+        enc.encode((usize::MAX, isize::MIN / 2), &mut buf)?;
+        let mut expected = BytesMut::new();
+        TupleEncoder::<(U64Codec, S64Codec)>::default()
+            .encode((u64::MAX, i64::MIN / 2), &mut expected)?;
+        assert_eq!(buf, expected);
+
+        let (a, b) = decode_value::<(usize, isize), NoopStream>(buf)?;
+        if cfg!(target_pointer_width = "64") {
+            assert_eq!(a, usize::MAX);
+            assert_eq!(b, isize::MIN / 2);
+        }
+
+        let v = decode_value::<Vec<usize>, NoopStream>(b"\x02\x00\x80\x01".as_slice())?;
+        assert_eq!(v, [0, 0x80]);
+        Ok(())
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test_log::test]
+    fn size_overflow() {
+        let mut buf = BytesMut::new();
+        U64Codec
+            .encode(u64::MAX, &mut buf)
+            .expect("failed to encode u64");
+        let err = decode_value::<usize, NoopStream>(buf).expect_err("decoding should overflow");
+        assert!(format!("{err:#}").contains("value overflows usize on this platform"));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn flatten() {
+        let batches = || {
+            stream::iter([
+                vec![b"ab".to_vec()],
+                vec![],
+                vec![b"c".to_vec(), vec![], b"de".to_vec()],
+            ])
+        };
+        let chunks: Vec<_> = flatten_byte_stream(batches(), false).collect().await;
+        assert_eq!(chunks, [Bytes::from("ab"), Bytes::from("cde")]);
+
+        let chunks: Vec<_> = flatten_byte_stream(batches(), true).collect().await;
+        assert_eq!(
+            chunks,
+            [Bytes::from("ab"), Bytes::from("c"), Bytes::from("de")]
+        );
+        assert_eq!(chunks.concat(), b"abcde");
+    }
+
+    #[test_log::test]
+    fn string_unchecked() -> anyhow::Result<()> {
+        let s = decode_value::<String, NoopStream>(b"\x04test".as_slice())?;
+        assert_eq!(s, "test");
+        decode_value::<String, NoopStream>(b"\x02\xc3\x28".as_slice())
+            .expect_err("invalid UTF-8 should fail to decode");
+
+        let mut buf = BytesMut::from(b"\x04test\x00".as_slice());
+        // SAFETY: the payload is valid UTF-8
+        let mut dec = unsafe { UncheckedStringDecoder::new() };
+        assert_eq!(dec.decode(&mut buf)?.as_deref(), Some("test"));
+        assert_eq!(dec.decode(&mut buf)?.as_deref(), Some(""));
+        assert!(buf.is_empty());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn encode_ref() -> anyhow::Result<()> {
+        let record = (0x42u8, String::from("test"), vec![1u32, 2], ());
+        // encode the same borrowed record for two subjects
+        let mut bufs = [BytesMut::new(), BytesMut::new()];
+        for buf in &mut bufs {
+            let mut enc = <&(u8, String, Vec<u32>, ()) as Encode<NoopStream>>::Encoder::default();
+            enc.encode(&record, buf)?;
+            if let Some(_f) = Deferred::<NoopStream>::take_deferred(&mut enc) {
+                bail!("no deferred write should have been returned");
+            }
+        }
+        let [a, b] = bufs;
+        assert_eq!(a, b);
+        assert_eq!(a.as_ref(), b"\x42\x04test\x02\x01\x02");
+        assert_eq!(
+            decode_value::<(u8, String, Vec<u32>, ()), NoopStream>(a)?,
+            record
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn bytes_zero_copy() -> anyhow::Result<()> {
+        let payload = Bytes::from_static(b"\x04test");
+        let buf = receive_bytes_zero_copy(&payload)?;
+        assert_eq!(buf, "test");
+        assert_eq!(buf.as_ptr(), payload[1..].as_ptr());
+
+        let payload = Bytes::from([b"\x80\x01".as_slice(), &[0x42; 0x80]].concat());
+        let buf = receive_bytes_zero_copy(&payload)?;
+        assert_eq!(buf, [0x42; 0x80].as_slice());
+        assert_eq!(buf.as_ptr(), payload[2..].as_ptr());
+
+        assert!(receive_bytes_zero_copy(&Bytes::from_static(b"\x00"))?.is_empty());
+        let err = receive_bytes_zero_copy(&Bytes::from_static(b"\x04te"))
+            .expect_err("incomplete payload should fail");
+        assert_eq!(err.to_string(), "incomplete payload");
+        let err = receive_bytes_zero_copy(&Bytes::from_static(b"\x01ab"))
+            .expect_err("trailing bytes should fail");
+        assert_eq!(err.to_string(), "payload contains `1` trailing bytes");
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn cstring() -> anyhow::Result<()> {
+        let v = CString::new("test")?;
+        let (buf, deferred) = encode_value::<_, NoopStream>(&v)?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x04test");
+        assert_eq!(encode_value::<_, NoopStream>(v.as_c_str())?.0, buf);
+        // wire-compatible with `string`
+        assert_eq!(decode_value::<String, NoopStream>(buf.clone())?, "test");
+        assert_eq!(decode_value::<CString, NoopStream>(buf)?, v);
+
+        let (buf, _) = encode_value::<_, NoopStream>(vec![CString::default(), v.clone()])?;
+        assert_eq!(buf.as_ref(), b"\x02\x00\x04test");
+        assert_eq!(
+            decode_value::<Vec<CString>, NoopStream>(buf)?,
+            [CString::default(), v]
+        );
+
+        let err = decode_value::<CString, NoopStream>(b"\x03a\x00b".as_slice())
+            .expect_err("interior nul byte should be rejected");
+        assert!(format!("{err:#}").contains("nul byte"), "{err:#}");
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn list_u8() -> anyhow::Result<()> {
+        // `list<u8>` is decoded using a bulk copy, rather than byte-by-byte
+        assert_eq!(
+            TypeId::of::<<Vec<u8> as Decode<NoopStream>>::Decoder>(),
+            TypeId::of::<ListDecoderU8>()
+        );
+
+        let items: Vec<u8> = (0..1 << 20).map(|i| (i % 0xff) as u8).collect();
+        let (buf, _) = encode_value::<_, NoopStream>(&items)?;
+
+        let start = std::time::Instant::now();
+        let bulk = decode_value::<Vec<u8>, NoopStream>(buf.as_ref())?;
+        let bulk_elapsed = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let generic = CoreVecDecoder::<U8Codec>::default()
+            .decode(&mut BytesMut::from(buf.as_ref()))?
+            .context("incomplete payload")?;
+        let generic_elapsed = start.elapsed();
+        trace!(?bulk_elapsed, ?generic_elapsed, "decoded 1 MiB `list<u8>`");
+
+        assert_eq!(bulk, items);
+        assert_eq!(generic, items);
+
+        // partial payloads are buffered until complete
+        let mut dec = ListDecoderU8::default();
+        let mut src = BytesMut::from(&buf[..0x1000]);
+        assert_eq!(dec.decode(&mut src)?, None);
+        src.extend_from_slice(&buf[0x1000..]);
+        assert_eq!(dec.decode(&mut src)?, Some(items));
+        assert!(src.is_empty());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn option_bytes() -> anyhow::Result<()> {
+        let cases = [
+            (None, b"\x00".as_slice()),
+            (Some(Bytes::new()), b"\x01\x00".as_slice()),
+            (Some(Bytes::from("foo")), b"\x01\x03foo".as_slice()),
+        ];
+        for (v, expected) in &cases {
+            let (buf, _) = encode_value::<_, NoopStream>(v)?;
+            assert_eq!(buf.as_ref(), *expected);
+            assert_eq!(decode_value::<Option<Bytes>, NoopStream>(buf)?, *v);
+
+            // incomplete payloads are not decoded as `None` or an empty value
+            for n in 0..expected.len() {
+                let mut src = BytesMut::from(&expected[..n]);
+                assert_eq!(
+                    <Option<Bytes> as Decode<NoopStream>>::Decoder::default().decode(&mut src)?,
+                    None
+                );
+            }
+        }
+
+        let values: Vec<_> = cases.into_iter().map(|(v, _)| v).collect();
+        let (buf, _) = encode_value::<_, NoopStream>(values.as_slice())?;
+        assert_eq!(buf.as_ref(), b"\x03\x00\x01\x00\x01\x03foo");
+        assert_eq!(decode_value::<Vec<Option<Bytes>>, NoopStream>(buf)?, values);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn map() -> anyhow::Result<()> {
+        let map: HashMap<String, u32> = (0..0x100).map(|i| (format!("key-{i}"), i)).collect();
+
+        let mut canonical = BytesMut::new();
+        MapEncoder::<NoopStream>::new(true).encode(map.clone(), &mut canonical)?;
+        for _ in 0..4 {
+            // use a fresh random hasher to vary iteration order
+            let map: HashMap<_, _> = map.clone().into_iter().collect();
+            let mut buf = BytesMut::new();
+            MapEncoder::<NoopStream>::new(true).encode(map, &mut buf)?;
+            assert_eq!(buf, canonical);
+        }
+
+        let mut buf = BytesMut::new();
+        let mut enc = <HashMap<String, u32> as Encode<NoopStream>>::Encoder::default();
+        enc.encode(map.clone(), &mut buf)?;
+        if let Some(_f) = Deferred::<NoopStream>::take_deferred(&mut enc) {
+            bail!("no deferred write should have been returned");
+        }
+        assert_eq!(buf.len(), canonical.len());
+        assert_eq!(decode_value::<HashMap<String, u32>, NoopStream>(buf)?, map);
+        assert_eq!(
+            decode_value::<HashMap<String, u32>, NoopStream>(canonical)?,
+            map
+        );
+
+        let (buf, _) = encode_value::<_, NoopStream>(vec![
+            ("foo".to_string(), 1_u32),
+            ("bar".to_string(), 2),
+            ("foo".to_string(), 3),
+        ])?;
+        let err = decode_value::<HashMap<String, u32>, NoopStream>(buf)
+            .expect_err("duplicate keys should be rejected");
+        assert_eq!(
+            err.downcast_ref::<std::io::Error>()
+                .map(std::io::Error::kind),
+            Some(std::io::ErrorKind::InvalidData)
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn canonical() -> anyhow::Result<()> {
+        fn digest(buf: &[u8]) -> u64 {
+            let mut hasher = std::hash::DefaultHasher::new();
+            buf.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let map: HashMap<String, u32> = (0..0x100).map(|i| (format!("key-{i}"), i)).collect();
+        let value = |map: &HashMap<String, u32>| {
+            // use a fresh random hasher to vary iteration order
+            let nested: HashMap<_, _> = map.clone().into_iter().collect();
+            (u64::MAX, vec![Some(nested)], map.clone())
+        };
+        let a = encode_value_canonical::<_, NoopStream>(value(&map))?;
+        let b = encode_value_canonical::<_, NoopStream>(value(&map))?;
+        assert_eq!(a, b);
+        assert_eq!(digest(&a), digest(&b));
+        assert_eq!(
+            decode_value::<
+                (u64, Vec<Option<HashMap<String, u32>>>, HashMap<String, u32>),
+                NoopStream,
+            >(a)?,
+            value(&map)
+        );
+        assert!(!is_canonical(), "canonical mode should be reset");
+
+        // asynchronous values are rejected, even if ready
+        let fut: Pin<Box<dyn Future<Output = u8> + Send>> = Box::pin(async { 42 });
+        let err = encode_value_canonical::<_, NoopStream>((1_u8, fut))
+            .expect_err("future should be rejected");
+        assert!(format!("{err:#}").contains("cannot be encoded canonically"));
+
+        let items: Pin<Box<dyn Stream<Item = Vec<u8>> + Send>> = Box::pin(stream::iter([vec![1]]));
+        encode_value_canonical::<_, NoopStream>(items).expect_err("stream should be rejected");
+        assert!(!is_canonical(), "canonical mode should be reset");
         Ok(())
     }
-}
 
-impl<W> Encode<W> for Pin<Box<dyn Stream<Item = Bytes> + Send>>
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderBytes<W>;
-}
+    #[test_log::test]
+    fn canonical_value() -> anyhow::Result<()> {
+        type Value = (Option<Result<f64, String>>, Vec<u32>);
+
+        let values: Vec<Value> = vec![
+            (Some(Ok(1.5)), vec![]),
+            (None, vec![1, 2]),
+            (Some(Err("foo".into())), vec![0x80]),
+            (Some(Ok(-0.0)), vec![]),
+            (Some(Ok(0.0)), vec![]),
+            (Some(Err("bar".into())), vec![0x7f]),
+            (None, vec![1, 2]),
+            (Some(Ok(f64::NAN)), vec![]),
+            (Some(Err("foo".into())), vec![0x80]),
+        ];
+        let canonical = |values: &[Value]| {
+            values
+                .iter()
+                .cloned()
+                .map(CanonicalValue::new::<NoopStream>)
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        let mut sorted = canonical(&values)?;
+        sorted.sort();
+        for (a, b) in zip(&sorted, &sorted[1..]) {
+            assert!(a.as_bytes() <= b.as_bytes());
+            assert_eq!(a == b, a.as_bytes() == b.as_bytes());
+        }
+        assert_eq!(sorted[0].get(), &(None, vec![1, 2]));
+        assert_eq!(sorted[0], sorted[1]);
+
+        // order does not depend on the input order
+        let mut reversed = canonical(&values)?;
+        reversed.reverse();
+        reversed.sort();
+        assert_eq!(reversed, sorted);
+        assert!(zip(&reversed, &sorted).all(|(a, b)| a.as_bytes() == b.as_bytes()));
+
+        // duplicates are removed from sets, `0.0` and `-0.0` are distinct
+        let set: std::collections::BTreeSet<_> = canonical(&values)?.into_iter().collect();
+        assert_eq!(set.len(), 7);
+
+        // set elements are ordered and deduplicated by their independently computed encodings
+        let mut encoded = values
+            .iter()
+            .cloned()
+            .map(|v| encode_value::<_, NoopStream>(v).map(|(buf, _)| buf))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        encoded.sort();
+        encoded.dedup();
+        assert_eq!(encoded.len(), set.len());
+        for (v, buf) in zip(set, encoded) {
+            assert_eq!(v.as_bytes(), &buf);
+            let (reencoded, _) = encode_value::<_, NoopStream>(v.into_inner())?;
+            assert_eq!(reencoded, buf);
+        }
 
-pub struct StreamEncoderRead<W> {
-    deferred: Option<DeferredFn<W>>,
-}
+        Ok(())
+    }
 
-impl<W> Default for StreamEncoderRead<W> {
-    fn default() -> Self {
-        Self { deferred: None }
+    /// In-memory reader, which counts the number of times it was indexed
+    struct CountingReader {
+        buf: Bytes,
+        indexed: Arc<std::sync::atomic::AtomicUsize>,
     }
-}
 
-impl<W> Deferred<W> for StreamEncoderRead<W> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
-        self.deferred.take()
+    impl crate::Index<Self> for CountingReader {
+        fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+            trace!(?path, "indexing reader");
+            self.indexed
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(Self {
+                buf: self.buf.clone(),
+                indexed: Arc::clone(&self.indexed),
+            })
+        }
     }
-}
 
-impl<W, S> tokio_util::codec::Encoder<S> for StreamEncoderRead<W>
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-    S: AsyncRead + Send + Unpin + 'static,
-{
-    type Error = std::io::Error;
+    impl AsyncRead for CountingReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> core::task::Poll<std::io::Result<()>> {
+            let n = buf.remaining().min(self.buf.len());
+            buf.put_slice(&self.buf.split_to(n));
+            core::task::Poll::Ready(Ok(()))
+        }
+    }
 
-    #[instrument(level = "trace", skip(self, items), fields(ty = "stream<u8>"))]
-    fn encode(&mut self, mut items: S, dst: &mut BytesMut) -> std::io::Result<()> {
-        // TODO: Check if reader is resolved
-        dst.reserve(1);
-        dst.put_u8(0x00);
-        self.deferred = Some(Box::new(|w, path| {
-            Box::pin(async move {
-                let mut root = w.index(&path).map_err(std::io::Error::other)?;
-                let mut buf = BytesMut::default();
-                let mut chunk = BytesMut::default();
-                loop {
-                    select! {
-                        res = items.read_buf(&mut chunk) => {
-                            let n = res?;
-                            if n == 0 {
-                                trace!("writing stream end");
-                                buf.reserve(1);
-                                buf.put_u8(0x00);
-                                return root.write_all(&buf).await
-                            }
-                            let n = u32::try_from(n).map_err(|err| {
-                                std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
-                            })?;
-                            trace!(n, "encoding chunk length");
-                            Leb128Encoder.encode(n, &mut buf)?;
-                            buf.extend_from_slice(&chunk);
-                            chunk.clear();
-                        }
-                        res = root.write(&buf), if !buf.is_empty() => {
-                            let n = res?;
-                            buf.advance(n);
-                        }
+    #[test_log::test(tokio::test)]
+    async fn stream_result() -> anyhow::Result<()> {
+        let mut buf = BytesMut::new();
+        for i in 0..1000 {
+            let chunk: Vec<Result<u32, String>> = (0..10)
+                .map(|j| {
+                    if j % 2 == 0 {
+                        Ok(i * 10 + j)
+                    } else {
+                        Err(format!("{i}"))
                     }
-                }
-            })
-        }));
+                })
+                .collect();
+            let mut enc = <Vec<Result<u32, String>> as Encode<CountingReader>>::Encoder::default();
+            enc.encode(chunk, &mut buf)?;
+        }
+        // stream end
+        buf.put_u8(0);
+
+        let indexed = Arc::default();
+        let r = Arc::new(CountingReader {
+            buf: buf.freeze(),
+            indexed: Arc::clone(&indexed),
+        });
+        let mut dec = <Pin<Box<dyn Stream<Item = Vec<Result<u32, String>>> + Send>> as Decode<
+            CountingReader,
+        >>::Decoder::default();
+        let st = dec
+            .decode(&mut BytesMut::from(b"\x00".as_slice()))?
+            .context("pending stream should be decoded")?;
+        let io = dec.take_deferred().context("stream should be deferred")?;
+        let (res, items) = join!(io(r, vec![0]), st.collect::<Vec<_>>());
+        res?;
+        let items = items.concat();
+        assert_eq!(items.len(), 10_000);
+        assert_eq!(items[42], Ok(42));
+        assert_eq!(items[43], Err("4".into()));
+        // per-item results are synchronous and must not be indexed
+        assert_eq!(indexed.load(std::sync::atomic::Ordering::Relaxed), 1);
         Ok(())
     }
-}
 
-impl<W> Encode<W> for Pin<Box<dyn AsyncRead + Send>>
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderRead<W>;
-}
+    #[derive(Clone, Default)]
+    struct PathWriter(
+        Arc<std::sync::Mutex<HashMap<Vec<usize>, Vec<u8>>>>,
+        Vec<usize>,
+    );
 
-impl<T, W> Encode<W> for std::io::Cursor<T>
-where
-    T: AsRef<[u8]> + Send + Unpin + 'static,
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderRead<W>;
-}
+    impl crate::Index<Self> for PathWriter {
+        fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+            Ok(Self(
+                Arc::clone(&self.0),
+                [self.1.as_slice(), path].concat(),
+            ))
+        }
+    }
 
-impl<W> Encode<W> for tokio::io::Empty
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderRead<W>;
-}
+    impl AsyncWrite for PathWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+            buf: &[u8],
+        ) -> core::task::Poll<std::io::Result<usize>> {
+            let mut paths = self.0.lock().unwrap();
+            paths
+                .entry(self.1.clone())
+                .or_default()
+                .extend_from_slice(buf);
+            core::task::Poll::Ready(Ok(buf.len()))
+        }
 
-#[cfg(feature = "io-std")]
-impl<W> Encode<W> for tokio::io::Stdin
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderRead<W>;
-}
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+        ) -> core::task::Poll<std::io::Result<()>> {
+            core::task::Poll::Ready(Ok(()))
+        }
 
-#[cfg(feature = "fs")]
-impl<W> Encode<W> for tokio::fs::File
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderRead<W>;
-}
+        fn poll_shutdown(
+            self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+        ) -> core::task::Poll<std::io::Result<()>> {
+            core::task::Poll::Ready(Ok(()))
+        }
+    }
 
-#[cfg(feature = "net")]
-impl<W> Encode<W> for tokio::net::TcpStream
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderRead<W>;
-}
+    /// Reader serving each path from a separate duplex stream, like a per-subject subscription
+    struct PathReader {
+        paths: Arc<std::sync::Mutex<HashMap<Vec<usize>, tokio::io::DuplexStream>>>,
+        path: Vec<usize>,
+        io: Option<tokio::io::DuplexStream>,
+    }
 
-#[cfg(all(unix, feature = "net"))]
-impl<W> Encode<W> for tokio::net::UnixStream
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderRead<W>;
-}
+    impl crate::Index<Self> for PathReader {
+        fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
+            let path = [self.path.as_slice(), path].concat();
+            let io = self
+                .paths
+                .lock()
+                .unwrap()
+                .remove(&path)
+                .with_context(|| format!("path {path:?} is not available"))?;
+            Ok(Self {
+                paths: Arc::clone(&self.paths),
+                path,
+                io: Some(io),
+            })
+        }
+    }
 
-#[cfg(all(unix, feature = "net"))]
-impl<W> Encode<W> for tokio::net::unix::pipe::Receiver
-where
-    W: AsyncWrite + crate::Index<W> + Send + Sync + Unpin + 'static,
-{
-    type Encoder = StreamEncoderRead<W>;
-}
+    impl AsyncRead for PathReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut core::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> core::task::Poll<std::io::Result<()>> {
+            let io = self.io.as_mut().expect("root reader should not be read");
+            Pin::new(io).poll_read(cx, buf)
+        }
+    }
 
-pub struct StreamDecoder<T, R>
-where
-    T: Decode<R>,
-{
-    dec: T::ListDecoder,
-    deferred: Option<DeferredFn<R>>,
-    _ty: PhantomData<T>,
-}
+    #[test_log::test(tokio::test)]
+    async fn stream_receiver_drop() -> anyhow::Result<()> {
+        // peers are kept connected, but idle, so that receive tasks can only stop by observing
+        // the dropped stream
+
+        /// Returns a [`PathReader`] serving path `[0]` and the peer end of it
+        fn reader() -> (Arc<PathReader>, tokio::io::DuplexStream) {
+            let (tx, rx) = tokio::io::duplex(64);
+            let r = Arc::new(PathReader {
+                paths: Arc::new(std::sync::Mutex::new(HashMap::from([(vec![0], rx)]))),
+                path: vec![],
+                io: None,
+            });
+            (r, tx)
+        }
 
-impl<T, R> Default for StreamDecoder<T, R>
-where
-    T: Decode<R>,
-{
-    fn default() -> Self {
-        Self {
-            dec: T::ListDecoder::default(),
-            deferred: None,
-            _ty: PhantomData,
+        /// Asserts that the receive task `io` stops promptly with a "receiver closed" error
+        async fn assert_stopped(
+            io: tokio::task::JoinHandle<std::io::Result<()>>,
+        ) -> anyhow::Result<()> {
+            let err = tokio::time::timeout(core::time::Duration::from_secs(5), io)
+                .await
+                .context("receive task did not stop after the stream was dropped")??
+                .expect_err("receive task should fail");
+            assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+            Ok(())
         }
+
+        let (r, mut peer) = reader();
+        let mut dec =
+            <Pin<Box<dyn Stream<Item = Vec<u32>> + Send>> as Decode<PathReader>>::Decoder::default(
+            );
+        let mut st = dec
+            .decode(&mut BytesMut::from(b"\x00".as_slice()))?
+            .context("pending stream should be decoded")?;
+        let io = tokio::spawn(dec.take_deferred().context("stream should be deferred")?(
+            r,
+            vec![0],
+        ));
+        peer.write_all(b"\x02\x01\x02").await?;
+        assert_eq!(st.next().await, Some(vec![1, 2]));
+        drop(st);
+        assert_stopped(io).await?;
+
+        let (r, mut peer) = reader();
+        let mut dec =
+            <Pin<Box<dyn Stream<Item = Bytes> + Send>> as Decode<PathReader>>::Decoder::default();
+        let mut st = dec
+            .decode(&mut BytesMut::from(b"\x00".as_slice()))?
+            .context("pending stream should be decoded")?;
+        let io = tokio::spawn(dec.take_deferred().context("stream should be deferred")?(
+            r,
+            vec![0],
+        ));
+        peer.write_all(b"\x03foo").await?;
+        assert_eq!(st.next().await.as_deref(), Some(b"foo".as_slice()));
+        drop(st);
+        assert_stopped(io).await?;
+
+        let (r, mut peer) = reader();
+        let mut dec = <Pin<Box<dyn AsyncRead + Send>> as Decode<PathReader>>::Decoder::default();
+        let mut st = dec
+            .decode(&mut BytesMut::from(b"\x00".as_slice()))?
+            .context("pending stream should be decoded")?;
+        let io = tokio::spawn(dec.take_deferred().context("stream should be deferred")?(
+            r,
+            vec![0],
+        ));
+        peer.write_all(b"\x03foo").await?;
+        let mut buf = [0; 3];
+        st.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"foo");
+        drop(st);
+        assert_stopped(io).await?;
+        Ok(())
     }
-}
 
-impl<T, R> Deferred<R> for StreamDecoder<T, R>
-where
-    T: Decode<R>,
-{
-    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-        self.deferred.take()
+    #[test_log::test(tokio::test)]
+    async fn stream_future_race() -> anyhow::Result<()> {
+        type Item = Pin<Box<dyn Future<Output = u32> + Send>>;
+
+        // yield once, so that the stream is not encoded eagerly
+        let items = stream::once(tokio::task::yield_now())
+            .filter_map(|()| async { None })
+            .chain(stream::iter([vec![0_u32, 1], vec![2, 3], vec![4]].map(
+                |chunk| {
+                    chunk
+                        .into_iter()
+                        .map(|v| Box::pin(async move { v }) as Item)
+                        .collect::<Vec<_>>()
+                },
+            )));
+        let mut enc =
+            <Pin<Box<dyn Stream<Item = Vec<Item>> + Send>> as Encode<PathWriter>>::Encoder::default(
+            );
+        let mut buf = BytesMut::new();
+        enc.encode(Box::pin(items), &mut buf)?;
+        assert_eq!(buf.as_ref(), b"\x00");
+        let w = PathWriter::default();
+        let io = enc.take_deferred().context("stream should be deferred")?;
+        io(Arc::new(w.clone()), vec![0]).await?;
+        let mut written = mem::take(&mut *w.0.lock().unwrap());
+        let frames = written
+            .remove([0].as_slice())
+            .context("stream frames missing")?;
+        // every element must be transmitted on its own path, regardless of chunking
+        assert_eq!(frames, b"\x02\x00\x00\x02\x00\x00\x01\x00\x00");
+        let mut written: Vec<_> = written.into_iter().collect();
+        written.sort();
+        assert_eq!(
+            written,
+            (0..5)
+                .map(|i| (vec![0, i], vec![i as u8]))
+                .collect::<Vec<_>>()
+        );
+
+        let mut paths = HashMap::new();
+        let mut txs = Vec::new();
+        for path in [
+            vec![0],
+            vec![0, 0],
+            vec![0, 1],
+            vec![0, 2],
+            vec![0, 3],
+            vec![0, 4],
+        ] {
+            let (tx, rx) = tokio::io::duplex(64);
+            paths.insert(path.clone(), rx);
+            txs.push((path, tx));
+        }
+        let r = Arc::new(PathReader {
+            paths: Arc::new(std::sync::Mutex::new(paths)),
+            path: vec![],
+            io: None,
+        });
+        let mut dec =
+            <Pin<Box<dyn Stream<Item = Vec<Item>> + Send>> as Decode<PathReader>>::Decoder::default(
+            );
+        let mut st = dec
+            .decode(&mut BytesMut::from(b"\x00".as_slice()))?
+            .context("pending stream should be decoded")?;
+        let io = tokio::spawn(dec.take_deferred().context("stream should be deferred")?(
+            r,
+            vec![0],
+        ));
+
+        // deliver all stream frames, including the end marker, before any of the elements
+        let mut txs = txs.into_iter();
+        let (_, mut root) = txs.next().unwrap();
+        root.write_all(&frames).await?;
+        let mut futs = Vec::new();
+        while futs.len() < 5 {
+            futs.extend(st.next().await.context("stream chunk missing")?);
+        }
+        for (path, mut tx) in txs.rev() {
+            tx.write_all(&[path[1] as u8]).await?;
+        }
+        let values = futures::future::join_all(futs).await;
+        assert_eq!(values, [0, 1, 2, 3, 4]);
+        io.await??;
+        assert!(st.next().await.is_none());
+        Ok(())
     }
-}
 
-#[instrument(level = "trace", skip(dec, r, tx), ret)]
-async fn handle_deferred_stream<C, T, R>(
-    dec: C,
-    r: Arc<R>,
-    mut path: Vec<usize>,
-    tx: mpsc::Sender<Vec<T>>,
-) -> std::io::Result<()>
-where
-    C: tokio_util::codec::Decoder<Item = T> + Deferred<R>,
-    R: AsyncRead + crate::Index<R> + Send + Unpin + 'static,
-    std::io::Error: From<C::Error>,
-{
-    let dec = ListDecoder::new(dec);
-    let indexed = r.index(&path).map_err(std::io::Error::other)?;
-    let mut framed = FramedRead::new(indexed, dec);
-    let mut tasks = JoinSet::new();
-    let mut i = 0_usize;
-    loop {
-        trace!("receiving stream chunk");
-        select! {
-            Some(chunk) = framed.next() => {
-                let chunk = chunk?;
-                if chunk.is_empty() {
-                    trace!("received stream end");
-                    while let Some(res) = tasks.join_next().await {
-                        res??;
-                    }
-                    return Ok(())
-                }
-                let end = i.checked_add(chunk.len()).ok_or_else(|| {
-                    std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        "stream element index would overflow usize",
-                    )
-                })?;
-                trace!(i, end, "received stream chunk");
-                tx.send(chunk).await.map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::BrokenPipe, "stream receiver closed")
-                })?;
-                for (i, deferred) in zip(i.., mem::take(&mut framed.decoder_mut().deferred)) {
-                    if let Some(deferred) = deferred {
-                        trace!(i, "handling async read");
-                        path.push(i);
-                        let indexed = r.index(&path).map_err(std::io::Error::other)?;
-                        trace!("spawning receive task");
-                        tasks.spawn(deferred(indexed.into(), path.clone()));
-                        path.pop();
-                    }
+    #[test_log::test(tokio::test)]
+    async fn future_stream() -> anyhow::Result<()> {
+        type Item = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+
+        // yield once, so that neither the future nor the stream are encoded eagerly
+        let items = stream::once(tokio::task::yield_now())
+            .filter_map(|()| async { None })
+            .chain(stream::iter([Bytes::from("foo"), Bytes::from("bar")]));
+        let fut = async move {
+            tokio::task::yield_now().await;
+            Box::pin(items) as Item
+        };
+        let (buf, io) = encode_value::<_, PathWriter>(
+            Box::pin(fut) as Pin<Box<dyn Future<Output = Item> + Send>>
+        )?;
+        assert_eq!(buf.as_ref(), b"\x00");
+        let w = PathWriter::default();
+        io.context("future should be deferred")?(Arc::new(w.clone()), vec![0]).await?;
+        let mut written = mem::take(&mut *w.0.lock().unwrap());
+        // future resolves to a pending stream, the elements of which are sent on a nested path
+        assert_eq!(
+            written.remove([0].as_slice()).as_deref(),
+            Some(b"\x00".as_slice())
+        );
+        let frames = written
+            .remove([0, 0].as_slice())
+            .context("stream frames missing")?;
+        assert!(written.is_empty(), "unexpected paths written: {written:?}");
+
+        let mut paths = HashMap::new();
+        let mut txs = Vec::new();
+        for path in [vec![0], vec![0, 0]] {
+            let (tx, rx) = tokio::io::duplex(64);
+            paths.insert(path, rx);
+            txs.push(tx);
+        }
+        let r = Arc::new(PathReader {
+            paths: Arc::new(std::sync::Mutex::new(paths)),
+            path: vec![],
+            io: None,
+        });
+        let mut dec =
+            <Pin<Box<dyn Future<Output = Item> + Send>> as Decode<PathReader>>::Decoder::default();
+        let fut = dec
+            .decode(&mut BytesMut::from(buf.as_ref()))?
+            .context("pending future should be decoded")?;
+        let io = tokio::spawn(dec.take_deferred().context("future should be deferred")?(
+            r,
+            vec![0],
+        ));
+
+        // resolve the future before any of the stream frames are available
+        let mut txs = txs.into_iter();
+        let mut root = txs.next().unwrap();
+        root.write_all(b"\x00").await?;
+        let mut st = fut.await;
+        let mut nested = txs.next().unwrap();
+        let (res, ()) = join!(nested.write_all(&frames), async {
+            assert_eq!(st.next().await.as_deref(), Some(b"foo".as_slice()));
+            assert_eq!(st.next().await.as_deref(), Some(b"bar".as_slice()));
+        });
+        res?;
+        assert!(st.next().await.is_none());
+        io.await??;
+        Ok(())
+    }
+
+    #[cfg(feature = "array")]
+    #[test_log::test]
+    fn array() -> anyhow::Result<()> {
+        let mat4: [f32; 16] = core::array::from_fn(|i| i as f32 * 0.5);
+        let (tuple, deferred) = encode_value::<_, NoopStream>((
+            0.0_f32, 0.5_f32, 1.0_f32, 1.5_f32, 2.0_f32, 2.5_f32, 3.0_f32, 3.5_f32, 4.0_f32,
+            4.5_f32, 5.0_f32, 5.5_f32, 6.0_f32, 6.5_f32, 7.0_f32, 7.5_f32,
+        ))?;
+        assert!(deferred.is_none());
+
+        let (buf, deferred) = encode_value::<_, NoopStream>(mat4)?;
+        assert!(deferred.is_none());
+        assert_eq!(buf, tuple, "array must be wire-compatible with a tuple");
+        let (buf, deferred) = encode_value::<_, NoopStream>(&mat4)?;
+        assert!(deferred.is_none());
+        assert_eq!(buf, tuple);
+
+        let v: [f32; 16] = decode_value::<_, NoopStream>(tuple.as_ref())?;
+        assert_eq!(v, mat4);
+
+        let vecs = vec![[1.0_f32, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let (buf, _) = encode_value::<_, NoopStream>(vecs.clone())?;
+        let v: Vec<[f32; 3]> = decode_value::<_, NoopStream>(buf.as_ref())?;
+        assert_eq!(v, vecs);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn duration() -> anyhow::Result<()> {
+        let d = Duration::new(90, 500_000_000);
+
+        let (buf, _) = encode_value::<_, NoopStream>(d)?;
+        assert_eq!(buf, encode_value::<_, NoopStream>(90_500_000_000_u64)?.0);
+        assert_eq!(decode_value::<Duration, NoopStream>(buf)?, d);
+
+        let (buf, _) = encode_value::<_, NoopStream>(DurationMillis(d))?;
+        assert_eq!(buf, encode_value::<_, NoopStream>(90_500_u64)?.0);
+        assert_eq!(
+            decode_value::<DurationMillis, NoopStream>(buf)?,
+            DurationMillis(d)
+        );
+
+        let (buf, _) = encode_value::<_, NoopStream>(DurationRecord(d))?;
+        assert_eq!(
+            buf,
+            encode_value::<_, NoopStream>((90_u64, 500_000_000_u32))?.0
+        );
+        assert_eq!(
+            decode_value::<DurationRecord, NoopStream>(buf)?,
+            DurationRecord(d)
+        );
+
+        let ds = vec![Duration::ZERO, d];
+        let (buf, _) = encode_value::<_, NoopStream>(&ds)?;
+        assert_eq!(decode_value::<Vec<Duration>, NoopStream>(buf)?, ds);
+
+        // overflow
+        assert!(encode_value::<_, NoopStream>(Duration::MAX).is_err());
+        assert!(encode_value::<_, NoopStream>(DurationMillis(Duration::MAX)).is_err());
+        let (buf, _) = encode_value::<_, NoopStream>(DurationRecord(Duration::MAX))?;
+        assert_eq!(
+            decode_value::<DurationRecord, NoopStream>(buf)?,
+            DurationRecord(Duration::MAX)
+        );
+        let (buf, _) = encode_value::<_, NoopStream>((0_u64, 1_000_000_000_u32))?;
+        assert!(decode_value::<DurationRecord, NoopStream>(buf).is_err());
+
+        // sub-millisecond precision is truncated
+        let (buf, _) = encode_value::<_, NoopStream>(DurationMillis(Duration::from_micros(1_999)))?;
+        assert_eq!(
+            decode_value::<DurationMillis, NoopStream>(buf)?,
+            DurationMillis(Duration::from_millis(1))
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn monotonic_instant() -> anyhow::Result<()> {
+        for ns in [0, 0x7f, 0x80, 1_000_000_000, u64::MAX] {
+            let (buf, deferred) = encode_value::<_, NoopStream>((MonotonicInstant(ns),))?;
+            assert!(deferred.is_none());
+            let (v,) = decode_value::<(MonotonicInstant,), NoopStream>(buf.clone())?;
+            assert_eq!(v, MonotonicInstant(ns));
+            // wire-compatible with `u64`
+            let (expected, _) = encode_value::<_, NoopStream>((ns,))?;
+            assert_eq!(buf, expected);
+        }
+
+        let instants = vec![MonotonicInstant(1), MonotonicInstant(u64::MAX)];
+        let (buf, _) = encode_value::<_, NoopStream>(&instants)?;
+        let v = decode_value::<Vec<MonotonicInstant>, NoopStream>(buf)?;
+        assert_eq!(v, instants);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn ip_addr() -> anyhow::Result<()> {
+        let v4 = Ipv4Addr::new(192, 168, 1, 1);
+        let (buf, deferred) = encode_value::<_, NoopStream>(v4)?;
+        assert!(deferred.is_none());
+        // octets in network order, not host order
+        assert_eq!(buf.as_ref(), b"\xc0\xa8\x01\x01");
+        assert_eq!(
+            buf,
+            encode_value::<_, NoopStream>((192_u8, 168_u8, 1_u8, 1_u8))?.0
+        );
+        assert_eq!(decode_value::<Ipv4Addr, NoopStream>(buf)?, v4);
+
+        // mix of multi-byte, single-byte and zero segments
+        let v6: Ipv6Addr = "2001:db8::ff00:42:8329".parse()?;
+        let (buf, _) = encode_value::<_, NoopStream>(v6)?;
+        assert_eq!(
+            buf.as_ref(),
+            b"\x81\x40\xb8\x1b\x00\x00\x00\x80\xfe\x03\x42\xa9\x86\x02"
+        );
+        assert_eq!(
+            buf,
+            encode_value::<_, NoopStream>((
+                0x2001_u16, 0x0db8_u16, 0_u16, 0_u16, 0_u16, 0xff00_u16, 0x0042_u16, 0x8329_u16
+            ))?
+            .0
+        );
+        assert_eq!(decode_value::<Ipv6Addr, NoopStream>(buf)?, v6);
+
+        let (buf, _) = encode_value::<_, NoopStream>(IpAddr::V4(v4))?;
+        assert_eq!(buf.as_ref(), b"\x00\xc0\xa8\x01\x01");
+        let (buf, _) = encode_value::<_, NoopStream>(IpAddr::V6(v6))?;
+        assert_eq!(buf[0], 0x01);
+        assert_eq!(
+            decode_value::<IpAddr, NoopStream>(buf.clone())?,
+            IpAddr::V6(v6)
+        );
+
+        // incomplete addresses are not consumed
+        for n in 0..buf.len() {
+            let mut src = BytesMut::from(&buf[..n]);
+            assert!(IpAddrCodec.decode(&mut src)?.is_none());
+            assert_eq!(src.len(), n);
+        }
+        IpAddrCodec
+            .decode(&mut BytesMut::from(b"\x02".as_slice()))
+            .expect_err("unknown discriminant should fail");
+
+        let addrs = vec![
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ];
+        let (buf, _) = encode_value::<_, NoopStream>(&addrs)?;
+        assert_eq!(decode_value::<Vec<IpAddr>, NoopStream>(buf)?, addrs);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn bytes_mut() -> anyhow::Result<()> {
+        let (buf, deferred) = encode_value::<_, NoopStream>(BytesMut::from("foo"))?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x03foo");
+        assert_eq!(buf, encode_value::<_, NoopStream>(Bytes::from("foo"))?.0);
+
+        let mut v = decode_value::<BytesMut, NoopStream>(buf)?;
+        v[0] = b'b';
+        v.extend_from_slice(b"bar");
+        assert_eq!(v.as_ref(), b"boobar");
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf.as_ref(), b"\x06boobar");
+
+        let v = vec![
+            BytesMut::from("foo"),
+            BytesMut::new(),
+            BytesMut::from("bar"),
+        ];
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf.as_ref(), b"\x03\x03foo\x00\x03bar");
+        assert_eq!(decode_value::<Vec<BytesMut>, NoopStream>(buf)?, v);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn drain_list() -> anyhow::Result<()> {
+        // `list<tuple<string, u32>>` with invalid UTF-8 in the second element, followed by a `u32`
+        let buf = b"\x03\x03foo\x01\x02\xff\xfe\x80\x01\x03baz\x03\x2a";
+        let framing = Framing::Tuple(vec![Framing::Bytes, Framing::Leb128]);
+        assert_eq!(framing.len(&buf[1..])?, Some(5));
+        assert_eq!(framing.len(&buf[1..4])?, None);
+        assert_eq!(Framing::List(Box::new(framing.clone())).len(buf)?, Some(16));
+
+        let mut dec = DrainListDecoder::new(
+            TupleDecoder::<(CoreNameDecoder, U32Codec), _>::default(),
+            framing.clone(),
+        );
+        let mut src = BytesMut::from(&buf[..]);
+        let err = dec
+            .decode(&mut src)?
+            .context("list not decoded")?
+            .expect_err("invalid UTF-8 element decoded");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(U32Codec.decode(&mut src)?, Some(42));
+        assert!(src.is_empty());
+
+        // the decoder can be reused and elements can arrive byte-by-byte
+        let buf = b"\x02\x03foo\x01\x03bar\x02\x03\x2a";
+        let mut src = BytesMut::new();
+        let mut decoded = None;
+        for b in buf {
+            src.put_u8(*b);
+            if decoded.is_none() {
+                decoded = dec.decode(&mut src)?;
+            }
+        }
+        let decoded = decoded.context("list not decoded")??;
+        assert_eq!(decoded, [("foo".into(), 1), ("bar".into(), 2)]);
+        assert_eq!(U32Codec.decode(&mut src)?, Some(3));
+        assert_eq!(U32Codec.decode(&mut src)?, Some(42));
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn framing_scan() -> anyhow::Result<()> {
+        // `list<tuple<string, list<u32>, tuple<>>>`
+        let framing = Framing::List(Box::new(Framing::Tuple(vec![
+            Framing::Bytes,
+            Framing::List(Box::new(Framing::Leb128)),
+            Framing::Tuple(vec![]),
+        ])));
+        let (buf, _) = encode_value::<_, NoopStream>(vec![
+            ("foo", vec![1_u32, 0x80], ()),
+            ("", vec![], ()),
+            ("bar", vec![0x4000], ()),
+        ])?;
+        assert_eq!(framing.len(&buf)?, Some(buf.len()));
+
+        // values scanned once are not scanned again, when the scan is resumed
+        let mut scan = FramingScan::default();
+        for i in 0..buf.len() {
+            assert_eq!(scan.len(&framing, &buf[..i])?, None);
+        }
+        // only the last `u32` remains to be scanned
+        assert_eq!(scan.offset, buf.len() - 3);
+        assert_eq!(scan.len(&framing, &buf)?, Some(buf.len()));
+
+        let err = Framing::Leb128
+            .len(&[0x80; MAX_LEB128_LEN])
+            .expect_err("overlong LEB128 integer should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn decode_counted() -> anyhow::Result<()> {
+        let (a, _) = encode_value::<_, NoopStream>(("test", vec![1_u32, 0x80]))?;
+        let (b, _) = encode_value::<_, NoopStream>((0x42_u8, "foo"))?;
+        let buf = [a.as_ref(), b.as_ref()].concat();
+
+        let ((s, v), n) = decode_value_counted::<(String, Vec<u32>), NoopStream>(&buf[..])?;
+        assert_eq!(s, "test");
+        assert_eq!(v, [1, 0x80]);
+        assert_eq!(n, a.len());
+
+        let ((x, s), m) = decode_value_counted::<(u8, String), NoopStream>(&buf[n..])?;
+        assert_eq!(x, 0x42);
+        assert_eq!(s, "foo");
+        assert_eq!(n + m, buf.len());
+
+        let err = decode_value_counted::<(String, Vec<u32>), NoopStream>(&buf[..n - 1])
+            .expect_err("incomplete payload should fail");
+        assert_eq!(err.to_string(), "incomplete payload");
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn unit_field() -> anyhow::Result<()> {
+        let (buf, _) = encode_value::<_, NoopStream>((1_u8, (), 2_u8))?;
+        assert_eq!(buf.as_ref(), b"\x01\x02");
+
+        // decoding `()` reads no bytes, not even from an empty buffer
+        let (v, n) = decode_value_counted::<(u8, (), u8), NoopStream>(&b"\x01\x02\xff"[..])?;
+        assert_eq!(v, (1, (), 2));
+        assert_eq!(n, 2);
+        let ((), n) = decode_value_counted::<(), NoopStream>(&b""[..])?;
+        assert_eq!(n, 0);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn bounded_string() -> anyhow::Result<()> {
+        let s = BoundedString::<4>::try_from(String::from("test"))
+            .map_err(|s| anyhow::anyhow!("`{s}` should fit"))?;
+        assert!(BoundedString::<4>::try_from(String::from("tests")).is_err());
+
+        let (buf, _) = encode_value::<_, NoopStream>((&s,))?;
+        assert_eq!(buf.as_ref(), b"\x04test");
+        let (v,) = decode_value::<(BoundedString<4>,), NoopStream>(buf.clone())?;
+        assert_eq!(v, s);
+
+        let err = decode_value::<(BoundedString<3>,), NoopStream>(buf)
+            .expect_err("over-long string should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: string length of `4` exceeds maximum of `3`"
+        );
+        // fails on the length header alone, without waiting for the payload
+        let err = BoundedStringCodec::<3>::default()
+            .decode(&mut BytesMut::from(b"\x04".as_slice()))
+            .expect_err("over-long string header should fail to decode");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let Err(err) = encode_value::<_, NoopStream>((BoundedString::<4>(String::from("tests")),))
+        else {
+            bail!("over-long string should fail to encode")
+        };
+        assert_eq!(
+            err.to_string(),
+            "string length of `5` exceeds maximum of `4`"
+        );
+
+        let v = vec![s.clone(), BoundedString(String::new())];
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(decode_value::<Vec<BoundedString<4>>, NoopStream>(buf)?, v);
+        Ok(())
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Tree {
+        Leaf(u32),
+        Node(Box<Tree>, Box<Tree>),
+    }
+
+    #[derive(Default)]
+    struct TreeEncoder(BoxEncoder<TreeEncoder>);
+
+    impl tokio_util::codec::Encoder<Tree> for TreeEncoder {
+        type Error = std::io::Error;
+
+        fn encode(&mut self, item: Tree, dst: &mut BytesMut) -> std::io::Result<()> {
+            match item {
+                Tree::Leaf(v) => {
+                    dst.put_u8(0);
+                    VarU32Codec.encode(v, dst)
+                }
+                Tree::Node(l, r) => {
+                    dst.put_u8(1);
+                    self.0.encode(l, dst)?;
+                    self.0.encode(r, dst)
                 }
-                i = end;
-            },
-            Some(res) = tasks.join_next() => {
-                trace!(?res, "receiver task finished");
-                res??;
             }
         }
     }
-}
 
-impl<T, R> tokio_util::codec::Decoder for StreamDecoder<T, R>
-where
-    T: Decode<R> + Send + 'static,
-    T::ListDecoder: Deferred<R>,
-    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
-    <T::Decoder as tokio_util::codec::Decoder>::Error: Send,
-    std::io::Error: From<<T::Decoder as tokio_util::codec::Decoder>::Error>,
-{
-    type Item = Pin<Box<dyn Stream<Item = Vec<T>> + Send>>;
-    type Error = <<T as Decode<R>>::ListDecoder as tokio_util::codec::Decoder>::Error;
+    impl<W> Deferred<W> for TreeEncoder {
+        fn take_deferred(&mut self) -> Option<DeferredFn<W>> {
+            None
+        }
+    }
 
-    #[instrument(level = "trace", skip(self), fields(ty = "stream"))]
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let Some(chunk) = self.dec.decode(src)? else {
-            return Ok(None);
-        };
-        if !chunk.is_empty() {
-            self.deferred = self.dec.take_deferred();
-            return Ok(Some(Box::pin(stream::iter([chunk]))));
+    impl<W> Encode<W> for Tree {
+        type Encoder = TreeEncoder;
+    }
+
+    #[derive(Default)]
+    struct TreeDecoder {
+        disc: Option<u8>,
+        left: Option<Box<Tree>>,
+        node: BoxDecoder<TreeDecoder>,
+    }
+
+    impl tokio_util::codec::Decoder for TreeDecoder {
+        type Item = Tree;
+        type Error = std::io::Error;
+
+        fn decode(&mut self, src: &mut BytesMut) -> std::io::Result<Option<Self::Item>> {
+            let disc = if let Some(disc) = self.disc {
+                disc
+            } else {
+                let Some(disc) = U8Codec.decode(src)? else {
+                    return Ok(None);
+                };
+                self.disc = Some(disc);
+                disc
+            };
+            let v = match disc {
+                0 => {
+                    let Some(v) = VarU32Codec.decode(src)? else {
+                        return Ok(None);
+                    };
+                    Tree::Leaf(v)
+                }
+                1 => {
+                    if self.left.is_none() {
+                        let Some(l) = self.node.decode(src)? else {
+                            return Ok(None);
+                        };
+                        self.left = Some(l);
+                    }
+                    let Some(r) = self.node.decode(src)? else {
+                        return Ok(None);
+                    };
+                    let l = self.left.take().expect("left subtree missing");
+                    Tree::Node(l, r)
+                }
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("unknown tree discriminant `{disc}`"),
+                    ))
+                }
+            };
+            self.disc = None;
+            Ok(Some(v))
         }
+    }
 
-        // stream is pending
-        let (tx, rx) = mpsc::channel(128);
-        self.deferred = Some(Box::new(|r, path| {
-            Box::pin(
-                async move { handle_deferred_stream(T::Decoder::default(), r, path, tx).await },
-            )
-        }));
-        return Ok(Some(Box::pin(ReceiverStream::new(rx))));
+    impl<R> Deferred<R> for TreeDecoder {
+        fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
+            None
+        }
     }
-}
 
-impl<T, R> Decode<R> for Pin<Box<dyn Stream<Item = Vec<T>> + Send>>
-where
-    T: Decode<R> + Send + 'static,
-    T::ListDecoder: Deferred<R> + Send,
-    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
-    <T::Decoder as tokio_util::codec::Decoder>::Error: Send,
-    std::io::Error: From<<T::Decoder as tokio_util::codec::Decoder>::Error>,
-{
-    type Decoder = StreamDecoder<T, R>;
-    type ListDecoder = ListDecoder<Self::Decoder, R>;
-}
+    impl<R: 'static> Decode<R> for Tree {
+        type Decoder = TreeDecoder;
+        type ListDecoder = ListDecoder<Self::Decoder, R>;
+    }
 
-pub struct StreamDecoderBytes<R> {
-    dec: CoreVecDecoderBytes,
-    deferred: Option<DeferredFn<R>>,
-}
+    fn tree() -> Tree {
+        Tree::Node(
+            Box::new(Tree::Leaf(1)),
+            Box::new(Tree::Node(
+                Box::new(Tree::Leaf(2)),
+                Box::new(Tree::Leaf(300)),
+            )),
+        )
+    }
 
-impl<R> Default for StreamDecoderBytes<R> {
-    fn default() -> Self {
-        Self {
-            dec: CoreVecDecoderBytes::default(),
-            deferred: None,
+    #[test_log::test]
+    fn boxed() -> anyhow::Result<()> {
+        let (buf, _) = encode_value::<_, NoopStream>(Box::new(42_u32))?;
+        assert_eq!(buf.as_ref(), b"\x2a");
+        assert_eq!(decode_value::<Box<u32>, NoopStream>(buf)?, Box::new(42));
+
+        let v = vec![Box::new(String::from("foo")), Box::new(String::from("bar"))];
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(decode_value::<Vec<Box<String>>, NoopStream>(buf)?, v);
+
+        let (buf, _) = encode_value::<_, NoopStream>(tree())?;
+        assert_eq!(buf.as_ref(), b"\x01\x00\x01\x01\x00\x02\x00\xac\x02");
+        assert_eq!(decode_value::<Tree, NoopStream>(buf.clone())?, tree());
+
+        // decoding resumes, when the payload is received one byte at a time
+        let mut dec = TreeDecoder::default();
+        let mut src = BytesMut::default();
+        let mut decoded = None;
+        for b in buf {
+            ensure!(decoded.is_none(), "tree decoded before end of payload");
+            src.put_u8(b);
+            decoded = dec.decode(&mut src)?;
         }
+        assert_eq!(decoded, Some(tree()));
+        Ok(())
     }
-}
 
-impl<R> Deferred<R> for StreamDecoderBytes<R> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-        self.deferred.take()
+    #[test_log::test]
+    fn rc() -> anyhow::Result<()> {
+        let v = Rc::new(String::from("foo"));
+        let shared = Rc::clone(&v);
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf.as_ref(), b"\x03foo");
+        let (owned, _) = encode_value::<_, NoopStream>(shared)?;
+        assert_eq!(owned, buf);
+        assert_eq!(decode_value::<Rc<String>, NoopStream>(buf)?, v);
+
+        let v = vec![Rc::new(Some(42_u32)), Rc::new(None)];
+        let (buf, _) = encode_value::<_, NoopStream>(v)?;
+        assert_eq!(buf.as_ref(), b"\x02\x01\x2a\x00");
+
+        let v = Some(Rc::new(42_u32));
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf.as_ref(), b"\x01\x2a");
+        assert_eq!(decode_value::<Option<Rc<u32>>, NoopStream>(buf)?, v);
+        Ok(())
     }
-}
 
-impl<R> tokio_util::codec::Decoder for StreamDecoderBytes<R>
-where
-    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
-{
-    type Item = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
-    type Error = std::io::Error;
+    #[test_log::test]
+    fn arc() -> anyhow::Result<()> {
+        let leaf = Arc::new(String::from("foo"));
+        // the same leaf is shared by multiple nodes and retained by the caller
+        let v = vec![
+            (Arc::clone(&leaf), Some(Arc::clone(&leaf))),
+            (Arc::new(String::from("bar")), None),
+        ];
+        let (buf, deferred) = encode_value::<_, NoopStream>(&v)?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x02\x03foo\x01\x03foo\x03bar\x00");
+        assert_eq!(Arc::strong_count(&leaf), 3);
+
+        // owned shared values are cloned rather than rejected
+        let (owned, _) = encode_value::<_, NoopStream>(v)?;
+        assert_eq!(owned, buf);
+        assert_eq!(Arc::strong_count(&leaf), 1);
+        assert_eq!(
+            decode_value::<Vec<(Arc<String>, Option<Arc<String>>)>, NoopStream>(buf)?,
+            [
+                (Arc::clone(&leaf), Some(Arc::clone(&leaf))),
+                (Arc::new(String::from("bar")), None)
+            ]
+        );
+
+        let leaf = Rc::new(1_u32);
+        let v = Rc::new(vec![Rc::clone(&leaf), Rc::new(2), Rc::clone(&leaf)]);
+        let shared = Rc::clone(&v);
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf.as_ref(), b"\x03\x01\x02\x01");
+        let (owned, _) = encode_value::<_, NoopStream>(shared)?;
+        assert_eq!(owned, buf);
+        Ok(())
+    }
 
-    #[instrument(level = "trace", skip(self), fields(ty = "stream<u8>"))]
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let Some(chunk) = self.dec.decode(src)? else {
-            return Ok(None);
+    #[test_log::test]
+    fn bit_int() -> anyhow::Result<()> {
+        assert_eq!(
+            UInt::<24>::try_from(0xff_ffff).map(UInt::get),
+            Ok(0xff_ffff)
+        );
+        assert_eq!(UInt::<24>::try_from(0x100_0000), Err(0x100_0000));
+        assert_eq!(UInt::<64>::try_from(u64::MAX).map(UInt::get), Ok(u64::MAX));
+        assert_eq!(SInt::<12>::try_from(2047).map(SInt::get), Ok(2047));
+        assert_eq!(SInt::<12>::try_from(-2048).map(SInt::get), Ok(-2048));
+        assert_eq!(SInt::<12>::try_from(2048), Err(2048));
+        assert_eq!(SInt::<12>::try_from(-2049), Err(-2049));
+
+        let v = (
+            UInt::<24>::try_from(0xff_ffff).unwrap(),
+            SInt::<12>::try_from(-2048).unwrap(),
+            vec![
+                UInt::<1>::try_from(0).unwrap(),
+                UInt::<1>::try_from(1).unwrap(),
+            ],
+        );
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(
+            decode_value::<(UInt<24>, SInt<12>, Vec<UInt<1>>), NoopStream>(buf)?,
+            v
+        );
+
+        let (buf, _) = encode_value::<_, NoopStream>((0x100_0000_u64, 2048_i64))?;
+        let err = decode_value::<(UInt<24>, SInt<64>), NoopStream>(buf.clone())
+            .expect_err("out of range unsigned value should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: value `16777216` does not fit in `24` bits"
+        );
+        let err = decode_value::<(UInt<32>, SInt<12>), NoopStream>(buf)
+            .expect_err("out of range signed value should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: value `2048` does not fit in `12` bits"
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn decode() -> anyhow::Result<()> {
+        let (a, b, c) = decode_value::<(u8, String, Vec<u32>), NoopStream>(
+            b"\x42\x04test\x02\x01\x02".as_slice(),
+        )?;
+        assert_eq!(a, 0x42);
+        assert_eq!(b, "test");
+        assert_eq!(c, [1, 2]);
+
+        let err = decode_value::<(u8, String), NoopStream>(b"\x42\x04te".as_slice())
+            .expect_err("incomplete payload should fail");
+        assert_eq!(err.to_string(), "incomplete payload");
+
+        let err = decode_value::<(u8,), NoopStream>(b"\x42\x42".as_slice())
+            .expect_err("trailing bytes should fail");
+        assert_eq!(err.to_string(), "payload contains `1` trailing bytes");
+
+        let Err(err) = decode_value::<(u8, Pin<Box<dyn Stream<Item = Bytes> + Send>>), NoopStream>(
+            b"\x42\x00".as_slice(),
+        ) else {
+            bail!("pending stream should fail")
         };
-        if !chunk.is_empty() {
-            return Ok(Some(Box::pin(stream::iter([chunk]))));
+        assert_eq!(
+            err.to_string(),
+            "value requires asynchronous I/O, which cannot be performed on a single buffer"
+        );
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn buffered_stream() -> anyhow::Result<()> {
+        let items: Pin<Box<dyn Stream<Item = Vec<u32>> + Send>> =
+            Box::pin(stream::iter([vec![1, 2], vec![], vec![3]]));
+        let (buf, deferred) = encode_value::<_, NoopStream>(items)?;
+        assert!(
+            deferred.is_none(),
+            "buffered stream should be encoded eagerly"
+        );
+        assert_eq!(buf.as_ref(), b"\x03\x01\x02\x03");
+        let items = decode_value::<Pin<Box<dyn Stream<Item = Vec<u32>> + Send>>, NoopStream>(buf)?;
+        assert_eq!(items.collect::<Vec<_>>().await.concat(), [1, 2, 3]);
+
+        let items: Pin<Box<dyn Stream<Item = Bytes> + Send>> =
+            Box::pin(stream::iter([Bytes::from("foo"), Bytes::from("bar")]));
+        let (buf, deferred) = encode_value::<_, NoopStream>(items)?;
+        assert!(
+            deferred.is_none(),
+            "buffered stream should be encoded eagerly"
+        );
+        assert_eq!(buf.as_ref(), b"\x06foobar");
+        let items = decode_value::<Pin<Box<dyn Stream<Item = Bytes> + Send>>, NoopStream>(buf)?;
+        assert_eq!(items.collect::<Vec<_>>().await.concat(), b"foobar");
+
+        // partially-ready and empty streams are transmitted asynchronously
+        let items: Pin<Box<dyn Stream<Item = Vec<u32>> + Send>> =
+            Box::pin(stream::iter([vec![1]]).chain(stream::pending()));
+        let (buf, deferred) = encode_value::<_, NoopStream>(items)?;
+        assert!(deferred.is_some(), "pending stream should be deferred");
+        assert_eq!(buf.as_ref(), b"\x00");
+
+        let items: Pin<Box<dyn Stream<Item = Bytes> + Send>> = Box::pin(stream::empty());
+        let (buf, deferred) = encode_value::<_, NoopStream>(items)?;
+        assert!(deferred.is_some(), "empty stream should be deferred");
+        assert_eq!(buf.as_ref(), b"\x00");
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn stream_items() -> anyhow::Result<()> {
+        let (buf, deferred) =
+            encode_value::<_, NoopStream>(StreamItems(stream::iter([1_u32, 2, 3])))?;
+        assert!(
+            deferred.is_none(),
+            "buffered stream should be encoded eagerly"
+        );
+        // wire-compatible with chunked streams
+        assert_eq!(
+            buf,
+            encode_value::<_, NoopStream>(Box::pin(stream::iter([vec![1_u32, 2, 3]]))
+                as Pin<Box<dyn Stream<Item = Vec<u32>> + Send>>)?
+            .0
+        );
+
+        let (tx, rx) = oneshot::channel();
+        let items = stream::iter([1_u32, 2]).chain(stream::once(rx).map(Result::unwrap));
+        let (buf, deferred) = encode_value::<_, PathWriter>(StreamItems(items))?;
+        assert_eq!(buf.as_ref(), b"\x00");
+        let deferred = deferred.context("pending stream should be deferred")?;
+        tx.send(3).unwrap();
+        let w = PathWriter::default();
+        deferred(Arc::new(w.clone()), vec![0]).await?;
+        let buf =
+            w.0.lock()
+                .unwrap()
+                .remove(&vec![0])
+                .context("stream not written")?;
+
+        let r = Arc::new(CountingReader {
+            buf: buf.into(),
+            indexed: Arc::default(),
+        });
+        let mut dec = <Pin<Box<dyn Stream<Item = Vec<u32>> + Send>> as Decode<
+            CountingReader,
+        >>::Decoder::default();
+        let st = dec
+            .decode(&mut BytesMut::from(b"\x00".as_slice()))?
+            .context("pending stream should be decoded")?;
+        let io = dec.take_deferred().context("stream should be deferred")?;
+        let (res, items) = join!(io(r, vec![0]), st.collect::<Vec<_>>());
+        res?;
+        assert_eq!(items.concat(), [1, 2, 3]);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn range() -> anyhow::Result<()> {
+        let (buf, deferred) = encode_value::<_, NoopStream>((2..0x80_u64, 3..=3_u64))?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x02\x80\x01\x03\x03");
+        // ranges are encoded as records of `start` and `end`
+        assert_eq!(
+            buf,
+            encode_value::<_, NoopStream>((2_u64, 0x80_u64, 3_u64, 3_u64))?.0
+        );
+        let (a, b) = decode_value::<
+            (core::ops::Range<u64>, core::ops::RangeInclusive<u64>),
+            NoopStream,
+        >(buf.clone())?;
+        assert_eq!(a, 2..0x80);
+        assert_eq!(b, 3..=3);
+
+        // byte-at-a-time
+        let mut dec = RangeCodec::default();
+        let mut src = BytesMut::new();
+        for b in &buf[..2] {
+            assert_eq!(dec.decode(&mut src)?, None);
+            src.put_u8(*b);
         }
+        assert_eq!(dec.decode(&mut src)?, None);
+        src.put_u8(buf[2]);
+        assert_eq!(dec.decode(&mut src)?, Some(2..0x80));
+
+        let rs = vec![0..0, 1..u64::MAX];
+        let (buf, _) = encode_value::<_, NoopStream>(&rs)?;
+        assert_eq!(
+            decode_value::<Vec<core::ops::Range<u64>>, NoopStream>(buf)?,
+            rs
+        );
+
+        let err = decode_value::<core::ops::Range<u64>, NoopStream>(b"\x03\x02".as_slice())
+            .expect_err("invalid range should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: invalid range `3..2`, start exceeds end"
+        );
+        let err =
+            decode_value::<core::ops::RangeInclusive<u64>, NoopStream>(b"\x03\x02".as_slice())
+                .expect_err("invalid range should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: invalid range `3..=2`, start exceeds end"
+        );
+        Ok(())
+    }
 
-        // stream is pending
-        let (tx, rx) = mpsc::channel(128);
-        let dec = mem::take(&mut self.dec);
-        self.deferred = Some(Box::new(|r, path| {
-            Box::pin(async move {
-                let indexed = r.index(&path).map_err(std::io::Error::other)?;
-                let mut framed = FramedRead::new(indexed, dec);
-                trace!("receiving stream chunk");
-                while let Some(chunk) = framed.next().await {
-                    let chunk = chunk?;
-                    if chunk.is_empty() {
-                        trace!("received stream end");
-                        return Ok(());
+    #[cfg(tokio_unstable)]
+    #[test]
+    fn spawn_named() -> anyhow::Result<()> {
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        struct TaskNames(Arc<std::sync::Mutex<Vec<String>>>);
+
+        impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for TaskNames {
+            fn on_new_span(
+                &self,
+                attrs: &tracing::span::Attributes<'_>,
+                _id: &tracing::span::Id,
+                _cx: tracing_subscriber::layer::Context<'_, S>,
+            ) {
+                struct Visitor<'a>(&'a mut Vec<String>);
+
+                impl tracing::field::Visit for Visitor<'_> {
+                    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
+                        if field.name() == "task.name" {
+                            self.0.push(format!("{value:?}"));
+                        }
                     }
-                    trace!(?chunk, "received byte stream chunk");
-                    tx.send(chunk).await.map_err(|_| {
-                        std::io::Error::new(
-                            std::io::ErrorKind::BrokenPipe,
-                            "stream receiver closed",
-                        )
-                    })?;
                 }
-                Ok(())
+
+                attrs.record(&mut Visitor(&mut self.0.lock().unwrap()));
+            }
+        }
+
+        let names = Arc::default();
+        let subscriber = tracing_subscriber::registry().with(TaskNames(Arc::clone(&names)));
+        let rt = tokio::runtime::Builder::new_current_thread().build()?;
+        tracing::subscriber::with_default(subscriber, || {
+            rt.block_on(async {
+                let mut tasks = JoinSet::new();
+                super::spawn_named(&mut tasks, || "wrpc stream receive [0, 1]".into(), async {})?;
+                tasks.join_next().await.context("task missing")??;
+                anyhow::Ok(())
             })
-        }));
-        return Ok(Some(Box::pin(ReceiverStream::new(rx))));
+        })?;
+        // `block_on` spawns an unnamed task as well
+        let names = names.lock().unwrap();
+        assert!(
+            names
+                .iter()
+                .any(|name| name == "wrpc stream receive [0, 1]"),
+            "named task not found in {names:?}"
+        );
+        Ok(())
     }
-}
 
-impl<R> Decode<R> for Pin<Box<dyn Stream<Item = Bytes> + Send>>
-where
-    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
-{
-    type Decoder = StreamDecoderBytes<R>;
-    type ListDecoder = ListDecoder<Self::Decoder, R>;
-}
+    #[test_log::test]
+    fn bitvec() -> anyhow::Result<()> {
+        let v = BitVec::from(vec![true, false, true]);
+        let (buf, deferred) = encode_value::<_, NoopStream>(&v)?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x03\x01\x05");
+        // encoding is identical to that of `tuple<u32, list<u8>>`
+        assert_eq!(
+            buf,
+            encode_value::<_, NoopStream>((3_u32, Bytes::from_static(b"\x05")))?.0
+        );
+
+        for n in [0_usize, 1, 7, 8, 9, 13, 16, 17, 1000] {
+            let v: BitVec = (0..n).map(|i| i % 3 == 0).collect();
+            let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+            let n_bytes = n.div_ceil(8);
+            let leb_len = |v: usize| if v < 0x80 { 1 } else { 2 };
+            assert_eq!(buf.len(), leb_len(n) + leb_len(n_bytes) + n_bytes);
+            assert_eq!(
+                decode_value::<BitVec, NoopStream>(buf.clone())?,
+                v,
+                "length {n}"
+            );
+
+            // byte-at-a-time
+            let mut dec = BitVecCodec::default();
+            let mut src = BytesMut::new();
+            for b in &buf[..buf.len() - 1] {
+                src.put_u8(*b);
+                assert_eq!(dec.decode(&mut src)?, None);
+            }
+            src.put_u8(buf[buf.len() - 1]);
+            assert_eq!(dec.decode(&mut src)?, Some(v));
+        }
 
-pub struct StreamDecoderRead<R> {
-    dec: CoreVecDecoderBytes,
-    deferred: Option<DeferredFn<R>>,
-}
+        let err = decode_value::<BitVec, NoopStream>(b"\x09\x01\xff".as_slice())
+            .expect_err("missing byte should fail");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: `1` bytes cannot contain exactly `9` packed bits"
+        );
+        let err = decode_value::<BitVec, NoopStream>(b"\x03\x01\x0d".as_slice())
+            .expect_err("unused bits should fail");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: unused bits of packed bit vector are set"
+        );
+        Ok(())
+    }
 
-impl<R> Default for StreamDecoderRead<R> {
-    fn default() -> Self {
-        Self {
-            dec: CoreVecDecoderBytes::default(),
-            deferred: None,
+    #[cfg(feature = "time")]
+    #[test_log::test]
+    fn offset_date_time() -> anyhow::Result<()> {
+        use time::OffsetDateTime;
+
+        for (v, expected) in [
+            (OffsetDateTime::UNIX_EPOCH, b"\x00\x00".as_slice()),
+            (
+                OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_123_456_789)?,
+                b"\x80\xe2\xcf\xaa\x06\x95\x9a\xef\x3a",
+            ),
+        ] {
+            let (buf, deferred) = encode_value::<_, NoopStream>(v)?;
+            assert!(deferred.is_none());
+            assert_eq!(buf.as_ref(), expected);
+            // encoding is identical to that of `record { seconds: u64, nanoseconds: u32 }`
+            assert_eq!(
+                buf,
+                encode_value::<_, NoopStream>((
+                    u64::try_from(v.unix_timestamp())?,
+                    v.nanosecond()
+                ))?
+                .0
+            );
+            assert_eq!(decode_value::<OffsetDateTime, NoopStream>(buf)?, v);
         }
+
+        // non-UTC offsets are normalized to UTC
+        let v =
+            OffsetDateTime::from_unix_timestamp(42)?.to_offset(time::UtcOffset::from_hms(2, 0, 0)?);
+        let (buf, _) = encode_value::<_, NoopStream>(v)?;
+        let got = decode_value::<OffsetDateTime, NoopStream>(buf)?;
+        assert_eq!(got, v);
+        assert_eq!(got.offset(), time::UtcOffset::UTC);
+
+        let Err(err) = encode_value::<_, NoopStream>(OffsetDateTime::from_unix_timestamp(-1)?)
+        else {
+            bail!("pre-epoch timestamp should fail to encode")
+        };
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "`1969-12-31 23:59:59.0 +00:00:00` precedes the Unix epoch and cannot be encoded as a `datetime`"
+        );
+
+        let (buf, _) = encode_value::<_, NoopStream>((u64::MAX, 0_u32))?;
+        let err = decode_value::<OffsetDateTime, NoopStream>(buf)
+            .expect_err("out of range seconds should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            format!(
+                "failed to decode value: datetime seconds value `{}` is out of range",
+                u64::MAX
+            )
+        );
+        let (buf, _) = encode_value::<_, NoopStream>((i64::MAX as u64, 0_u32))?;
+        let err = decode_value::<OffsetDateTime, NoopStream>(buf)
+            .expect_err("out of range timestamp should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            format!("failed to decode value: datetime `{}.000000000` is out of range of `time::OffsetDateTime`", i64::MAX)
+        );
+        let (buf, _) = encode_value::<_, NoopStream>((0_u64, 1_000_000_000_u32))?;
+        let err = decode_value::<OffsetDateTime, NoopStream>(buf)
+            .expect_err("invalid nanoseconds should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: datetime nanoseconds value `1000000000` exceeds a second"
+        );
+        Ok(())
     }
-}
 
-impl<R> Deferred<R> for StreamDecoderRead<R> {
-    fn take_deferred(&mut self) -> Option<DeferredFn<R>> {
-        self.deferred.take()
+    #[cfg(feature = "either")]
+    #[test_log::test]
+    fn either() -> anyhow::Result<()> {
+        use either::Either;
+
+        let v: Either<u8, String> = Either::Left(42);
+        let (buf, deferred) = encode_value::<_, NoopStream>(&v)?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x00\x2a");
+        assert_eq!(decode_value::<Either<u8, String>, NoopStream>(buf)?, v);
+
+        let v: Either<u8, String> = Either::Right("foo".into());
+        let (buf, deferred) = encode_value::<_, NoopStream>(v.clone())?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x01\x03foo");
+        assert_eq!(decode_value::<Either<u8, String>, NoopStream>(buf)?, v);
+
+        decode_value::<Either<u8, String>, NoopStream>(b"\x02\x2a".as_slice())
+            .expect_err("unknown case should fail");
+        Ok(())
     }
-}
 
-impl<R> tokio_util::codec::Decoder for StreamDecoderRead<R>
-where
-    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
-{
-    type Item = Pin<Box<dyn AsyncRead + Send>>;
-    type Error = std::io::Error;
+    #[cfg(feature = "control-flow")]
+    #[test_log::test]
+    fn control_flow() -> anyhow::Result<()> {
+        use core::ops::ControlFlow;
+
+        let v: ControlFlow<String, u8> = ControlFlow::Continue(42);
+        let (buf, deferred) = encode_value::<_, NoopStream>(&v)?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x00\x2a");
+        assert_eq!(decode_value::<ControlFlow<String, u8>, NoopStream>(buf)?, v);
+
+        let v: ControlFlow<String, u8> = ControlFlow::Break("foo".into());
+        let (buf, deferred) = encode_value::<_, NoopStream>(v.clone())?;
+        assert!(deferred.is_none());
+        assert_eq!(buf.as_ref(), b"\x01\x03foo");
+        assert_eq!(decode_value::<ControlFlow<String, u8>, NoopStream>(buf)?, v);
+
+        // unit `continue` payload, like in `ControlFlow<B>`, is encoded as just the discriminant
+        let v: Vec<ControlFlow<u32>> = vec![ControlFlow::Continue(()), ControlFlow::Break(7)];
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf.as_ref(), b"\x02\x00\x01\x07");
+        assert_eq!(decode_value::<Vec<ControlFlow<u32>>, NoopStream>(buf)?, v);
+
+        decode_value::<ControlFlow<String, u8>, NoopStream>(b"\x02\x2a".as_slice())
+            .expect_err("unknown case should fail");
+        Ok(())
+    }
 
-    #[instrument(level = "trace", skip(self), fields(ty = "stream<u8>"))]
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let Some(chunk) = self.dec.decode(src)? else {
-            return Ok(None);
-        };
-        if !chunk.is_empty() {
-            return Ok(Some(Box::pin(std::io::Cursor::new(chunk))));
+    #[cfg(feature = "smallvec")]
+    #[test_log::test]
+    fn smallvec() -> anyhow::Result<()> {
+        use smallvec::{smallvec, SmallVec};
+
+        for v in [
+            SmallVec::<[u32; 4]>::new(),
+            smallvec![1, 2, 3],
+            smallvec![1, 2, 3, 4],
+            smallvec![1, 2, 3, 4, 5],
+        ] {
+            let (buf, deferred) = encode_value::<_, NoopStream>(&v)?;
+            assert!(deferred.is_none());
+            assert_eq!(buf, encode_value::<_, NoopStream>(v.to_vec())?.0);
+            let (owned, _) = encode_value::<_, NoopStream>(v.clone())?;
+            assert_eq!(buf, owned);
+            let decoded = decode_value::<SmallVec<[u32; 4]>, NoopStream>(buf)?;
+            assert_eq!(decoded, v);
+            assert_eq!(decoded.spilled(), v.len() > 4);
         }
 
-        // stream is pending
-        let (tx, rx) = mpsc::channel(128);
-        let dec = mem::take(&mut self.dec);
-        self.deferred = Some(Box::new(|r, path| {
-            Box::pin(async move {
-                let indexed = r.index(&path).map_err(std::io::Error::other)?;
-                let mut framed = FramedRead::new(indexed, dec);
-                trace!("receiving stream chunk");
-                while let Some(chunk) = framed.next().await {
-                    let chunk = chunk?;
-                    if chunk.is_empty() {
-                        trace!("received stream end");
-                        return Ok(());
-                    }
-                    trace!(?chunk, "received byte stream chunk");
-                    tx.send(std::io::Result::Ok(chunk)).await.map_err(|_| {
-                        std::io::Error::new(
-                            std::io::ErrorKind::BrokenPipe,
-                            "stream receiver closed",
-                        )
-                    })?;
-                }
-                Ok(())
-            })
-        }));
-        return Ok(Some(Box::pin(StreamReader::new(ReceiverStream::new(rx)))));
+        let v: SmallVec<[String; 2]> = smallvec!["foo".into(), "bar".into()];
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf.as_ref(), b"\x02\x03foo\x03bar");
+        let decoded = decode_value::<SmallVec<[String; 2]>, NoopStream>(buf.clone())?;
+        assert_eq!(decoded, v);
+        assert!(!decoded.spilled());
+        assert_eq!(
+            decode_value::<Vec<String>, NoopStream>(buf)?,
+            ["foo", "bar"]
+        );
+
+        let v: SmallVec<[u8; 8]> = (0..=32).collect();
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        let decoded = decode_value::<SmallVec<[u8; 8]>, NoopStream>(buf)?;
+        assert_eq!(decoded, v);
+        assert!(decoded.spilled());
+
+        // elements are decoded as they arrive
+        let v: SmallVec<[u32; 4]> = smallvec![1, 300, 70_000];
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        let mut dec = <SmallVec<[u32; 4]> as Decode<NoopStream>>::Decoder::default();
+        let mut src = BytesMut::default();
+        for b in &buf[..buf.len() - 1] {
+            src.put_u8(*b);
+            assert_eq!(dec.decode(&mut src)?, None);
+        }
+        src.put_u8(buf[buf.len() - 1]);
+        assert_eq!(dec.decode(&mut src)?, Some(v));
+        assert!(src.is_empty());
+        Ok(())
     }
-}
 
-impl<R> Decode<R> for Pin<Box<dyn AsyncRead + Send>>
-where
-    R: AsyncRead + crate::Index<R> + Send + Sync + Unpin + 'static,
-{
-    type Decoder = StreamDecoderRead<R>;
-    type ListDecoder = ListDecoder<Self::Decoder, R>;
-}
+    #[cfg(feature = "chrono")]
+    #[test_log::test]
+    fn chrono_date_time() -> anyhow::Result<()> {
+        use chrono::{DateTime, NaiveDate, Utc};
+
+        for v in [
+            DateTime::UNIX_EPOCH,
+            DateTime::from_timestamp(1_700_000_000, 123_456_789).context("invalid timestamp")?,
+        ] {
+            let (buf, deferred) = encode_value::<_, NoopStream>(v)?;
+            assert!(deferred.is_none());
+            assert_eq!(
+                buf,
+                encode_value::<_, NoopStream>((
+                    u64::try_from(v.timestamp())?,
+                    v.timestamp_subsec_nanos()
+                ))?
+                .0
+            );
+            assert_eq!(decode_value::<DateTime<Utc>, NoopStream>(buf)?, v);
+        }
 
-#[cfg(test)]
-mod tests {
-    use anyhow::bail;
+        // leap second is encoded as the start of the following second
+        let leap = NaiveDate::from_ymd_opt(2016, 12, 31)
+            .and_then(|d| d.and_hms_nano_opt(23, 59, 59, 1_500_000_000))
+            .context("invalid leap second")?
+            .and_utc();
+        let (buf, _) = encode_value::<_, NoopStream>(leap)?;
+        assert_eq!(
+            decode_value::<DateTime<Utc>, NoopStream>(buf)?,
+            DateTime::from_timestamp(1_483_228_800, 0).context("invalid timestamp")?
+        );
+
+        let Err(err) = encode_value::<_, NoopStream>(
+            DateTime::from_timestamp(-1, 0).context("invalid timestamp")?,
+        ) else {
+            bail!("pre-epoch timestamp should fail to encode")
+        };
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "`1969-12-31 23:59:59 UTC` precedes the Unix epoch and cannot be encoded as a `datetime`"
+        );
+
+        let (buf, _) = encode_value::<_, NoopStream>((i64::MAX as u64, 0_u32))?;
+        let err = decode_value::<DateTime<Utc>, NoopStream>(buf)
+            .expect_err("out of range timestamp should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            format!("failed to decode value: datetime `{}.000000000` is out of range of `chrono::DateTime<chrono::Utc>`", i64::MAX)
+        );
+        Ok(())
+    }
 
-    use super::*;
+    /// [`AsyncWrite`] recording all written data and the size of each write
+    #[derive(Clone, Default)]
+    struct RecordingWriter(Arc<std::sync::Mutex<(Vec<usize>, Vec<u8>)>>);
 
-    struct NoopStream;
+    impl crate::Index<Self> for RecordingWriter {
+        fn index(&self, _path: &[usize]) -> anyhow::Result<Self> {
+            Ok(self.clone())
+        }
+    }
 
-    impl crate::Index<Self> for NoopStream {
-        fn index(&self, path: &[usize]) -> anyhow::Result<Self> {
-            panic!("index should not be called with path {path:?}")
+    impl AsyncWrite for RecordingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let mut rec = self.0.lock().unwrap();
+            rec.0.push(buf.len());
+            rec.1.extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
         }
     }
 
     #[test_log::test(tokio::test)]
-    async fn codec() -> anyhow::Result<()> {
-        let mut buf = BytesMut::new();
-        let mut enc = <(u8, u32) as Encode<NoopStream>>::Encoder::default();
-        enc.encode((0x42, 0x42), &mut buf)?;
-        if let Some(_f) = Deferred::<NoopStream>::take_deferred(&mut enc) {
-            bail!("no deferred write should have been returned");
+    async fn write_list_chunked() -> anyhow::Result<()> {
+        let items: Vec<u64> = (0..1_000_000).map(|i| i << 20).collect();
+        let (expected, _) = encode_value::<_, RecordingWriter>(&items)?;
+        assert!(expected.len() > 4 * WRITE_LIST_CHUNK_SIZE);
+
+        let mut w = RecordingWriter::default();
+        let deferred = write_list(&mut w, &items).await?;
+        assert!(deferred.is_none());
+        let (writes, buf) = mem::take(&mut *w.0.lock().unwrap());
+        assert_eq!(buf, expected);
+        assert!(writes.len() > 1, "value should be written in chunks");
+        // a `u64` element is encoded in at most 10 bytes
+        let max = writes.iter().max().copied().unwrap_or_default();
+        assert!(
+            max < WRITE_LIST_CHUNK_SIZE + 10,
+            "chunk of `{max}` bytes exceeds the buffer size"
+        );
+
+        let mut w = RecordingWriter::default();
+        let items: Vec<Pin<Box<dyn Future<Output = u32> + Send>>> =
+            vec![Box::pin(async { 1 }), Box::pin(async { 2 })];
+        let deferred = write_list(&mut w, items)
+            .await?
+            .context("futures should be deferred")?;
+        deferred(Arc::new(w.clone()), Vec::default()).await?;
+        let (_, buf) = mem::take(&mut *w.0.lock().unwrap());
+        assert_eq!(buf.len(), 3 + 2);
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn reorder() -> anyhow::Result<()> {
+        let items = stream::iter([(2, 'c'), (0, 'a'), (1, 'b'), (4, 'e'), (3, 'd'), (5, 'f')]);
+        let items: Vec<_> = reorder_indexed(items, 2).try_collect().await?;
+        assert_eq!(items, ['a', 'b', 'c', 'd', 'e', 'f']);
+
+        let items = stream::iter([(0, 'a'), (1, 'b'), (5, 'f')]);
+        let mut items = Box::pin(reorder_indexed(items, 2));
+        assert_eq!(items.next().await.transpose()?, Some('a'));
+        assert_eq!(items.next().await.transpose()?, Some('b'));
+        let err = items
+            .next()
+            .await
+            .context("error missing")?
+            .expect_err("item outside of window should fail");
+        assert_eq!(
+            err.to_string(),
+            "item `5` arrived more than `2` items ahead of item `2`"
+        );
+        assert!(items.next().await.is_none());
+
+        for (items, expected) in [
+            (vec![(1, 'b'), (1, 'b')], "duplicate item `1`"),
+            (vec![(0, 'a'), (0, 'a')], "duplicate item `0`"),
+            (vec![(1, 'b')], "stream ended before item `0` arrived"),
+        ] {
+            let err = reorder_indexed(stream::iter(items), 2)
+                .try_collect::<Vec<_>>()
+                .await
+                .expect_err("invalid items should fail");
+            assert_eq!(err.to_string(), expected);
         }
-        assert_eq!(buf.as_ref(), b"\x42\x42");
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn num_wrapper() -> anyhow::Result<()> {
+        use core::num::{Saturating, Wrapping};
+
+        let v = Wrapping(u32::MAX) + Wrapping(2);
+        let (buf, deferred) = encode_value::<_, NoopStream>(v)?;
+        assert!(deferred.is_none());
+        // encoding is identical to that of the wrapped value
+        assert_eq!(buf, encode_value::<_, NoopStream>(1_u32)?.0);
+        assert_eq!(decode_value::<Wrapping<u32>, NoopStream>(buf)?, v);
+
+        let v = vec![Wrapping(u32::MAX), Wrapping(0), Wrapping(42)];
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf, encode_value::<_, NoopStream>(vec![u32::MAX, 0, 42])?.0);
+        assert_eq!(decode_value::<Vec<Wrapping<u32>>, NoopStream>(buf)?, v);
+
+        let v = Saturating(u64::MAX) + Saturating(1);
+        let (buf, _) = encode_value::<_, NoopStream>(&v)?;
+        assert_eq!(buf, encode_value::<_, NoopStream>(u64::MAX)?.0);
+        assert_eq!(decode_value::<Saturating<u64>, NoopStream>(buf)?, v);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn infallible() -> anyhow::Result<()> {
+        use core::convert::Infallible;
+
+        let v: Result<u32, Infallible> = Ok(42);
+        let (buf, deferred) = encode_value::<_, NoopStream>(v)?;
+        assert!(deferred.is_none());
+        // encoding is identical to that of a `result<u32>`
+        assert_eq!(buf, encode_value::<_, NoopStream>(Ok::<_, ()>(42_u32))?.0);
+        assert_eq!(buf.as_ref(), b"\x00\x2a");
+        assert_eq!(decode_value::<Result<u32, Infallible>, NoopStream>(buf)?, v);
+
+        let (buf, _) = encode_value::<_, NoopStream>(vec![v, v])?;
+        assert_eq!(
+            decode_value::<Vec<Result<u32, Infallible>>, NoopStream>(buf)?,
+            [v, v]
+        );
+
+        let err = decode_value::<Result<u32, Infallible>, NoopStream>(b"\x01".as_slice())
+            .expect_err("`result::err` should fail to decode");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to decode value: received a value of uninhabited type `Infallible`, e.g. a `result::err` for a result without an error type"
+        );
         Ok(())
     }
 }
@@ -0,0 +1,161 @@
+#![cfg(all(feature = "derive", feature = "test-util"))]
+
+use core::pin::Pin;
+
+use bytes::Bytes;
+use futures::{stream, Stream, StreamExt as _};
+use wrpc_transport::{
+    assert_roundtrip, decode_value, encode_value, Decode, Encode, MockOutgoing, ReplayIncoming,
+};
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+struct Record {
+    name: String,
+    port: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[wrpc(crate = "wrpc_transport")]
+struct Point(f32, f32);
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+struct Unit;
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+struct Tagged<T>(T, Unit);
+
+#[derive(Clone, Copy, Debug, PartialEq, Encode, Decode)]
+#[wrpc(transparent)]
+struct UserId(u64);
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[wrpc(transparent)]
+struct Name {
+    inner: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[wrpc(transparent)]
+struct Wrapper<T>(T);
+
+#[derive(Clone, Copy, Debug, PartialEq, Encode, Decode)]
+enum Level {
+    Debug,
+    Info,
+    Error = 5,
+    Fatal,
+}
+
+#[derive(Encode)]
+struct Async {
+    id: u32,
+    items: Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>,
+}
+
+#[test_log::test]
+fn derive_named() -> anyhow::Result<()> {
+    let v = Record {
+        name: "foo".into(),
+        port: 8080,
+    };
+    let (buf, deferred) = encode_value::<_, MockOutgoing>(&v)?;
+    assert!(deferred.is_none());
+    assert_eq!(buf, encode_value::<_, MockOutgoing>(("foo", 8080_u16))?.0);
+    assert_roundtrip(&v);
+    assert_roundtrip(&vec![v.clone(), v]);
+    Ok(())
+}
+
+#[test_log::test]
+fn derive_tuple() -> anyhow::Result<()> {
+    let v = Point(1.5, -2.0);
+    let (buf, _) = encode_value::<_, MockOutgoing>(v.clone())?;
+    assert_eq!(buf, encode_value::<_, MockOutgoing>((1.5_f32, -2.0_f32))?.0);
+    assert_eq!(
+        decode_value::<(f32, f32), ReplayIncoming>(buf)?,
+        (1.5, -2.0)
+    );
+    assert_roundtrip(&v);
+
+    let v = Tagged(Point(0.0, 1.0), Unit);
+    assert_roundtrip(&v);
+    assert_roundtrip(&Tagged(String::from("bar"), Unit));
+    Ok(())
+}
+
+#[test_log::test]
+fn derive_unit() -> anyhow::Result<()> {
+    let (buf, deferred) = encode_value::<_, MockOutgoing>(Unit)?;
+    assert!(deferred.is_none());
+    assert!(buf.is_empty());
+    assert_eq!(decode_value::<Unit, ReplayIncoming>(Bytes::new())?, Unit);
+    assert_roundtrip(&Unit);
+    assert_roundtrip(&vec![Unit, Unit, Unit]);
+    Ok(())
+}
+
+#[test_log::test]
+fn derive_transparent() -> anyhow::Result<()> {
+    let (buf, _) = encode_value::<_, MockOutgoing>(UserId(300))?;
+    assert_eq!(buf, encode_value::<_, MockOutgoing>(300_u64)?.0);
+    assert_eq!(decode_value::<UserId, ReplayIncoming>(buf)?, UserId(300));
+    assert_roundtrip(&UserId(u64::MAX));
+    assert_roundtrip(&vec![UserId(1), UserId(2)]);
+
+    let v = Name {
+        inner: "foo".into(),
+    };
+    let (buf, _) = encode_value::<_, MockOutgoing>(&v)?;
+    assert_eq!(buf, encode_value::<_, MockOutgoing>("foo")?.0);
+    assert_roundtrip(&v);
+
+    let v = Wrapper(Record {
+        name: "bar".into(),
+        port: 80,
+    });
+    let (buf, _) = encode_value::<_, MockOutgoing>(&v)?;
+    assert_eq!(buf, encode_value::<_, MockOutgoing>(&v.0)?.0);
+    assert_roundtrip(&v);
+    assert_roundtrip(&Some(Wrapper(UserId(7))));
+    Ok(())
+}
+
+#[test_log::test]
+fn derive_enum() -> anyhow::Result<()> {
+    for (v, disc) in [
+        (Level::Debug, 0_u32),
+        (Level::Info, 1),
+        (Level::Error, 5),
+        (Level::Fatal, 6),
+    ] {
+        let (buf, deferred) = encode_value::<_, MockOutgoing>(v)?;
+        assert!(deferred.is_none());
+        assert_eq!(buf, encode_value::<_, MockOutgoing>(disc)?.0);
+        assert_eq!(decode_value::<Level, ReplayIncoming>(buf)?, v);
+        assert_roundtrip(&v);
+    }
+    assert_roundtrip(&vec![Level::Fatal, Level::Debug, Level::Error]);
+
+    for disc in [2_u32, 4, 7, 300] {
+        let (buf, _) = encode_value::<_, MockOutgoing>(disc)?;
+        let err = decode_value::<Level, ReplayIncoming>(buf)
+            .expect_err("unknown discriminant should fail");
+        assert!(
+            format!("{err:#}").contains(&format!("unknown enum discriminant `{disc}`")),
+            "unexpected error: {err:#}"
+        );
+    }
+    Ok(())
+}
+
+#[test_log::test]
+fn derive_async() -> anyhow::Result<()> {
+    let v = Async {
+        id: 42,
+        items: Box::pin(stream::pending().chain(stream::iter([vec![1, 2]]))),
+    };
+    let (buf, deferred) = encode_value::<_, MockOutgoing>(v)?;
+    assert_eq!(buf.as_ref(), b"\x2a\x00");
+    assert!(deferred.is_some(), "stream should be deferred");
+    Ok(())
+}
@@ -43,6 +43,7 @@ fn attach_with_context(err: anyhow::Error) -> anyhow::Error {
 
 struct Config {
     opts: Opts,
+    module: Option<syn::Ident>,
     resolve: Resolve,
     world: WorldId,
     files: Vec<PathBuf>,
@@ -62,6 +63,7 @@ impl Parse for Config {
         let mut opts = Opts::default();
         let mut world = None;
         let mut source = None;
+        let mut module = None;
         let mut features = Vec::new();
 
         if input.peek(token::Brace) {
@@ -111,6 +113,9 @@ impl Parse for Config {
                     Opt::GenerateUnusedTypes(enable) => {
                         opts.generate_unused_types = enable.value();
                     }
+                    Opt::Debug(enable) => {
+                        opts.debug = enable.value();
+                    }
                     Opt::Features(f) => {
                         features.extend(f.into_iter().map(|f| f.value()));
                     }
@@ -138,6 +143,14 @@ impl Parse for Config {
                     Opt::WrpcTransportPath(path) => {
                         opts.wrpc_transport_path = Some(path.value());
                     }
+                    Opt::Module(name) => {
+                        if module.is_some() {
+                            return Err(Error::new(name.span(), "cannot specify second module"));
+                        }
+                        module = Some(name.parse::<syn::Ident>().map_err(|_| {
+                            Error::new(name.span(), "module name must be an identifier")
+                        })?);
+                    }
                 }
             }
         } else {
@@ -154,6 +167,7 @@ impl Parse for Config {
             .map_err(|e| anyhow_to_syn(call_site, e))?;
         Ok(Config {
             opts,
+            module,
             resolve,
             world,
             files,
@@ -280,6 +294,9 @@ impl Config {
             );
         }
 
+        if let Some(module) = self.module {
+            contents = quote::quote!(pub mod #module { #contents });
+        }
         Ok(contents)
     }
 }
@@ -295,6 +312,7 @@ mod kw {
     syn::custom_keyword!(with);
     syn::custom_keyword!(generate_all);
     syn::custom_keyword!(generate_unused_types);
+    syn::custom_keyword!(debug);
     syn::custom_keyword!(features);
     syn::custom_keyword!(anyhow_path);
     syn::custom_keyword!(bytes_path);
@@ -304,6 +322,7 @@ mod kw {
     syn::custom_keyword!(tracing_path);
     syn::custom_keyword!(wasm_tokio_path);
     syn::custom_keyword!(wrpc_transport_path);
+    syn::custom_keyword!(module);
 }
 
 enum Opt {
@@ -317,6 +336,7 @@ enum Opt {
     With(HashMap<String, WithOption>),
     GenerateAll,
     GenerateUnusedTypes(syn::LitBool),
+    Debug(syn::LitBool),
     Features(Vec<syn::LitStr>),
     AnyhowPath(syn::LitStr),
     BytesPath(syn::LitStr),
@@ -326,6 +346,7 @@ enum Opt {
     TracingPath(syn::LitStr),
     WasmTokioPath(syn::LitStr),
     WrpcTransportPath(syn::LitStr),
+    Module(syn::LitStr),
 }
 
 impl Parse for Opt {
@@ -387,6 +408,10 @@ impl Parse for Opt {
             input.parse::<kw::generate_unused_types>()?;
             input.parse::<Token![:]>()?;
             Ok(Opt::GenerateUnusedTypes(input.parse()?))
+        } else if l.peek(kw::debug) {
+            input.parse::<kw::debug>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::Debug(input.parse()?))
         } else if l.peek(kw::features) {
             input.parse::<kw::features>()?;
             input.parse::<Token![:]>()?;
@@ -426,6 +451,10 @@ impl Parse for Opt {
             input.parse::<kw::wrpc_transport_path>()?;
             input.parse::<Token![:]>()?;
             Ok(Opt::WrpcTransportPath(input.parse()?))
+        } else if l.peek(kw::module) {
+            input.parse::<kw::module>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::Module(input.parse()?))
         } else {
             Err(l.error())
         }
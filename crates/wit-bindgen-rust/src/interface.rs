@@ -12,7 +12,7 @@ use wit_bindgen_core::wit_parser::{
     WorldKey,
 };
 use wit_bindgen_core::{uwrite, uwriteln, Source, TypeInfo};
-use wrpc_introspect::{async_paths_ty, async_paths_tyid, is_ty, rpc_func_name};
+use wrpc_introspect::{is_ty, rpc_func_name, AsyncPaths};
 
 pub struct InterfaceGenerator<'a> {
     pub src: Source,
@@ -23,6 +23,22 @@ pub struct InterfaceGenerator<'a> {
 }
 
 impl InterfaceGenerator<'_> {
+    /// Returns the async paths of `ty`, walking the type tree only once per type
+    fn async_paths_ty(&mut self, ty: &Type) -> AsyncPaths {
+        self.gen
+            .async_paths
+            .async_paths_ty(self.resolve, ty)
+            .clone()
+    }
+
+    /// Returns the async paths of `id`, walking the type tree only once per type
+    fn async_paths_tyid(&mut self, id: TypeId) -> AsyncPaths {
+        self.gen
+            .async_paths
+            .async_paths_tyid(self.resolve, id)
+            .clone()
+    }
+
     pub(super) fn generate_exports<'a>(
         &mut self,
         identifier: Identifier<'a>,
@@ -227,7 +243,7 @@ pub fn serve_interface<'a, T: {wrpc_transport}::Serve>(
             let paths = func.params.iter().enumerate().fold(
                 BTreeSet::default(),
                 |mut paths, (i, (_, ty))| {
-                    let (nested, fut) = async_paths_ty(self.resolve, ty);
+                    let (nested, fut) = self.async_paths_ty(ty);
                     for path in nested {
                         let mut s = String::with_capacity(3 + path.len() * 6);
                         s.push_str(&format!("[Some({i})"));
@@ -343,10 +359,12 @@ pub fn serve_interface<'a, T: {wrpc_transport}::Serve>(
             uwrite!(
                 self.src,
                 r#"
-                                let rx = rx.map({tracing}::Instrument::in_current_span).map({tokio}::spawn);
+                                // abort parameter receipt if the invocation is dropped
+                                let rx = rx.map(|rx| {tokio_util}::task::AbortOnDropHandle::new({tokio}::spawn({tracing}::Instrument::in_current_span(rx))));
                                 {tracing}::trace!(instance = "{instance}", func = "{wit_name}", "calling handler");
                                 match {trait_name}::{name}(&handler, cx"#,
                 tokio = self.gen.tokio_path(),
+                tokio_util = self.gen.tokio_util_path(),
                 tracing = self.gen.tracing_path(),
                 wit_name = func.name,
             );
@@ -386,17 +404,11 @@ pub fn serve_interface<'a, T: {wrpc_transport}::Serve>(
                                                 }}
                                             }},
                                             Err(err) => {{
-                                                if let Some(rx) = rx {{
-                                                    rx.abort();
-                                                }}
                                                 {anyhow}::bail!("failed to transmit `{instance}.{wit_name}` invocation results")
                                             }},
                                         }}
                                     }},
                                     Err(err) => {{
-                                        if let Some(rx) = rx {{
-                                            rx.abort();
-                                        }}
                                         {anyhow}::bail!("failed to serve `{instance}.{wit_name}` invocation")
                                     }},
                                 }}
@@ -538,13 +550,13 @@ pub fn serve_interface<'a, T: {wrpc_transport}::Serve>(
         self.src.push_str("#[allow(clippy::all)]\n");
 
         let async_params = func.params.iter().any(|(_, ty)| {
-            let (paths, fut) = async_paths_ty(self.resolve, ty);
+            let (paths, fut) = self.async_paths_ty(ty);
             fut || !paths.is_empty()
         });
         let paths = func.results.iter_types().enumerate().fold(
             BTreeSet::default(),
             |mut paths, (i, ty)| {
-                let (nested, fut) = async_paths_ty(self.resolve, ty);
+                let (nested, fut) = self.async_paths_ty(ty);
                 for path in nested {
                     let mut s = String::with_capacity(7 + path.len() * 6);
                     s.push_str(&format!("[Some({i})"));
@@ -859,6 +871,29 @@ pub fn serve_interface<'a, T: {wrpc_transport}::Serve>(
         }
     }
 
+    /// Returns `true` if `id` is `wasi:clocks/monotonic-clock.instant` of a `0.2.x` version of the
+    /// package, which is represented by a dedicated type to prevent mixing it up with other `u64`
+    /// values
+    fn is_monotonic_instant(&self, id: TypeId, ty: &Type) -> bool {
+        let TypeOwner::Interface(iface) = self.resolve.types[id].owner else {
+            return false;
+        };
+        let iface = &self.resolve.interfaces[iface];
+        let Some(pkg) = iface.package else {
+            return false;
+        };
+        let pkg = &self.resolve.packages[pkg].name;
+        *ty == Type::U64
+            && pkg.namespace == "wasi"
+            && pkg.name == "clocks"
+            && pkg
+                .version
+                .as_ref()
+                .is_some_and(|v| v.major == 0 && v.minor == 2)
+            && iface.name.as_deref() == Some("monotonic-clock")
+            && self.resolve.types[id].name.as_deref() == Some("instant")
+    }
+
     fn type_path_with_name(&self, id: TypeId, name: String, submodule: bool) -> String {
         if let TypeOwner::Interface(id) = self.resolve.types[id].owner {
             if let Some(path) = self.path_to_interface(id) {
@@ -893,7 +928,7 @@ pub fn serve_interface<'a, T: {wrpc_transport}::Serve>(
                     TypeDefKind::Stream(ty) => self.print_stream(ty, false),
                     TypeDefKind::Type(ty) => self.print_import_param_ty(ty),
                     _ => {
-                        let (paths, fut) = async_paths_tyid(self.resolve, *id);
+                        let (paths, fut) = self.async_paths_tyid(*id);
                         if !fut && paths.is_empty() {
                             self.push_str("&'a ");
                         }
@@ -1059,7 +1094,7 @@ pub fn serve_interface<'a, T: {wrpc_transport}::Serve>(
             self.push_str(name);
             self.push_str("::");
             self.push_str(&case_name);
-            let is_async = payload.is_some_and(|ty| async_paths_ty(self.resolve, ty).1);
+            let is_async = payload.is_some_and(|ty| self.async_paths_ty(ty).1);
             if payload.is_some() {
                 if is_async {
                     self.push_str("(_)");
@@ -1144,7 +1179,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
             .cloned()
             .collect();
         if let Some(name) = self.name_of(id) {
-            let (paths, _) = async_paths_tyid(self.resolve, id);
+            let (paths, _) = self.async_paths_tyid(id);
 
             self.rustdoc(docs);
             let mut derives = additional_derives.clone();
@@ -1193,7 +1228,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
             let tokio_util = self.gen.tokio_util_path().to_string();
             let wrpc_transport = self.gen.wrpc_transport_path().to_string();
 
-            let (paths, _) = async_paths_tyid(self.resolve, id);
+            let (paths, _) = self.async_paths_tyid(id);
             if paths.is_empty() {
                 uwriteln!(
                     self.src,
@@ -1505,7 +1540,7 @@ mod {mod_name} {{
             );
             self.push_str(&format!("f.debug_struct(\"{name}\")"));
             for field in fields {
-                let (_, fut) = async_paths_ty(self.resolve, &field.ty);
+                let (_, fut) = self.async_paths_ty(&field.ty);
                 if fut {
                     self.push_str(&format!(r#".field("{}", &"<async>")"#, field.name));
                 } else {
@@ -1540,6 +1575,9 @@ mod {mod_name} {{
 
     fn type_resource(&mut self, _id: TypeId, name: &str, docs: &Docs) {
         self.rustdoc(docs);
+        if self.gen.opts.debug {
+            self.push_str("#[derive(::core::fmt::Debug)]\n");
+        }
         uwriteln!(
             self.src,
             r"
@@ -1693,7 +1731,7 @@ mod {mod_name} {{
             .cloned()
             .collect();
         if let Some(name) = self.name_of(id) {
-            let (paths, _) = async_paths_tyid(self.resolve, id);
+            let (paths, _) = self.async_paths_tyid(id);
 
             self.rustdoc(docs);
             let mut derives = additional_derives.clone();
@@ -1851,7 +1889,7 @@ mod {mod_name} {{
             } else {
                 let tokio = self.gen.tokio_path().to_string();
 
-                let (paths, _) = async_paths_tyid(self.resolve, id);
+                let (paths, _) = self.async_paths_tyid(id);
                 if paths.is_empty() {
                     uwrite!(
                         self.src,
@@ -2371,7 +2409,15 @@ mod {mod_name} {{
         if let Some(name) = self.name_of(id) {
             self.rustdoc(docs);
             uwrite!(self.src, "pub type {name} = ");
-            self.print_ty(ty, true, false);
+            if self.is_monotonic_instant(id, ty) {
+                uwrite!(
+                    self.src,
+                    "{}::MonotonicInstant",
+                    self.gen.wrpc_transport_path()
+                );
+            } else {
+                self.print_ty(ty, true, false);
+            }
             self.push_str(";\n");
         }
     }
@@ -12,6 +12,7 @@ use wit_bindgen_core::{
     name_package_module, uwrite, uwriteln, Files, InterfaceGenerator as _, Source, Types,
     WorldGenerator,
 };
+use wrpc_introspect::AsyncPathCache;
 
 mod interface;
 
@@ -42,6 +43,9 @@ struct RustWrpc {
 
     export_paths: Vec<String>,
     with: GenerationConfiguration,
+
+    /// Async paths of types, which are looked up repeatedly while generating bindings
+    async_paths: AsyncPathCache,
 }
 
 #[derive(Default)]
@@ -111,6 +115,13 @@ pub struct Opts {
     #[cfg_attr(feature = "clap", arg(long = "additional_derive_attribute", short = 'd', default_values_t = Vec::<String>::new()))]
     pub additional_derive_attributes: Vec<String>,
 
+    /// Whether to implement `Debug` for all generated types.
+    ///
+    /// Records, variants, enums and flags always implement `Debug`, this additionally derives
+    /// it for the remaining types, like resources.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub debug: bool,
+
     /// Remapping of interface names to rust module names.
     ///
     /// Argument must be of the form `k=v` and this option can be passed
@@ -523,6 +523,83 @@ mod generate_unused_types {
     });
 }
 
+mod debug {
+    use core::fmt::Debug;
+
+    wit_bindgen_wrpc::generate!({
+        inline: "
+            package foo:bar;
+
+            world bindings {
+                import component;
+            }
+
+            interface component {
+                resource res;
+                record rec {
+                    x: u32,
+                }
+                variant var {
+                    r(rec),
+                    empty,
+                }
+
+                f: func(r: borrow<res>, v: var) -> rec;
+            }
+        ",
+        debug: true,
+    });
+
+    fn assert_debug<T: Debug>() {}
+
+    #[test]
+    fn types_implement_debug() {
+        use foo::bar::component::{Rec, Res, Var};
+
+        assert_debug::<Res>();
+        assert_debug::<Rec>();
+        assert_debug::<Var>();
+        assert_eq!(format!("{:?}", Rec { x: 42 }), "Rec { x: 42 }");
+    }
+}
+
+mod module {
+    wit_bindgen_wrpc::generate!({
+        inline: "
+            package foo:bar;
+
+            world bindings {
+                import component;
+                record rec {
+                    x: u32,
+                }
+                import f: func(r: rec);
+            }
+
+            interface component {
+                record rec {
+                    y: u32,
+                }
+            }
+        ",
+        generate_unused_types: true,
+        module: "bindings",
+    });
+
+    // would collide with the generated world-level record, if not nested
+    struct Rec;
+
+    #[allow(dead_code)]
+    async fn test(
+        wrpc: &impl wit_bindgen_wrpc::wrpc_transport::Invoke<Context = ()>,
+    ) -> anyhow::Result<()> {
+        bindings::f(wrpc, (), &bindings::Rec { x: 42 }).await?;
+        let _ = bindings::foo::bar::component::Rec { y: 42 };
+        let Rec = Rec;
+        Ok(())
+    }
+}
+
 #[allow(unused)]
 mod gated_features {
     wit_bindgen_wrpc::generate!({
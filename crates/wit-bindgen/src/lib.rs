@@ -740,12 +740,26 @@
 ///     // or return value of a function.
 ///     generate_unused_types: false,
 ///
+///     // Whether to implement `Debug` for all generated types, including
+///     // resources, which do not implement it otherwise.
+///     //
+///     // By default this is `false`.
+///     debug: false,
+///
 ///     // A list of "features" which correspond to WIT features to activate
 ///     // when parsing WIT files. This enables `@unstable` annotations showing
 ///     // up and having bindings generated for them.
 ///     //
 ///     // By default this is an empty list.
 ///     features: ["foo", "bar", "baz"],
+///
+///     // Name of a public submodule to place all generated items in, which
+///     // avoids collisions with items defined next to the macro invocation.
+///     // Note, that relative paths, e.g. in `with`, are then resolved relative
+///     // to the submodule.
+///     //
+///     // By default, items are generated at the invocation site.
+///     module: "bindings",
 /// });
 /// ```
 ///
@@ -0,0 +1,42 @@
+use anyhow::Context as _;
+use clap::Parser;
+use url::Url;
+
+mod bindings {
+    wit_bindgen_wrpc::generate!({
+        with: {
+            "wrpc-examples:hello/handler": generate
+        }
+    });
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Redis URL to connect to
+    #[arg(short, long, default_value = "redis://127.0.0.1:6379")]
+    redis: Url,
+
+    /// Prefixes to invoke `wrpc-examples:hello/handler.hello` on
+    #[arg(default_value = "rust")]
+    prefixes: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt().init();
+
+    let Args { redis, prefixes } = Args::parse();
+
+    let redis = redis::Client::open(String::from(redis)).context("invalid Redis URL")?;
+    for prefix in prefixes {
+        let wrpc = wrpc_transport_redis::Client::new(&redis, prefix.clone())
+            .await
+            .context("failed to connect to Redis")?;
+        let hello = bindings::wrpc_examples::hello::handler::hello(&wrpc, ())
+            .await
+            .context("failed to invoke `wrpc-examples.hello/handler.hello`")?;
+        eprintln!("{prefix}: {hello}");
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use core::pin::pin;
+
+use anyhow::Context as _;
+use clap::Parser;
+use futures::stream::select_all;
+use futures::{StreamExt as _, TryStreamExt as _};
+use tokio::{select, signal};
+use tracing::{info, warn};
+use url::Url;
+
+mod bindings {
+    wit_bindgen_wrpc::generate!({
+        with: {
+            "wrpc-examples:hello/handler": generate,
+        }
+    });
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Redis URL to connect to
+    #[arg(short, long, default_value = "redis://127.0.0.1:6379")]
+    redis: Url,
+
+    /// Prefix to serve `wrpc-examples:hello/handler.hello` on
+    #[arg(default_value = "rust")]
+    prefix: String,
+}
+
+#[derive(Clone, Copy)]
+struct Server;
+
+impl bindings::exports::wrpc_examples::hello::handler::Handler<()> for Server {
+    async fn hello(&self, (): ()) -> anyhow::Result<String> {
+        Ok("hello from Rust".to_string())
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt().init();
+
+    let Args { redis, prefix } = Args::parse();
+
+    let redis = redis::Client::open(String::from(redis)).context("invalid Redis URL")?;
+    let wrpc = wrpc_transport_redis::Client::new(&redis, prefix)
+        .await
+        .context("failed to connect to Redis")?;
+    let invocations = bindings::serve(&wrpc, Server)
+        .await
+        .context("failed to serve `wrpc-examples.hello/handler.hello`")?;
+    // NOTE: This will conflate all invocation streams into a single stream via `futures::stream::SelectAll`,
+    // to customize this, iterate over the returned `invocations` and set up custom handling per export
+    let mut invocations = select_all(invocations.into_iter().map(
+        |(instance, name, invocations)| {
+            invocations
+                .try_buffer_unordered(16) // handle up to 16 invocations concurrently
+                .map(move |res| (instance, name, res))
+        },
+    ));
+    let shutdown = signal::ctrl_c();
+    let mut shutdown = pin!(shutdown);
+    loop {
+        select! {
+            Some((instance, name, res)) = invocations.next() => {
+                match res {
+                    Ok(()) => {
+                        info!(instance, name, "invocation successfully handled");
+                    }
+                    Err(err) => {
+                        warn!(?err, instance, name, "failed to accept invocation");
+                    }
+                }
+            }
+            res = &mut shutdown => {
+                return res.context("failed to listen for ^C")
+            }
+        }
+    }
+}
@@ -0,0 +1,4 @@
+(record
+  (field "name" string)
+  (field "port" u16)
+  (field "tags" (list string)))
@@ -0,0 +1,228 @@
+//! Decodes a captured wRPC payload given its type and prints the decoded value.
+//!
+//! The type is specified as a component model value type in WebAssembly text format, for example:
+//!
+//! ```sh
+//! cargo run -p inspect-payload -- --type-file sample/record.wat sample/record.hex
+//! ```
+//!
+//! which prints:
+//!
+//! ```text
+//! Record(
+//!     [
+//!         (
+//!             "name",
+//!             String(
+//!                 "foo",
+//!             ),
+//!         ),
+//!         (
+//!             "port",
+//!             U16(
+//!                 8080,
+//!             ),
+//!         ),
+//!         ...
+//! ```
+//!
+//! The type is imported by a synthesized component, so the component model rules for imported
+//! types apply - `record`, `variant`, `enum` and `flags` types can only be used at the top level,
+//! not nested within other types.
+//!
+//! Captured payloads only contain the synchronous part of the value, so types containing
+//! `stream` or `future` cannot be decoded. Use a `tuple` type to decode a complete parameter
+//! or result frame of a function.
+
+use std::io::{self, Read as _};
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Context as _};
+use base64::Engine as _;
+use bytes::Bytes;
+use clap::{Parser, ValueEnum};
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, ResourceTable, Type};
+use wasmtime::{Engine, Store};
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView};
+use wrpc_runtime_wasmtime::{decode_value, SharedResourceTable, WrpcView};
+use wrpc_transport::{Invoke, MockOutgoing, ReplayIncoming};
+
+/// Encoding of the payload file
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum Encoding {
+    /// Hexadecimal, whitespace is ignored
+    #[default]
+    Hex,
+    /// Standard base64, whitespace is ignored
+    Base64,
+    /// Raw bytes
+    Binary,
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Value type in WebAssembly text format, e.g. `(tuple string u32)`
+    #[arg(short, long = "type", required_unless_present = "type_file")]
+    ty: Option<String>,
+
+    /// Path to a file containing the value type in WebAssembly text format
+    #[arg(long, conflicts_with = "ty")]
+    type_file: Option<PathBuf>,
+
+    /// Encoding of the payload file
+    #[arg(short, long, value_enum, default_value_t)]
+    encoding: Encoding,
+
+    /// Path to the payload file, `-` reads from stdin
+    payload: PathBuf,
+}
+
+/// [Invoke] implementation, which fails all invocations
+struct NoopInvoke;
+
+impl Invoke for NoopInvoke {
+    type Context = ();
+    type Outgoing = MockOutgoing;
+    type Incoming = ReplayIncoming;
+
+    async fn invoke<P>(
+        &self,
+        (): Self::Context,
+        instance: &str,
+        func: &str,
+        _params: Bytes,
+        _paths: impl AsRef<[P]> + Send,
+    ) -> anyhow::Result<(Self::Outgoing, Self::Incoming)>
+    where
+        P: AsRef<[Option<usize>]> + Send + Sync,
+    {
+        bail!("cannot invoke `{instance}.{func}`")
+    }
+}
+
+struct Ctx {
+    wasi: WasiCtx,
+    table: ResourceTable,
+    shared_resources: SharedResourceTable,
+}
+
+impl WrpcView for Ctx {
+    type Invoke = NoopInvoke;
+
+    fn client(&self) -> &Self::Invoke {
+        &NoopInvoke
+    }
+
+    fn shared_resources(&mut self) -> &mut SharedResourceTable {
+        &mut self.shared_resources
+    }
+}
+
+impl WasiView for Ctx {
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+/// Compiles a component importing a function taking `ty` as the only parameter and returns the
+/// parameter [Type]
+fn compile_type(engine: &Engine, ty: &str) -> anyhow::Result<Type> {
+    let component = Component::new(
+        engine,
+        format!(
+            r#"(component
+    (type $t' {ty})
+    (import "t" (type $t (eq $t')))
+    (import "f" (func (param "v" $t)))
+)"#
+        ),
+    )
+    .context("failed to compile type")?;
+    let Some(ComponentItem::ComponentFunc(f)) = component.component_type().get_import(engine, "f")
+    else {
+        bail!("function import missing in compiled component")
+    };
+    let ty = f
+        .params()
+        .next()
+        .context("function parameter missing in compiled component")?;
+    Ok(ty)
+}
+
+/// Decodes the payload file contents according to `encoding`
+fn decode_payload(buf: Vec<u8>, encoding: Encoding) -> anyhow::Result<Bytes> {
+    match encoding {
+        Encoding::Binary => Ok(buf.into()),
+        Encoding::Hex => {
+            let digits = buf
+                .into_iter()
+                .filter(|c| !c.is_ascii_whitespace())
+                .map(|c| {
+                    char::from(c)
+                        .to_digit(16)
+                        .with_context(|| format!("invalid hex digit `{}`", c.escape_ascii()))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            ensure!(digits.len() % 2 == 0, "odd number of hex digits");
+            let (pairs, _) = digits.as_chunks::<2>();
+            Ok(pairs.iter().map(|[h, l]| (h << 4 | l) as u8).collect())
+        }
+        Encoding::Base64 => {
+            let buf: Vec<u8> = buf
+                .into_iter()
+                .filter(|c| !c.is_ascii_whitespace())
+                .collect();
+            let buf = base64::engine::general_purpose::STANDARD
+                .decode(buf)
+                .map_err(|err| anyhow::anyhow!("invalid base64: {err}"))?;
+            Ok(buf.into())
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let Args {
+        ty,
+        type_file,
+        encoding,
+        payload,
+    } = Args::parse();
+
+    let ty = match (ty, type_file) {
+        (Some(ty), _) => ty,
+        (None, Some(path)) => std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read type from `{}`", path.display()))?,
+        (None, None) => bail!("type must be specified"),
+    };
+    let buf = if payload.as_os_str() == "-" {
+        let mut buf = Vec::default();
+        io::stdin().read_to_end(&mut buf).map(|_| buf)
+    } else {
+        std::fs::read(&payload)
+    }
+    .with_context(|| format!("failed to read payload from `{}`", payload.display()))?;
+    let buf = decode_payload(buf, encoding)?;
+
+    let engine = Engine::default();
+    let ty = compile_type(&engine, &ty)?;
+    let mut store = Store::new(
+        &engine,
+        Ctx {
+            wasi: WasiCtxBuilder::new().build(),
+            table: ResourceTable::default(),
+            shared_resources: SharedResourceTable::default(),
+        },
+    );
+    let val = decode_value(&mut store, &[], &ty, buf)
+        .await
+        .context("failed to decode payload")?;
+    println!("{val:#?}");
+    Ok(())
+}
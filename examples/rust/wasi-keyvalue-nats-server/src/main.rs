@@ -9,7 +9,7 @@ use bytes::Bytes;
 use clap::Parser;
 use futures::stream::select_all;
 use futures::{StreamExt as _, TryStreamExt as _};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::RwLock;
 use tokio::{select, signal};
 use tracing::{debug, info, instrument, warn};
 use tracing_subscriber::layer::SubscriberExt as _;
@@ -53,7 +53,7 @@ async fn main() -> anyhow::Result<()> {
 
     let Args { nats, prefix } = Args::parse();
 
-    let nats = connect(nats)
+    let nats = wrpc_transport_nats::connect(String::from(nats), async_nats::ConnectOptions::new())
         .await
         .context("failed to connect to NATS.io")?;
     let invocations = bindings::serve(
@@ -92,35 +92,6 @@ async fn main() -> anyhow::Result<()> {
     }
 }
 
-/// Connect to NATS.io server and ensure that the connection is fully established before
-/// returning the resulting [`async_nats::Client`]
-async fn connect(url: Url) -> anyhow::Result<async_nats::Client> {
-    let (conn_tx, mut conn_rx) = mpsc::channel(1);
-    let client = async_nats::connect_with_options(
-        String::from(url),
-        async_nats::ConnectOptions::new()
-            .retry_on_initial_connect()
-            .event_callback(move |event| {
-                let conn_tx = conn_tx.clone();
-                async move {
-                    if let async_nats::Event::Connected = event {
-                        conn_tx
-                            .send(())
-                            .await
-                            .expect("failed to send NATS.io server connection notification");
-                    }
-                }
-            }),
-    )
-    .await
-    .context("failed to connect to NATS.io server")?;
-    conn_rx
-        .recv()
-        .await
-        .context("failed to await NATS.io server connection to be established")?;
-    Ok(client)
-}
-
 type Bucket = Arc<RwLock<HashMap<String, Bytes>>>;
 
 #[derive(Clone, Debug, Default)]
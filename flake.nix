@@ -136,6 +136,7 @@
                 nativeCheckInputs
                 ++ [
                   pkgs.nats-server
+                  pkgs.redis
 
                   pkgs.pkgsUnstable.go
                 ];
@@ -226,6 +227,7 @@
               pkgs.cargo-audit
               pkgs.nats-server
               pkgs.natscli
+              pkgs.redis
               pkgs.wit-deps
 
               pkgs.pkgsUnstable.go
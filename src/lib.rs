@@ -6,6 +6,9 @@ pub mod transport {
 
     #[cfg(feature = "quic")]
     pub use wrpc_transport_quic as quic;
+
+    #[cfg(feature = "redis")]
+    pub use wrpc_transport_redis as redis;
 }
 
 pub mod runtime {
@@ -82,6 +82,56 @@ where
     Ok(res)
 }
 
+#[cfg(feature = "redis")]
+pub async fn start_redis() -> anyhow::Result<(
+    u16,
+    redis::Client,
+    JoinHandle<anyhow::Result<ExitStatus>>,
+    oneshot::Sender<()>,
+)> {
+    let port = free_port().await?;
+    let (server, stop_tx) = spawn_server(Command::new("redis-server").args([
+        "--port",
+        &port.to_string(),
+        "--save",
+        "",
+        "--appendonly",
+        "no",
+    ]))
+    .await
+    .context("failed to start Redis server")?;
+
+    let client = redis::Client::open(format!("redis://localhost:{port}"))
+        .context("failed to construct Redis client")?;
+    // wait for the server to start accepting connections
+    let mut attempts = 50;
+    while let Err(err) = client.get_multiplexed_async_connection().await {
+        attempts -= 1;
+        if attempts == 0 {
+            return Err(err).context("failed to connect to Redis server");
+        }
+        tokio::time::sleep(core::time::Duration::from_millis(100)).await;
+    }
+    Ok((port, client, server, stop_tx))
+}
+
+#[cfg(feature = "redis")]
+pub async fn with_redis<T, Fut>(f: impl FnOnce(u16, redis::Client) -> Fut) -> anyhow::Result<T>
+where
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let (port, redis_client, redis_server, stop_tx) = start_redis()
+        .await
+        .context("failed to start Redis server")?;
+    let res = f(port, redis_client).await.context("closure failed")?;
+    stop_tx.send(()).expect("failed to stop Redis server");
+    redis_server
+        .await
+        .context("failed to await Redis server stop")?
+        .context("Redis server failed to stop")?;
+    Ok(res)
+}
+
 #[cfg(feature = "quic")]
 pub async fn with_quic<T, Fut>(
     names: &[&str],
@@ -602,8 +602,67 @@ where
         .serve_values("test", "sync", [Box::default(); 0])
         .await
         .context("failed to serve `test.sync`")?;
+    let empty_inv = srv
+        .serve_values("test", "empty", Vec::<Box<[Option<usize>]>>::default())
+        .await
+        .context("failed to serve `test.empty`")?;
+    let unit_inv = srv
+        .serve_values("test", "unit", Vec::<Box<[Option<usize>]>>::default())
+        .await
+        .context("failed to serve `test.unit`")?;
     let mut async_inv = pin!(async_inv);
     let mut sync_inv = pin!(sync_inv);
+    let mut empty_inv = pin!(empty_inv);
+    let mut unit_inv = pin!(unit_inv);
+
+    join!(
+        async {
+            info!("receiving `test.empty` parameters");
+            let (_, (), rx, tx) = empty_inv
+                .try_next()
+                .await
+                .expect("failed to accept invocation")
+                .expect("unexpected end of stream");
+            assert!(rx.is_none());
+            info!("transmitting `test.empty` returns");
+            tx(("test",)).await.expect("failed to send response");
+        }
+        .instrument(info_span!("server")),
+        async {
+            info!("invoking `test.empty`");
+            let (a,): (String,) = clt
+                .invoke_values_blocking(C::default(), "test", "empty", (), &[[]; 0])
+                .await
+                .expect("failed to invoke `test.empty`");
+            assert_eq!(a, "test");
+            info!("finishing `test.empty` session");
+        }
+        .instrument(info_span!("client")),
+    );
+
+    join!(
+        async {
+            info!("receiving `test.unit` parameters");
+            let (_, (), rx, tx) = unit_inv
+                .try_next()
+                .await
+                .expect("failed to accept invocation")
+                .expect("unexpected end of stream");
+            assert!(rx.is_none());
+            info!("transmitting `test.unit` returns");
+            tx(()).await.expect("failed to send response");
+        }
+        .instrument(info_span!("server")),
+        async {
+            info!("invoking `test.unit`");
+            let () = clt
+                .invoke_values_blocking(C::default(), "test", "unit", (), &[[]; 0])
+                .await
+                .expect("failed to invoke `test.unit`");
+            info!("finishing `test.unit` session");
+        }
+        .instrument(info_span!("client")),
+    );
 
     join!(
         async {
@@ -889,6 +948,554 @@ async fn rust_dynamic_nats() -> anyhow::Result<()> {
     .await
 }
 
+#[cfg(feature = "nats")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_nats_connect_event_callback() -> anyhow::Result<()> {
+    common::with_nats(|port, _| async move {
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::unbounded_channel();
+        let nats = wrpc_transport_nats::connect_with_event_callback(
+            format!("nats://localhost:{port}"),
+            async_nats::ConnectOptions::new(),
+            move |event| {
+                let events_tx = events_tx.clone();
+                async move {
+                    _ = events_tx.send(event);
+                }
+            },
+        )
+        .await?;
+        assert_eq!(
+            nats.connection_state(),
+            async_nats::connection::State::Connected
+        );
+        let event = tokio::time::timeout(Duration::from_secs(1), events_rx.recv())
+            .await
+            .context("event callback was not called")?;
+        assert!(matches!(event, Some(async_nats::Event::Connected)));
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "nats")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_nats_builder() -> anyhow::Result<()> {
+    use core::num::NonZeroUsize;
+
+    common::with_nats(|_, nats_client| async {
+        let builder = wrpc_transport_nats::ClientBuilder::new(nats_client)
+            .prefix("test-prefix")
+            .cache_subscriptions(NonZeroUsize::new(16).context("capacity must not be zero")?);
+        let subscriber = builder
+            .subscriber()
+            .context("subscription cache missing")?
+            .clone();
+        assert!(subscriber.is_empty());
+
+        let mut servers = Vec::with_capacity(2);
+        for _ in 0..2 {
+            let invocations = builder
+                .build()
+                .serve_fn(
+                    "test",
+                    "inc",
+                    Vec::<Box<[Option<usize>]>>::default(),
+                    |(x,): (u32,)| async move { Ok((x + 1,)) },
+                )
+                .await
+                .context("failed to serve `test.inc`")?;
+            servers.push(Box::pin(invocations));
+        }
+        assert_eq!(
+            subscriber.len(),
+            1,
+            "identical subscription should be reused"
+        );
+        let mut invocations = servers.pop().context("server missing")?;
+        drop(servers);
+        for x in [1_u32, 41] {
+            try_join!(
+                async {
+                    let (v,): (u32,) = builder
+                        .build()
+                        .invoke_values_blocking(None, "test", "inc", (x,), &[[]; 0])
+                        .await
+                        .context("failed to invoke `test.inc`")?;
+                    assert_eq!(v, x + 1);
+                    anyhow::Ok(())
+                },
+                async {
+                    let inv = invocations
+                        .try_next()
+                        .await
+                        .context("failed to accept invocation")?
+                        .context("unexpected end of stream")?;
+                    inv.await.context("failed to handle `test.inc`")
+                },
+            )?;
+            assert_eq!(subscriber.len(), 1);
+        }
+        drop(invocations);
+        assert!(subscriber.is_empty());
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "nats")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_nats_prefix_override() -> anyhow::Result<()> {
+    use core::pin::pin;
+
+    common::with_nats(|_, nats_client| async {
+        let clt = wrpc_transport_nats::Client::new(nats_client, "test-prefix", None);
+        let mut servers = Vec::default();
+        for tenant in ["tenant-a", "tenant-b"] {
+            let invocations = clt
+                .on_prefix(tenant)
+                .serve_fn(
+                    "test",
+                    "tenant",
+                    Vec::<Box<[Option<usize>]>>::default(),
+                    move |(): ()| async move { Ok((tenant.to_string(),)) },
+                )
+                .await
+                .with_context(|| format!("failed to serve `test.tenant` for `{tenant}`"))?;
+            servers.push(spawn(async move {
+                let mut invocations = pin!(invocations);
+                let inv = invocations
+                    .try_next()
+                    .await
+                    .context("failed to accept invocation")?
+                    .context("unexpected end of stream")?;
+                inv.await.context("failed to handle `test.tenant`")
+            }));
+        }
+        for tenant in ["tenant-b", "tenant-a"] {
+            let (v,): (String,) = clt
+                .invoke_values_on(tenant, None, "test", "tenant", (), &[[]; 0])
+                .await
+                .with_context(|| format!("failed to invoke `test.tenant` on `{tenant}`"))?;
+            assert_eq!(v, tenant);
+        }
+        for srv in servers {
+            srv.await??;
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "nats")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_nats_queue_group() -> anyhow::Result<()> {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::io::AsyncWriteExt as _;
+
+    const INVOCATIONS: usize = 8;
+
+    common::with_nats(|_, nats_client| async {
+        let clt = wrpc_transport_nats::Client::new(nats_client, "test-prefix", None);
+        let accepted: Arc<[AtomicUsize; 2]> = Arc::default();
+        let mut servers = Vec::default();
+        for i in 0..2 {
+            let invocations = clt
+                .serve_shared("replicas", "test", "shared", [])
+                .await
+                .context("failed to serve `test.shared`")?;
+            let accepted = Arc::clone(&accepted);
+            servers.push(spawn(async move {
+                let mut invocations = Box::pin(invocations);
+                while let Some((_, mut outgoing, _)) = invocations.try_next().await? {
+                    accepted[i].fetch_add(1, Ordering::Relaxed);
+                    let (buf, _) = wrpc_transport::encode_value::<
+                        _,
+                        wrpc_transport_nats::SubjectWriter,
+                    >((u32::try_from(i)?,))?;
+                    outgoing.write_all(&buf).await?;
+                    outgoing.shutdown().await?;
+                }
+                anyhow::Ok(())
+            }));
+        }
+        for _ in 0..INVOCATIONS {
+            let ((i,), _) = clt
+                .invoke_values::<_, (), (u32,)>(None, "test", "shared", (), &[[]; 0])
+                .await
+                .context("failed to invoke `test.shared`")?;
+            assert!(i < 2);
+        }
+        // allow any duplicate deliveries to arrive
+        sleep(Duration::from_millis(100)).await;
+        let counts = accepted
+            .iter()
+            .map(|n| n.load(Ordering::Relaxed))
+            .collect::<Vec<_>>();
+        assert_eq!(counts.iter().sum::<usize>(), INVOCATIONS, "{counts:?}");
+        for srv in servers {
+            srv.abort();
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "nats")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_nats_caching_subscriber() -> anyhow::Result<()> {
+    use core::num::NonZeroUsize;
+    use core::pin::pin;
+
+    common::with_nats(|_, nats_client| async {
+        let nats_client = Arc::new(nats_client);
+        let subscriber = wrpc_transport_nats::CachingSubscriber::new(
+            Arc::clone(&nats_client),
+            NonZeroUsize::new(16).context("capacity must not be zero")?,
+        );
+        assert!(subscriber.is_empty());
+
+        let a = subscriber.subscribe("test.cached").await?;
+        let b = subscriber.subscribe("test.cached").await?;
+        assert_eq!(subscriber.len(), 1);
+
+        let c = subscriber.subscribe("test.other").await?;
+        assert_eq!(subscriber.len(), 2);
+        drop(c);
+        assert_eq!(subscriber.len(), 1);
+
+        nats_client
+            .publish("test.cached", Bytes::from("test"))
+            .await
+            .context("failed to publish message")?;
+        let (a, b) = try_join!(
+            a.take(1).try_collect::<Vec<_>>(),
+            b.take(1).try_collect::<Vec<_>>()
+        )?;
+        assert_eq!(a[0].payload, "test");
+        assert_eq!(b[0].payload, "test");
+        assert!(subscriber.is_empty());
+
+        // a lagging consumer is notified of dropped messages
+        let lagging = wrpc_transport_nats::CachingSubscriber::new(
+            Arc::clone(&nats_client),
+            NonZeroUsize::MIN,
+        );
+        let mut sub = pin!(lagging.subscribe("test.lagging").await?);
+        for payload in ["foo", "bar"] {
+            nats_client
+                .publish("test.lagging", Bytes::from(payload))
+                .await
+                .context("failed to publish message")?;
+        }
+        nats_client.flush().await.context("failed to flush")?;
+        sleep(Duration::from_millis(100)).await;
+        let err = sub
+            .next()
+            .await
+            .context("unexpected end of stream")?
+            .expect_err("consumer should have lagged");
+        assert_eq!(
+            err.to_string(),
+            "cached subscription consumer lagged, `1` messages dropped"
+        );
+        let msg = sub.try_next().await?.context("unexpected end of stream")?;
+        assert_eq!(msg.payload, "bar");
+
+        // servers of the same function share a single subscription
+        let clt = wrpc_transport_nats::Client::new(Arc::clone(&nats_client), "test-prefix", None)
+            .with_subscriber(subscriber.clone());
+        let serve = || {
+            clt.serve_fn(
+                "test",
+                "inc",
+                Vec::<Box<[Option<usize>]>>::default(),
+                |(x,): (u32,)| async move { Ok((x + 1,)) },
+            )
+        };
+        let unused = serve().await.context("failed to serve `test.inc`")?;
+        let invocations = serve().await.context("failed to serve `test.inc`")?;
+        assert_eq!(subscriber.len(), 1);
+        drop(unused);
+        let mut invocations = Box::pin(invocations);
+        try_join!(
+            async {
+                let (v,): (u32,) = clt
+                    .invoke_values_blocking(None, "test", "inc", (41_u32,), &[[]; 0])
+                    .await
+                    .context("failed to invoke `test.inc`")?;
+                assert_eq!(v, 42);
+                anyhow::Ok(())
+            },
+            async {
+                let inv = invocations
+                    .try_next()
+                    .await
+                    .context("failed to accept invocation")?
+                    .context("unexpected end of stream")?;
+                inv.await.context("failed to handle `test.inc`")
+            },
+        )?;
+        drop(invocations);
+        assert!(subscriber.is_empty());
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "nats")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_nats_redelivery() -> anyhow::Result<()> {
+    use core::pin::pin;
+
+    common::with_nats(|_, nats_client| async {
+        let nats_client = Arc::new(nats_client);
+        let builder = wrpc_transport_nats::ClientBuilder::new(Arc::clone(&nats_client))
+            .prefix("test-prefix")
+            .redelivery(wrpc_transport_nats::Redelivery {
+                timeout: Duration::from_millis(100),
+                max_retries: 5,
+                dedupe_capacity: 16,
+            });
+        let clt = builder.build();
+        let srv = builder.build();
+
+        // invocation is started before the function is served, so the first handshake is dropped
+        try_join!(
+            async {
+                let (v,): (u32,) = clt
+                    .invoke_values_blocking(None, "test", "inc", (41_u32,), &[[]; 0])
+                    .await
+                    .context("failed to invoke `test.inc`")?;
+                assert_eq!(v, 42);
+                anyhow::Ok(())
+            },
+            async {
+                sleep(Duration::from_millis(50)).await;
+                let invocations = srv
+                    .serve_fn(
+                        "test",
+                        "inc",
+                        Vec::<Box<[Option<usize>]>>::default(),
+                        |(x,): (u32,)| async move { Ok((x + 1,)) },
+                    )
+                    .await
+                    .context("failed to serve `test.inc`")?;
+                let mut invocations = pin!(invocations);
+                let inv = invocations
+                    .try_next()
+                    .await
+                    .context("failed to accept invocation")?
+                    .context("unexpected end of stream")?;
+                inv.await.context("failed to handle `test.inc`")
+            },
+        )?;
+
+        // retransmitted handshake is acknowledged, but not accepted again
+        let invocations = srv
+            .serve("test", "dup", Vec::<Box<[Option<usize>]>>::default())
+            .await
+            .context("failed to serve `test.dup`")?;
+        let mut invocations = pin!(invocations);
+        let inbox = nats_client.new_inbox();
+        let mut acks = nats_client
+            .subscribe(inbox.clone())
+            .await
+            .context("failed to subscribe on handshake subject")?;
+        let subject = wrpc_transport_nats::invocation_subject("test-prefix", "test", "dup");
+        for _ in 0..2 {
+            nats_client
+                .publish_with_reply(subject.clone(), inbox.clone(), Bytes::default())
+                .await
+                .context("failed to publish handshake")?;
+        }
+        invocations
+            .try_next()
+            .await
+            .context("failed to accept invocation")?
+            .context("unexpected end of stream")?;
+        let res = tokio::time::timeout(Duration::from_millis(200), invocations.next()).await;
+        assert!(
+            res.is_err(),
+            "retransmitted handshake should not be accepted"
+        );
+        let a = acks.next().await.context("handshake not acknowledged")?;
+        let b = acks
+            .next()
+            .await
+            .context("retransmission not acknowledged")?;
+        assert!(a.reply.is_some());
+        assert_eq!(a.reply, b.reply);
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "nats")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_serve_fn_nats() -> anyhow::Result<()> {
+    common::with_nats(|_, nats_client| async {
+        let clt = wrpc_transport_nats::Client::new(nats_client, "test-prefix", None);
+        let fail = clt
+            .serve_fn(
+                "test",
+                "fail",
+                Vec::<Box<[Option<usize>]>>::default(),
+                |(): ()| async move { anyhow::Result::<(u32,)>::Err(anyhow::anyhow!("test")) },
+            )
+            .await
+            .context("failed to serve `test.fail`")?;
+        let panic = clt
+            .serve_fn(
+                "test",
+                "panic",
+                Vec::<Box<[Option<usize>]>>::default(),
+                |(): ()| async move {
+                    if true {
+                        panic!("test panic")
+                    }
+                    anyhow::Ok((0_u32,))
+                },
+            )
+            .await
+            .context("failed to serve `test.panic`")?;
+        let mut fail = Box::pin(fail);
+        let mut panic = Box::pin(panic);
+        let (res, ()) = try_join!(
+            async {
+                let inv = fail
+                    .try_next()
+                    .await
+                    .context("failed to accept invocation")?
+                    .context("unexpected end of stream")?;
+                anyhow::Ok(inv.await)
+            },
+            async {
+                // NATS.io only signals the end of results on shutdown, so this would wait
+                // forever if the result stream of a failed invocation was not shut down
+                tokio::time::timeout(
+                    Duration::from_secs(10),
+                    clt.invoke_values_blocking::<_, _, (u32,)>(None, "test", "fail", (), &[[]; 0]),
+                )
+                .await
+                .context("invocation of a failing handler did not complete")?
+                .expect_err("invocation should fail without results");
+                Ok(())
+            },
+        )?;
+        let err = res.expect_err("handler error should be returned");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to handle `test.fail` invocation: test"
+        );
+
+        let (res, ()) = try_join!(
+            async {
+                let inv = panic
+                    .try_next()
+                    .await
+                    .context("failed to accept invocation")?
+                    .context("unexpected end of stream")?;
+                anyhow::Ok(inv.await)
+            },
+            async {
+                tokio::time::timeout(
+                    Duration::from_secs(10),
+                    clt.invoke_values_blocking::<_, _, (u32,)>(None, "test", "panic", (), &[[]; 0]),
+                )
+                .await
+                .context("invocation of a panicking handler did not complete")?
+                .expect_err("invocation should fail without results");
+                Ok(())
+            },
+        )?;
+        let err = res.expect_err("handler panic should be returned");
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to handle `test.panic` invocation: handler panicked: test panic"
+        );
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "redis")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_bindgen_redis() -> anyhow::Result<()> {
+    common::with_redis(|_, redis_client| async move {
+        let client = wrpc_transport_redis::Client::new(&redis_client, "test-prefix")
+            .await
+            .context("failed to connect to Redis server")?;
+        let client = Arc::new(client);
+        assert_bindgen(Arc::clone(&client), client).await
+    })
+    .await
+}
+
+#[cfg(feature = "redis")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_dynamic_redis() -> anyhow::Result<()> {
+    common::with_redis(|_, redis_client| async move {
+        let client = wrpc_transport_redis::Client::new(&redis_client, "test-prefix")
+            .await
+            .context("failed to connect to Redis server")?;
+        let client = Arc::new(client);
+        assert_dynamic(Arc::clone(&client), client).await
+    })
+    .await
+}
+
+#[cfg(feature = "redis")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_serve_values_redis() -> anyhow::Result<()> {
+    common::with_redis(|_, redis_client| async move {
+        let clt = wrpc_transport_redis::Client::new(&redis_client, "test-prefix")
+            .await
+            .context("failed to connect to Redis server")?;
+        clt.ready().await.context("client not ready")?;
+        let invocations = clt
+            .serve_values::<(), (u32,)>("test", "fail", Vec::<Box<[Option<usize>]>>::default())
+            .await
+            .context("failed to serve `test.fail`")?;
+        let mut invocations = Box::pin(invocations);
+        try_join!(
+            async {
+                // the result transmitter is dropped, like it is when a handler fails
+                let ((), (), _, _) = invocations
+                    .try_next()
+                    .await
+                    .context("failed to accept invocation")?
+                    .context("unexpected end of stream")?;
+                anyhow::Ok(())
+            },
+            async {
+                // Redis pub/sub only signals the end of results on shutdown, so this would wait
+                // forever if the dropped result stream was not shut down
+                tokio::time::timeout(
+                    Duration::from_secs(10),
+                    clt.invoke_values_blocking::<_, _, (u32,)>((), "test", "fail", (), &[[]; 0]),
+                )
+                .await
+                .context("invocation of a failing handler did not complete")?
+                .expect_err("invocation should fail without results");
+                Ok(())
+            },
+        )?;
+        Ok(())
+    })
+    .await
+}
+
 #[cfg(feature = "quic")]
 #[test_log::test(tokio::test(flavor = "multi_thread"))]
 #[instrument(ret)]
@@ -936,7 +1543,7 @@ async fn rust_dynamic_quic() -> anyhow::Result<()> {
     use core::pin::pin;
 
     common::with_quic(
-        &["sync.test", "async.test"],
+        &["empty.test", "unit.test", "sync.test", "async.test"],
         |port, clt_ep, srv_ep| async move {
             let clt = wrpc_transport_quic::Client::new(clt_ep, (Ipv6Addr::LOCALHOST, port));
             let srv = wrpc_transport_quic::Server::default();
@@ -960,3 +1567,549 @@ async fn rust_dynamic_quic() -> anyhow::Result<()> {
     )
     .await
 }
+
+#[cfg(feature = "quic")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_serve_fn_quic() -> anyhow::Result<()> {
+    use core::net::Ipv6Addr;
+    use core::pin::pin;
+
+    common::with_quic(
+        &["inc.test", "fail.test", "panic.test"],
+        |port, clt_ep, srv_ep| async move {
+            let clt = wrpc_transport_quic::Client::new(clt_ep, (Ipv6Addr::LOCALHOST, port));
+            let srv = Arc::new(wrpc_transport_quic::Server::default());
+
+            let inc = srv
+                .serve_fn(
+                    "test",
+                    "inc",
+                    Vec::<Box<[Option<usize>]>>::default(),
+                    |(x,): (u32,)| async move { Ok((x + 1,)) },
+                )
+                .await
+                .context("failed to serve `test.inc`")?;
+            let fail = srv
+                .serve_fn(
+                    "test",
+                    "fail",
+                    Vec::<Box<[Option<usize>]>>::default(),
+                    |(): ()| async move { anyhow::Result::<(u32,)>::Err(anyhow::anyhow!("test")) },
+                )
+                .await
+                .context("failed to serve `test.fail`")?;
+            let panic = srv
+                .serve_fn(
+                    "test",
+                    "panic",
+                    Vec::<Box<[Option<usize>]>>::default(),
+                    |(): ()| async move {
+                        if true {
+                            panic!("test panic")
+                        }
+                        anyhow::Ok((0_u32,))
+                    },
+                )
+                .await
+                .context("failed to serve `test.panic`")?;
+            let mut inc = pin!(inc);
+            let mut fail = pin!(fail);
+            let mut panic = pin!(panic);
+            let mut fut = pin!(async {
+                try_join!(
+                    async {
+                        let inv = inc
+                            .try_next()
+                            .await
+                            .context("failed to accept invocation")?
+                            .context("unexpected end of stream")?;
+                        inv.await.context("failed to handle `test.inc`")
+                    },
+                    async {
+                        let (v,): (u32,) = clt
+                            .invoke_values_blocking((), "test", "inc", (41_u32,), &[[]; 0])
+                            .await
+                            .context("failed to invoke `test.inc`")?;
+                        assert_eq!(v, 42);
+                        Ok(())
+                    },
+                )?;
+                join!(
+                    async {
+                        let inv = fail
+                            .try_next()
+                            .await
+                            .expect("failed to accept invocation")
+                            .expect("unexpected end of stream");
+                        let err = inv.await.expect_err("handler error should be returned");
+                        assert_eq!(
+                            format!("{:#}", err),
+                            "failed to handle `test.fail` invocation: test"
+                        );
+                    },
+                    async {
+                        clt.invoke_values_blocking::<_, _, (u32,)>(
+                            (),
+                            "test",
+                            "fail",
+                            (),
+                            &[[]; 0],
+                        )
+                        .await
+                        .expect_err("invocation should fail without results");
+                    },
+                );
+                join!(
+                    async {
+                        let inv = panic
+                            .try_next()
+                            .await
+                            .expect("failed to accept invocation")
+                            .expect("unexpected end of stream");
+                        let err = inv.await.expect_err("handler panic should be returned");
+                        assert_eq!(
+                            format!("{:#}", err),
+                            "failed to handle `test.panic` invocation: handler panicked: test panic"
+                        );
+                    },
+                    async {
+                        clt.invoke_values_blocking::<_, _, (u32,)>(
+                            (),
+                            "test",
+                            "panic",
+                            (),
+                            &[[]; 0],
+                        )
+                        .await
+                        .expect_err("invocation should fail without results");
+                    },
+                );
+                anyhow::Ok(())
+            });
+            loop {
+                select! {
+                    res = &mut fut => {
+                        res?;
+                        return Ok(())
+                    }
+                    res = srv.accept(&srv_ep) => {
+                        let ok = res.expect("failed to accept connection");
+                        assert!(ok);
+                        continue
+                    }
+                }
+            }
+        },
+    )
+    .await
+}
+
+#[cfg(feature = "quic")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_list_futures_quic() -> anyhow::Result<()> {
+    use core::future::Future;
+    use core::net::Ipv6Addr;
+    use core::pin::{pin, Pin};
+
+    common::with_quic(&["futures.test"], |port, clt_ep, srv_ep| async move {
+        let clt = wrpc_transport_quic::Client::new(clt_ep, (Ipv6Addr::LOCALHOST, port));
+        let srv = Arc::new(wrpc_transport_quic::Server::default());
+
+        let invocations = srv
+            .serve_fn(
+                "test",
+                "futures",
+                [Box::from([Some(0), None])],
+                |(futs,): (Vec<Pin<Box<dyn Future<Output = u32> + Send>>>,)| async move {
+                    let values = futures::future::join_all(futs).await;
+                    Ok((values,))
+                },
+            )
+            .await
+            .context("failed to serve `test.futures`")?;
+        let mut invocations = pin!(invocations);
+        let mut fut = pin!(async {
+            try_join!(
+                async {
+                    let inv = invocations
+                        .try_next()
+                        .await
+                        .context("failed to accept invocation")?
+                        .context("unexpected end of stream")?;
+                    inv.await.context("failed to handle `test.futures`")
+                },
+                async {
+                    let futs: Vec<Pin<Box<dyn Future<Output = u32> + Send>>> = (0..5_u32)
+                        .map(|i| {
+                            Box::pin(async move {
+                                sleep(Duration::from_millis(u64::from(5 - i))).await;
+                                i * 2
+                            })
+                                as Pin<Box<dyn Future<Output = u32> + Send>>
+                        })
+                        .collect();
+                    let (values,): (Vec<u32>,) = clt
+                        .invoke_values_blocking((), "test", "futures", (futs,), &[[Some(0), None]])
+                        .await
+                        .context("failed to invoke `test.futures`")?;
+                    assert_eq!(values, [0, 2, 4, 6, 8]);
+                    Ok(())
+                }
+            )?;
+            anyhow::Ok(())
+        });
+        loop {
+            select! {
+                res = &mut fut => {
+                    res?;
+                    return Ok(())
+                }
+                res = srv.accept(&srv_ep) => {
+                    let ok = res.expect("failed to accept connection");
+                    assert!(ok);
+                    continue
+                }
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(feature = "quic")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_invoke_channels_quic() -> anyhow::Result<()> {
+    use core::net::Ipv6Addr;
+    use core::pin::{pin, Pin};
+
+    use tokio::io::AsyncReadExt as _;
+
+    common::with_quic(&["channels.test"], |port, clt_ep, srv_ep| async move {
+        let clt = wrpc_transport_quic::Client::new(clt_ep, (Ipv6Addr::LOCALHOST, port));
+        let srv = Arc::new(wrpc_transport_quic::Server::default());
+
+        let invocations = srv
+            .serve_fn(
+                "test",
+                "channels",
+                [Box::from([Some(0)])],
+                |(st,): (Pin<Box<dyn Stream<Item = Bytes> + Send>>,)| async move {
+                    let n = st.map(|buf| buf.len()).collect::<Vec<_>>().await;
+                    Ok((u32::try_from(n.into_iter().sum::<usize>())?,))
+                },
+            )
+            .await
+            .context("failed to serve `test.channels`")?;
+        let mut invocations = pin!(invocations);
+        let mut fut = pin!(async {
+            try_join!(
+                async {
+                    let inv = invocations
+                        .try_next()
+                        .await
+                        .context("failed to accept invocation")?
+                        .context("unexpected end of stream")?;
+                    inv.await.context("failed to handle `test.channels`")
+                },
+                async {
+                    // yield once, so that the stream is not encoded eagerly
+                    let st: Pin<Box<dyn Stream<Item = Bytes> + Send>> = Box::pin(
+                        stream::once(tokio::task::yield_now())
+                            .filter_map(|()| async { None })
+                            .chain(stream::iter(["foo", "bar", "baz"].map(Bytes::from))),
+                    );
+                    let (incoming, tx) = clt
+                        .invoke_channels((), "test", "channels", (st,), &[[Some(0)]])
+                        .await
+                        .context("failed to invoke `test.channels`")?;
+                    let tx = tx.context("stream parameter should be transmitted asynchronously")?;
+                    let ((), buf) = try_join!(tx, async {
+                        let mut incoming = incoming;
+                        let mut buf = vec![];
+                        incoming
+                            .read_to_end(&mut buf)
+                            .await
+                            .context("failed to receive results")?;
+                        Ok(buf)
+                    })?;
+                    let (n,) =
+                        wrpc_transport::decode_value::<(u32,), wrpc_transport_quic::Incoming>(buf.as_slice())?;
+                    assert_eq!(n, 9);
+                    Ok(())
+                }
+            )?;
+            anyhow::Ok(())
+        });
+        loop {
+            select! {
+                res = &mut fut => {
+                    res?;
+                    return Ok(())
+                }
+                res = srv.accept(&srv_ep) => {
+                    let ok = res.expect("failed to accept connection");
+                    assert!(ok);
+                    continue
+                }
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(feature = "quic")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_reflect_quic() -> anyhow::Result<()> {
+    use core::net::Ipv6Addr;
+    use core::pin::pin;
+
+    use wrpc_transport::{FunctionSignature, Reflection};
+
+    common::with_quic(&["reflect.wrpc"], |port, clt_ep, srv_ep| async move {
+        let clt = wrpc_transport_quic::Client::new(clt_ep, (Ipv6Addr::LOCALHOST, port));
+        let srv = Arc::new(wrpc_transport_quic::Server::default());
+
+        let reflection = Reflection::default();
+        let sigs = [
+            FunctionSignature {
+                instance: "test".into(),
+                name: "inc".into(),
+                params: vec!["u32".into()],
+                results: vec!["u32".into()],
+            },
+            FunctionSignature {
+                instance: "wrpc-test:integration/shared".into(),
+                name: "fallible".into(),
+                params: vec![],
+                results: vec!["result<bool, string>".into()],
+            },
+        ];
+        for sig in sigs.clone() {
+            reflection.register(sig);
+        }
+        let invocations = reflection
+            .serve(srv.as_ref())
+            .await
+            .context("failed to serve reflection")?;
+        let mut invocations = pin!(invocations);
+        let mut fut = pin!(async {
+            try_join!(
+                async {
+                    let inv = invocations
+                        .try_next()
+                        .await
+                        .context("failed to accept invocation")?
+                        .context("unexpected end of stream")?;
+                    inv.await.context("failed to handle reflection")
+                },
+                async {
+                    let listed = clt
+                        .list_functions(())
+                        .await
+                        .context("failed to list functions")?;
+                    assert_eq!(listed, sigs);
+                    Ok(())
+                }
+            )?;
+            anyhow::Ok(())
+        });
+        loop {
+            select! {
+                res = &mut fut => {
+                    res?;
+                    return Ok(())
+                }
+                res = srv.accept(&srv_ep) => {
+                    let ok = res.expect("failed to accept connection");
+                    assert!(ok);
+                    continue
+                }
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(feature = "quic")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[instrument(ret)]
+async fn rust_invoke_split_quic() -> anyhow::Result<()> {
+    use core::net::Ipv6Addr;
+    use core::pin::{pin, Pin};
+
+    common::with_quic(&["split.test"], |port, clt_ep, srv_ep| async move {
+        let clt = wrpc_transport_quic::Client::new(clt_ep, (Ipv6Addr::LOCALHOST, port));
+        let srv = Arc::new(wrpc_transport_quic::Server::default());
+
+        let invocations = srv
+            .serve_values("test", "split", [Box::from([Some(1)])])
+            .await
+            .context("failed to serve `test.split`")?;
+        let mut invocations = pin!(invocations);
+        let mut fut = pin!(async {
+            try_join!(
+                async {
+                    info!("receiving `test.split` parameters");
+                    let (_, (a, b), rx, tx): (
+                        _,
+                        (u32, Pin<Box<dyn Stream<Item = Bytes> + Send>>),
+                        _,
+                        _,
+                    ) = invocations
+                        .try_next()
+                        .await
+                        .context("failed to accept invocation")?
+                        .context("unexpected end of stream")?;
+                    let ((), b) = try_join!(
+                        async {
+                            if let Some(rx) = rx {
+                                rx.await.context("failed to receive async parameters")?;
+                            }
+                            anyhow::Ok(())
+                        },
+                        async { Ok(b.collect::<Vec<_>>().await.concat()) }
+                    )?;
+                    assert_eq!(b, b"test");
+                    info!("transmitting `test.split` returns");
+                    tx((a + 1,)).await.context("failed to send response")
+                },
+                async {
+                    let (release_tx, release_rx) = oneshot::channel::<()>();
+                    let b: Pin<Box<dyn Stream<Item = Bytes> + Send>> =
+                        Box::pin(stream::once(async {
+                            release_rx.await.expect("failed to await stream release");
+                            Bytes::from("test")
+                        }));
+                    info!("invoking `test.split`");
+                    let results = tokio::time::timeout(
+                        Duration::from_secs(10),
+                        clt.invoke_values_split((), "test", "split", (42u32, b), &[[Some(1)]]),
+                    )
+                    .await
+                    .context("parameters were not sent before the stream completed")?
+                    .context("failed to invoke `test.split`")?;
+                    release_tx.send(()).expect("failed to release stream");
+                    let ((v,), io): ((u32,), _) = results.await?;
+                    if let Some(io) = io {
+                        io.await.context("failed to complete async I/O")?;
+                    }
+                    assert_eq!(v, 43);
+                    Ok(())
+                }
+            )
+        });
+        loop {
+            select! {
+                res = &mut fut => {
+                    res?;
+                    return Ok(())
+                }
+                res = srv.accept(&srv_ep) => {
+                    let ok = res.expect("failed to accept connection");
+                    assert!(ok);
+                    continue
+                }
+            }
+        }
+    })
+    .await
+}
+
+#[test]
+fn rust_bindgen_monotonic_instant() {
+    wrpc::generate!({
+        inline: "
+            package wasi:clocks@0.2.0;
+
+            interface monotonic-clock {
+                type instant = u64;
+                type duration = u64;
+
+                now: func() -> instant;
+                elapsed: func(since: instant) -> duration;
+            }
+
+            world clocks {
+                import monotonic-clock;
+            }
+        "
+    });
+
+    fn instant(v: wasi::clocks::monotonic_clock::Instant) -> wrpc_transport::MonotonicInstant {
+        v
+    }
+    fn duration(v: wasi::clocks::monotonic_clock::Duration) -> u64 {
+        v
+    }
+    assert_eq!(instant(wrpc_transport::MonotonicInstant(42)).0, 42);
+    assert_eq!(duration(42), 42);
+}
+
+#[test]
+fn rust_bindgen_monotonic_instant_version() {
+    // only `wasi:clocks@0.2.x` is known to define `instant` as nanoseconds
+    wrpc::generate!({
+        inline: "
+            package wasi:clocks@0.3.0;
+
+            interface monotonic-clock {
+                type instant = u64;
+
+                now: func() -> instant;
+            }
+
+            world clocks {
+                import monotonic-clock;
+            }
+        "
+    });
+
+    fn instant(v: wasi::clocks::monotonic_clock::Instant) -> u64 {
+        v
+    }
+    assert_eq!(instant(42), 42);
+}
+
+#[test]
+fn rust_bindgen_unit_tuple_field() -> anyhow::Result<()> {
+    wrpc::generate!({
+        inline: "
+            package wrpc-test:unit;
+
+            interface unit {
+                record rec {
+                    a: u8,
+                    b: tuple<>,
+                    c: u8,
+                }
+
+                f: func(r: rec);
+            }
+
+            world units {
+                import unit;
+            }
+        ",
+        additional_derives: [PartialEq],
+    });
+    use wrpc_test::unit::unit::Rec;
+
+    // `tuple<>` is encoded as zero bytes and must not consume the bytes of the next field
+    let v = Rec { a: 1, b: (), c: 2 };
+    let Ok((buf, deferred)) = wrpc_transport::encode_value::<_, wrpc_transport::MockOutgoing>(&v)
+    else {
+        anyhow::bail!("failed to encode record")
+    };
+    assert!(deferred.is_none());
+    assert_eq!(buf.as_ref(), [0x01, 0x02]);
+    let (decoded, n) = wrpc_transport::decode_value_counted::<Rec, wrpc_transport::ReplayIncoming>(
+        &[0x01, 0x02, 0xff][..],
+    )?;
+    assert_eq!(decoded, v);
+    assert_eq!(n, 2);
+    wrpc_transport::assert_roundtrip(&v);
+    Ok(())
+}